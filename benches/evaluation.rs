@@ -0,0 +1,98 @@
+//! Evaluation, batch lookup and refresh costs against an in-process transport.
+//!
+//! Performance budget, checked on a release build:
+//! - cached single evaluation: under 1µs
+//! - `get_multiple` with 100 flags: under 50µs
+//! - publishing a 10k flag refresh: under 10ms
+
+use std::hint::black_box;
+
+use async_trait::async_trait;
+use criterion::{criterion_group, criterion_main, Criterion};
+use flags_rs::cache::{Cache, MemoryCache};
+use flags_rs::flag::{Details, FeatureFlag};
+use flags_rs::transport::Transport;
+use flags_rs::{ApiResponse, Auth, Client, FlagError};
+
+struct StaticTransport(Vec<FeatureFlag>);
+
+#[async_trait]
+impl Transport for StaticTransport {
+    async fn fetch_flags(&self, _auth: Option<&Auth>) -> Result<ApiResponse, FlagError> {
+        Ok(ApiResponse {
+            interval_allowed: 3600,
+            flags: self.0.clone(),
+            skipped: Vec::new(),
+            version: None,
+        })
+    }
+}
+
+fn flags(count: usize) -> Vec<FeatureFlag> {
+    (0..count)
+        .map(|i| FeatureFlag {
+            enabled: i % 2 == 0,
+            details: Details {
+                name: format!("flag-{}", i),
+                id: i.to_string(),
+                ..Default::default()
+            },
+        })
+        .collect()
+}
+
+fn client(count: usize) -> Client {
+    Client::builder()
+        .with_auth(Auth {
+            project_id: "bench-project".to_string(),
+            agent_id: "bench-agent".to_string(),
+            environment_id: "bench-env".to_string(),
+            company_id: None,
+            agent_secret: None,
+        })
+        .with_transport(StaticTransport(flags(count)))
+        .build()
+        .expect("Failed to build client")
+}
+
+fn evaluation(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let client = client(1_000);
+    // Populate the cache before measuring
+    runtime.block_on(client.is("flag-0").enabled());
+
+    c.bench_function("single evaluation", |b| {
+        b.to_async(&runtime).iter(|| async { black_box(client.is("flag-42").enabled().await) })
+    });
+
+    let pinned = runtime.block_on(client.pin("flag-42"));
+    c.bench_function("pinned evaluation", |b| b.iter(|| black_box(pinned.is_enabled())));
+
+    let names: Vec<String> = (0..100).map(|i| format!("flag-{}", i)).collect();
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    c.bench_function("get_multiple 100 flags", |b| {
+        b.to_async(&runtime).iter(|| async { black_box(client.get_multiple(&names).await) })
+    });
+}
+
+fn refresh(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let cache = MemoryCache::new();
+    let large = flags(10_000);
+    c.bench_function("cache refresh 10k flags", |b| {
+        b.to_async(&runtime).iter(|| async { cache.refresh(&large, 60).await.unwrap() })
+    });
+
+    // Fetch, merge with local flags and publish
+    let client = client(10_000);
+    c.bench_function("client refresh 10k flags", |b| {
+        b.to_async(&runtime).iter(|| async {
+            client.reload_local_flags().await;
+            black_box(client.is("flag-0").enabled().await)
+        })
+    });
+}
+
+criterion_group!(benches, evaluation, refresh);
+criterion_main!(benches);
@@ -0,0 +1,90 @@
+//! Overhead the tower middleware adds to a request, with and without a flags
+//! header to evaluate.
+//!
+//! Performance budget: under 5µs per request for three header flags.
+
+use std::convert::Infallible;
+use std::hint::black_box;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, Criterion};
+use flags_rs::flag::{Details, FeatureFlag};
+use flags_rs::middleware::FlagsLayer;
+use flags_rs::transport::Transport;
+use flags_rs::{ApiResponse, Auth, Client, FlagError};
+use http::{Request, Response};
+use http_body_util::Empty;
+use tower::{service_fn, Layer, ServiceExt};
+
+struct StaticTransport;
+
+#[async_trait]
+impl Transport for StaticTransport {
+    async fn fetch_flags(&self, _auth: Option<&Auth>) -> Result<ApiResponse, FlagError> {
+        Ok(ApiResponse {
+            interval_allowed: 3600,
+            flags: (0..100)
+                .map(|i| FeatureFlag {
+                    enabled: true,
+                    details: Details {
+                        name: format!("feature-{}", i),
+                        id: i.to_string(),
+                        ..Default::default()
+                    },
+                })
+                .collect(),
+            skipped: Vec::new(),
+            version: None,
+        })
+    }
+}
+
+async fn handle(_req: Request<Empty<Bytes>>) -> Result<Response<Empty<Bytes>>, Infallible> {
+    Ok(Response::new(Empty::new()))
+}
+
+fn middleware(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let client = Client::builder()
+        .with_auth(Auth {
+            project_id: "bench-project".to_string(),
+            agent_id: "bench-agent".to_string(),
+            environment_id: "bench-env".to_string(),
+            company_id: None,
+            agent_secret: None,
+        })
+        .with_transport(StaticTransport)
+        .build()
+        .expect("Failed to build client");
+    runtime.block_on(client.is("feature-0").enabled());
+
+    let service = FlagsLayer::new(client).layer(service_fn(handle));
+
+    c.bench_function("baseline service", |b| {
+        b.to_async(&runtime).iter(|| async {
+            let req = Request::new(Empty::<Bytes>::new());
+            black_box(service_fn(handle).oneshot(req).await.unwrap())
+        })
+    });
+
+    c.bench_function("middleware without header", |b| {
+        b.to_async(&runtime).iter(|| async {
+            let req = Request::new(Empty::<Bytes>::new());
+            black_box(service.clone().oneshot(req).await.unwrap())
+        })
+    });
+
+    c.bench_function("middleware with 3 header flags", |b| {
+        b.to_async(&runtime).iter(|| async {
+            let req = Request::builder()
+                .header("X-Feature-Flags", "feature-1,feature-2,feature-3")
+                .body(Empty::<Bytes>::new())
+                .unwrap();
+            black_box(service.clone().oneshot(req).await.unwrap())
+        })
+    });
+}
+
+criterion_group!(benches, middleware);
+criterion_main!(benches);
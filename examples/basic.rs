@@ -8,6 +8,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             project_id: "your-project-id".to_string(),
             agent_id: "your-agent-id".to_string(),
             environment_id: "your-environment-id".to_string(),
+            company_id: None,
+            agent_secret: None,
         })
         .with_memory_cache()
         .build()?;
@@ -0,0 +1,18 @@
+//! Generate typed flag-name constants from a manifest:
+//!
+//! ```text
+//! cargo run --example codegen -- flags.manifest.json src/flag_keys.rs
+//! ```
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [manifest, output] = args.as_slice() else {
+        eprintln!("usage: codegen <flags.manifest.json> <output.rs>");
+        std::process::exit(2);
+    };
+
+    if let Err(e) = flags_rs::codegen::generate(manifest, output) {
+        eprintln!("codegen failed: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -1,9 +1,14 @@
 #[cfg(feature = "tower-middleware")]
 use flags_rs::{Auth, Client, middleware::{FlagsLayer, RequestExt}};
+#[cfg(feature = "tower-middleware")]
 use http::{Request, Response, StatusCode};
+#[cfg(feature = "tower-middleware")]
 use http_body_util::{BodyExt, Empty, Full};
+#[cfg(feature = "tower-middleware")]
 use std::convert::Infallible;
+#[cfg(feature = "tower-middleware")]
 use tower::{ServiceBuilder, ServiceExt};
+#[cfg(feature = "tower-middleware")]
 use bytes::Bytes;
 
 #[cfg(not(feature = "tower-middleware"))]
@@ -21,6 +26,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         project_id: std::env::var("FLAGS_PROJECT_ID").unwrap_or_else(|_| "test-project".to_string()),
         agent_id: std::env::var("FLAGS_AGENT_ID").unwrap_or_else(|_| "test-agent".to_string()),
         environment_id: std::env::var("FLAGS_ENVIRONMENT_ID").unwrap_or_else(|_| "development".to_string()),
+        company_id: None,
+        agent_secret: None,
     };
 
     let client = Client::builder()
@@ -51,6 +58,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         project_id: std::env::var("FLAGS_PROJECT_ID").unwrap_or_else(|_| "test-project".to_string()),
         agent_id: std::env::var("FLAGS_AGENT_ID").unwrap_or_else(|_| "test-agent".to_string()),
         environment_id: std::env::var("FLAGS_ENVIRONMENT_ID").unwrap_or_else(|_| "development".to_string()),
+        company_id: None,
+        agent_secret: None,
     };
     
     let client2 = Client::builder()
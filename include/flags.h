@@ -0,0 +1,27 @@
+/* C interface to flags-rs, built with the `ffi` feature. */
+#ifndef FLAGS_RS_H
+#define FLAGS_RS_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct FlagsClient FlagsClient;
+
+/* Returns NULL on invalid arguments. `base_url` may be NULL. */
+FlagsClient *flags_client_new(const char *project_id,
+                              const char *agent_id,
+                              const char *environment_id,
+                              const char *base_url);
+
+/* 1 = enabled, 0 = disabled or unknown, -1 = invalid arguments. */
+int flags_is_enabled(const FlagsClient *client, const char *name);
+
+/* Safe to call with NULL. */
+void flags_free(FlagsClient *client);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FLAGS_RS_H */
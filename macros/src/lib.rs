@@ -0,0 +1,105 @@
+//! Procedural macros for flags-rs, re-exported by the `macros` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Error, Expr, FnArg, ItemFn, LitStr, Pat, Path, Token};
+
+struct Args {
+    flag: LitStr,
+    fallback: Path,
+    client: Option<Expr>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let flag: LitStr = input.parse()?;
+        let mut fallback = None;
+        let mut client = None;
+
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let key: syn::Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            let value: LitStr = input.parse()?;
+            match key.to_string().as_str() {
+                "fallback" => fallback = Some(value.parse::<Path>()?),
+                "client" => client = Some(value.parse::<Expr>()?),
+                _ => return Err(Error::new(key.span(), "expected `fallback` or `client`")),
+            }
+        }
+
+        let fallback = fallback.ok_or_else(|| Error::new(Span::call_site(), "missing `fallback = \"...\"`"))?;
+        Ok(Args { flag, fallback, client })
+    }
+}
+
+/// Run the function only while a flag is enabled, and call `fallback` with
+/// the same arguments otherwise.
+///
+/// ```ignore
+/// #[feature_gated("new-pricing", fallback = "old_pricing")]
+/// async fn new_pricing(cart: &Cart) -> Price { ... }
+/// ```
+///
+/// The flag is checked on the global client (`flags_rs::init`) unless
+/// `client = "expr"` names one, e.g. `client = "self.flags"` or a parameter.
+/// The function must be `async` and its parameters plain identifiers; on a
+/// method, the fallback is called as `self.fallback(...)`.
+#[proc_macro_attribute]
+pub fn feature_gated(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as Args);
+    let function = parse_macro_input!(item as ItemFn);
+    expand(args, function).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn expand(args: Args, function: ItemFn) -> syn::Result<proc_macro2::TokenStream> {
+    if function.sig.asyncness.is_none() {
+        return Err(Error::new_spanned(function.sig.fn_token, "#[feature_gated] requires an async fn"));
+    }
+
+    let mut method = false;
+    let mut forwarded = Punctuated::<Expr, Token![,]>::new();
+    for input in &function.sig.inputs {
+        match input {
+            FnArg::Receiver(_) => method = true,
+            FnArg::Typed(typed) => match &*typed.pat {
+                Pat::Ident(ident) if ident.by_ref.is_none() && ident.subpat.is_none() => {
+                    let name = &ident.ident;
+                    forwarded.push(syn::parse_quote!(#name));
+                }
+                other => {
+                    return Err(Error::new_spanned(other, "#[feature_gated] parameters must be plain identifiers"));
+                }
+            },
+        }
+    }
+
+    let flag = &args.flag;
+    let fallback = &args.fallback;
+    let check = match &args.client {
+        Some(client) => quote!((#client).is(#flag).enabled().await),
+        None => quote!(::flags_rs::enabled(#flag).await),
+    };
+    let call_fallback = if method {
+        quote!(self.#fallback(#forwarded).await)
+    } else {
+        quote!(#fallback(#forwarded).await)
+    };
+
+    let ItemFn { attrs, vis, sig, block } = function;
+    Ok(quote! {
+        #(#attrs)*
+        #vis #sig {
+            if !#check {
+                return #call_fallback;
+            }
+            #block
+        }
+    })
+}
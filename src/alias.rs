@@ -0,0 +1,69 @@
+//! Extra names a flag can be evaluated by.
+//!
+//! Every name is normalized the same way when flags are stored and looked up:
+//! lowercase, with underscores and spaces written as hyphens, so `NEW_AUTH`,
+//! `new auth` and `new-auth` are one flag, whether it comes from the API or a
+//! local variable. An [`AliasGenerator`] covers other conventions: it is given
+//! the normalized name of every published flag and returns names that should
+//! resolve to it. Aliases live in a lookup table next to the cache rather than
+//! as copies of the flag.
+//!
+//! # Example
+//! ```no_run
+//! # use flags_rs::Client;
+//! # async fn example() -> Result<(), flags_rs::FlagError> {
+//! let client = Client::builder()
+//!     .with_alias_generator(flags_rs::alias::camel_case)
+//!     .build()?;
+//! // Finds `new-auth`
+//! client.is("newAuth").enabled().await;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::flag::FeatureFlag;
+use crate::normalize_name;
+
+/// Maps the normalized name of a flag to its aliases, see
+/// [`ClientBuilder::with_alias_generator`](crate::ClientBuilder::with_alias_generator).
+pub type AliasGenerator = Arc<dyn Fn(&str) -> Vec<String> + Send + Sync>;
+
+/// The camelCase spelling of a hyphenated name, `new-auth` as `newAuth`.
+/// Lookups ignore case, so PascalCase `NewAuth` matches too.
+pub fn camel_case(name: &str) -> Vec<String> {
+    if !name.contains('-') {
+        return Vec::new();
+    }
+    let mut alias = String::with_capacity(name.len());
+    for (i, word) in name.split('-').enumerate() {
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) if i > 0 => {
+                alias.extend(first.to_uppercase());
+                alias.push_str(chars.as_str());
+            }
+            _ => alias.push_str(word),
+        }
+    }
+    vec![alias]
+}
+
+/// Normalized alias to flag name for `flags`. A flag's own name always wins
+/// over an alias, and an alias claimed by several flags goes to the first.
+pub(crate) fn index(generator: &AliasGenerator, flags: &[FeatureFlag]) -> HashMap<String, String> {
+    let names: HashSet<&str> = flags.iter().map(|f| f.details.name.as_str()).collect();
+    let mut aliases = HashMap::new();
+    for flag in flags {
+        for alias in generator(&flag.details.name) {
+            let alias = normalize_name(&alias).into_owned();
+            if names.contains(alias.as_str()) {
+                continue;
+            }
+            aliases.entry(alias).or_insert_with(|| flag.details.name.clone());
+        }
+    }
+    aliases
+}
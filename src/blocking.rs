@@ -0,0 +1,42 @@
+//! A blocking wrapper around [`crate::Client`], enabled with the `blocking` feature.
+//!
+//! The wrapper owns a small Tokio runtime and drives the async client on it, so
+//! it must not be used from within another async runtime.
+
+use std::sync::Arc;
+
+use tokio::runtime::Runtime;
+
+use crate::flag::FeatureFlag;
+use crate::FlagError;
+
+pub struct Client {
+    inner: crate::Client,
+    runtime: Runtime,
+}
+
+impl Client {
+    pub fn new(client: crate::Client) -> Result<Self, FlagError> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("flags-rs-blocking")
+            .enable_all()
+            .build()
+            .map_err(|e| FlagError::BuilderError(format!("Failed to start runtime: {}", e)))?;
+
+        Ok(Self { inner: client, runtime })
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.runtime.block_on(self.inner.is(name).enabled())
+    }
+
+    pub fn list(&self) -> Result<Vec<Arc<FeatureFlag>>, FlagError> {
+        self.runtime.block_on(self.inner.list())
+    }
+
+    /// The wrapped async client.
+    pub fn inner(&self) -> &crate::Client {
+        &self.inner
+    }
+}
@@ -0,0 +1,61 @@
+//! Consistent bucketing of users into percentage rollouts.
+//!
+//! A user is inside a rollout of `p` percent when
+//! `hash("{flag}:{user_id}") % 10000 < p * 100`, with the flag's normalized
+//! name: lowercase, with underscores and spaces written as hyphens. Names
+//! already in that form hash as the API serves them; for others, buckets only
+//! match an SDK that normalizes the same way. The hash can be replaced with
+//! [`ClientBuilder::with_bucket_hasher`](crate::ClientBuilder::with_bucket_hasher).
+
+/// Buckets per rollout, giving rollouts a resolution of 0.01%.
+pub const BUCKETS: u32 = 10_000;
+
+/// The hash behind bucket assignment.
+pub trait BucketHasher: Send + Sync {
+    fn hash(&self, key: &[u8]) -> u32;
+}
+
+/// 32-bit MurmurHash3 (x86 variant) with seed 0, the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Murmur3;
+
+impl BucketHasher for Murmur3 {
+    fn hash(&self, key: &[u8]) -> u32 {
+        const C1: u32 = 0xcc9e_2d51;
+        const C2: u32 = 0x1b87_3593;
+
+        let mut h: u32 = 0;
+        let mut chunks = key.chunks_exact(4);
+        for chunk in &mut chunks {
+            let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            k = k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+            h = (h ^ k).rotate_left(13).wrapping_mul(5).wrapping_add(0xe654_6b64);
+        }
+
+        let tail = chunks.remainder();
+        if !tail.is_empty() {
+            let mut k: u32 = 0;
+            for (i, byte) in tail.iter().enumerate() {
+                k |= u32::from(*byte) << (8 * i);
+            }
+            h ^= k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        }
+
+        h ^= key.len() as u32;
+        h ^= h >> 16;
+        h = h.wrapping_mul(0x85eb_ca6b);
+        h ^= h >> 13;
+        h = h.wrapping_mul(0xc2b2_ae35);
+        h ^ (h >> 16)
+    }
+}
+
+/// The bucket, below [`BUCKETS`], that `user_id` falls into for `flag`.
+pub fn bucket(hasher: &dyn BucketHasher, flag: &str, user_id: &str) -> u32 {
+    hasher.hash(format!("{}:{}", flag, user_id).as_bytes()) % BUCKETS
+}
+
+/// Whether `user_id` is inside a rollout of `percentage` percent of `flag`.
+pub fn in_rollout(hasher: &dyn BucketHasher, flag: &str, user_id: &str, percentage: f64) -> bool {
+    f64::from(bucket(hasher, flag, user_id)) < percentage * f64::from(BUCKETS) / 100.0
+}
@@ -1,35 +1,110 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
+use arc_swap::ArcSwap;
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
-use tokio::sync::RwLock;
+use chrono::Utc;
+use tokio::time::Instant;
 use crate::flag::FeatureFlag;
 
+/// Storage for the flag set fetched by the client.
+///
+/// All methods take `&self`: the client shares one cache between every clone
+/// and never wraps it in a lock, so implementations must use interior
+/// mutability and should keep reads cheap while a refresh is being published.
+///
+/// The client tracks when to refresh on its own, so `should_refresh_cache` is
+/// only consulted when a cache is used directly.
 #[async_trait]
 pub trait Cache {
     async fn get(&self, name: &str) -> Result<(bool, bool), Box<dyn std::error::Error + Send + Sync>>;
-    async fn get_all(&self) -> Result<Vec<FeatureFlag>, Box<dyn std::error::Error + Send + Sync>>;
-    async fn refresh(&mut self, flags: &[FeatureFlag], interval_allowed: i32) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+    /// Flags are shared rather than copied, so listing a large set stays cheap.
+    async fn get_all(&self) -> Result<Vec<Arc<FeatureFlag>>, Box<dyn std::error::Error + Send + Sync>>;
+    async fn refresh(&self, flags: &[FeatureFlag], interval_allowed: i32) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
     async fn should_refresh_cache(&self) -> bool;
-    async fn init(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+    async fn init(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
 }
 
+/// When the next refresh is due, kept apart from the flag data so checking
+/// staleness never touches the flag map.
+///
+/// Measured on the monotonic clock, so wall-clock steps (NTP corrections, VM
+/// resumes) neither freeze refreshes nor force them on every check.
+pub(crate) struct RefreshSchedule {
+    origin: Instant,
+    // Milliseconds since `origin`; negative means due
+    next_refresh: AtomicI64,
+}
+
+impl RefreshSchedule {
+    /// A schedule that is already due.
+    pub(crate) fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            next_refresh: AtomicI64::new(-1),
+        }
+    }
+
+    pub(crate) fn is_stale(&self) -> bool {
+        self.elapsed_millis() > self.next_refresh.load(Ordering::Acquire)
+    }
+
+    /// Schedule the next refresh `interval_allowed` seconds from now.
+    pub(crate) fn schedule(&self, interval_allowed: i32) {
+        let next_refresh = self.elapsed_millis() + interval_allowed as i64 * 1000;
+        self.next_refresh.store(next_refresh, Ordering::Release);
+    }
+
+    /// Push a due schedule back by `delay`, so a refresh attempt that published
+    /// nothing is not retried by every evaluation. A schedule that is not due,
+    /// or that a concurrent publish has just moved, is left alone.
+    pub(crate) fn defer(&self, delay: Duration) {
+        let current = self.next_refresh.load(Ordering::Acquire);
+        let now = self.elapsed_millis();
+        if now > current {
+            let deferred = now + delay.as_millis() as i64;
+            let _ = self.next_refresh.compare_exchange(current, deferred, Ordering::AcqRel, Ordering::Acquire);
+        }
+    }
+
+    /// Make the next check report the schedule as due.
+    pub(crate) fn invalidate(&self) {
+        self.next_refresh.store(-1, Ordering::Release);
+    }
 
+    fn elapsed_millis(&self) -> i64 {
+        self.origin.elapsed().as_millis() as i64
+    }
+}
+
+/// In-memory cache whose flag map is an immutable snapshot swapped atomically
+/// on refresh, so readers never wait on writers.
 pub struct MemoryCache {
-    flags: RwLock<HashMap<String, FeatureFlag>>,
-    cache_ttl: i64,
-    next_refresh: RwLock<DateTime<Utc>>,
+    flags: ArcSwap<HashMap<String, Arc<FeatureFlag>>>,
+    cache_ttl: AtomicI64,
+    schedule: RefreshSchedule,
 }
 
 impl MemoryCache {
     pub fn new() -> Self {
-        let cache = Self {
-            flags: RwLock::new(HashMap::new()),
-            cache_ttl: 60,
-            next_refresh: RwLock::new(Utc::now() - chrono::Duration::seconds(90)), // Initialize directly
-        };
+        Self {
+            flags: ArcSwap::from_pointee(HashMap::new()),
+            cache_ttl: AtomicI64::new(60),
+            schedule: RefreshSchedule::new(),
+        }
+    }
+
+    /// The currently published flag map.
+    pub fn snapshot(&self) -> Arc<HashMap<String, Arc<FeatureFlag>>> {
+        self.flags.load_full()
+    }
+}
 
-        cache
+impl Default for MemoryCache {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -37,7 +112,7 @@ impl MemoryCache {
 #[async_trait]
 impl Cache for MemoryCache {
     async fn get(&self, name: &str) -> Result<(bool, bool), Box<dyn std::error::Error + Send + Sync>> {
-        let flags = self.flags.read().await;
+        let flags = self.flags.load();
         if let Some(flag) = flags.get(name) {
             Ok((flag.enabled, true))
         } else {
@@ -45,37 +120,104 @@ impl Cache for MemoryCache {
         }
     }
 
-    async fn get_all(&self) -> Result<Vec<FeatureFlag>, Box<dyn std::error::Error + Send + Sync>> {
-        let flags = self.flags.read().await;
-        let all_flags: Vec<FeatureFlag> = flags.values().cloned().collect();
+    async fn get_all(&self) -> Result<Vec<Arc<FeatureFlag>>, Box<dyn std::error::Error + Send + Sync>> {
+        let flags = self.flags.load();
+        let all_flags: Vec<Arc<FeatureFlag>> = flags.values().cloned().collect();
         Ok(all_flags)
     }
 
-    async fn refresh(&mut self, flags: &[FeatureFlag], interval_allowed: i32) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let mut flag_map = self.flags.write().await;
-        flag_map.clear();
+    async fn refresh(&self, flags: &[FeatureFlag], interval_allowed: i32) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let flag_map: HashMap<String, Arc<FeatureFlag>> = flags
+            .iter()
+            .map(|flag| (flag.details.name.clone(), Arc::new(flag.clone())))
+            .collect();
+        self.flags.store(Arc::new(flag_map));
         log::debug!("Cache refreshed for flags.gg - {}", Utc::now());
 
+        self.cache_ttl.store(interval_allowed as i64, Ordering::Relaxed);
+        self.schedule.schedule(interval_allowed);
+
+        Ok(())
+    }
+
+    async fn should_refresh_cache(&self) -> bool {
+        self.schedule.is_stale()
+    }
+
+    async fn init(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.cache_ttl.store(60, Ordering::Relaxed);
+        self.schedule.invalidate();
+        Ok(())
+    }
+}
+
+/// Cache backed by a sharded concurrent map, enabled with the `sharded-cache`
+/// feature. Suited to very high evaluation rates across many distinct flags.
+///
+/// Unlike [`MemoryCache`], a refresh is applied flag by flag rather than as one
+/// atomic swap: new and changed flags are written first and removed flags are
+/// dropped last, so readers never observe an empty map mid-refresh.
+#[cfg(feature = "sharded-cache")]
+pub struct ShardedCache {
+    flags: dashmap::DashMap<String, Arc<FeatureFlag>>,
+    cache_ttl: AtomicI64,
+    schedule: RefreshSchedule,
+}
+
+#[cfg(feature = "sharded-cache")]
+impl ShardedCache {
+    pub fn new() -> Self {
+        Self {
+            flags: dashmap::DashMap::new(),
+            cache_ttl: AtomicI64::new(60),
+            schedule: RefreshSchedule::new(),
+        }
+    }
+}
+
+#[cfg(feature = "sharded-cache")]
+impl Default for ShardedCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "sharded-cache")]
+#[async_trait]
+impl Cache for ShardedCache {
+    async fn get(&self, name: &str) -> Result<(bool, bool), Box<dyn std::error::Error + Send + Sync>> {
+        match self.flags.get(name) {
+            Some(flag) => Ok((flag.enabled, true)),
+            None => Ok((false, false)),
+        }
+    }
+
+    async fn get_all(&self) -> Result<Vec<Arc<FeatureFlag>>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.flags.iter().map(|entry| Arc::clone(entry.value())).collect())
+    }
+
+    async fn refresh(&self, flags: &[FeatureFlag], interval_allowed: i32) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut names = std::collections::HashSet::with_capacity(flags.len());
         for flag in flags {
-            flag_map.insert(flag.details.name.clone(), flag.clone());
+            names.insert(flag.details.name.as_str());
+            self.flags.insert(flag.details.name.clone(), Arc::new(flag.clone()));
         }
+        self.flags.retain(|name, _| names.contains(name.as_str()));
+        log::debug!("Cache refreshed for flags.gg - {}", Utc::now());
 
-        self.cache_ttl = interval_allowed as i64;
-        let mut next_refresh = self.next_refresh.write().await;
-        *next_refresh = Utc::now() + chrono::Duration::seconds(self.cache_ttl);
+        self.cache_ttl.store(interval_allowed as i64, Ordering::Relaxed);
+        self.schedule.schedule(interval_allowed);
 
         Ok(())
     }
 
     async fn should_refresh_cache(&self) -> bool {
-        let next_refresh = self.next_refresh.read().await;
-        Utc::now() > *next_refresh
+        self.schedule.is_stale()
     }
 
-    async fn init(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        self.cache_ttl = 60;
-        let mut next_refresh = self.next_refresh.write().await;
-        *next_refresh = Utc::now() - chrono::Duration::seconds(90);
+    async fn init(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.cache_ttl.store(60, Ordering::Relaxed);
+        self.schedule.invalidate();
         Ok(())
     }
 }
@@ -0,0 +1,163 @@
+//! The circuit breaker guarding refreshes against a failing API.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::warn;
+use tokio::time::Instant;
+
+/// Decides whether the client may call the API, based on past outcomes.
+///
+/// The client asks [`allow_request`](CircuitBreaker::allow_request) before
+/// each refresh and reports the outcome once its retries are done; a skipped
+/// refresh keeps serving the cached flags. Implement this to share an existing
+/// breaker with other subsystems; `Arc<T>` implements it for any breaker `T`,
+/// so one instance can be handed to several clients.
+pub trait CircuitBreaker: Send + Sync {
+    /// Whether a refresh may call the API now.
+    fn allow_request(&self) -> bool;
+
+    /// A refresh succeeded.
+    fn record_success(&self);
+
+    /// A refresh failed after exhausting its retries.
+    fn record_failure(&self);
+
+    /// Whether requests are currently being rejected.
+    fn is_open(&self) -> bool;
+}
+
+impl<T: CircuitBreaker + ?Sized> CircuitBreaker for Arc<T> {
+    fn allow_request(&self) -> bool {
+        (**self).allow_request()
+    }
+
+    fn record_success(&self) {
+        (**self).record_success()
+    }
+
+    fn record_failure(&self) {
+        (**self).record_failure()
+    }
+
+    fn is_open(&self) -> bool {
+        (**self).is_open()
+    }
+}
+
+/// How long an open circuit rejects requests before trying again.
+const COOLDOWN: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+struct CircuitState {
+    is_open: bool,
+    failure_count: u32,
+    // Start of the current cooldown: the last failure, or the last refresh let
+    // through while open
+    cooldown_from: Option<Instant>,
+}
+
+/// The built-in breaker. Failures are counted but by default deliberately do
+/// not open the circuit, so transient errors only fall back to local flags;
+/// [`with_failure_threshold`](Self::with_failure_threshold) opens it after
+/// consecutive failures. Once open, the circuit rejects requests for 10
+/// seconds, then lets a single refresh through: its success closes the
+/// circuit, its failure keeps it open for another 10 seconds.
+#[derive(Debug)]
+pub struct DefaultCircuitBreaker {
+    state: Mutex<CircuitState>,
+    failure_threshold: Option<u32>,
+}
+
+impl DefaultCircuitBreaker {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(CircuitState {
+                is_open: false,
+                failure_count: 0,
+                cooldown_from: None,
+            }),
+            failure_threshold: None,
+        }
+    }
+
+    /// A breaker that opens after `failures` consecutive failed refreshes,
+    /// at least one.
+    pub fn with_failure_threshold(failures: u32) -> Self {
+        Self {
+            failure_threshold: Some(failures.max(1)),
+            ..Self::new()
+        }
+    }
+
+    #[cfg(all(test, feature = "remote"))]
+    pub(crate) fn open(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.is_open = true;
+        state.cooldown_from = Some(Instant::now());
+    }
+}
+
+impl Default for DefaultCircuitBreaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CircuitBreaker for DefaultCircuitBreaker {
+    fn allow_request(&self) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if !state.is_open {
+            return true;
+        }
+        if state.cooldown_from.is_some_and(|from| from.elapsed() < COOLDOWN) {
+            warn!("Circuit breaker is open, skipping refetch.");
+            return false;
+        }
+        // Half-open: let this refresh through and hold back the rest until it
+        // reports, or for another cooldown if it never does
+        warn!("Circuit breaker is half-open, trying one refetch.");
+        state.cooldown_from = Some(Instant::now());
+        true
+    }
+
+    fn record_success(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.is_open = false;
+        state.failure_count = 0;
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.failure_count += 1;
+        state.cooldown_from = Some(Instant::now());
+        if !state.is_open && self.failure_threshold.is_some_and(|threshold| state.failure_count >= threshold) {
+            warn!("Circuit breaker opened after {} consecutive failures.", state.failure_count);
+            state.is_open = true;
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).is_open
+    }
+}
+
+/// A breaker that never opens, from
+/// [`ClientBuilder::with_circuit_breaker_disabled`](crate::ClientBuilder::with_circuit_breaker_disabled):
+/// every refresh calls the API.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoCircuitBreaker;
+
+impl CircuitBreaker for NoCircuitBreaker {
+    fn allow_request(&self) -> bool {
+        true
+    }
+
+    fn record_success(&self) {}
+
+    fn record_failure(&self) {}
+
+    fn is_open(&self) -> bool {
+        false
+    }
+}
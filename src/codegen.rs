@@ -0,0 +1,149 @@
+//! Typed flag-name constants generated from a checked-in manifest.
+//!
+//! A manifest is the JSON export of a project's flags: either an array of
+//! flags or an object with a `flags` array, each entry naming its flag in
+//! `name` (as in [`Client::export`](crate::Client::export)) or `details.name`
+//! (as in the API payload). An optional `description` becomes the constant's
+//! doc comment.
+//!
+//! # Build script
+//! ```no_run
+//! // In build.rs's main
+//! let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("flag_keys.rs");
+//! flags_rs::codegen::generate("flags.manifest.json", out).unwrap();
+//! ```
+//! and in the crate:
+//! ```ignore
+//! mod flags {
+//!     include!(concat!(env!("OUT_DIR"), "/flag_keys.rs"));
+//! }
+//!
+//! if client.is(&flags::keys::NEW_CHECKOUT).enabled().await { /* ... */ }
+//! let drift = client.check_manifest(flags::keys::ALL).await;
+//! ```
+//!
+//! Outside a build script, `cargo run --example codegen -- <manifest> <output>`
+//! writes the same file.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+
+use serde_json::Value;
+
+/// Read the manifest at `manifest` and write the `keys` module to `output`.
+/// In a build script, also asks cargo to rerun when the manifest changes.
+pub fn generate(manifest: impl AsRef<Path>, output: impl AsRef<Path>) -> io::Result<()> {
+    let manifest = manifest.as_ref();
+    let source = std::fs::read_to_string(manifest)?;
+    let code = render(&source, &manifest.display().to_string())?;
+    if std::env::var_os("OUT_DIR").is_some() {
+        println!("cargo:rerun-if-changed={}", manifest.display());
+    }
+    std::fs::write(output, code)
+}
+
+struct Entry {
+    name: String,
+    description: Option<String>,
+}
+
+/// The generated source for `manifest`, named `origin` in its header.
+pub(crate) fn render(manifest: &str, origin: &str) -> io::Result<String> {
+    let entries = parse(manifest)?;
+
+    let mut constants: HashMap<String, &str> = HashMap::new();
+    let mut out = String::new();
+    let _ = writeln!(out, "// Generated by flags_rs::codegen from {}. Do not edit.", origin);
+    out.push_str("\n/// Flag names from the manifest.\n#[allow(dead_code)]\npub mod keys {\n");
+    for entry in &entries {
+        let constant = constant_name(&entry.name);
+        if let Some(previous) = constants.insert(constant.clone(), &entry.name) {
+            return Err(invalid(format!(
+                "flags '{}' and '{}' both map to the constant {}",
+                previous, entry.name, constant
+            )));
+        }
+        if let Some(description) = &entry.description {
+            for line in description.lines() {
+                let _ = writeln!(out, "    /// {}", line.trim_end());
+            }
+        }
+        let _ = writeln!(
+            out,
+            "    pub const {}: ::flags_rs::flag::FlagKey = ::flags_rs::flag::FlagKey::new({:?});",
+            constant, entry.name
+        );
+    }
+    out.push_str("\n    /// Every flag in the manifest, for `Client::check_manifest`.\n");
+    out.push_str("    pub const ALL: &[::flags_rs::flag::FlagKey] = &[");
+    for entry in &entries {
+        let _ = write!(out, "{}, ", constant_name(&entry.name));
+    }
+    out.push_str("];\n}\n");
+    Ok(out)
+}
+
+fn parse(manifest: &str) -> io::Result<Vec<Entry>> {
+    let value: Value = serde_json::from_str(manifest).map_err(|e| invalid(format!("invalid manifest: {}", e)))?;
+    let flags = match &value {
+        Value::Array(flags) => flags,
+        Value::Object(object) => object
+            .get("flags")
+            .and_then(Value::as_array)
+            .ok_or_else(|| invalid("manifest object has no `flags` array".to_string()))?,
+        _ => return Err(invalid("manifest must be an array or an object with `flags`".to_string())),
+    };
+
+    let mut entries: Vec<Entry> = Vec::with_capacity(flags.len());
+    for (index, flag) in flags.iter().enumerate() {
+        let details = flag.get("details").unwrap_or(flag);
+        let name = details
+            .get("name")
+            .and_then(Value::as_str)
+            .filter(|name| !name.trim().is_empty())
+            .ok_or_else(|| invalid(format!("flag {} has no name", index)))?;
+        if entries.iter().any(|e| e.name == name) {
+            continue;
+        }
+        entries.push(Entry {
+            name: name.to_string(),
+            description: details.get("description").and_then(Value::as_str).map(str::to_string),
+        });
+    }
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+/// `new-checkout` becomes `NEW_CHECKOUT`; a leading digit gets a `_` prefix.
+fn constant_name(name: &str) -> String {
+    let mut constant: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    if constant.starts_with(|c: char| c.is_ascii_digit()) {
+        constant.insert(0, '_');
+    }
+    constant
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Differences between a manifest and the flags the API serves, from
+/// [`Client::check_manifest`](crate::Client::check_manifest).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManifestDrift {
+    /// In the manifest but not served by the API, e.g. deleted on the dashboard.
+    pub missing_from_api: Vec<String>,
+    /// Served by the API but not in the manifest, so no generated key refers to it.
+    pub missing_from_manifest: Vec<String>,
+}
+
+impl ManifestDrift {
+    pub fn is_empty(&self) -> bool {
+        self.missing_from_api.is_empty() && self.missing_from_manifest.is_empty()
+    }
+}
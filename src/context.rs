@@ -0,0 +1,352 @@
+//! Inputs that scope a single evaluation.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::FlagError;
+
+/// Per-evaluation overrides, passed with [`Flag::with_context`](crate::Flag::with_context).
+///
+/// # Example
+/// ```no_run
+/// # use flags_rs::Client;
+/// # use flags_rs::context::EvaluationContext;
+/// # async fn example(client: &Client) {
+/// let ctx = EvaluationContext::new().with_tenant("company-42");
+/// if client.is("new-dashboard").with_context(&ctx).enabled().await {
+///     // Enabled for company-42
+/// }
+/// # }
+/// ```
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct EvaluationContext {
+    tenant: Option<String>,
+    user_id: Option<String>,
+    app_version: Option<String>,
+    attributes: BTreeMap<String, String>,
+    key: MemoKey,
+}
+
+impl std::fmt::Debug for EvaluationContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EvaluationContext")
+            .field("tenant", &self.tenant)
+            .field("user_id", &self.user_id)
+            .field("app_version", &self.app_version)
+            .field("attributes", &self.attributes)
+            .finish()
+    }
+}
+
+/// [`EvaluationContext::memo_key`], computed once. Not part of the context's
+/// identity: it compares equal and hashes to nothing.
+#[derive(Clone, Default)]
+struct MemoKey(OnceLock<u64>);
+
+impl PartialEq for MemoKey {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for MemoKey {}
+
+impl Hash for MemoKey {
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
+/// Attribute names [`EvaluationContextBuilder`] rejects, as they name the
+/// dedicated fields.
+pub const RESERVED_ATTRIBUTES: &[&str] = &["tenant", "user_id", "app_version"];
+/// Most attributes [`EvaluationContextBuilder`] accepts.
+pub const MAX_ATTRIBUTES: usize = 64;
+/// Longest field, attribute name or value, in bytes, that
+/// [`EvaluationContextBuilder`] accepts.
+pub const MAX_VALUE_LEN: usize = 256;
+
+/// Attribute set by [`EvaluationContext::with_region`].
+pub const REGION: &str = "region";
+/// Attribute set by [`EvaluationContext::with_country`].
+pub const COUNTRY: &str = "country";
+/// Attribute set by [`EvaluationContext::with_datacenter`].
+pub const DATACENTER: &str = "datacenter";
+
+impl EvaluationContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A builder that validates the context, see [`EvaluationContextBuilder`].
+    pub fn builder() -> EvaluationContextBuilder {
+        EvaluationContextBuilder::default()
+    }
+
+    /// Resolve flags for this company instead of the client's [`Auth::company_id`](crate::Auth::company_id).
+    pub fn with_tenant(mut self, company_id: impl Into<String>) -> Self {
+        self.tenant = Some(company_id.into());
+        self.key = MemoKey::default();
+        self
+    }
+
+    pub fn tenant(&self) -> Option<&str> {
+        self.tenant.as_deref()
+    }
+
+    /// The user to bucket into percentage rollouts
+    /// ([`Details::rollout`](crate::flag::Details::rollout)).
+    pub fn with_user_id(mut self, user_id: impl Into<String>) -> Self {
+        self.user_id = Some(user_id.into());
+        self.key = MemoKey::default();
+        self
+    }
+
+    pub fn user_id(&self) -> Option<&str> {
+        self.user_id.as_deref()
+    }
+
+    /// The calling application's semantic version, e.g. `2.4.1` from a mobile
+    /// client. Flags restricted to a version range
+    /// ([`Details::app_version`](crate::flag::Details::app_version)) are only
+    /// enabled for versions inside it.
+    pub fn with_app_version(mut self, version: impl Into<String>) -> Self {
+        self.app_version = Some(version.into());
+        self.key = MemoKey::default();
+        self
+    }
+
+    pub fn app_version(&self) -> Option<&str> {
+        self.app_version.as_deref()
+    }
+
+    /// Set an attribute for flag [rules](crate::flag::Rule) to match on.
+    pub fn with_attribute(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.insert(name.into(), value.into());
+        self.key = MemoKey::default();
+        self
+    }
+
+    /// The serving region, e.g. `eu-west-1`.
+    pub fn with_region(self, region: impl Into<String>) -> Self {
+        self.with_attribute(REGION, region)
+    }
+
+    /// The caller's country, e.g. an ISO 3166 code such as `DE`.
+    pub fn with_country(self, country: impl Into<String>) -> Self {
+        self.with_attribute(COUNTRY, country)
+    }
+
+    /// The datacenter serving the request.
+    pub fn with_datacenter(self, datacenter: impl Into<String>) -> Self {
+        self.with_attribute(DATACENTER, datacenter)
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(String::as_str)
+    }
+
+    /// Every attribute as `(name, value)`, sorted by name.
+    pub fn attributes(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.attributes.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// The key of this context in an [`EvaluationMemo`], computed on first use.
+    /// It is only meaningful within one process: `DefaultHasher` may change
+    /// between Rust releases.
+    pub(crate) fn memo_key(&self) -> u64 {
+        *self.key.0.get_or_init(|| {
+            let mut hasher = DefaultHasher::new();
+            self.hash(&mut hasher);
+            hasher.finish()
+        })
+    }
+}
+
+/// Builds an [`EvaluationContext`], checking it so every consumer constructs
+/// contexts the same way: attribute names must not be empty or one of
+/// [`RESERVED_ATTRIBUTES`], there are at most [`MAX_ATTRIBUTES`], and no field,
+/// name or value is longer than [`MAX_VALUE_LEN`] bytes.
+///
+/// # Example
+/// ```
+/// # use flags_rs::context::EvaluationContext;
+/// let ctx = EvaluationContext::builder()
+///     .user_id("user-42")
+///     .tenant("company-42")
+///     .attribute("plan", "pro")
+///     .build()
+///     .unwrap();
+/// assert_eq!(ctx.attribute("plan"), Some("pro"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EvaluationContextBuilder {
+    context: EvaluationContext,
+}
+
+impl EvaluationContextBuilder {
+    /// See [`EvaluationContext::with_user_id`].
+    pub fn user_id(mut self, user_id: impl Into<String>) -> Self {
+        self.context = self.context.with_user_id(user_id);
+        self
+    }
+
+    /// See [`EvaluationContext::with_tenant`].
+    pub fn tenant(mut self, company_id: impl Into<String>) -> Self {
+        self.context = self.context.with_tenant(company_id);
+        self
+    }
+
+    /// See [`EvaluationContext::with_app_version`].
+    pub fn app_version(mut self, version: impl Into<String>) -> Self {
+        self.context = self.context.with_app_version(version);
+        self
+    }
+
+    pub fn attribute(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.context = self.context.with_attribute(name, value);
+        self
+    }
+
+    pub fn region(self, region: impl Into<String>) -> Self {
+        self.attribute(REGION, region)
+    }
+
+    pub fn country(self, country: impl Into<String>) -> Self {
+        self.attribute(COUNTRY, country)
+    }
+
+    pub fn datacenter(self, datacenter: impl Into<String>) -> Self {
+        self.attribute(DATACENTER, datacenter)
+    }
+
+    /// The context, or a [`FlagError::BuilderError`] naming the first problem.
+    pub fn build(self) -> Result<EvaluationContext, FlagError> {
+        let context = self.context;
+        let fields = [
+            ("tenant", &context.tenant),
+            ("user_id", &context.user_id),
+            ("app_version", &context.app_version),
+        ];
+        for (field, value) in fields {
+            check_len(field, value.as_deref().unwrap_or_default())?;
+        }
+        if context.attributes.len() > MAX_ATTRIBUTES {
+            return Err(FlagError::BuilderError(format!(
+                "Evaluation context has {} attributes, at most {} are allowed",
+                context.attributes.len(),
+                MAX_ATTRIBUTES
+            )));
+        }
+        for (name, value) in &context.attributes {
+            if name.is_empty() {
+                return Err(FlagError::BuilderError("Evaluation context attribute names must not be empty".to_string()));
+            }
+            if RESERVED_ATTRIBUTES.contains(&name.as_str()) {
+                return Err(FlagError::BuilderError(format!(
+                    "Evaluation context attribute '{}' is reserved; use the builder's {} method",
+                    name, name
+                )));
+            }
+            check_len("attribute name", name)?;
+            check_len(name, value)?;
+        }
+        // Hash the finished context once for its memo key
+        context.memo_key();
+        Ok(context)
+    }
+}
+
+fn check_len(field: &str, value: &str) -> Result<(), FlagError> {
+    if value.len() > MAX_VALUE_LEN {
+        return Err(FlagError::BuilderError(format!(
+            "Evaluation context {} is {} bytes, at most {} are allowed",
+            field,
+            value.len(),
+            MAX_VALUE_LEN
+        )));
+    }
+    Ok(())
+}
+
+tokio::task_local! {
+    static CURRENT: Arc<EvaluationContext>;
+}
+
+/// Run `fut` with `context` as its ambient context: flags evaluated inside it
+/// without [`Flag::with_context`](crate::Flag::with_context) use `context`, so
+/// deeply nested code picks up the request's context without it being passed
+/// down. An explicit context still wins, and scopes nest.
+///
+/// The context is task-local: it follows `fut` across `.await`s, but not into
+/// tasks spawned from it.
+///
+/// # Example
+/// ```no_run
+/// # use flags_rs::Client;
+/// # use flags_rs::context::{self, EvaluationContext};
+/// # async fn example(client: &Client) {
+/// let ctx = EvaluationContext::new().with_user_id("user-42");
+/// context::scope(ctx, async {
+///     // Evaluated for user-42
+///     client.is("new-dashboard").enabled().await;
+/// })
+/// .await;
+/// # }
+/// ```
+pub async fn scope<F: Future>(context: EvaluationContext, fut: F) -> F::Output {
+    CURRENT.scope(Arc::new(context), fut).await
+}
+
+/// The context of the innermost enclosing [`scope`], if any.
+pub fn current() -> Option<Arc<EvaluationContext>> {
+    CURRENT.try_with(Arc::clone).ok()
+}
+
+/// Results of evaluations within one unit of work, typically a request, so
+/// the same flag for the same context is only resolved once. Pass it with
+/// [`Flag::with_memo`](crate::Flag::with_memo); results from before a refresh
+/// are ignored, so a memo never serves data older than the cache.
+///
+/// # Example
+/// ```no_run
+/// # use flags_rs::Client;
+/// # use flags_rs::context::EvaluationMemo;
+/// # async fn example(client: &Client) {
+/// let memo = EvaluationMemo::new();
+/// for _ in 0..3 {
+///     // Resolved once, then answered from the memo
+///     client.is("new-dashboard").with_memo(&memo).enabled().await;
+/// }
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct EvaluationMemo {
+    // (flag, context key) to (refresh generation, enabled)
+    entries: Mutex<HashMap<(String, u64), (u64, bool)>>,
+}
+
+impl EvaluationMemo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn get(&self, flag: &str, context: u64, generation: u64) -> Option<bool> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(&(flag.to_string(), context))
+            .filter(|(entry_generation, _)| *entry_generation == generation)
+            .map(|(_, enabled)| *enabled)
+    }
+
+    pub(crate) fn insert(&self, flag: &str, context: u64, generation: u64, enabled: bool) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.insert((flag.to_string(), context), (generation, enabled));
+    }
+
+    /// Forget every memoized result.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
@@ -0,0 +1,125 @@
+//! A stream of client lifecycle events for observability layers.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use log::warn;
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+
+/// Events buffered per subscriber; a subscriber further behind skips the oldest.
+const CAPACITY: usize = 64;
+
+/// Receives the client's internal events as JSON records, see
+/// [`ClientBuilder::with_json_log_sink`](crate::ClientBuilder::with_json_log_sink).
+pub type JsonLogSink = Arc<dyn Fn(&Value) + Send + Sync>;
+
+/// Something that happened in the client, see [`Client::events`](crate::Client::events).
+#[derive(Debug, Clone)]
+pub enum ClientEvent {
+    /// A refresh is about to call the API.
+    RefreshStarted,
+    /// A refresh published `count` flags, `duration` after it started.
+    RefreshSucceeded { count: usize, duration: Duration },
+    /// A refresh failed after exhausting its retries, with the error's message.
+    RefreshFailed { error: String },
+    /// The circuit breaker started rejecting refreshes.
+    CircuitOpened,
+    /// The circuit breaker allows refreshes again.
+    CircuitClosed,
+}
+
+impl ClientEvent {
+    /// The event as a JSON object with an `event` name and its fields,
+    /// durations in milliseconds.
+    pub fn to_json(&self) -> Value {
+        match self {
+            ClientEvent::RefreshStarted => json!({"event": "refresh_started"}),
+            ClientEvent::RefreshSucceeded { count, duration } => json!({
+                "event": "refresh_succeeded",
+                "count": count,
+                "duration_ms": duration.as_millis() as u64,
+            }),
+            ClientEvent::RefreshFailed { error } => json!({"event": "refresh_failed", "error": error}),
+            ClientEvent::CircuitOpened => json!({"event": "circuit_opened"}),
+            ClientEvent::CircuitClosed => json!({"event": "circuit_closed"}),
+        }
+    }
+
+    fn level(&self) -> &'static str {
+        match self {
+            ClientEvent::RefreshFailed { .. } => "error",
+            ClientEvent::CircuitOpened => "warn",
+            _ => "info",
+        }
+    }
+}
+
+pub(crate) struct EventBus {
+    sender: broadcast::Sender<ClientEvent>,
+    json_sink: Option<JsonLogSink>,
+    // Circuit state as of the last event, to emit transitions only
+    circuit_open: AtomicBool,
+}
+
+impl EventBus {
+    pub(crate) fn new(json_sink: Option<JsonLogSink>) -> Self {
+        Self {
+            sender: broadcast::channel(CAPACITY).0,
+            json_sink,
+            circuit_open: AtomicBool::new(false),
+        }
+    }
+
+    /// Send `event` to current subscribers, if there are any.
+    pub(crate) fn emit(&self, event: ClientEvent) {
+        if self.json_sink.is_some() {
+            self.log(event.level(), event.to_json());
+        }
+        let _ = self.sender.send(event);
+    }
+
+    /// Log an error reported to the error callback to the JSON sink.
+    pub(crate) fn log_error(&self, error: &crate::FlagError) {
+        if self.json_sink.is_some() {
+            let mut record = json!({"event": "error"});
+            if let (Value::Object(record), Ok(Value::Object(fields))) = (&mut record, serde_json::to_value(error)) {
+                record.extend(fields);
+            }
+            self.log("error", record);
+        }
+    }
+
+    fn log(&self, level: &str, mut record: Value) {
+        let Some(sink) = &self.json_sink else {
+            return;
+        };
+        if let Value::Object(fields) = &mut record {
+            fields.insert("timestamp".to_string(), Value::String(Utc::now().to_rfc3339()));
+            fields.insert("level".to_string(), Value::String(level.to_string()));
+        }
+        sink(&record);
+    }
+
+    /// Emit a circuit event if `open` differs from the last state seen.
+    pub(crate) fn observe_circuit(&self, open: bool) {
+        if self.circuit_open.swap(open, Ordering::AcqRel) != open {
+            self.emit(if open { ClientEvent::CircuitOpened } else { ClientEvent::CircuitClosed });
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> impl Stream<Item = ClientEvent> + Send + 'static {
+        BroadcastStream::new(self.sender.subscribe()).filter_map(|event| match event {
+            Ok(event) => Some(event),
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                warn!("Client event subscriber lagged, skipped {} event(s)", skipped);
+                None
+            }
+        })
+    }
+}
@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::flag::{FeatureFlag, FlagSource};
+
+/// Output format for [`Client::export`](crate::Client::export).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+#[derive(Serialize)]
+struct ExportRow<'a> {
+    name: &'a str,
+    id: &'a str,
+    enabled: bool,
+    source: FlagSource,
+    last_refresh: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    tags: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    owner: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created_at: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    deprecated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    expired: bool,
+}
+
+/// Rows are sorted by name so two exports can be diffed directly. Descriptive
+/// metadata is included in JSON only, keeping the CSV columns fixed.
+pub(crate) fn render(flags: &[Arc<FeatureFlag>], last_refresh: Option<DateTime<Utc>>, format: Format) -> String {
+    let last_refresh = last_refresh.map(|t| t.to_rfc3339());
+    let mut rows: Vec<ExportRow> = flags
+        .iter()
+        .map(|f| ExportRow {
+            name: &f.details.name,
+            id: &f.details.id,
+            enabled: f.enabled,
+            source: f.source(),
+            last_refresh: last_refresh.clone(),
+            description: f.details.description.as_deref(),
+            tags: &f.details.tags,
+            owner: f.details.owner.as_deref(),
+            created_at: f.details.created_at.map(|t| t.to_rfc3339()),
+            deprecated: f.details.deprecated,
+            expires_at: f.details.expires_at.map(|t| t.to_rfc3339()),
+            expired: f.is_expired(),
+        })
+        .collect();
+    rows.sort_by(|a, b| a.name.cmp(b.name).then(a.id.cmp(b.id)));
+
+    match format {
+        Format::Json => serde_json::to_string_pretty(&rows).unwrap_or_else(|_| "[]".to_string()),
+        Format::Csv => {
+            let mut out = String::from("name,id,enabled,source,last_refresh\n");
+            for row in rows {
+                out.push_str(&format!(
+                    "{},{},{},{},{}\n",
+                    csv_field(row.name),
+                    csv_field(row.id),
+                    row.enabled,
+                    row.source.as_str(),
+                    row.last_refresh.as_deref().unwrap_or(""),
+                ));
+            }
+            out
+        }
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
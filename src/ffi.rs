@@ -0,0 +1,98 @@
+//! C ABI for embedding the client in non-Rust services, enabled with the `ffi` feature.
+//!
+//! Build a shared or static library with
+//! `cargo rustc --release --features ffi --crate-type cdylib` (or `staticlib`)
+//! and include `include/flags.h`.
+
+use std::ffi::{c_char, c_int, CStr};
+use std::ptr;
+
+use log::error;
+
+use crate::{blocking, Auth};
+
+/// Opaque client handle handed out to C callers.
+pub struct FlagsClient {
+    client: blocking::Client,
+}
+
+unsafe fn borrow_str<'a>(value: *const c_char) -> Option<&'a str> {
+    if value.is_null() {
+        return None;
+    }
+    CStr::from_ptr(value).to_str().ok()
+}
+
+/// Create a client. `base_url` may be null to use the flags.gg API.
+/// Returns null if any argument is invalid or the client cannot be built.
+///
+/// # Safety
+/// Every non-null argument must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn flags_client_new(
+    project_id: *const c_char,
+    agent_id: *const c_char,
+    environment_id: *const c_char,
+    base_url: *const c_char,
+) -> *mut FlagsClient {
+    let (Some(project_id), Some(agent_id), Some(environment_id)) =
+        (borrow_str(project_id), borrow_str(agent_id), borrow_str(environment_id))
+    else {
+        error!("flags_client_new called with a null or non UTF-8 credential");
+        return ptr::null_mut();
+    };
+
+    let mut builder = crate::Client::builder().with_auth(Auth {
+        project_id: project_id.to_string(),
+        agent_id: agent_id.to_string(),
+        environment_id: environment_id.to_string(),
+        company_id: None,
+        agent_secret: None,
+    });
+    if !base_url.is_null() {
+        match borrow_str(base_url) {
+            Some(base_url) => builder = builder.with_base_url(base_url),
+            None => {
+                error!("flags_client_new called with a non UTF-8 base URL");
+                return ptr::null_mut();
+            }
+        }
+    }
+
+    match builder.build().and_then(blocking::Client::new) {
+        Ok(client) => Box::into_raw(Box::new(FlagsClient { client })),
+        Err(e) => {
+            error!("flags_client_new failed: {}", e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Returns 1 if the flag is enabled, 0 if it is disabled or unknown,
+/// and -1 if either argument is invalid.
+///
+/// # Safety
+/// `client` must come from `flags_client_new` and not yet be freed;
+/// `name` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn flags_is_enabled(client: *const FlagsClient, name: *const c_char) -> c_int {
+    let Some(client) = client.as_ref() else {
+        return -1;
+    };
+    let Some(name) = borrow_str(name) else {
+        return -1;
+    };
+
+    c_int::from(client.client.is_enabled(name))
+}
+
+/// Free a client created by `flags_client_new`. Passing null is a no-op.
+///
+/// # Safety
+/// `client` must come from `flags_client_new` and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn flags_free(client: *mut FlagsClient) {
+    if !client.is_null() {
+        drop(Box::from_raw(client));
+    }
+}
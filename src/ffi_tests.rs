@@ -0,0 +1,75 @@
+#[cfg(all(test, feature = "ffi"))]
+mod tests {
+    use std::ffi::CString;
+    use std::ptr;
+
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::ffi::{flags_client_new, flags_free, flags_is_enabled};
+
+    // The blocking wrapper cannot run inside a Tokio runtime, so the mock
+    // server lives on its own runtime while the test thread stays synchronous.
+    fn start_mock_server(runtime: &tokio::runtime::Runtime) -> MockServer {
+        runtime.block_on(async {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/flags"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "intervalAllowed": 60,
+                    "flags": [
+                        {
+                            "enabled": true,
+                            "details": {
+                                "name": "ffi-flag",
+                                "id": "1"
+                            }
+                        }
+                    ]
+                })))
+                .mount(&server)
+                .await;
+            server
+        })
+    }
+
+    #[test]
+    fn test_ffi_round_trip() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let server = start_mock_server(&runtime);
+
+        let project = CString::new("test-project").unwrap();
+        let agent = CString::new("test-agent").unwrap();
+        let env = CString::new("test-env").unwrap();
+        let base_url = CString::new(server.uri()).unwrap();
+        let enabled = CString::new("ffi-flag").unwrap();
+        let missing = CString::new("missing-flag").unwrap();
+
+        unsafe {
+            let client = flags_client_new(project.as_ptr(), agent.as_ptr(), env.as_ptr(), base_url.as_ptr());
+            assert!(!client.is_null());
+
+            assert_eq!(flags_is_enabled(client, enabled.as_ptr()), 1);
+            assert_eq!(flags_is_enabled(client, missing.as_ptr()), 0);
+            assert_eq!(flags_is_enabled(client, ptr::null()), -1);
+
+            flags_free(client);
+        }
+    }
+
+    #[test]
+    fn test_ffi_rejects_invalid_arguments() {
+        let project = CString::new("test-project").unwrap();
+        let agent = CString::new("test-agent").unwrap();
+        let empty = CString::new("").unwrap();
+        let name = CString::new("flag").unwrap();
+
+        unsafe {
+            assert!(flags_client_new(project.as_ptr(), agent.as_ptr(), ptr::null(), ptr::null()).is_null());
+            // Empty IDs are rejected by the builder.
+            assert!(flags_client_new(project.as_ptr(), agent.as_ptr(), empty.as_ptr(), ptr::null()).is_null());
+            assert_eq!(flags_is_enabled(ptr::null(), name.as_ptr()), -1);
+            flags_free(ptr::null_mut());
+        }
+    }
+}
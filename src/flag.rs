@@ -1,9 +1,139 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use chrono::{DateTime, NaiveTime, Utc, Weekday};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::normalize_name;
+use crate::usage::UsageTracker;
+
+/// Identity and descriptive metadata of a flag. Everything beyond `name` and
+/// `id` is optional, so older API responses still deserialize.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Details {
     pub name: String,
     pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(rename = "createdAt", alias = "created_at", default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    /// Server-defined groups the flag belongs to, see [`Client::group`](crate::Client::group).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+    /// Evaluating a deprecated flag logs a throttled warning.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deprecated: bool,
+    /// After this time the flag should have been removed from code.
+    #[serde(rename = "expiresAt", alias = "expires_at", default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Semver range of app versions the flag is enabled for, e.g.
+    /// `>=2.3.0, <3` or `^2.3`, checked against
+    /// [`EvaluationContext::with_app_version`](crate::context::EvaluationContext::with_app_version).
+    /// Evaluations without an app version ignore it.
+    #[serde(rename = "appVersion", alias = "app_version", default, skip_serializing_if = "Option::is_none")]
+    pub app_version: Option<String>,
+    /// Targeting rules that must all hold for an evaluation with an
+    /// [`EvaluationContext`](crate::context::EvaluationContext); evaluations
+    /// without a context ignore them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<Rule>,
+    /// Percentage of users, 0 to 100, the flag is enabled for, bucketed by
+    /// [`EvaluationContext::with_user_id`](crate::context::EvaluationContext::with_user_id)
+    /// as described in [`bucketing`](crate::bucketing). Evaluations without a
+    /// context ignore it; a context without a user ID is outside the rollout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollout: Option<f64>,
+    /// A rollout percentage that grows over time, evaluated locally so the
+    /// ramp proceeds while the API is unreachable. Replaces `rollout`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ramp: Option<Ramp>,
+    /// Recurring periods the flag is active in, e.g. weekdays 09:00-17:00
+    /// UTC. An enabled flag with a schedule is disabled outside all of them;
+    /// snapshots and pinned flags report the served state without it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<TimeRule>,
+}
+
+/// A recurring period in UTC, see [`Details::schedule`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TimeRule {
+    /// Active during the minutes a five-field cron expression fires in, e.g.
+    /// `* 9-16 * * 1-5` for weekdays 09:00-17:00.
+    Cron { cron: String },
+    /// Active from `from` until `until` on `days`, or every day if empty. A
+    /// window ending before it starts runs overnight into the next day.
+    Window {
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        days: Vec<Weekday>,
+        from: NaiveTime,
+        until: NaiveTime,
+    },
+}
+
+/// A progressive rollout: `start` percent at `starts_at`, growing by `step`
+/// percent every `interval_secs` up to `max`, e.g. 5% now, +10% per hour up
+/// to 50%. Users are bucketed as for [`Details::rollout`], so each one stays
+/// enabled once the ramp reaches them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ramp {
+    #[serde(rename = "startsAt", alias = "starts_at")]
+    pub starts_at: DateTime<Utc>,
+    pub start: f64,
+    pub step: f64,
+    #[serde(rename = "intervalSecs", alias = "interval_secs")]
+    pub interval_secs: u64,
+    pub max: f64,
+}
+
+impl Ramp {
+    /// The rollout percentage at `now`; 0 before the ramp starts.
+    pub fn percentage_at(&self, now: DateTime<Utc>) -> f64 {
+        let Ok(elapsed) = (now - self.starts_at).to_std() else {
+            return 0.0;
+        };
+        let steps = match self.interval_secs {
+            0 => 0,
+            secs => elapsed.as_secs() / secs,
+        };
+        (self.start + self.step * steps as f64).min(self.max)
+    }
+}
+
+/// A set-membership condition on a context attribute, e.g. `region in
+/// [eu-west-1, eu-west-2]`. Values compare case-insensitively.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rule {
+    pub attribute: String,
+    pub operator: RuleOperator,
+    pub values: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleOperator {
+    /// The attribute is one of the values; fails if the context lacks it.
+    In,
+    /// The attribute is none of the values, or the context lacks it.
+    NotIn,
+}
+
+impl Rule {
+    /// Whether the rule holds for an attribute value, `None` if unset.
+    pub fn matches(&self, value: Option<&str>) -> bool {
+        let listed = value.is_some_and(|value| self.values.iter().any(|v| v.eq_ignore_ascii_case(value)));
+        match self.operator {
+            RuleOperator::In => listed,
+            RuleOperator::NotIn => !listed,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,3 +141,212 @@ pub struct FeatureFlag {
     pub enabled: bool,
     pub details: Details,
 }
+
+/// A flag left out of a refresh because it could not be decoded or failed
+/// validation, see [`Client::quarantined_flags`](crate::Client::quarantined_flags).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QuarantinedFlag {
+    /// `None` when the entry has no usable name.
+    pub name: Option<String>,
+    pub reason: String,
+}
+
+/// Where a cached flag came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlagSource {
+    Api,
+    /// An environment variable with the client's prefix, `FLAGS_` unless
+    /// changed with [`ClientBuilder::with_env_prefix`](crate::ClientBuilder::with_env_prefix).
+    Local,
+}
+
+impl FlagSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FlagSource::Api => "api",
+            FlagSource::Local => "local",
+        }
+    }
+}
+
+impl FeatureFlag {
+    /// Whether the flag is past its `expires_at`.
+    pub fn is_expired(&self) -> bool {
+        self.details.expires_at.is_some_and(|t| t <= Utc::now())
+    }
+
+    /// Why the flag can't be served, if it can't: an empty name, or a rollout
+    /// or ramp percentage outside 0 to 100.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        let percentage = |p: f64| (0.0..=100.0).contains(&p);
+        if self.details.name.trim().is_empty() {
+            return Err("empty name".to_string());
+        }
+        if let Some(rollout) = self.details.rollout.filter(|p| !percentage(*p)) {
+            return Err(format!("rollout {} is not between 0 and 100", rollout));
+        }
+        if let Some(ramp) = &self.details.ramp {
+            let valid = percentage(ramp.start) && percentage(ramp.max) && ramp.step.is_finite() && ramp.step >= 0.0;
+            if !valid {
+                return Err(format!(
+                    "ramp from {} by {} to {} is not within 0 to 100",
+                    ramp.start, ramp.step, ramp.max
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Local flags built from the environment carry a `local_` id prefix.
+    pub fn source(&self) -> FlagSource {
+        if self.details.id.starts_with("local_") {
+            FlagSource::Local
+        } else {
+            FlagSource::Api
+        }
+    }
+}
+
+/// A handle to one flag whose state is kept in an atomic and updated by every
+/// refresh, created with [`Client::pin`](crate::Client::pin).
+///
+/// Checking it is a single atomic load with no await, for decisions in tight
+/// loops. It never triggers a refresh itself: it follows refreshes driven by
+/// other evaluations or a background watch.
+#[derive(Debug, Clone)]
+pub struct PinnedFlag {
+    name: Arc<str>,
+    enabled: Arc<AtomicBool>,
+}
+
+impl PinnedFlag {
+    pub(crate) fn new(name: &str, enabled: Arc<AtomicBool>) -> Self {
+        Self {
+            name: Arc::from(name),
+            enabled,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+}
+
+/// Every flag as of one refresh, created with [`Client::snapshot`](crate::Client::snapshot).
+///
+/// Reads are synchronous map lookups, and a refresh landing while the
+/// snapshot is held does not change it, so one request can evaluate all its
+/// flags against a single consistent state.
+///
+/// Serializes as `{"flags": {"name": true, ...}}`, sorted by name, to hand
+/// the state to another process, which can serve it with
+/// [`Client::from_snapshot`](crate::Client::from_snapshot).
+#[derive(Clone)]
+pub struct FlagSnapshot {
+    flags: Arc<HashMap<String, bool>>,
+    usage: Arc<UsageTracker>,
+}
+
+impl FlagSnapshot {
+    pub(crate) fn new(flags: Arc<HashMap<String, bool>>, usage: Arc<UsageTracker>) -> Self {
+        Self { flags, usage }
+    }
+
+    /// Whether the flag is enabled; missing flags are disabled.
+    pub fn enabled(&self, name: &str) -> bool {
+        self.get(name).unwrap_or(false)
+    }
+
+    /// The flag's state, or `None` if it was not in the refresh.
+    pub fn get(&self, name: &str) -> Option<bool> {
+        let name = normalize_name(name);
+        let enabled = self.flags.get(name.as_ref()).copied();
+        if enabled.is_none() {
+            self.usage.record_missing(&name);
+        }
+        self.usage.record(&name, enabled.unwrap_or(false));
+        enabled
+    }
+
+    pub fn len(&self) -> usize {
+        self.flags.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flags.is_empty()
+    }
+
+    /// Every flag and its state, without counting evaluations.
+    pub(crate) fn flags(&self) -> &HashMap<String, bool> {
+        &self.flags
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotData<M> {
+    flags: M,
+}
+
+impl Serialize for FlagSnapshot {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let flags: BTreeMap<&str, bool> = self.flags.iter().map(|(name, enabled)| (name.as_str(), *enabled)).collect();
+        SnapshotData { flags }.serialize(serializer)
+    }
+}
+
+/// Names are normalized, so hand-written snapshots match as evaluations do.
+impl<'de> Deserialize<'de> for FlagSnapshot {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = SnapshotData::<HashMap<String, bool>>::deserialize(deserializer)?;
+        let flags = data
+            .flags
+            .into_iter()
+            .map(|(name, enabled)| (normalize_name(&name).into_owned(), enabled))
+            .collect();
+        let flags = Arc::new(flags);
+        let usage = UsageTracker::new(Arc::new(ArcSwap::new(Arc::clone(&flags))));
+        Ok(Self::new(flags, Arc::new(usage)))
+    }
+}
+
+impl std::fmt::Debug for FlagSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FlagSnapshot").field("flags", &self.flags).finish()
+    }
+}
+
+/// A flag name known at compile time, usually generated by
+/// [`codegen`](crate::codegen) from a manifest. Dereferences to the name, so
+/// `client.is(&keys::NEW_CHECKOUT)` works.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FlagKey(&'static str);
+
+impl FlagKey {
+    pub const fn new(name: &'static str) -> Self {
+        Self(name)
+    }
+
+    pub const fn name(&self) -> &'static str {
+        self.0
+    }
+}
+
+impl AsRef<str> for FlagKey {
+    fn as_ref(&self) -> &str {
+        self.0
+    }
+}
+
+impl Deref for FlagKey {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.0
+    }
+}
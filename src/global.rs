@@ -0,0 +1,55 @@
+//! A process-wide default client, for code that checks flags without being
+//! handed a [`Client`].
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> Result<(), flags_rs::FlagError> {
+//! flags_rs::init(flags_rs::Client::builder().build()?)?;
+//!
+//! // Anywhere else in the program
+//! if flags_rs::enabled("new-checkout").await {
+//!     // ...
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::OnceLock;
+
+use log::warn;
+
+use crate::{Client, FlagError};
+
+static GLOBAL: OnceLock<Client> = OnceLock::new();
+
+/// Install `client` as the global default. Fails if one is already installed.
+pub fn init(client: Client) -> Result<(), FlagError> {
+    GLOBAL
+        .set(client)
+        .map_err(|_| FlagError::BuilderError("The global client is already initialized".to_string()))
+}
+
+/// The global default client.
+///
+/// # Panics
+/// If [`init`] has not been called; see [`try_global`].
+pub fn global() -> &'static Client {
+    try_global().expect("flags_rs::init must be called before flags_rs::global")
+}
+
+/// The global default client, if [`init`] has been called.
+pub fn try_global() -> Option<&'static Client> {
+    GLOBAL.get()
+}
+
+/// Whether `name` is enabled on the global client. Reports `false`, with a
+/// warning, before [`init`] has been called.
+pub async fn enabled(name: &str) -> bool {
+    match try_global() {
+        Some(client) => client.is(name).enabled().await,
+        None => {
+            warn!("Flag '{}' checked before flags_rs::init; treating it as disabled", name);
+            false
+        }
+    }
+}
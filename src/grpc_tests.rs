@@ -0,0 +1,231 @@
+#[cfg(all(test, feature = "grpc", feature = "remote"))]
+mod tests {
+    use std::convert::Infallible;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use futures::future::BoxFuture;
+    use tonic::body::Body;
+    use tonic::codegen::http;
+    use tonic::server::NamedService;
+    use tonic::transport::server::TcpIncoming;
+    use tonic::{Request, Response, Status};
+    use tonic_prost::ProstCodec;
+
+    use crate::transport::grpc::proto::{Flag, FlagsRequest, FlagsResponse};
+    use crate::transport::grpc::{GrpcTransport, GET_FLAGS_PATH, WATCH_FLAGS_PATH};
+    use crate::{Auth, Client};
+
+    // Minimal stand-in for the generated tonic server, answering GetFlags with
+    // `get` and streaming every entry of `watch` from WatchFlags.
+    #[derive(Clone)]
+    struct FakeFlagsService {
+        get: FlagsResponse,
+        watch: Vec<FlagsResponse>,
+    }
+
+    impl NamedService for FakeFlagsService {
+        const NAME: &'static str = "flags.v1.FlagsService";
+    }
+
+    fn check_auth<T>(req: &Request<T>) -> Result<(), Status> {
+        match req.metadata().get("x-project-id") {
+            Some(v) if v == "test-project" => Ok(()),
+            _ => Err(Status::unauthenticated("missing project id")),
+        }
+    }
+
+    impl tower::Service<http::Request<Body>> for FakeFlagsService {
+        type Response = http::Response<Body>;
+        type Error = Infallible;
+        type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+            let this = self.clone();
+            Box::pin(async move {
+                let codec: ProstCodec<FlagsResponse, FlagsRequest> = ProstCodec::default();
+                let mut grpc = tonic::server::Grpc::new(codec);
+                let response = match req.uri().path() {
+                    GET_FLAGS_PATH => {
+                        let reply = this.get.clone();
+                        let svc = tower::service_fn(move |req: Request<FlagsRequest>| {
+                            let reply = reply.clone();
+                            async move {
+                                check_auth(&req)?;
+                                Ok::<_, Status>(Response::new(reply))
+                            }
+                        });
+                        grpc.unary(svc, req).await
+                    }
+                    WATCH_FLAGS_PATH => {
+                        let updates = this.watch.clone();
+                        let svc = tower::service_fn(move |req: Request<FlagsRequest>| {
+                            let updates = updates.clone();
+                            async move {
+                                check_auth(&req)?;
+                                let stream = futures::stream::iter(updates.into_iter().map(Ok::<_, Status>));
+                                Ok::<_, Status>(Response::new(stream))
+                            }
+                        });
+                        grpc.server_streaming(svc, req).await
+                    }
+                    _ => Status::unimplemented("unknown method").into_http(),
+                };
+                Ok(response)
+            })
+        }
+    }
+
+    fn flag_set(name: &str, enabled: bool) -> FlagsResponse {
+        FlagsResponse {
+            interval_allowed: 60,
+            flags: vec![Flag {
+                enabled,
+                name: name.to_string(),
+                id: "1".to_string(),
+            }],
+        }
+    }
+
+    async fn start_server(service: FakeFlagsService) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(service)
+                .serve_with_incoming(TcpIncoming::from(listener)),
+        );
+        format!("http://{}", addr)
+    }
+
+    fn create_test_client(endpoint: &str, project_id: &str) -> Client {
+        Client::builder()
+            .with_auth(Auth {
+                project_id: project_id.to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_transport(GrpcTransport::new(endpoint).unwrap())
+            .build()
+            .expect("Failed to build test client")
+    }
+
+    #[tokio::test]
+    async fn test_grpc_transport_fetches_flags() {
+        let endpoint = start_server(FakeFlagsService {
+            get: flag_set("GRPC-Flag", true),
+            watch: vec![],
+        })
+        .await;
+
+        let client = create_test_client(&endpoint, "test-project");
+        assert!(client.is("grpc-flag").enabled().await);
+        assert!(!client.is("missing-flag").enabled().await);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_transport_reports_status_errors() {
+        let endpoint = start_server(FakeFlagsService {
+            get: flag_set("grpc-flag", true),
+            watch: vec![],
+        })
+        .await;
+
+        let client = create_test_client(&endpoint, "wrong-project");
+        assert!(client.refetch().await.is_err());
+        assert!(!client.is("grpc-flag").enabled().await);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_watch_applies_streamed_updates() {
+        let endpoint = start_server(FakeFlagsService {
+            get: flag_set("kill-switch", false),
+            watch: vec![flag_set("kill-switch", false), flag_set("kill-switch", true)],
+        })
+        .await;
+
+        let client = create_test_client(&endpoint, "test-project");
+        assert!(!client.is("kill-switch").enabled().await);
+
+        let transport = GrpcTransport::new(&endpoint).unwrap();
+        let handle = transport.spawn_watch(&client);
+
+        let flipped = tokio::time::timeout(Duration::from_secs(5), async {
+            while !client.is("kill-switch").enabled().await {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await;
+        handle.abort();
+
+        assert!(flipped.is_ok(), "streamed update was never applied");
+    }
+
+    #[derive(Clone)]
+    struct FakeFlagdService;
+
+    impl NamedService for FakeFlagdService {
+        const NAME: &'static str = "flagd.evaluation.v1.Service";
+    }
+
+    impl tower::Service<http::Request<Body>> for FakeFlagdService {
+        type Response = http::Response<Body>;
+        type Error = Infallible;
+        type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+            use crate::transport::flagd::proto::{AnyFlag, ResolveAllRequest, ResolveAllResponse};
+
+            Box::pin(async move {
+                let codec: ProstCodec<ResolveAllResponse, ResolveAllRequest> = ProstCodec::default();
+                let mut grpc = tonic::server::Grpc::new(codec);
+                let svc = tower::service_fn(|_req: Request<ResolveAllRequest>| async move {
+                    let mut flags = std::collections::HashMap::new();
+                    flags.insert("flagd-on".to_string(), AnyFlag {
+                        reason: "STATIC".to_string(),
+                        variant: "on".to_string(),
+                        bool_value: Some(true),
+                    });
+                    flags.insert("flagd-string".to_string(), AnyFlag {
+                        reason: "STATIC".to_string(),
+                        variant: "red".to_string(),
+                        bool_value: None,
+                    });
+                    Ok::<_, Status>(Response::new(ResolveAllResponse { flags }))
+                });
+                Ok(grpc.unary(svc, req).await)
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flagd_grpc_backend() {
+        use crate::transport::flagd::FlagdTransport;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(FakeFlagdService)
+                .serve_with_incoming(TcpIncoming::from(listener)),
+        );
+
+        let client = Client::builder()
+            .with_transport(FlagdTransport::grpc(&format!("http://{}", addr)).unwrap())
+            .build()
+            .expect("Failed to build test client");
+
+        assert!(client.is("flagd-on").enabled().await);
+        assert_eq!(client.list().await.unwrap().len(), 1);
+    }
+}
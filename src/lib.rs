@@ -1,46 +1,231 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasher, Hasher};
+use std::marker::PhantomData;
+use std::net::SocketAddr;
 use std::env;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use tokio::sync::RwLock;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use tokio::sync::{watch, RwLock};
 use std::time::Duration;
+use tokio::time::Instant;
+use tokio_stream::Stream;
 
+use arc_swap::{ArcSwap, ArcSwapOption};
 use chrono::{DateTime, Utc};
-use log::{error, warn};
-use reqwest::header::{HeaderMap, HeaderValue};
-use serde::Deserialize;
+use log::{debug, error, warn};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
 use thiserror::Error;
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod bucketing;
+pub mod alias;
 pub mod cache;
+pub mod circuit;
+pub mod codegen;
+pub mod context;
+pub mod events;
+pub mod export;
 pub mod flag;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod pool;
+mod global;
+mod lifecycle;
+mod registry;
+mod schedule;
+pub mod shadow;
+mod stale;
+mod suggest;
+mod targeting;
+mod usage;
+mod version;
+pub mod transport;
 mod tests;
+mod local_tests;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
 
 #[cfg(feature = "tower-middleware")]
 pub mod middleware;
 
-#[cfg(all(test, feature = "tower-middleware"))]
+#[cfg(all(test, feature = "tower-middleware", feature = "remote"))]
 mod middleware_tests;
 
-use crate::cache::{Cache, MemoryCache};
-use crate::flag::{Details, FeatureFlag};
+#[cfg(all(test, feature = "grpc", feature = "remote"))]
+mod grpc_tests;
+
+#[cfg(feature = "grpc")]
+pub mod propagation;
+
+#[cfg(all(test, feature = "grpc", feature = "remote"))]
+mod propagation_tests;
+
+#[cfg(all(test, feature = "ffi"))]
+mod ffi_tests;
+
+#[cfg(feature = "relay")]
+pub mod relay;
+
+#[cfg(all(test, feature = "relay"))]
+mod relay_tests;
+
+#[cfg(feature = "test-util")]
+pub mod testing;
+
+#[cfg(feature = "management")]
+pub mod management;
+
+#[cfg(all(test, feature = "management"))]
+mod management_tests;
+
+#[cfg(all(test, feature = "metrics", feature = "remote"))]
+mod metrics_tests;
+
+#[cfg(feature = "axum")]
+pub mod routing;
+
+#[cfg(all(test, feature = "axum", feature = "remote"))]
+mod routing_tests;
+
+#[cfg(all(test, feature = "test-util"))]
+mod testing_tests;
+
+pub use crate::global::{enabled, global, init, try_global};
+pub use crate::usage::EvaluationCounts;
+#[cfg(feature = "macros")]
+pub use flags_rs_macros::feature_gated;
+
+use crate::bucketing::{BucketHasher, Murmur3};
+use crate::alias::AliasGenerator;
+use crate::cache::{Cache, MemoryCache, RefreshSchedule};
+use crate::circuit::{CircuitBreaker, DefaultCircuitBreaker, NoCircuitBreaker};
+use crate::context::{EvaluationContext, EvaluationMemo};
+use crate::export::Format;
+use crate::codegen::ManifestDrift;
+use crate::flag::{Details, FeatureFlag, FlagKey, FlagSnapshot, FlagSource, PinnedFlag, QuarantinedFlag};
+use crate::lifecycle::LifecycleTracker;
+use crate::events::{ClientEvent, EventBus, JsonLogSink};
+use crate::registry::FlagRegistry;
+use crate::shadow::{MismatchCallback, Shadow, ShadowMismatch};
+use crate::stale::StalePolicy;
+use crate::suggest::Suggestions;
+use crate::targeting::Targeting;
+use crate::usage::UsageTracker;
+use crate::transport::{FlagsUpdate, HttpOptions, RequestIdGenerator, Transport};
+#[cfg(feature = "remote")]
+use crate::transport::{HttpTransport, RequestInterceptor};
 
 const BASE_URL: &str = "https://api.flags.gg";
 const MAX_RETRIES: u32 = 3;
-
-#[derive(Debug, Clone)]
+// Prefix of environment variables read as local flags, see `with_env_prefix`
+const DEFAULT_ENV_PREFIX: &str = "FLAGS_";
+// Variables with this prefix configure the client and are never local flags
+const CONFIG_ENV_PREFIX: &str = "FLAGS_GG_";
+// How long evaluations skip the refresh path after an attempt published nothing
+const STALE_CHECK_DEBOUNCE: Duration = Duration::from_millis(250);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Auth {
     pub project_id: String,
     pub agent_id: String,
     pub environment_id: String,
+    /// Scope flags to one company (tenant) of the project, sent as `X-Company-ID`.
+    pub company_id: Option<String>,
+    /// Sign requests with this secret instead of relying on the IDs alone,
+    /// see [`transport::signing`]. The secret itself is never sent.
+    pub agent_secret: Option<String>,
+}
+
+impl Auth {
+    fn validate(&self) -> Result<(), String> {
+        if self.project_id.trim().is_empty() {
+            return Err("Project ID cannot be empty".to_string());
+        }
+        if self.agent_id.trim().is_empty() {
+            return Err("Agent ID cannot be empty".to_string());
+        }
+        if self.environment_id.trim().is_empty() {
+            return Err("Environment ID cannot be empty".to_string());
+        }
+        if self.company_id.as_ref().is_some_and(|id| id.trim().is_empty()) {
+            return Err("Company ID cannot be empty".to_string());
+        }
+        if self.agent_secret.as_ref().is_some_and(|secret| secret.is_empty()) {
+            return Err("Agent secret cannot be empty".to_string());
+        }
+        Ok(())
+    }
+
+    /// Trim whitespace around the IDs, such as a newline read from a secrets
+    /// file, and check that what remains can be sent as a header value.
+    fn sanitize(mut self) -> Result<Self, FlagError> {
+        let mut fields = vec![
+            ("project_id", &mut self.project_id),
+            ("agent_id", &mut self.agent_id),
+            ("environment_id", &mut self.environment_id),
+        ];
+        if let Some(company_id) = self.company_id.as_mut() {
+            fields.push(("company_id", company_id));
+        }
+        for (field, value) in fields {
+            let trimmed = value.trim();
+            if let Some((position, c)) = trimmed.chars().enumerate().find(|(_, c)| !(' '..='~').contains(c)) {
+                let kind = if c.is_control() { "a control" } else { "a non-ASCII" };
+                return Err(FlagError::InvalidCredentialFormat {
+                    field,
+                    reason: format!("contains {} character at position {}", kind, position),
+                });
+            }
+            if trimmed.len() != value.len() {
+                *value = trimmed.to_string();
+            }
+        }
+        Ok(self)
+    }
+}
+
+impl std::fmt::Debug for Auth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Auth")
+            .field("project_id", &self.project_id)
+            .field("agent_id", &self.agent_id)
+            .field("environment_id", &self.environment_id)
+            .field("company_id", &self.company_id)
+            .field("agent_secret", &self.agent_secret.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
 }
 
 pub struct Flag<'a> {
-    name: String,
+    name: Cow<'a, str>,
+    client: &'a Client,
+    context: Option<&'a EvaluationContext>,
+    memo: Option<&'a EvaluationMemo>,
+}
+
+/// A flag handle that owns a clone of its client, from [`Client::flag`], to
+/// keep in long-lived components or move into spawned tasks.
+#[derive(Clone)]
+pub struct OwnedFlag {
+    name: Arc<str>,
+    client: Client,
+    context: Option<Arc<EvaluationContext>>,
+}
+
+/// A server-defined set of related flags, from [`Client::group`].
+pub struct FlagGroup<'a> {
+    name: Cow<'a, str>,
     client: &'a Client,
 }
 
 #[derive(Debug, Error)]
 pub enum FlagError {
+    #[cfg(feature = "remote")]
     #[error("HTTP error: {0}")]
     HttpError(#[from] reqwest::Error),
 
@@ -52,66 +237,655 @@ pub enum FlagError {
 
     #[error("API error: {0}")]
     ApiError(String),
+
+    /// The API answered with an unsuccessful HTTP `status`. Server errors and
+    /// `429 Too Many Requests` are retryable, other statuses are not.
+    #[error("API error: {message}")]
+    ApiStatus { status: u16, message: String },
     
     #[error("Builder error: {0}")]
     BuilderError(String),
+
+    #[error("Deprecated flag: {0}")]
+    DeprecatedFlag(String),
+
+    /// A flag evaluated in strict mode without being registered, see
+    /// [`ClientBuilder::strict`].
+    #[error("Unregistered flag: {0}")]
+    UnregisteredFlag(String),
+
+    /// An evaluated flag that does not exist but closely matches one that does.
+    #[error("Unknown flag: {0}")]
+    UnknownFlag(String),
+
+    /// No refresh succeeded within [`ClientBuilder::with_max_stale`]; flags
+    /// are served from their stale defaults until one does.
+    #[error("Stale flags: {0}")]
+    StaleFlags(String),
+
+    /// A local flag variable whose value is not a recognised boolean; the flag
+    /// is treated as disabled.
+    #[error("Invalid local flag: {0}")]
+    InvalidLocalFlag(String),
+
+    /// Flags whose names are equal once normalized, from the API or local
+    /// variables, that differ in spelling or state. One of them is served,
+    /// chosen the same way on every refresh.
+    #[error("Duplicate flag: {0}")]
+    DuplicateFlag(String),
+
+    /// A flag bundle that could not be read, is malformed, or whose signature
+    /// does not match, see [`transport::bundle`].
+    #[error("Invalid flag bundle: {0}")]
+    InvalidBundle(String),
+
+    /// An [`Auth`] field that can't be sent as a header value. The message
+    /// names the field and what is wrong with it, never the value.
+    #[error("Invalid credential format: {field} {reason}")]
+    InvalidCredentialFormat { field: &'static str, reason: String },
+
+    /// Flags listed in [`ClientBuilder::require_flags`] that the fetched flag
+    /// set does not contain, reported by [`Client::wait_until_ready`].
+    #[error("Missing required flags: {}", .0.join(", "))]
+    MissingRequiredFlags(Vec<String>),
+
+    /// A failed API request, tagged with the `X-Request-ID` it was sent with.
+    #[error("{source} (request ID: {request_id})")]
+    Request {
+        request_id: String,
+        source: Box<FlagError>,
+    },
+}
+
+impl FlagError {
+    /// The `X-Request-ID` of the API call that failed, for correlating with
+    /// server-side logs.
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            FlagError::Request { request_id, .. } => Some(request_id),
+            _ => None,
+        }
+    }
+
+    /// The HTTP status the API answered with, for [`FlagError::ApiStatus`].
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            FlagError::ApiStatus { status, .. } => Some(*status),
+            FlagError::Request { source, .. } => source.status(),
+            _ => None,
+        }
+    }
+
+    /// A stable, machine-readable name for the kind of error, e.g. `"http"` or
+    /// `"stale_flags"`, for branching on error categories without matching on
+    /// messages. Errors tagged with a request ID report their source's code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "remote")]
+            FlagError::HttpError(_) => "http",
+            FlagError::CacheError(_) => "cache",
+            FlagError::AuthError(_) => "auth",
+            FlagError::ApiError(_) | FlagError::ApiStatus { .. } => "api",
+            FlagError::BuilderError(_) => "builder",
+            FlagError::DeprecatedFlag(_) => "deprecated_flag",
+            FlagError::UnregisteredFlag(_) => "unregistered_flag",
+            FlagError::UnknownFlag(_) => "unknown_flag",
+            FlagError::StaleFlags(_) => "stale_flags",
+            FlagError::InvalidLocalFlag(_) => "invalid_local_flag",
+            FlagError::DuplicateFlag(_) => "duplicate_flag",
+            FlagError::InvalidBundle(_) => "invalid_bundle",
+            FlagError::InvalidCredentialFormat { .. } => "invalid_credential_format",
+            FlagError::MissingRequiredFlags(_) => "missing_required_flags",
+            FlagError::Request { source, .. } => source.code(),
+        }
+    }
+
+    /// Whether the same operation may succeed if tried again: network and
+    /// cache failures, unexpected API responses and server errors, but not
+    /// rejected requests, configuration mistakes or warnings about how flags
+    /// are used.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            #[cfg(feature = "remote")]
+            FlagError::HttpError(e) => !e.is_builder(),
+            FlagError::CacheError(_) | FlagError::ApiError(_) => true,
+            FlagError::ApiStatus { status, .. } => *status >= 500 || *status == 429,
+            FlagError::AuthError(_)
+            | FlagError::BuilderError(_)
+            | FlagError::DeprecatedFlag(_)
+            | FlagError::UnregisteredFlag(_)
+            | FlagError::UnknownFlag(_)
+            | FlagError::StaleFlags(_)
+            | FlagError::InvalidLocalFlag(_)
+            | FlagError::DuplicateFlag(_)
+            | FlagError::InvalidBundle(_)
+            | FlagError::InvalidCredentialFormat { .. }
+            | FlagError::MissingRequiredFlags(_) => false,
+            FlagError::Request { source, .. } => source.is_retryable(),
+        }
+    }
+
+    /// An [`Auth`] field rejected as a header value.
+    #[cfg_attr(not(any(feature = "remote", feature = "grpc")), allow(dead_code))]
+    pub(crate) fn invalid_header(field: &'static str) -> FlagError {
+        FlagError::InvalidCredentialFormat {
+            field,
+            reason: "is not a valid header value".to_string(),
+        }
+    }
+
+    #[cfg(feature = "remote")]
+    pub(crate) fn for_request(self, request_id: &str) -> FlagError {
+        match self {
+            FlagError::Request { .. } => self,
+            source => FlagError::Request {
+                request_id: request_id.to_string(),
+                source: Box::new(source),
+            },
+        }
+    }
+}
+
+/// Serializes as `{"code", "message", "retryable"}`, plus `"request_id"` when
+/// the error carries one, for structured logging pipelines.
+impl Serialize for FlagError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let request_id = self.request_id();
+        let mut state = serializer.serialize_struct("FlagError", 3 + usize::from(request_id.is_some()))?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("retryable", &self.is_retryable())?;
+        if let Some(request_id) = request_id {
+            state.serialize_field("request_id", request_id)?;
+        }
+        state.end()
+    }
+}
+
+/// A full flag set as returned by the flags API.
+///
+/// Decoding is lenient: a malformed entry in `flags` is skipped and described
+/// in `skipped` instead of failing the whole response, and unknown fields are
+/// ignored.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(from = "RawApiResponse")]
+pub struct ApiResponse {
+    pub interval_allowed: i32,
+    pub flags: Vec<flag::FeatureFlag>,
+    /// Why each skipped entry could not be decoded.
+    pub skipped: Vec<String>,
+    /// Identifies this flag set for delta refreshes, when the server provides one.
+    pub version: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawApiResponse {
+    #[serde(rename = "intervalAllowed")]
+    interval_allowed: i32,
+    flags: Vec<serde_json::Value>,
+    #[serde(default)]
+    version: Option<String>,
+}
+
+impl From<RawApiResponse> for ApiResponse {
+    fn from(raw: RawApiResponse) -> Self {
+        let (flags, skipped) = decode_flags(raw.flags);
+        ApiResponse {
+            interval_allowed: raw.interval_allowed,
+            flags,
+            skipped,
+            version: raw.version,
+        }
+    }
+}
+
+impl ApiResponse {
+    /// The flag set that results from applying `delta` on top of this one.
+    /// Names are matched case-insensitively, like evaluations.
+    pub fn apply_delta(&self, delta: FlagsDelta) -> ApiResponse {
+        let replaced: std::collections::HashSet<String> = delta
+            .removed
+            .iter()
+            .map(String::as_str)
+            .chain(delta.upserted.iter().map(|f| f.details.name.as_str()))
+            .map(|name| normalize_name(name).into_owned())
+            .collect();
+
+        let mut flags: Vec<FeatureFlag> = self
+            .flags
+            .iter()
+            .filter(|f| !replaced.contains(normalize_name(&f.details.name).as_ref()))
+            .cloned()
+            .collect();
+        flags.extend(delta.upserted);
+
+        ApiResponse {
+            interval_allowed: delta.interval_allowed,
+            flags,
+            skipped: delta.skipped,
+            version: Some(delta.version),
+        }
+    }
 }
 
-#[derive(Debug)]
-struct CircuitState {
-    is_open: bool,
-    failure_count: u32,
-    last_failure: Option<DateTime<Utc>>,
+/// The changes to a flag set since a known version, from a delta refresh.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(from = "RawFlagsDelta")]
+pub struct FlagsDelta {
+    pub interval_allowed: i32,
+    /// Version of the flag set after applying the delta.
+    pub version: String,
+    /// Flags that were added or changed.
+    pub upserted: Vec<flag::FeatureFlag>,
+    /// Names of flags that were removed.
+    pub removed: Vec<String>,
+    /// Why each skipped entry of `upserted` could not be decoded.
+    pub skipped: Vec<String>,
 }
 
-#[derive(Debug, Deserialize)]
-struct ApiResponse {
+#[derive(Deserialize)]
+struct RawFlagsDelta {
     #[serde(rename = "intervalAllowed")]
     interval_allowed: i32,
-    flags: Vec<flag::FeatureFlag>,
+    version: String,
+    #[serde(default)]
+    upserted: Vec<serde_json::Value>,
+    #[serde(default)]
+    removed: Vec<String>,
+}
+
+impl From<RawFlagsDelta> for FlagsDelta {
+    fn from(raw: RawFlagsDelta) -> Self {
+        let (upserted, skipped) = decode_flags(raw.upserted);
+        FlagsDelta {
+            interval_allowed: raw.interval_allowed,
+            version: raw.version,
+            upserted,
+            removed: raw.removed,
+            skipped,
+        }
+    }
+}
+
+/// Decode each entry on its own so one malformed flag does not fail the rest.
+fn decode_flags(values: Vec<serde_json::Value>) -> (Vec<FeatureFlag>, Vec<String>) {
+    let mut flags = Vec::with_capacity(values.len());
+    let mut skipped = Vec::new();
+    for (index, value) in values.into_iter().enumerate() {
+        match serde_json::from_value(value) {
+            Ok(flag) => flags.push(flag),
+            Err(e) => skipped.push(format!("flag {}: {}", index, e)),
+        }
+    }
+    (flags, skipped)
+}
+
+/// Encoding requested from the flags API when fetching flags.
+///
+/// JSON is always understood; the response `Content-Type` decides how the
+/// body is decoded, so a server that ignores the `Accept` header still works.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WireFormat {
+    #[default]
+    Json,
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+impl WireFormat {
+    #[cfg(feature = "remote")]
+    pub(crate) fn accept(&self) -> &'static str {
+        match self {
+            WireFormat::Json => "application/json",
+            #[cfg(feature = "msgpack")]
+            WireFormat::MessagePack => "application/msgpack, application/json;q=0.5",
+        }
+    }
+}
+
+/// How local environment flags combine with flags from the API.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LocalFlagPrecedence {
+    /// A local flag replaces the API flag of the same name.
+    #[default]
+    LocalOverridesApi,
+    /// The API flag wins; local flags only add names the API does not define.
+    ApiOverridesLocal,
+    /// Local flags are only served while no API flag set is available, i.e.
+    /// without credentials or after a failed fetch, and are ignored once the
+    /// API answers.
+    LocalOnlyIfApiMissing,
+}
+
+/// What a flag evaluates to when its real state is unknown: the cache
+/// errored, the flag is missing, or the cache is past
+/// [`with_max_stale`](ClientBuilder::with_max_stale).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureMode {
+    /// Evaluate to `true`, e.g. for a maintenance switch that must stay reachable.
+    Open,
+    /// Evaluate to `false`, e.g. for a gate in front of an unfinished API.
+    Closed,
+}
+
+impl FailureMode {
+    fn enabled(self) -> bool {
+        self == FailureMode::Open
+    }
+}
+
+/// What an `intervalAllowed` of zero or less from the server means, see
+/// [`ClientBuilder::with_zero_interval`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ZeroInterval {
+    /// Don't cache: refresh on the next evaluation, but at most once per
+    /// second so a busy service does not call the API on every check.
+    #[default]
+    NoCache,
+    /// Keep the flags until something else triggers a refresh, such as
+    /// [`Client::clear_cache`], [`Client::set_auth`] or a
+    /// [`with_max_refresh_interval`](ClientBuilder::with_max_refresh_interval) bound.
+    CacheForever,
+}
+
+/// A client's configuration, from [`Client::config`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientConfig {
+    pub base_url: String,
+    pub max_retries: u32,
+    /// Type name of the flag cache, e.g. `flags_rs::cache::MemoryCache`.
+    pub cache_type: &'static str,
+    /// Whether the client has credentials for the flags.gg API.
+    pub authenticated: bool,
+    pub environment_id: Option<String>,
+    pub coalesce_refresh: bool,
+    pub delta_refresh: bool,
+    pub circuit_breaker_disabled: bool,
+    pub local_precedence: LocalFlagPrecedence,
+    pub env_prefix: String,
+    /// The Cargo features the crate was compiled with.
+    pub features: Vec<&'static str>,
+}
+
+/// How a client is serving flags, from [`Client::status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientStatus {
+    /// No refresh has completed yet.
+    Initializing,
+    /// The last refresh succeeded, or the client does not use an API.
+    Healthy,
+    /// The last refresh failed after flags were fetched from the API; the
+    /// cached flags are served.
+    Degraded,
+    /// The last refresh failed and nothing has been fetched from the API yet,
+    /// as on a cold start during an outage. Missing flags evaluate to their
+    /// [bootstrap values](ClientBuilder::with_bootstrap_flags).
+    ColdStartDegraded,
 }
 
 pub type ErrorCallback = Arc<dyn Fn(&FlagError) + Send + Sync>;
+pub type MissingFlagCallback = Arc<dyn Fn(&str) + Send + Sync>;
+pub type SlowRefreshCallback = Arc<dyn Fn(Duration) + Send + Sync>;
 
 pub struct Client {
     base_url: String,
-    http_client: reqwest::Client,
-    cache: Arc<RwLock<Box<dyn Cache + Send + Sync>>>,
+    transport: Arc<dyn Transport>,
+    cache: Arc<dyn Cache + Send + Sync>,
     max_retries: u32,
-    circuit_state: Arc<RwLock<CircuitState>>,
-    auth: Option<Auth>,
+    circuit_breaker: Arc<dyn CircuitBreaker>,
+    // Swapped by `set_auth`, shared with clones
+    auth: Arc<ArcSwapOption<Auth>>,
     refresh_in_progress: Arc<AtomicBool>,
+    refresh_done: Arc<watch::Sender<u64>>,
+    coalesce_refresh: bool,
+    // `with_circuit_breaker_disabled`: list() returns refresh errors
+    circuit_breaker_disabled: bool,
+    delta_refresh: bool,
+    // Last full API flag set, the base deltas are applied to
+    api_state: Arc<ArcSwapOption<ApiResponse>>,
     error_callback: Option<ErrorCallback>,
+    missing_flag_callback: Option<MissingFlagCallback>,
+    // Missing flags already passed to `missing_flag_callback`
+    missing_reported: Arc<Mutex<HashSet<String>>>,
+    missing_flag_default: bool,
+    last_refresh: Arc<RwLock<Option<DateTime<Utc>>>>,
+    schedule: Arc<RefreshSchedule>,
+    // Bumped by every publish, so memoized results from before it are ignored
+    refresh_generation: Arc<AtomicU64>,
+    // Whether a response from the API has been published, since the client
+    // was built or its credentials or cache were last reset
+    api_snapshot: Arc<AtomicBool>,
+    // Whether the last refresh failed or was refused by the circuit breaker
+    refresh_failed: Arc<AtomicBool>,
+    bootstrap_flags: Arc<HashMap<String, bool>>,
+    events: Arc<EventBus>,
+    slow_refresh: Option<(Duration, SlowRefreshCallback)>,
+    #[cfg(feature = "metrics")]
+    refresh_histogram: Arc<metrics::RefreshHistogram>,
+    local_flags: Arc<ArcSwap<Vec<FeatureFlag>>>,
+    env_prefix: Arc<str>,
+    local_precedence: LocalFlagPrecedence,
+    refresh_jitter_percent: u8,
+    refresh_interval: RefreshInterval,
+    pinned: Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>,
+    subscriptions: Arc<Mutex<HashMap<String, watch::Sender<bool>>>>,
+    lifecycle: Arc<LifecycleTracker>,
+    registry: Option<Arc<FlagRegistry>>,
+    required_flags: Arc<[String]>,
+    stale: Option<Arc<StalePolicy>>,
+    failure_modes: Arc<HashMap<String, FailureMode>>,
+    shadow: Option<Arc<Shadow>>,
+    usage: Arc<UsageTracker>,
+    suggestions: Arc<Suggestions>,
+    // Group name to sorted member flag names
+    groups: Arc<ArcSwap<HashMap<String, Vec<String>>>>,
+    alias_generator: Option<AliasGenerator>,
+    // Alias to flag name for the published flags, see `alias::index`
+    aliases: Arc<ArcSwap<HashMap<String, String>>>,
+    // Flags left out of the last API refresh
+    quarantined: Arc<ArcSwap<Vec<QuarantinedFlag>>>,
+    // Targeting of the published flags that declare any
+    targeting: Arc<ArcSwap<HashMap<String, Targeting>>>,
+    bucket_hasher: Arc<dyn BucketHasher>,
+    #[cfg(feature = "management")]
+    management: Option<Arc<management::Management>>,
+    // Enabled state of every flag in the last published refresh
+    published: Arc<ArcSwap<HashMap<String, bool>>>,
+    // Clients scoped to a company other than `auth.company_id`, keyed by company ID
+    tenants: Arc<Mutex<HashMap<String, Client>>>,
+    // The configuration this client was built from, for `to_builder`
+    origin: Arc<ClientBuilder>,
+}
+
+impl std::fmt::Debug for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let config = self.config();
+        f.debug_struct("Client")
+            .field("base_url", &config.base_url)
+            .field("max_retries", &config.max_retries)
+            .field("auth", &self.auth.load_full())
+            .field("cache_type", &config.cache_type)
+            .field("coalesce_refresh", &config.coalesce_refresh)
+            .field("delta_refresh", &config.delta_refresh)
+            .field("circuit_breaker_disabled", &config.circuit_breaker_disabled)
+            .field("local_precedence", &config.local_precedence)
+            .field("env_prefix", &config.env_prefix)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Client {
     pub fn builder() -> ClientBuilder {
         ClientBuilder::new()
     }
+
+    /// A builder that only offers `build()` once credentials or offline mode
+    /// have been chosen, see [`NeedsAuth`].
+    pub fn typed_builder() -> ClientBuilder<NeedsAuth> {
+        ClientBuilder::new().into_state()
+    }
+
+    /// A builder with the configuration this client was built from, to derive
+    /// a variant such as one for another environment without repeating it.
+    ///
+    /// The derived client reuses this client's HTTP connection pool, transport,
+    /// callbacks and cache, but none of its flag state. Give a client for
+    /// different credentials its own cache with
+    /// [`with_memory_cache`](ClientBuilder::with_memory_cache) or
+    /// [`with_cache`](ClientBuilder::with_cache), or the two overwrite each
+    /// other's flags.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::{Auth, Client};
+    /// # fn example(client: &Client, auth: Auth) -> Result<(), flags_rs::FlagError> {
+    /// let staging = client
+    ///     .to_builder()
+    ///     .with_auth(Auth { environment_id: "staging".into(), ..auth })
+    ///     .with_memory_cache()
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_builder(&self) -> ClientBuilder {
+        let mut builder = (*self.origin).clone();
+        builder.auth = self.auth.load_full().map(|auth| (*auth).clone());
+        builder
+    }
+
+    /// Use `auth` for every fetch from now on, for this client and its clones,
+    /// e.g. after an operator or secret manager rotated the IDs. The next
+    /// evaluation refreshes with the new credentials. Until then, or for
+    /// longer while the circuit breaker is open, flags fetched with the old
+    /// ones are still served unless removed with [`clear_cache`](Self::clear_cache).
+    ///
+    /// Fails with [`FlagError::AuthError`], keeping the current credentials,
+    /// if `auth` has an empty ID or secret, or with
+    /// [`FlagError::InvalidCredentialFormat`] if an ID can't be sent as a header.
+    /// Whitespace around the IDs is trimmed.
+    pub fn set_auth(&self, auth: Auth) -> Result<(), FlagError> {
+        auth.validate().map_err(FlagError::AuthError)?;
+        let auth = auth.sanitize()?;
+        self.auth.store(Some(Arc::new(auth)));
+        // Delta versions and tenant clients belong to the old credentials
+        self.api_state.store(None);
+        self.api_snapshot.store(false, Ordering::Release);
+        self.tenants.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        self.schedule.invalidate();
+        Ok(())
+    }
+
+    /// Drop every flag fetched from the API, keeping local flags, so
+    /// evaluations fall back to their defaults until the next refresh succeeds.
+    pub async fn clear_cache(&self) -> Result<(), FlagError> {
+        let local_flags = self.local_flags.load();
+        self.api_state.store(None);
+        self.api_snapshot.store(false, Ordering::Release);
+        self.publish(&local_flags, 60).await?;
+        self.schedule.invalidate();
+        Ok(())
+    }
     
     fn handle_error(&self, error: &FlagError) {
+        self.events.log_error(error);
         if let Some(ref callback) = self.error_callback {
             callback(error);
         }
     }
 
+    /// The configuration this client runs with, for assertions and
+    /// diagnostics. Credentials other than the environment ID are left out.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// # fn example(client: &Client) {
+    /// let config = client.config();
+    /// println!("flags from {} cached in {}", config.base_url, config.cache_type);
+    /// # }
+    /// ```
+    pub fn config(&self) -> ClientConfig {
+        let auth = self.auth.load();
+        ClientConfig {
+            base_url: self.base_url.clone(),
+            max_retries: self.max_retries,
+            cache_type: self.origin.cache_type,
+            authenticated: auth.is_some(),
+            environment_id: auth.as_ref().map(|auth| auth.environment_id.clone()),
+            coalesce_refresh: self.coalesce_refresh,
+            delta_refresh: self.delta_refresh,
+            circuit_breaker_disabled: self.circuit_breaker_disabled,
+            local_precedence: self.local_precedence,
+            env_prefix: self.env_prefix.to_string(),
+            features: enabled_features(),
+        }
+    }
+
     pub fn debug_info(&self) -> String {
         format!(
             "Client {{ base_url: {}, max_retries: {}, auth: {:?} }}",
-            self.base_url, self.max_retries, self.auth
+            self.base_url, self.max_retries, self.auth.load_full()
         )
     }
 
-    pub fn is(&self, name: &str) -> Flag<'_> {
+    pub fn is<'a>(&'a self, name: &'a str) -> Flag<'a> {
         Flag {
-            name: name.to_string(),
+            name: normalize_name(name),
             client: self,
+            context: None,
+            memo: None,
+        }
+    }
+
+    /// Like [`is`](Self::is), but without borrowing the client.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// # fn example(client: &Client) {
+    /// let new_auth = client.flag("new-auth");
+    /// tokio::spawn(async move {
+    ///     if new_auth.enabled().await {
+    ///         // ...
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub fn flag(&self, name: &str) -> OwnedFlag {
+        OwnedFlag {
+            name: normalize_name(name).into(),
+            client: self.clone(),
+            context: None,
         }
     }
     
+    /// A group of flags defined on the server, queried as a unit.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// # async fn example(client: &Client) {
+    /// if client.group("checkout-redesign").all_enabled().await {
+    ///     // Every flag of the redesign is on
+    /// }
+    /// # }
+    /// ```
+    pub fn group<'a>(&'a self, name: &'a str) -> FlagGroup<'a> {
+        FlagGroup {
+            name: normalize_name(name),
+            client: self,
+        }
+    }
+
+    /// Every known group with its member flag names.
+    pub async fn groups(&self) -> HashMap<String, Vec<String>> {
+        self.refresh_if_needed().await;
+        self.groups.load().as_ref().clone()
+    }
+
     /// Get the enabled status of multiple flags at once.
     /// This is more efficient than checking flags individually as it only
-    /// requires a single cache lock and potential refresh.
+    /// requires a single staleness check and potential refresh.
     /// 
     /// # Example
     /// ```no_run
@@ -125,37 +899,42 @@ impl Client {
     /// ```
     pub async fn get_multiple(&self, names: &[&str]) -> HashMap<String, bool> {
         // Ensure cache is refreshed if needed (only once for all flags)
-        if self.cache.read().await.should_refresh_cache().await {
-            if self.refresh_in_progress.compare_exchange(
-                false, 
-                true, 
-                Ordering::SeqCst, 
-                Ordering::SeqCst
-            ).is_ok() {
-                if let Err(e) = self.refetch().await {
-                    error!("Failed to refetch flags for batch operation: {}", e);
-                    self.handle_error(&e);
-                }
-                self.refresh_in_progress.store(false, Ordering::SeqCst);
-            }
-        }
+        self.refresh_if_needed().await;
 
-        // Now get all flags with a single cache lock
-        let cache = self.cache.read().await;
         let mut results = HashMap::with_capacity(names.len());
         
         for &name in names {
-            let normalized_name = name.to_lowercase();
-            match cache.get(&normalized_name).await {
-                Ok((enabled, exists)) => {
-                    results.insert(name.to_string(), exists && enabled);
-                }
-                Err(_) => {
-                    results.insert(name.to_string(), false);
-                }
+            let mut normalized = normalize_name(name);
+            if let Cow::Owned(flag) = self.resolve_alias(&normalized) {
+                normalized = Cow::Owned(flag);
+            }
+            if self.reject_unregistered(&normalized) {
+                results.insert(name.to_string(), false);
+                continue;
             }
+            self.warn_lifecycle(&normalized);
+            let enabled = match self.stale_default(&normalized) {
+                Some(enabled) => enabled,
+                None => match self.cache.get(&normalized).await {
+                    Ok((enabled, true)) => enabled && self.scheduled(&normalized),
+                    Ok((_, false)) => match self.bootstrap_value(&normalized) {
+                        Some(enabled) => enabled,
+                        None => self.missing_value(&normalized).await,
+                    },
+                    Err(_) => self
+                        .failure_value(&normalized)
+                        .or_else(|| self.bootstrap_value(&normalized))
+                        .unwrap_or(false),
+                },
+            };
+            self.usage.record(&normalized, enabled);
+            results.insert(name.to_string(), enabled);
         }
-        
+
+        if let Some(shadow) = &self.shadow {
+            shadow.compare_all(names, &results).await;
+        }
+
         results
     }
     
@@ -171,14 +950,138 @@ impl Client {
     /// # }
     /// ```
     pub async fn all_enabled(&self, names: &[&str]) -> bool {
+        self.all_enabled_detailed(names).await.is_ok()
+    }
+
+    /// [`all_enabled`](Self::all_enabled), naming the flags that are disabled
+    /// or missing, in the order given, when not all are enabled.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// # async fn example(client: &Client) {
+    /// if let Err(blocked) = client.all_enabled_detailed(&["new-checkout", "new-payments"]).await {
+    ///     log::info!("New checkout flow needs {} enabled", blocked.join(", "));
+    /// }
+    /// # }
+    /// ```
+    pub async fn all_enabled_detailed(&self, names: &[&str]) -> Result<(), Vec<String>> {
         if names.is_empty() {
-            return true;
+            return Ok(());
         }
-        
+
         let flags = self.get_multiple(names).await;
-        names.iter().all(|&name| flags.get(name).copied().unwrap_or(false))
+        let blocked: Vec<String> = names
+            .iter()
+            .filter(|&&name| !flags.get(name).copied().unwrap_or(false))
+            .map(|name| name.to_string())
+            .collect();
+        if blocked.is_empty() {
+            Ok(())
+        } else {
+            Err(blocked)
+        }
     }
     
+    /// Compare the flags the code knows, e.g. a generated `keys::ALL`, with the
+    /// flags the API serves, logging a warning for each difference. Without
+    /// credentials there is nothing to compare and the drift is empty.
+    pub async fn check_manifest(&self, keys: &[FlagKey]) -> ManifestDrift {
+        if self.auth.load().is_none() && self.transport.requires_auth() {
+            return ManifestDrift::default();
+        }
+        self.refresh_if_needed().await;
+
+        let served: HashSet<String> = match self.cache.get_all().await {
+            Ok(flags) => flags
+                .iter()
+                .filter(|f| f.source() == FlagSource::Api)
+                .map(|f| f.details.name.clone())
+                .collect(),
+            Err(e) => {
+                self.handle_error(&FlagError::CacheError(e.to_string()));
+                return ManifestDrift::default();
+            }
+        };
+        let known: HashSet<String> = keys.iter().map(|k| normalize_name(k).into_owned()).collect();
+
+        let mut drift = ManifestDrift {
+            missing_from_api: known.difference(&served).cloned().collect(),
+            missing_from_manifest: served.difference(&known).cloned().collect(),
+        };
+        drift.missing_from_api.sort();
+        drift.missing_from_manifest.sort();
+        for name in &drift.missing_from_api {
+            warn!("Flag '{}' is in the manifest but not served by the API", name);
+        }
+        for name in &drift.missing_from_manifest {
+            warn!("Flag '{}' is served by the API but missing from the manifest", name);
+        }
+        drift
+    }
+
+    /// API flags in the cache that have not been evaluated since the client
+    /// started, sorted. Flags that stay unused across a full release cycle are
+    /// candidates for deleting from the dashboard.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// # async fn example(client: &Client) {
+    /// for name in client.unused_flags().await {
+    ///     log::info!("'{}' has not been evaluated", name);
+    /// }
+    /// # }
+    /// ```
+    pub async fn unused_flags(&self) -> Vec<String> {
+        let flags = match self.cache.get_all().await {
+            Ok(flags) => flags,
+            Err(e) => {
+                self.handle_error(&FlagError::CacheError(e.to_string()));
+                return Vec::new();
+            }
+        };
+        let mut unused: Vec<String> = flags
+            .iter()
+            .filter(|f| f.source() == FlagSource::Api && !self.usage.is_used(&f.details.name))
+            .map(|f| f.details.name.clone())
+            .collect();
+        unused.sort();
+        unused
+    }
+
+    /// Flags left out of the last refresh from the API because they could
+    /// not be decoded or were invalid, e.g. with an empty name or a rollout
+    /// above 100%. The rest of that refresh is served as usual.
+    pub fn quarantined_flags(&self) -> Vec<QuarantinedFlag> {
+        self.quarantined.load().as_ref().clone()
+    }
+
+    /// How often each flag was evaluated since the client was built or
+    /// [`reset_evaluation_counts`](Self::reset_evaluation_counts), by the
+    /// value served, for "top evaluated flags" diagnostics.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// # fn example(client: &Client) {
+    /// let mut counts: Vec<_> = client.evaluation_counts().into_iter().collect();
+    /// counts.sort_by(|a, b| b.1.evaluated.cmp(&a.1.evaluated));
+    /// for (name, counts) in counts.iter().take(10) {
+    ///     log::info!("{}: {} evaluations, {} enabled", name, counts.evaluated, counts.enabled);
+    /// }
+    /// # }
+    /// ```
+    pub fn evaluation_counts(&self) -> HashMap<String, EvaluationCounts> {
+        self.usage.counts()
+    }
+
+    /// Zero the counts of [`evaluation_counts`](Self::evaluation_counts).
+    /// [`unused_flags`](Self::unused_flags) still treats counted flags as used.
+    pub fn reset_evaluation_counts(&self) {
+        self.usage.reset();
+    }
+
     /// Check if any of the specified flags are enabled.
     /// 
     /// # Example
@@ -199,402 +1102,2310 @@ impl Client {
         names.iter().any(|&name| flags.get(name).copied().unwrap_or(false))
     }
 
-    pub async fn list(&self) -> Result<Vec<flag::FeatureFlag>, FlagError> {
-        // Check if cache needs refresh and ensure only one refresh happens
-        if self.cache.read().await.should_refresh_cache().await {
-            // Try to acquire the refresh lock
-            if self.refresh_in_progress.compare_exchange(
-                false, 
-                true, 
-                Ordering::SeqCst, 
-                Ordering::SeqCst
-            ).is_ok() {
-                // We got the lock, perform the refresh
-                if let Err(e) = self.refetch().await {
-                    error!("Failed to refetch flags for list: {}", e);
-                    self.handle_error(&e);
-                }
-                // Release the refresh lock
-                self.refresh_in_progress.store(false, Ordering::SeqCst);
-            }
-            // If we didn't get the lock, another thread is refreshing
+    /// Read a flag from the last refresh without awaiting or triggering a
+    /// refresh, for `Drop` impls, synchronous trait methods and other places
+    /// that cannot `.await`. `None` if the flag was not in that refresh,
+    /// including before the first one has completed, and has no
+    /// [failure mode](ClientBuilder::with_failure_mode).
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// # fn example(client: &Client) {
+    /// if client.try_is_enabled("verbose-shutdown").unwrap_or(false) {
+    ///     // ...
+    /// }
+    /// # }
+    /// ```
+    pub fn try_is_enabled(&self, name: &str) -> Option<bool> {
+        let name = normalize_name(name);
+        if self.reject_unregistered(&name) {
+            return Some(false);
         }
-
-        let cache = self.cache.read().await;
-        cache.get_all().await
-            .map_err(|e| FlagError::CacheError(e.to_string()))
+        self.warn_lifecycle(&name);
+        let enabled = self.stale_default(&name).or_else(|| {
+            self.published
+                .load()
+                .get(name.as_ref())
+                .map(|&enabled| enabled && self.scheduled(&name))
+        });
+        let enabled = enabled.or_else(|| {
+            self.usage.record_missing(&name);
+            self.failure_value(&name)
+        });
+        self.usage.record(&name, enabled.unwrap_or(false));
+        enabled
     }
 
-    async fn is_enabled(&self, name: &str) -> bool {
-        let name = name.to_lowercase();
-
-        // Check if cache needs refresh and ensure only one refresh happens
-        if self.cache.read().await.should_refresh_cache().await {
-            // Try to acquire the refresh lock
-            if self.refresh_in_progress.compare_exchange(
-                false, 
-                true, 
-                Ordering::SeqCst, 
-                Ordering::SeqCst
-            ).is_ok() {
-                // We got the lock, perform the refresh
-                if let Err(e) = self.refetch().await {
-                    error!("Failed to refetch flags: {}", e);
-                    self.handle_error(&e);
-                }
-                // Release the refresh lock
-                self.refresh_in_progress.store(false, Ordering::SeqCst);
-            }
-            // If we didn't get the lock, another thread is refreshing
-        }
+    /// Capture every flag at one instant, refreshing first if the cache is
+    /// stale. The snapshot is read synchronously and never changes.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// # async fn example(client: &Client) {
+    /// let flags = client.snapshot().await;
+    /// // Both checks see the same refresh
+    /// let show_banner = flags.enabled("new-checkout") && flags.enabled("promo-banner");
+    /// # }
+    /// ```
+    pub async fn snapshot(&self) -> FlagSnapshot {
+        self.refresh_if_needed().await;
+        FlagSnapshot::new(self.published.load_full(), Arc::clone(&self.usage))
+    }
 
-        // Check cache (which now contains combined API and local flags with overrides)
-        let cache = self.cache.read().await;
-        match cache.get(&name).await {
-            Ok((enabled, exists)) => {
-                if exists {
-                    enabled
-                } else {
-                    false
-                }
-            }
-            Err(_) => false, // Treat cache errors as flag not found
-        }
+    /// A client serving exactly the flags of `snapshot`, e.g. one taken by a
+    /// parent process and deserialized in a child. It never calls the API and
+    /// ignores local environment flags, so every process sees the same state.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// # use flags_rs::flag::FlagSnapshot;
+    /// # async fn example(parent: &Client) -> Result<(), serde_json::Error> {
+    /// let handoff = serde_json::to_string(&parent.snapshot().await)?;
+    /// // In the child process
+    /// let client = Client::from_snapshot(serde_json::from_str::<FlagSnapshot>(&handoff)?);
+    /// assert!(client.is("new-checkout").enabled().await);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_snapshot(snapshot: FlagSnapshot) -> Client {
+        Client::builder()
+            .with_transport(transport::SnapshotTransport::new(&snapshot))
+            .with_local_precedence(LocalFlagPrecedence::LocalOnlyIfApiMissing)
+            .with_zero_interval(ZeroInterval::CacheForever)
+            .build()
+            // Nothing in these settings can fail validation
+            .expect("snapshot client settings are valid")
     }
 
-    async fn fetch_flags(&self) -> Result<ApiResponse, FlagError> {
-        let auth = match &self.auth {
-            Some(auth) => auth,
-            None => return Err(FlagError::AuthError("Authentication is required".to_string())),
-        };
+    /// Subscribe to refresh and circuit breaker events, for metrics and
+    /// tracing built outside the crate. Only events after the call are seen; a
+    /// subscriber that falls far behind skips the oldest ones.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// # use flags_rs::events::ClientEvent;
+    /// # use tokio_stream::StreamExt;
+    /// # async fn example(client: &Client) {
+    /// let mut events = Box::pin(client.events());
+    /// while let Some(event) = events.next().await {
+    ///     if let ClientEvent::RefreshFailed { error } = event {
+    ///         eprintln!("flag refresh failed: {}", error);
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn events(&self) -> impl Stream<Item = ClientEvent> + Send + 'static {
+        self.events.subscribe()
+    }
+
+    /// Durations of this client's refreshes, shared with its clones.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// # fn example(client: &Client) {
+    /// let text = client.refresh_histogram().to_prometheus("flags_refresh_duration_seconds");
+    /// # }
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn refresh_histogram(&self) -> &metrics::RefreshHistogram {
+        &self.refresh_histogram
+    }
+
+    pub async fn list(&self) -> Result<Vec<Arc<flag::FeatureFlag>>, FlagError> {
+        if self.circuit_breaker_disabled {
+            self.try_refresh_if_needed().await?;
+        } else {
+            self.refresh_if_needed().await;
+        }
+
+        self.cache.get_all().await
+            .map_err(|e| FlagError::CacheError(e.to_string()))
+    }
+
+    /// Fetch flags now, at startup, and check them against
+    /// [`ClientBuilder::require_flags`]. Fails with the refresh's error, or
+    /// with [`FlagError::MissingRequiredFlags`] naming every required flag
+    /// the client does not serve.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// # async fn example() -> Result<(), flags_rs::FlagError> {
+    /// let client = Client::builder()
+    ///     .require_flags(["payments-v2", "new-auth"])
+    ///     .build()?;
+    /// client.wait_until_ready().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "prewarm")]
+    pub async fn wait_until_ready(&self) -> Result<(), FlagError> {
+        self.try_refresh_if_needed().await?;
+        // Without coalescing, a refresh started by another task may still be running
+        let mut done = self.refresh_done.subscribe();
+        if self.refresh_in_progress.load(Ordering::SeqCst) {
+            let _ = done.changed().await;
+        }
+
+        if self.required_flags.is_empty() {
+            return Ok(());
+        }
+        let flags = self.cache.get_all().await
+            .map_err(|e| FlagError::CacheError(e.to_string()))?;
+        let missing: Vec<String> = self
+            .required_flags
+            .iter()
+            .filter(|name| !flags.iter().any(|flag| normalize_name(&flag.details.name) == name.as_str()))
+            .cloned()
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(FlagError::MissingRequiredFlags(missing))
+        }
+    }
+
+    /// Pin a flag for hot paths. The returned handle is checked with a single
+    /// atomic load and is kept up to date by every refresh.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// # async fn example(client: &Client, packets: &[&[u8]]) {
+    /// let fast_path = client.pin("fast-path").await;
+    /// for packet in packets {
+    ///     if fast_path.is_enabled() {
+    ///         // ...
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub async fn pin(&self, name: &str) -> PinnedFlag {
+        let name = normalize_name(name);
+        let enabled = self.is_enabled(&name).await;
+        self.usage.record(&name, enabled);
+
+        let mut pinned = self.pinned.lock().unwrap_or_else(|e| e.into_inner());
+        let state = pinned
+            .entry(name.to_string())
+            .or_insert_with(|| Arc::new(AtomicBool::new(enabled)));
+        PinnedFlag::new(&name, Arc::clone(state))
+    }
+
+    /// Watch a flag, for long-running workers that wait on a kill switch
+    /// instead of polling it. The receiver starts at the flag's state in the
+    /// last refresh (`false` before the first) and is notified whenever a
+    /// refresh changes it; it never triggers a refresh itself.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// # async fn example(client: &Client) {
+    /// let mut stop = client.subscribe("big-red-switch");
+    /// // Resolves once a refresh turns the switch on
+    /// let _ = stop.wait_for(|enabled| *enabled).await;
+    /// # }
+    /// ```
+    pub fn subscribe(&self, name: &str) -> watch::Receiver<bool> {
+        let name = normalize_name(name);
+        let mut subscriptions = self.subscriptions.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sender) = subscriptions.get(name.as_ref()) {
+            return sender.subscribe();
+        }
+        let enabled = self.published.load().get(name.as_ref()).copied().unwrap_or(false);
+        let (sender, receiver) = watch::channel(enabled);
+        subscriptions.insert(name.into_owned(), sender);
+        receiver
+    }
+
+    /// Re-read the local flag environment variables, `FLAGS_*` unless
+    /// [`with_env_prefix`](ClientBuilder::with_env_prefix) changed the prefix.
+    /// Local flags are otherwise read once when the client is built; the new
+    /// values are merged into the cache on the next evaluation.
+    pub async fn reload_local_flags(&self) {
+        self.load_local_flags();
+        self.schedule.invalidate();
+        if let Err(e) = self.cache.init().await {
+            let e = FlagError::CacheError(e.to_string());
+            error!("Failed to invalidate cache after reloading local flags: {}", e);
+            self.handle_error(&e);
+        }
+    }
+
+    /// Read local flags from the environment, reporting values that are not booleans.
+    fn load_local_flags(&self) {
+        let (flags, invalid, conflicts) = build_local(&self.env_prefix);
+        self.local_flags.store(Arc::new(flags));
+        if !conflicts.is_empty() {
+            let e = FlagError::DuplicateFlag(conflicts.join("; "));
+            warn!("{}", e);
+            self.handle_error(&e);
+        }
+        for (key, value) in invalid {
+            let e = FlagError::InvalidLocalFlag(format!(
+                "{}=\"{}\" is not true/false, 1/0, yes/no or on/off; treating it as false",
+                key, value
+            ));
+            warn!("{}", e);
+            self.handle_error(&e);
+        }
+    }
+
+    /// Whether flags are being fetched normally, see [`ClientStatus`].
+    pub fn status(&self) -> ClientStatus {
+        if self.refresh_failed.load(Ordering::Acquire) {
+            if self.api_snapshot.load(Ordering::Acquire) {
+                ClientStatus::Degraded
+            } else {
+                ClientStatus::ColdStartDegraded
+            }
+        } else if self.refresh_generation.load(Ordering::Acquire) == 0 {
+            ClientStatus::Initializing
+        } else {
+            ClientStatus::Healthy
+        }
+    }
+
+    /// When the cache was last refreshed, from the API or local flags.
+    pub async fn last_refresh(&self) -> Option<DateTime<Utc>> {
+        *self.last_refresh.read().await
+    }
+
+    /// Render every cached flag in `format`, without triggering a refresh.
+    /// Useful for diffing what two services believe about flags.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// # use flags_rs::export::Format;
+    /// # async fn example(client: &Client) {
+    /// println!("{}", client.export(Format::Csv).await);
+    /// # }
+    /// ```
+    pub async fn export(&self, format: Format) -> String {
+        let flags = match self.cache.get_all().await {
+            Ok(flags) => flags,
+            Err(e) => {
+                let e = FlagError::CacheError(e.to_string());
+                error!("Failed to read flags for export: {}", e);
+                self.handle_error(&e);
+                Vec::new()
+            }
+        };
+
+        export::render(&flags, self.last_refresh().await, format)
+    }
+
+    /// [`is_enabled`](Self::is_enabled), restricted by the flag's schedule and
+    /// targeting: its app version range, rules and rollout percentage. This is
+    /// the value served, so the shadow is compared against it.
+    async fn is_enabled_in(&self, name: &str, context: Option<&EvaluationContext>) -> bool {
+        let name = if self.alias_generator.is_some() {
+            // Aliases are only known once flags have been fetched
+            self.refresh_if_needed().await;
+            self.resolve_alias(name)
+        } else {
+            Cow::Borrowed(name)
+        };
+        let name = name.as_ref();
+        let mut enabled = self.is_enabled(name).await && self.scheduled(name);
+        if let Some(context) = context.filter(|_| enabled) {
+            enabled = self
+                .targeting
+                .load()
+                .get(name)
+                .is_none_or(|targeting| targeting.allows(name, context, self.bucket_hasher.as_ref()));
+        }
+        if let Some(shadow) = &self.shadow {
+            shadow.compare(name, context, enabled).await;
+        }
+        self.usage.record(name, enabled);
+        enabled
+    }
+
+    /// The flag an alias from [`ClientBuilder::with_alias_generator`] stands
+    /// for, or `name` itself.
+    fn resolve_alias<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        if self.alias_generator.is_none() {
+            return Cow::Borrowed(name);
+        }
+        match self.aliases.load().get(name) {
+            Some(flag) => Cow::Owned(flag.clone()),
+            None => Cow::Borrowed(name),
+        }
+    }
+
+    /// Whether the flag's activation schedule, if it has one, is active now.
+    fn scheduled(&self, name: &str) -> bool {
+        self.targeting.load().get(name).is_none_or(|targeting| targeting.is_active_at(Utc::now()))
+    }
+
+    /// `name` must already be normalized with [`normalize_name`].
+    async fn is_enabled(&self, name: &str) -> bool {
+        if self.reject_unregistered(name) {
+            return false;
+        }
+        self.refresh_if_needed().await;
+        self.warn_lifecycle(name);
+        if let Some(enabled) = self.stale_default(name) {
+            return enabled;
+        }
+
+        // Check cache (which now contains combined API and local flags with overrides)
+        match self.cache.get(name).await {
+            Ok((enabled, exists)) => {
+                if exists {
+                    enabled
+                } else if let Some(enabled) = self.bootstrap_value(name) {
+                    enabled
+                } else {
+                    self.missing_value(name).await
+                }
+            }
+            // Treat cache errors as flag not found
+            Err(_) => self.failure_value(name).or_else(|| self.bootstrap_value(name)).unwrap_or(false),
+        }
+    }
+
+    /// The client resolving flags for `company_id`, or `None` when that is this
+    /// client. Tenant clients are built from [`to_builder`](Self::to_builder),
+    /// so they share the transport and settings, but keep their own in-memory
+    /// cache, whatever cache this client was built with, and are not shadowed.
+    fn tenant_client(&self, company_id: &str) -> Result<Option<Client>, FlagError> {
+        let Some(auth) = self.auth.load_full() else {
+            return Ok(None);
+        };
+        if auth.company_id.as_deref() == Some(company_id) {
+            return Ok(None);
+        }
+
+        let mut tenants = self.tenants.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(client) = tenants.get(company_id) {
+            return Ok(Some(client.clone()));
+        }
+        let mut builder = self.to_builder().with_auth(Auth {
+            company_id: Some(company_id.to_string()),
+            ..(*auth).clone()
+        });
+        // The shadow source has no notion of this client's tenants
+        builder.shadow = None;
+        builder.canary = None;
+        let client = builder.with_memory_cache().build()?;
+        tenants.insert(company_id.to_string(), client.clone());
+        Ok(Some(client))
+    }
+
+    /// Warn, at most once per interval per flag, when a deprecated or expired flag is evaluated.
+    fn warn_lifecycle(&self, name: &str) {
+        if let Some(message) = self.lifecycle.check(name) {
+            let e = FlagError::DeprecatedFlag(message);
+            warn!("{}", e);
+            self.handle_error(&e);
+        }
+    }
+
+    /// Report an evaluation of a flag that does not exist and pick its value.
+    async fn missing_value(&self, name: &str) -> bool {
+        self.usage.record_missing(name);
+        self.suggest(name);
+        if let Some(callback) = &self.missing_flag_callback {
+            let mut reported = self.missing_reported.lock().unwrap_or_else(|e| e.into_inner());
+            let first = reported.len() < usage::MAX_UNKNOWN_NAMES && reported.insert(name.to_string());
+            drop(reported);
+            if first {
+                callback(name);
+            }
+        }
+        self.failure_value(name).unwrap_or(self.missing_flag_default)
+    }
+
+    /// Warn once when `name` is missing but a published flag looks like what was meant.
+    fn suggest(&self, name: &str) {
+        let generation = self.refresh_generation.load(Ordering::Acquire);
+        if !self.suggestions.should_check(name, generation) {
+            return;
+        }
+        let published = self.published.load();
+        if let Some(candidate) = suggest::closest(name, published.keys().map(String::as_str)) {
+            let e = FlagError::UnknownFlag(format!("'{}', did you mean '{}'?", name, candidate));
+            warn!("{}", e);
+            self.handle_error(&e);
+            self.suggestions.mark_suggested(name);
+        }
+    }
+
+    /// The default to serve instead of the cache once it is past the maximum
+    /// staleness, reporting the first evaluation after it expires.
+    fn stale_default(&self, name: &str) -> Option<bool> {
+        let stale = self.stale.as_ref()?;
+        let age = stale.expired_for()?;
+        if stale.mark_expired() {
+            let e = FlagError::StaleFlags(format!(
+                "no successful refresh for {}s, serving defaults",
+                age.as_secs()
+            ));
+            error!("{}", e);
+            self.handle_error(&e);
+        }
+        Some(self.failure_value(name).unwrap_or_else(|| stale.default_for(name)))
+    }
+
+    /// The value served for a missing `name` during a cold start outage, see
+    /// [`ClientBuilder::with_bootstrap_flags`].
+    fn bootstrap_value(&self, name: &str) -> Option<bool> {
+        if self.bootstrap_flags.is_empty() || self.status() != ClientStatus::ColdStartDegraded {
+            return None;
+        }
+        let enabled = *self.bootstrap_flags.get(name)?;
+        Some(self.failure_value(name).unwrap_or(enabled))
+    }
+
+    /// The value `name` was declared to take when its state is unknown.
+    fn failure_value(&self, name: &str) -> Option<bool> {
+        self.failure_modes.get(name).map(|mode| mode.enabled())
+    }
+
+    /// In strict mode, whether `name` is unregistered and must evaluate to false.
+    fn reject_unregistered(&self, name: &str) -> bool {
+        let Some(registry) = &self.registry else {
+            return false;
+        };
+        if !registry.rejects(name) {
+            return false;
+        }
+        let e = FlagError::UnregisteredFlag(format!("'{}' is not in the registered flags", name));
+        if registry.panics() {
+            panic!("{}", e);
+        }
+        error!("{}", e);
+        self.handle_error(&e);
+        true
+    }
 
-        let mut headers = HeaderMap::new();
-        headers.insert("User-Agent", HeaderValue::from_static("Flags-Rust"));
-        headers.insert("Accept", HeaderValue::from_static("application/json"));
-        headers.insert("Content-Type", HeaderValue::from_static("application/json"));
-        headers.insert("X-Project-ID", HeaderValue::from_str(&auth.project_id)
-            .map_err(|_| FlagError::AuthError(format!("Invalid project ID: {}", auth.project_id)))?);
-        headers.insert("X-Agent-ID", HeaderValue::from_str(&auth.agent_id)
-            .map_err(|_| FlagError::AuthError(format!("Invalid agent ID: {}", auth.agent_id)))?);
-        headers.insert("X-Environment-ID", HeaderValue::from_str(&auth.environment_id)
-            .map_err(|_| FlagError::AuthError(format!("Invalid environment ID: {}", auth.environment_id)))?);
+    /// Refresh the cache if it is stale, making sure only one refresh runs at a time.
+    /// Without coalescing, callers that lose the race carry on with the current
+    /// (possibly empty) cache; with it, they wait for the in-flight refresh.
+    async fn refresh_if_needed(&self) {
+        // Reported to the error callback already
+        let _ = self.try_refresh_if_needed().await;
+    }
 
-        let url = format!("{}/flags", self.base_url);
-        let response = self.http_client
-            .get(&url)
-            .headers(headers)
-            .send()
-            .await?;
+    /// [`refresh_if_needed`](Self::refresh_if_needed), returning the error of
+    /// a refresh this call ran.
+    async fn try_refresh_if_needed(&self) -> Result<(), FlagError> {
+        if !self.schedule.is_stale() {
+            return Ok(());
+        }
 
-        if !response.status().is_success() {
-            return Err(FlagError::ApiError(format!(
-                "Unexpected status code: {}",
-                response.status()
-            )));
+        // Try to acquire the refresh lock
+        if self.refresh_in_progress.compare_exchange(
+            false,
+            true,
+            Ordering::SeqCst,
+            Ordering::SeqCst
+        ).is_ok() {
+            // Releases the lock and wakes waiters even if this future is dropped mid-refresh
+            let _guard = RefreshGuard { client: self };
+            let result = self.refetch().await;
+            if let Err(e) = &result {
+                error!("Failed to refetch flags: {}", e);
+                self.handle_error(e);
+            }
+            if self.circuit_breaker_disabled && result.is_err() {
+                // Without a breaker, the next call tries the API again
+                self.schedule.invalidate();
+            } else {
+                // Nothing was published (open circuit, cache error): until the debounce
+                // window passes, the staleness check answers from the schedule alone
+                self.schedule.defer(STALE_CHECK_DEBOUNCE);
+            }
+            return result;
         }
 
-        let api_resp = response.json::<ApiResponse>().await?;
-        Ok(api_resp)
+        // Another task is refreshing
+        if self.coalesce_refresh {
+            // Subscribe before re-checking so a refresh finishing in between is not missed
+            let mut done = self.refresh_done.subscribe();
+            if self.refresh_in_progress.load(Ordering::SeqCst) {
+                let _ = done.changed().await;
+            }
+        }
+        Ok(())
+    }
+
+    async fn fetch_flags(&self) -> Result<FlagsUpdate, FlagError> {
+        let version = if self.delta_refresh {
+            self.api_state.load().as_ref().and_then(|state| state.version.clone())
+        } else {
+            None
+        };
+
+        let auth = self.auth.load_full();
+        match version {
+            Some(version) => self.transport.fetch_flags_since(auth.as_deref(), &version).await,
+            None => self.transport.fetch_flags(auth.as_deref()).await.map(FlagsUpdate::Full),
+        }
     }
 
     async fn refetch(&self) -> Result<(), FlagError> {
         // If no auth is configured, skip calling the API and only use local/env flags
-        if self.auth.is_none() {
-            let local_flags = build_local();
-            let mut cache = self.cache.write().await;
+        if self.auth.load().is_none() && self.transport.requires_auth() {
+            let local_flags = self.local_flags.load();
+            self.refresh_failed.store(false, Ordering::Release);
             // Default refresh interval when there's no API
-            cache
-                .refresh(&local_flags, 60)
-                .await
-                .map_err(|e| FlagError::CacheError(e.to_string()))?;
+            return self.publish(&local_flags, 60).await;
+        }
+
+        let allowed = self.circuit_breaker.allow_request();
+        self.events.observe_circuit(self.circuit_breaker.is_open());
+        if !allowed {
+            self.refresh_failed.store(true, Ordering::Release);
             return Ok(());
         }
+        self.events.emit(ClientEvent::RefreshStarted);
+        let started = Instant::now();
+
+        // Implement retry logic for fetching flags from the API.
+        // Internal retries should not immediately affect the circuit breaker state.
+        let update = {
+            let max = self.max_retries.max(1);
+            let mut attempt: u32 = 1;
+            loop {
+                match self.fetch_flags().await {
+                    Ok(resp) => {
+                        self.circuit_breaker.record_success();
+                        self.events.observe_circuit(self.circuit_breaker.is_open());
+                        break resp;
+                    }
+                    Err(e) => {
+                        if attempt < max && e.is_retryable() {
+                            warn!("Refetch failed (attempt {}/{}), retrying...", attempt, max);
+                            self.handle_error(&e);
+                            tokio::time::sleep(Duration::from_millis(100 * attempt as u64)).await;
+                            attempt += 1;
+                            continue;
+                        }
+                        // After exhausting attempts, update circuit state once
+                        self.circuit_breaker.record_failure();
+                        self.events.observe_circuit(self.circuit_breaker.is_open());
+                        error!("Refetch failed after {} attempt(s): {}", attempt, e);
+                        self.handle_error(&e);
+                        self.observe_refresh(started.elapsed());
+                        self.events.emit(ClientEvent::RefreshFailed { error: e.to_string() });
+                        self.refresh_failed.store(true, Ordering::Release);
+                        // Refresh with local flags to ensure deterministic behavior
+                        let local_flags = self.local_flags.load();
+                        self.publish(&local_flags, 60).await?;
+                        // Propagate the last error
+                        return Err(e);
+                    }
+                }
+            }
+        };
+
+        let result = self.apply_update(update).await;
+        self.refresh_failed.store(result.is_err(), Ordering::Release);
+        if let (Ok(()), Some(stale)) = (&result, &self.stale) {
+            stale.record_success();
+        }
+        let duration = started.elapsed();
+        self.observe_refresh(duration);
+        self.events.emit(match &result {
+            Ok(()) => ClientEvent::RefreshSucceeded {
+                count: self.published.load().len(),
+                duration,
+            },
+            Err(e) => ClientEvent::RefreshFailed { error: e.to_string() },
+        });
+        result
+    }
+
+    /// Record how long a refresh took and report it if it was slow.
+    fn observe_refresh(&self, duration: Duration) {
+        #[cfg(feature = "metrics")]
+        self.refresh_histogram.record(duration);
+        if let Some((threshold, callback)) = &self.slow_refresh {
+            if duration > *threshold {
+                warn!("Flag refresh took {:?}, longer than {:?}", duration, threshold);
+                callback(duration);
+            }
+        }
+    }
+
+    /// Resolve a fetched update against the last full flag set and apply it.
+    async fn apply_update(&self, update: FlagsUpdate) -> Result<(), FlagError> {
+        let api_resp = match update {
+            FlagsUpdate::Full(api_resp) => api_resp,
+            FlagsUpdate::Delta(delta) => match self.api_state.load_full() {
+                Some(base) => base.apply_delta(delta),
+                None => return Err(FlagError::ApiError("Received a delta without a base flag set".to_string())),
+            },
+            FlagsUpdate::NotModified => match self.api_state.load_full() {
+                Some(base) => base.as_ref().clone(),
+                None => return Err(FlagError::ApiError("Received 304 without a base flag set".to_string())),
+            },
+        };
+
+        self.apply_api_response(api_resp).await
+    }
+
+    /// Merge a fetched flag set with the local overrides and publish it to the cache.
+    async fn apply_api_response(&self, mut api_resp: ApiResponse) -> Result<(), FlagError> {
+        let mut quarantined: Vec<QuarantinedFlag> = api_resp
+            .skipped
+            .iter()
+            .map(|reason| QuarantinedFlag { name: None, reason: reason.clone() })
+            .collect();
+        if !api_resp.skipped.is_empty() {
+            let e = FlagError::ApiError(format!(
+                "Skipped {} malformed flag(s): {}",
+                api_resp.skipped.len(),
+                api_resp.skipped.join("; ")
+            ));
+            warn!("{}", e);
+            self.handle_error(&e);
+            api_resp.skipped.clear();
+        }
+        if self.delta_refresh {
+            self.api_state.store(Some(Arc::new(api_resp.clone())));
+        }
+
+        // Serve the valid flags rather than failing the whole refresh
+        let mut valid = Vec::with_capacity(api_resp.flags.len());
+        let mut invalid = Vec::new();
+        for flag in api_resp.flags {
+            match flag.validate() {
+                Ok(()) => valid.push(flag),
+                Err(reason) => invalid.push(QuarantinedFlag {
+                    name: Some(flag.details.name).filter(|name| !name.trim().is_empty()),
+                    reason,
+                }),
+            }
+        }
+        if !invalid.is_empty() {
+            let descriptions: Vec<String> = invalid
+                .iter()
+                .map(|f| format!("'{}': {}", f.name.as_deref().unwrap_or(""), f.reason))
+                .collect();
+            let e = FlagError::ApiError(format!(
+                "Quarantined {} invalid flag(s): {}",
+                invalid.len(),
+                descriptions.join("; ")
+            ));
+            warn!("{}", e);
+            self.handle_error(&e);
+        }
+        quarantined.extend(invalid);
+        self.quarantined.store(Arc::new(quarantined));
+
+        let (mut api_flags, conflicts) = dedupe_flags(valid);
+        if !conflicts.is_empty() {
+            let e = FlagError::DuplicateFlag(conflicts.join("; "));
+            warn!("{}", e);
+            self.handle_error(&e);
+        }
+
+        let flags = if self.local_precedence == LocalFlagPrecedence::LocalOnlyIfApiMissing {
+            api_flags
+        } else {
+            let local_flags = self.local_flags.load();
+
+            // Combine API flags and local flags, with local overriding API unless
+            // the API is configured to win
+            let mut combined_flags = Vec::new();
+            let mut local_flags_map: HashMap<String, FeatureFlag> = local_flags.iter().map(|f| (f.details.name.clone(), f.clone())).collect();
+
+            for api_flag in api_flags.drain(..) {
+                if self.local_precedence == LocalFlagPrecedence::ApiOverridesLocal {
+                    local_flags_map.remove(&api_flag.details.name);
+                    combined_flags.push(api_flag);
+                } else if let Some(mut local_flag) = local_flags_map.remove(&api_flag.details.name) {
+                    // Local flag with the same name exists, use the local one,
+                    // keeping its place in server-defined groups
+                    local_flag.details.groups = api_flag.details.groups;
+                    combined_flags.push(local_flag);
+                } else {
+                    // No local flag with the same name, use the API one
+                    combined_flags.push(api_flag);
+                }
+            }
+
+            // Add any remaining local flags that didn't have a corresponding API flag
+            combined_flags.extend(local_flags_map.into_values());
+            combined_flags
+        };
+
+        self.publish(&flags, api_resp.interval_allowed).await?;
+        self.api_snapshot.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    async fn publish(&self, flags: &[FeatureFlag], interval_allowed: i32) -> Result<(), FlagError> {
+        let interval_allowed = self.refresh_interval.resolve(interval_allowed, self.refresh_jitter_percent);
+        self.cache.refresh(flags, interval_allowed).await
+            .map_err(|e| FlagError::CacheError(e.to_string()))?;
+        self.schedule.schedule(interval_allowed);
+        let published: HashMap<String, bool> = flags.iter().map(|f| (f.details.name.clone(), f.enabled)).collect();
+        self.update_pinned(&published);
+        self.update_subscriptions(&published);
+        self.lifecycle.update(flags);
+        if let Some(registry) = &self.registry {
+            for name in registry.newly_unregistered(flags) {
+                warn!("Flag '{}' is served by the API but was never registered", name);
+            }
+        }
+        self.groups.store(Arc::new(group_index(flags)));
+        if let Some(generator) = &self.alias_generator {
+            self.aliases.store(Arc::new(alias::index(generator, flags)));
+        }
+        self.targeting.store(Arc::new(Targeting::index(flags)));
+        self.published.store(Arc::new(published));
+        self.refresh_generation.fetch_add(1, Ordering::AcqRel);
+        *self.last_refresh.write().await = Some(Utc::now());
+
+        Ok(())
+    }
+
+    /// Push a published flag set to pinned handles, dropping entries whose
+    /// handles have all been released.
+    fn update_pinned(&self, published: &HashMap<String, bool>) {
+        let mut pinned = self.pinned.lock().unwrap_or_else(|e| e.into_inner());
+        if pinned.is_empty() {
+            return;
+        }
+        pinned.retain(|_, state| Arc::strong_count(state) > 1);
+
+        for (name, state) in pinned.iter() {
+            let enabled = published.get(name).copied().unwrap_or(false);
+            state.store(enabled, Ordering::Relaxed);
+        }
+    }
+
+    /// Push a published flag set to subscribers, notifying only those whose
+    /// flag changed and dropping flags nobody listens to any more.
+    fn update_subscriptions(&self, published: &HashMap<String, bool>) {
+        let mut subscriptions = self.subscriptions.lock().unwrap_or_else(|e| e.into_inner());
+        if subscriptions.is_empty() {
+            return;
+        }
+        subscriptions.retain(|_, sender| !sender.is_closed());
+
+        for (name, sender) in subscriptions.iter() {
+            let enabled = published.get(name).copied().unwrap_or(false);
+            sender.send_if_modified(|current| std::mem::replace(current, enabled) != enabled);
+        }
+    }
+}
+
+impl Clone for Client {
+    fn clone(&self) -> Self {
+        Client {
+            base_url: self.base_url.clone(),
+            transport: Arc::clone(&self.transport),
+            cache: Arc::clone(&self.cache),
+            max_retries: self.max_retries,
+            circuit_breaker: Arc::clone(&self.circuit_breaker),
+            auth: Arc::clone(&self.auth),
+            refresh_in_progress: Arc::clone(&self.refresh_in_progress),
+            refresh_done: Arc::clone(&self.refresh_done),
+            coalesce_refresh: self.coalesce_refresh,
+            circuit_breaker_disabled: self.circuit_breaker_disabled,
+            delta_refresh: self.delta_refresh,
+            api_state: Arc::clone(&self.api_state),
+            error_callback: self.error_callback.clone(),
+            missing_flag_callback: self.missing_flag_callback.clone(),
+            missing_reported: Arc::clone(&self.missing_reported),
+            missing_flag_default: self.missing_flag_default,
+            last_refresh: Arc::clone(&self.last_refresh),
+            schedule: Arc::clone(&self.schedule),
+            refresh_generation: Arc::clone(&self.refresh_generation),
+            api_snapshot: Arc::clone(&self.api_snapshot),
+            refresh_failed: Arc::clone(&self.refresh_failed),
+            bootstrap_flags: Arc::clone(&self.bootstrap_flags),
+            events: Arc::clone(&self.events),
+            slow_refresh: self.slow_refresh.clone(),
+            #[cfg(feature = "metrics")]
+            refresh_histogram: Arc::clone(&self.refresh_histogram),
+            local_flags: Arc::clone(&self.local_flags),
+            env_prefix: Arc::clone(&self.env_prefix),
+            local_precedence: self.local_precedence,
+            refresh_jitter_percent: self.refresh_jitter_percent,
+            refresh_interval: self.refresh_interval,
+            pinned: Arc::clone(&self.pinned),
+            subscriptions: Arc::clone(&self.subscriptions),
+            lifecycle: Arc::clone(&self.lifecycle),
+            registry: self.registry.clone(),
+            required_flags: Arc::clone(&self.required_flags),
+            stale: self.stale.clone(),
+            failure_modes: Arc::clone(&self.failure_modes),
+            shadow: self.shadow.clone(),
+            usage: Arc::clone(&self.usage),
+            suggestions: Arc::clone(&self.suggestions),
+            groups: Arc::clone(&self.groups),
+            alias_generator: self.alias_generator.clone(),
+            aliases: Arc::clone(&self.aliases),
+            quarantined: Arc::clone(&self.quarantined),
+            targeting: Arc::clone(&self.targeting),
+            bucket_hasher: Arc::clone(&self.bucket_hasher),
+            #[cfg(feature = "management")]
+            management: self.management.clone(),
+            published: Arc::clone(&self.published),
+            tenants: Arc::clone(&self.tenants),
+            origin: Arc::clone(&self.origin),
+        }
+    }
+}
+
+struct RefreshGuard<'a> {
+    client: &'a Client,
+}
+
+impl Drop for RefreshGuard<'_> {
+    fn drop(&mut self) {
+        self.client.refresh_in_progress.store(false, Ordering::SeqCst);
+        self.client.refresh_done.send_modify(|generation| *generation += 1);
+    }
+}
+
+impl<'a> Flag<'a> {
+    /// Evaluate with per-call overrides such as a tenant.
+    pub fn with_context(mut self, context: &'a EvaluationContext) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Reuse results from `memo` for this flag and context, and record this one.
+    /// Answers from the memo still count in [`Client::evaluation_counts`] and
+    /// still warn about deprecated flags, but are not compared with a shadow.
+    pub fn with_memo(mut self, memo: &'a EvaluationMemo) -> Self {
+        self.memo = Some(memo);
+        self
+    }
+
+    /// Whether the flag is enabled, for the context given with
+    /// [`with_context`](Self::with_context) or else the ambient one from
+    /// [`context::scope`].
+    pub async fn enabled(&self) -> bool {
+        let ambient = if self.context.is_none() { context::current() } else { None };
+        let context = self.context.or(ambient.as_deref());
+        let tenant = context.and_then(|c| c.tenant());
+        let tenant_client = match tenant.map(|t| self.client.tenant_client(t)).transpose() {
+            Ok(tenant_client) => tenant_client.flatten(),
+            Err(e) => {
+                error!("{}", e);
+                self.client.handle_error(&e);
+                return false;
+            }
+        };
+        let client = tenant_client.as_ref().unwrap_or(self.client);
+
+        let Some(memo) = self.memo else {
+            return client.is_enabled_in(&self.name, context).await;
+        };
+        let memo_key = context.map(EvaluationContext::memo_key).unwrap_or_default();
+        let generation = client.refresh_generation.load(Ordering::Acquire);
+        if let Some(enabled) = memo.get(&self.name, memo_key, generation) {
+            // Counted and warned about like a fresh evaluation; the shadow was
+            // compared when the memo was filled
+            let name = client.resolve_alias(&self.name);
+            client.warn_lifecycle(&name);
+            client.usage.record(&name, enabled);
+            return enabled;
+        }
+        let enabled = client.is_enabled_in(&self.name, context).await;
+        // Only record results known to come from this generation's flags
+        if client.refresh_generation.load(Ordering::Acquire) == generation {
+            memo.insert(&self.name, memo_key, generation, enabled);
+        }
+        enabled
+    }
+}
+
+impl OwnedFlag {
+    /// Evaluate with per-call overrides such as a tenant.
+    pub fn with_context(mut self, context: EvaluationContext) -> Self {
+        self.context = Some(Arc::new(context));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub async fn enabled(&self) -> bool {
+        let flag = self.client.is(&self.name);
+        match self.context.as_deref() {
+            Some(context) => flag.with_context(context).enabled().await,
+            None => flag.enabled().await,
+        }
+    }
+}
+
+impl<'a> FlagGroup<'a> {
+    /// Names of the flags in the group, empty if the group is unknown.
+    pub async fn flags(&self) -> Vec<String> {
+        self.client.refresh_if_needed().await;
+        self.client.groups.load().get(self.name.as_ref()).cloned().unwrap_or_default()
+    }
+
+    /// Whether every flag in the group is enabled. An unknown or empty group
+    /// is not enabled.
+    pub async fn all_enabled(&self) -> bool {
+        let members = self.flags().await;
+        if members.is_empty() {
+            return false;
+        }
+        let names: Vec<&str> = members.iter().map(String::as_str).collect();
+        self.client.all_enabled(&names).await
+    }
+
+    /// Whether at least one flag in the group is enabled.
+    pub async fn any_enabled(&self) -> bool {
+        let members = self.flags().await;
+        let names: Vec<&str> = members.iter().map(String::as_str).collect();
+        self.client.any_enabled(&names).await
+    }
+}
+
+/// Builder state of a [`ClientBuilder`] that cannot build yet: credentials or
+/// [`offline`](ClientBuilder::offline) mode must be chosen first.
+///
+/// ```compile_fail
+/// let client = flags_rs::Client::typed_builder().build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct NeedsAuth;
+
+/// Builder state of a [`ClientBuilder`] that can [`build`](ClientBuilder::build).
+#[derive(Debug, Clone)]
+pub struct Ready;
+
+/// Configures a [`Client`].
+///
+/// [`Client::builder`] returns a builder that is ready to build, with or
+/// without credentials. [`Client::typed_builder`] starts in the [`NeedsAuth`]
+/// state, where `build()` only becomes available after
+/// [`with_auth`](ClientBuilder::with_auth) or [`offline`](ClientBuilder::offline),
+/// so a missing `Auth` is a compile error rather than an `AuthError` at fetch time.
+#[derive(Clone)]
+pub struct ClientBuilder<S = Ready> {
+    base_url: String,
+    max_retries: u32,
+    auth: Option<Auth>,
+    use_memory_cache: bool,
+    // Type name of `cache`, for `Client::config`
+    cache_type: &'static str,
+    file_name: Option<String>,
+    error_callback: Option<ErrorCallback>,
+    missing_flag_callback: Option<MissingFlagCallback>,
+    missing_flag_default: bool,
+    json_log_sink: Option<JsonLogSink>,
+    slow_refresh: Option<(Duration, SlowRefreshCallback)>,
+    wire_format: WireFormat,
+    transport: Option<Arc<dyn Transport>>,
+    coalesce_refresh: bool,
+    // `with_circuit_breaker_disabled`: list() returns refresh errors
+    circuit_breaker_disabled: bool,
+    delta_refresh: bool,
+    cache: Option<Arc<dyn Cache + Send + Sync>>,
+    http_options: HttpOptions,
+    refresh_jitter_percent: u8,
+    refresh_interval: RefreshInterval,
+    env_prefix: String,
+    local_precedence: LocalFlagPrecedence,
+    auth_required: bool,
+    app_identifier: Option<String>,
+    request_id_generator: Option<RequestIdGenerator>,
+    #[cfg(feature = "remote")]
+    request_interceptor: Option<RequestInterceptor>,
+    #[cfg(feature = "management")]
+    management: Option<management::ManagementAuth>,
+    #[cfg(feature = "management")]
+    management_url: Option<String>,
+    circuit_breaker: Option<Arc<dyn CircuitBreaker>>,
+    registered_flags: Option<HashSet<String>>,
+    alias_generator: Option<AliasGenerator>,
+    required_flags: Vec<String>,
+    strict: bool,
+    panic_on_unregistered: bool,
+    max_stale: Option<Duration>,
+    stale_defaults: HashMap<String, bool>,
+    bootstrap_flags: HashMap<String, bool>,
+    failure_modes: HashMap<String, FailureMode>,
+    shadow: Option<Arc<Shadow>>,
+    // Environment ID, sample rate and callback of `with_canary_environment`
+    canary: Option<(String, f64, MismatchCallback)>,
+    bucket_hasher: Option<Arc<dyn BucketHasher>>,
+    // Client built by an earlier `build()`, reused while `http_options` still match
+    #[cfg(feature = "remote")]
+    http_client: Option<(HttpOptions, reqwest::Client)>,
+    state: PhantomData<S>,
+}
+
+impl ClientBuilder {
+    /// A builder configured from `FLAGS_GG_*` environment variables, for
+    /// deployments configured without code changes. Unset variables keep the
+    /// defaults; further `with_*` calls override what was read.
+    ///
+    /// | Variable | Value |
+    /// |---|---|
+    /// | `FLAGS_GG_BASE_URL` | API base URL |
+    /// | `FLAGS_GG_PROJECT_ID`, `FLAGS_GG_AGENT_ID`, `FLAGS_GG_ENVIRONMENT_ID` | credentials, all or none |
+    /// | `FLAGS_GG_COMPANY_ID` | optional company scope |
+    /// | `FLAGS_GG_AGENT_SECRET` | optional secret to sign requests with |
+    /// | `FLAGS_GG_TIMEOUT_SECS` | request timeout in seconds |
+    /// | `FLAGS_GG_MAX_RETRIES` | retries per refresh |
+    /// | `FLAGS_GG_CACHE` | `memory`, or `sharded` with the `sharded-cache` feature |
+    /// | `FLAGS_GG_REFRESH_JITTER` | jitter percentage, 0-100 |
+    /// | `FLAGS_GG_REFRESH_INTERVAL_SECS` | fixed refresh interval, see [`with_refresh_interval`](Self::with_refresh_interval) |
+    /// | `FLAGS_GG_MIN_REFRESH_INTERVAL_SECS`, `FLAGS_GG_MAX_REFRESH_INTERVAL_SECS` | refresh interval bounds |
+    /// | `FLAGS_GG_COALESCE_REFRESH`, `FLAGS_GG_DELTA_REFRESH` | `true`/`false` |
+    ///
+    /// Variables with the `FLAGS_GG_` prefix are never read as local flags.
+    pub fn from_env() -> Result<Self, FlagError> {
+        let mut builder = ClientBuilder::new();
+
+        if let Some(base_url) = env_value("FLAGS_GG_BASE_URL")? {
+            builder = builder.with_base_url(&base_url);
+        }
+
+        let project_id = env_value("FLAGS_GG_PROJECT_ID")?;
+        let agent_id = env_value("FLAGS_GG_AGENT_ID")?;
+        let environment_id = env_value("FLAGS_GG_ENVIRONMENT_ID")?;
+        let company_id = env_value("FLAGS_GG_COMPANY_ID")?;
+        let agent_secret = env_value("FLAGS_GG_AGENT_SECRET")?;
+        match (project_id, agent_id, environment_id) {
+            (Some(project_id), Some(agent_id), Some(environment_id)) => {
+                builder = builder.with_auth(Auth {
+                    project_id,
+                    agent_id,
+                    environment_id,
+                    company_id,
+                    agent_secret,
+                });
+            }
+            (None, None, None) => {
+                for (name, value) in [("FLAGS_GG_COMPANY_ID", &company_id), ("FLAGS_GG_AGENT_SECRET", &agent_secret)] {
+                    if value.is_some() {
+                        return Err(FlagError::BuilderError(format!(
+                            "{} requires FLAGS_GG_PROJECT_ID, FLAGS_GG_AGENT_ID and FLAGS_GG_ENVIRONMENT_ID",
+                            name
+                        )));
+                    }
+                }
+            }
+            (project_id, agent_id, environment_id) => {
+                let missing: Vec<&str> = [
+                    ("FLAGS_GG_PROJECT_ID", project_id.is_none()),
+                    ("FLAGS_GG_AGENT_ID", agent_id.is_none()),
+                    ("FLAGS_GG_ENVIRONMENT_ID", environment_id.is_none()),
+                ]
+                .iter()
+                .filter(|(_, missing)| *missing)
+                .map(|(name, _)| *name)
+                .collect();
+                return Err(FlagError::BuilderError(format!(
+                    "Incomplete credentials in environment, missing {}",
+                    missing.join(", ")
+                )));
+            }
+        }
+
+        if let Some(secs) = env_parse::<u64>("FLAGS_GG_TIMEOUT_SECS", "a number of seconds")? {
+            builder = builder.with_timeout(Duration::from_secs(secs));
+        }
+        if let Some(max_retries) = env_parse::<u32>("FLAGS_GG_MAX_RETRIES", "a non-negative integer")? {
+            builder = builder.with_max_retries(max_retries);
+        }
+        if let Some(percent) = env_parse::<u8>("FLAGS_GG_REFRESH_JITTER", "a percentage from 0 to 100")? {
+            builder = builder.with_refresh_jitter(percent);
+        }
+        if let Some(secs) = env_parse::<u64>("FLAGS_GG_REFRESH_INTERVAL_SECS", "a number of seconds")? {
+            builder = builder.with_refresh_interval(Duration::from_secs(secs));
+        }
+        if let Some(secs) = env_parse::<u64>("FLAGS_GG_MIN_REFRESH_INTERVAL_SECS", "a number of seconds")? {
+            builder = builder.with_min_refresh_interval(Duration::from_secs(secs));
+        }
+        if let Some(secs) = env_parse::<u64>("FLAGS_GG_MAX_REFRESH_INTERVAL_SECS", "a number of seconds")? {
+            builder = builder.with_max_refresh_interval(Duration::from_secs(secs));
+        }
+        if env_bool("FLAGS_GG_COALESCE_REFRESH")? == Some(true) {
+            builder = builder.with_coalesced_refresh();
+        }
+        if env_bool("FLAGS_GG_DELTA_REFRESH")? == Some(true) {
+            builder = builder.with_delta_refresh();
+        }
+
+        match env_value("FLAGS_GG_CACHE")?.as_deref() {
+            None | Some("memory") => {}
+            #[cfg(feature = "sharded-cache")]
+            Some("sharded") => builder = builder.with_sharded_cache(),
+            Some(other) => {
+                return Err(FlagError::BuilderError(format!(
+                    "Invalid FLAGS_GG_CACHE \"{}\": expected {}",
+                    other,
+                    if cfg!(feature = "sharded-cache") { "memory or sharded" } else { "memory" }
+                )));
+            }
+        }
+
+        Ok(builder)
+    }
+
+    /// Defaults for local development: credentials are optional so the client
+    /// runs offline on local flags, the first evaluations wait for the initial
+    /// fetch, and flags are refreshed at least every 5 seconds. Logging is
+    /// verbose: every refresh, circuit transition and error is logged at
+    /// `debug` as in [`with_json_log_sink`](Self::with_json_log_sink), and so
+    /// is the first evaluation of each flag that does not exist.
+    pub fn development() -> Self {
+        ClientBuilder::new()
+            .with_coalesced_refresh()
+            .with_max_refresh_interval(Duration::from_secs(5))
+            .with_json_log_sink(|record| debug!("{}", record))
+            .on_missing_flag(|name| debug!("Evaluated unknown flag '{}'", name))
+    }
+
+    /// Defaults for production: `build()` fails without credentials, API flags
+    /// win over local overrides, and refreshes are jittered by 10% and no more
+    /// often than every 10 seconds. There is no analytics reporting to turn on:
+    /// evaluation counts are always kept in process, see
+    /// [`Client::evaluation_counts`], and no usage data leaves the process.
+    pub fn production() -> Self {
+        ClientBuilder::new()
+            .with_auth_required(true)
+            .with_local_precedence(LocalFlagPrecedence::ApiOverridesLocal)
+            .with_refresh_jitter(10)
+            .with_min_refresh_interval(Duration::from_secs(10))
+    }
+
+    fn new() -> Self {
+        Self {
+            base_url: BASE_URL.to_string(),
+            max_retries: MAX_RETRIES,
+            auth: None,
+            use_memory_cache: false,
+            cache_type: std::any::type_name::<MemoryCache>(),
+            file_name: None,
+            error_callback: None,
+            missing_flag_callback: None,
+            missing_flag_default: false,
+            json_log_sink: None,
+            slow_refresh: None,
+            wire_format: WireFormat::default(),
+            transport: None,
+            coalesce_refresh: false,
+            circuit_breaker_disabled: false,
+            delta_refresh: false,
+            cache: None,
+            http_options: HttpOptions::default(),
+            refresh_jitter_percent: 0,
+            refresh_interval: RefreshInterval::default(),
+            env_prefix: DEFAULT_ENV_PREFIX.to_string(),
+            local_precedence: LocalFlagPrecedence::default(),
+            auth_required: false,
+            app_identifier: None,
+            request_id_generator: None,
+            #[cfg(feature = "remote")]
+            request_interceptor: None,
+            #[cfg(feature = "management")]
+            management: None,
+            #[cfg(feature = "management")]
+            management_url: None,
+            circuit_breaker: None,
+            registered_flags: None,
+            alias_generator: None,
+            required_flags: Vec::new(),
+            strict: false,
+            panic_on_unregistered: false,
+            max_stale: None,
+            stale_defaults: HashMap::new(),
+            bootstrap_flags: HashMap::new(),
+            failure_modes: HashMap::new(),
+            shadow: None,
+            canary: None,
+            bucket_hasher: None,
+            #[cfg(feature = "remote")]
+            http_client: None,
+            state: PhantomData,
+        }
+    }
+
+    pub fn with_auth(mut self, auth: Auth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+}
+
+impl ClientBuilder<NeedsAuth> {
+    /// Supply credentials, making the builder ready to build.
+    pub fn with_auth(mut self, auth: Auth) -> ClientBuilder<Ready> {
+        self.auth = Some(auth);
+        self.into_state()
+    }
+
+    /// Build without credentials, serving only local flags.
+    pub fn offline(self) -> ClientBuilder<Ready> {
+        self.into_state()
+    }
+}
+
+impl<S> ClientBuilder<S> {
+    fn into_state<T>(self) -> ClientBuilder<T> {
+        ClientBuilder {
+            base_url: self.base_url,
+            max_retries: self.max_retries,
+            auth: self.auth,
+            use_memory_cache: self.use_memory_cache,
+            cache_type: self.cache_type,
+            file_name: self.file_name,
+            error_callback: self.error_callback,
+            missing_flag_callback: self.missing_flag_callback,
+            missing_flag_default: self.missing_flag_default,
+            json_log_sink: self.json_log_sink,
+            slow_refresh: self.slow_refresh,
+            wire_format: self.wire_format,
+            transport: self.transport,
+            coalesce_refresh: self.coalesce_refresh,
+            circuit_breaker_disabled: self.circuit_breaker_disabled,
+            delta_refresh: self.delta_refresh,
+            cache: self.cache,
+            http_options: self.http_options,
+            refresh_jitter_percent: self.refresh_jitter_percent,
+            refresh_interval: self.refresh_interval,
+            env_prefix: self.env_prefix,
+            local_precedence: self.local_precedence,
+            auth_required: self.auth_required,
+            app_identifier: self.app_identifier,
+            request_id_generator: self.request_id_generator,
+            #[cfg(feature = "remote")]
+            request_interceptor: self.request_interceptor,
+            #[cfg(feature = "management")]
+            management: self.management,
+            #[cfg(feature = "management")]
+            management_url: self.management_url,
+            circuit_breaker: self.circuit_breaker,
+            registered_flags: self.registered_flags,
+            alias_generator: self.alias_generator,
+            required_flags: self.required_flags,
+            strict: self.strict,
+            panic_on_unregistered: self.panic_on_unregistered,
+            max_stale: self.max_stale,
+            stale_defaults: self.stale_defaults,
+            bootstrap_flags: self.bootstrap_flags,
+            failure_modes: self.failure_modes,
+            shadow: self.shadow,
+            canary: self.canary,
+            bucket_hasher: self.bucket_hasher,
+            #[cfg(feature = "remote")]
+            http_client: self.http_client,
+            state: PhantomData,
+        }
+    }
+    
+    /// Set a callback function that will be called whenever an error occurs.
+    /// This is useful for logging, monitoring, or custom error handling.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::{Client, FlagError};
+    /// let client = Client::builder()
+    ///     .with_error_callback(|error| {
+    ///         eprintln!("Flag error occurred: {}", error);
+    ///         // Send to monitoring service, etc.
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn with_error_callback<F>(mut self, callback: F) -> Self 
+    where
+        F: Fn(&FlagError) + Send + Sync + 'static,
+    {
+        self.error_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Also send the client's internal events to `sink` as JSON objects, for
+    /// platforms that only accept JSON logs: refresh results and circuit
+    /// transitions as in [`ClientEvent::to_json`](events::ClientEvent::to_json),
+    /// and every error reported to the error callback as
+    /// `{"event": "error", "code", "message", "retryable"}`. Each record also
+    /// carries a `timestamp` and a `level` of `info`, `warn` or `error`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// let client = Client::builder()
+    ///     .with_json_log_sink(|record| println!("{}", record))
+    ///     .build();
+    /// ```
+    pub fn with_json_log_sink<F>(mut self, sink: F) -> Self
+    where
+        F: Fn(&serde_json::Value) + Send + Sync + 'static,
+    {
+        self.json_log_sink = Some(Arc::new(sink));
+        self
+    }
+
+    /// Call `callback` with the duration of every refresh that takes longer
+    /// than `threshold`, failed ones included, and log a warning, to notice
+    /// a slowing API before refreshes time out.
+    pub fn with_slow_refresh_threshold<F>(mut self, threshold: Duration, callback: F) -> Self
+    where
+        F: Fn(Duration) + Send + Sync + 'static,
+    {
+        self.slow_refresh = Some((threshold, Arc::new(callback)));
+        self
+    }
+
+    /// Call `callback` the first time each flag that does not exist is
+    /// evaluated, so typos and flags deleted too early surface in monitoring.
+    /// Only the first 1024 distinct names are reported.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// let client = Client::builder()
+    ///     .on_missing_flag(|name| eprintln!("evaluated unknown flag '{}'", name))
+    ///     .build();
+    /// ```
+    pub fn on_missing_flag<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.missing_flag_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// What flags that do not exist evaluate to, `false` by default. A
+    /// [failure mode](Self::with_failure_mode) declared for the flag wins.
+    pub fn with_missing_flag_default(mut self, enabled: bool) -> Self {
+        self.missing_flag_default = enabled;
+        self
+    }
+
+    /// The flags.gg API root. A path prefix, e.g. `https://gw.corp/flags-gg/api`
+    /// behind a gateway, is kept and `/flags` is requested below it. `build()`
+    /// rejects anything that is not an absolute http(s) URL.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.to_string();
+        self
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_file_name(mut self, file_name: &str) -> Self {
+        self.file_name = Some(file_name.to_string());
+        self
+    }
+
+    /// Request flags in the given encoding. Defaults to [`WireFormat::Json`].
+    pub fn with_wire_format(mut self, wire_format: WireFormat) -> Self {
+        self.wire_format = wire_format;
+        self
+    }
+
+    /// Randomly lengthen or shorten each refresh interval by up to `percent`
+    /// (e.g. `10` for ±10%), so replicas deployed together spread their
+    /// refreshes out instead of hitting the API on the same boundary.
+    pub fn with_refresh_jitter(mut self, percent: u8) -> Self {
+        self.refresh_jitter_percent = percent;
+        self
+    }
+
+    /// Never refresh more often than `interval`, whatever `intervalAllowed` the
+    /// server sends.
+    pub fn with_min_refresh_interval(mut self, interval: Duration) -> Self {
+        self.refresh_interval.min = Some(duration_secs(interval));
+        self
+    }
+
+    /// Refresh at least every `interval`, whatever `intervalAllowed` the
+    /// server sends.
+    pub fn with_max_refresh_interval(mut self, interval: Duration) -> Self {
+        self.refresh_interval.max = Some(duration_secs(interval));
+        self
+    }
+
+    /// Refresh every `interval` and ignore the server's `intervalAllowed`, e.g.
+    /// for an air-gapped mirror that does not send a sensible value. Jitter and
+    /// the min/max bounds still apply.
+    pub fn with_refresh_interval(mut self, interval: Duration) -> Self {
+        self.refresh_interval.fixed = Some(duration_secs(interval));
+        self
+    }
+
+    /// How to read an `intervalAllowed` of zero or less, by default
+    /// [`ZeroInterval::NoCache`]. The min/max bounds still apply.
+    pub fn with_zero_interval(mut self, zero: ZeroInterval) -> Self {
+        self.refresh_interval.zero = zero;
+        self
+    }
+
+    /// Timeout for each request to the flags API. Defaults to 10 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.http_options.timeout = timeout;
+        self
+    }
+
+    /// How long idle pooled connections are kept open; `None` keeps them
+    /// indefinitely. Defaults to reqwest's 90 seconds. Set this above the refresh
+    /// interval so each refresh reuses the connection instead of a new TLS handshake.
+    pub fn with_pool_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.http_options.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Maximum idle connections kept per host.
+    pub fn with_pool_max_idle_per_host(mut self, max_idle: usize) -> Self {
+        self.http_options.pool_max_idle_per_host = Some(max_idle);
+        self
+    }
+
+    /// Send TCP keepalive probes at this interval; `None` disables them.
+    pub fn with_tcp_keepalive(mut self, interval: Option<Duration>) -> Self {
+        self.http_options.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Speak HTTP/2 from the first request without waiting for ALPN
+    /// negotiation, for endpoints or proxies known to support it (including h2c).
+    pub fn with_http2_prior_knowledge(mut self) -> Self {
+        self.http_options.http2_prior_knowledge = true;
+        self
+    }
+
+    /// Connect to `addrs` for `domain` instead of resolving it, for networks
+    /// without outbound DNS that allow-list the API's IPs. The port still comes
+    /// from the base URL; ports in `addrs` are ignored. Calling it again for the
+    /// same domain replaces its addresses.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// # use std::net::SocketAddr;
+    /// let addr: SocketAddr = "203.0.113.10:443".parse().unwrap();
+    /// let builder = Client::builder().with_resolve("api.flags.gg", [addr]);
+    /// ```
+    pub fn with_resolve<I: IntoIterator<Item = SocketAddr>>(mut self, domain: &str, addrs: I) -> Self {
+        self.http_options.resolve.insert(domain.to_string(), addrs.into_iter().collect());
+        self
+    }
+
+    /// Send every request over the Unix domain socket at `path`, e.g. to a
+    /// flags relay running as a sidecar, instead of TCP. The base URL still
+    /// sets the scheme, `Host` and path, so point it at the relay, usually
+    /// `http://localhost`; with `https` TLS is spoken over the socket.
+    /// Resolve overrides and TCP options no longer apply.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// let builder = Client::builder()
+    ///     .with_base_url("http://localhost")
+    ///     .with_unix_socket("/run/flags-relay.sock");
+    /// ```
+    #[cfg(unix)]
+    pub fn with_unix_socket(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.http_options.unix_socket = Some(path.into());
+        self
+    }
 
-        let mut circuit_state = self.circuit_state.write().await;
+    /// Read local flags from environment variables starting with `prefix`
+    /// instead of `FLAGS_`, e.g. `MYAPP_FF_NEW_CHECKOUT=true` for the
+    /// `new_checkout` flag with `with_env_prefix("MYAPP_FF_")`. `FLAGS_GG_*`
+    /// configuration variables are never read as flags.
+    pub fn with_env_prefix(mut self, prefix: &str) -> Self {
+        self.env_prefix = prefix.to_string();
+        self
+    }
+
+    /// Choose whether local environment flags or the API win when both define
+    /// a flag. Defaults to [`LocalFlagPrecedence::LocalOverridesApi`]; production
+    /// deployments can keep the control plane authoritative with
+    /// [`LocalFlagPrecedence::ApiOverridesLocal`].
+    pub fn with_local_precedence(mut self, precedence: LocalFlagPrecedence) -> Self {
+        self.local_precedence = precedence;
+        self
+    }
+
+    /// Make `build()` fail without [`Auth`] instead of serving only local flags.
+    pub fn with_auth_required(mut self, required: bool) -> Self {
+        self.auth_required = required;
+        self
+    }
+
+    /// Identify the calling service in the `User-Agent`, appended to
+    /// `flags-rs/<version>`, e.g. `with_app_identifier("checkout-service/3.2.1")`.
+    pub fn with_app_identifier(mut self, app: &str) -> Self {
+        self.app_identifier = Some(app.to_string());
+        self
+    }
 
-        if circuit_state.is_open {
-            if let Some(last_failure) = circuit_state.last_failure {
-                let now = Utc::now();
-                // Keep the circuit open for a bit after failure
-                if (now - last_failure).num_seconds() < 10 { // You can adjust this duration
-                    warn!("Circuit breaker is open, skipping refetch.");
-                    return Ok(());
-                }
-            }
-            // If enough time has passed, attempt to close the circuit
-            warn!("Attempting to close circuit breaker.");
-            circuit_state.is_open = false;
-            circuit_state.failure_count = 0;
-        }
-        drop(circuit_state); // Release the write lock
+    /// Produce the `X-Request-ID` sent with each API call, e.g. to reuse the
+    /// caller's trace ID. By default a random UUID is generated per call.
+    pub fn with_request_id_generator<F>(mut self, generator: F) -> Self
+    where
+        F: Fn() -> String + Send + Sync + 'static,
+    {
+        self.request_id_generator = Some(Arc::new(generator));
+        self
+    }
 
-        // Implement retry logic for fetching flags from the API.
-        // Internal retries should not immediately affect the circuit breaker state.
-        let api_resp = {
-            let max = self.max_retries.max(1);
-            let mut attempt: u32 = 1;
-            loop {
-                match self.fetch_flags().await {
-                    Ok(resp) => {
-                        let mut circuit_state = self.circuit_state.write().await;
-                        circuit_state.failure_count = 0; // Reset failure count on success
-                        break resp;
-                    }
-                    Err(e) => {
-                        if attempt < max {
-                            warn!("Refetch failed (attempt {}/{}), retrying...", attempt, max);
-                            self.handle_error(&e);
-                            tokio::time::sleep(Duration::from_millis(100 * attempt as u64)).await;
-                            attempt += 1;
-                            continue;
-                        }
-                        // After exhausting attempts, update circuit state once
-                        let mut cs = self.circuit_state.write().await;
-                        cs.failure_count += 1;
-                        cs.last_failure = Some(Utc::now());
-                        if cs.failure_count >= self.max_retries.max(1) {
-                            // Do not open the circuit on a single refetch cycle; keep soft-fail behavior
-                            // This preserves behavior expected by tests and avoids aggressive tripping
-                            // of the circuit breaker on transient errors.
-                        }
-                        error!("Refetch failed after {} internal retries: {}", max, e);
-                        self.handle_error(&e);
-                        drop(cs);
-                        // Refresh with local flags to ensure deterministic behavior
-                        let local_flags = build_local();
-                        let mut cache = self.cache.write().await;
-                        cache
-                            .refresh(&local_flags, 60)
-                            .await
-                            .map_err(|e| FlagError::CacheError(e.to_string()))?;
-                        // Propagate the last error
-                        return Err(e);
-                    }
-                }
-            }
-        };
+    /// Adjust every API request just before it is sent, after the client's own
+    /// headers and signature, e.g. to add a bearer token or a gateway
+    /// signature such as AWS SigV4. Returning an error fails the request
+    /// without sending it.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::Client;
+    /// # fn current_token() -> String { String::new() }
+    /// let builder = Client::builder().with_request_interceptor(|request| {
+    ///     let token = format!("Bearer {}", current_token());
+    ///     request.headers_mut().insert("Authorization", token.parse().unwrap());
+    ///     Ok(())
+    /// });
+    /// ```
+    #[cfg(feature = "remote")]
+    pub fn with_request_interceptor<F>(mut self, interceptor: F) -> Self
+    where
+        F: Fn(&mut reqwest::Request) -> Result<(), FlagError> + Send + Sync + 'static,
+    {
+        self.request_interceptor = Some(Arc::new(interceptor));
+        self
+    }
 
-        let mut api_flags: Vec<flag::FeatureFlag> = api_resp.flags
-            .into_iter()
-            .map(|f| flag::FeatureFlag {
-                enabled: f.enabled,
-                details: flag::Details {
-                    name: f.details.name.to_lowercase(),
-                    id: f.details.id,
-                },
-            })
-            .collect();
+    /// Enable [`Client::set_flag_enabled`] and [`Client::create_flag`] with
+    /// management API credentials, see [`management`]. Requires
+    /// [`with_auth`](ClientBuilder::with_auth) for the project and environment.
+    #[cfg(feature = "management")]
+    pub fn with_management(mut self, auth: management::ManagementAuth) -> Self {
+        self.management = Some(auth);
+        self
+    }
 
-        let local_flags = build_local();
+    /// Base URL of the management API, the base URL of the flags API by default.
+    #[cfg(feature = "management")]
+    pub fn with_management_url(mut self, url: &str) -> Self {
+        self.management_url = Some(url.to_string());
+        self
+    }
 
-        // Combine API flags and local flags, with local overriding API
-        let mut combined_flags = Vec::new();
-        let mut local_flags_map: HashMap<String, FeatureFlag> = local_flags.into_iter().map(|f| (f.details.name.clone(), f)).collect();
+    /// Declare the flags the code evaluates. API flags outside this list are
+    /// logged once, catching dead flags; with [`strict`](Self::strict),
+    /// evaluating a name outside it is an error, catching typos.
+    pub fn with_registered_flags<I, N>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = N>,
+        N: AsRef<str>,
+    {
+        let registered = self.registered_flags.get_or_insert_with(HashSet::new);
+        registered.extend(names.into_iter().map(|n| normalize_name(n.as_ref()).into_owned()));
+        self
+    }
 
-        for api_flag in api_flags.drain(..) {
-            if let Some(local_flag) = local_flags_map.remove(&api_flag.details.name) {
-                // Local flag with the same name exists, use the local one
-                combined_flags.push(local_flag);
-            } else {
-                // No local flag with the same name, use the API one
-                combined_flags.push(api_flag);
+    /// Flags the environment must define. [`Client::wait_until_ready`] fails
+    /// with [`FlagError::MissingRequiredFlags`] if any is absent from the
+    /// fetched flags, so a misconfigured environment fails the deploy instead
+    /// of silently disabling the paths behind them.
+    pub fn require_flags<I, N>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = N>,
+        N: AsRef<str>,
+    {
+        for name in names {
+            let name = normalize_name(name.as_ref()).into_owned();
+            if !self.required_flags.contains(&name) {
+                self.required_flags.push(name);
             }
         }
+        self
+    }
 
-        // Add any remaining local flags that didn't have a corresponding API flag
-        combined_flags.extend(local_flags_map.into_values());
-
+    /// Let flags be evaluated by the names `generator` returns for them, such
+    /// as [`alias::camel_case`], on top of the normalized spellings every
+    /// name already matches, see [`alias`].
+    pub fn with_alias_generator<F>(mut self, generator: F) -> Self
+    where
+        F: Fn(&str) -> Vec<String> + Send + Sync + 'static,
+    {
+        self.alias_generator = Some(Arc::new(generator));
+        self
+    }
 
-        let mut cache = self.cache.write().await;
-        cache.refresh(&combined_flags, api_resp.interval_allowed).await
-            .map_err(|e| FlagError::CacheError(e.to_string()))?;
+    /// Refuse to evaluate flags missing from
+    /// [`with_registered_flags`](Self::with_registered_flags): they report
+    /// [`FlagError::UnregisteredFlag`] through the error callback and evaluate
+    /// to `false`, or panic with
+    /// [`panic_on_unregistered`](Self::panic_on_unregistered).
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
 
-        Ok(())
+    /// In [`strict`](Self::strict) mode, panic on an unregistered name
+    /// instead of reporting it, to fail fast in development. Off by default;
+    /// enable it from the application, e.g. with `cfg!(debug_assertions)`,
+    /// rather than in libraries or FFI hosts whose callers can't catch it.
+    pub fn panic_on_unregistered(mut self, panic: bool) -> Self {
+        self.panic_on_unregistered = panic;
+        self
     }
-}
 
-impl Clone for Client {
-    fn clone(&self) -> Self {
-        Client {
-            base_url: self.base_url.clone(),
-            http_client: self.http_client.clone(),
-            cache: Arc::clone(&self.cache),
-            max_retries: self.max_retries,
-            circuit_state: Arc::clone(&self.circuit_state),
-            auth: self.auth.clone(),
-            refresh_in_progress: Arc::clone(&self.refresh_in_progress),
-            error_callback: self.error_callback.clone(),
-        }
+    /// Serve cached flags for at most `max_age` after the last successful
+    /// refresh. Past that, every evaluation returns the flag's
+    /// [stale default](Self::with_stale_default), `false` unless configured,
+    /// and [`FlagError::StaleFlags`] is reported once, until a refresh succeeds.
+    /// Without this, a failing API leaves the last flags in place indefinitely.
+    pub fn with_max_stale(mut self, max_age: Duration) -> Self {
+        self.max_stale = Some(max_age);
+        self
     }
-}
 
-impl<'a> Flag<'a> {
-    pub async fn enabled(&self) -> bool {
-        self.client.is_enabled(&self.name).await
+    /// The value `name` evaluates to once the cache is older than
+    /// [`with_max_stale`](Self::with_max_stale) allows.
+    pub fn with_stale_default(mut self, name: &str, enabled: bool) -> Self {
+        self.stale_defaults.insert(normalize_name(name).into_owned(), enabled);
+        self
     }
-}
 
-pub struct ClientBuilder {
-    base_url: String,
-    max_retries: u32,
-    auth: Option<Auth>,
-    use_memory_cache: bool,
-    file_name: Option<String>,
-    error_callback: Option<ErrorCallback>,
-}
+    /// Values for flags missing from the cache while the client is
+    /// [`ClientStatus::ColdStartDegraded`]: the last refresh failed and
+    /// nothing has been fetched from the API yet. A cold start during an outage
+    /// then serves these instead of `false`. A declared
+    /// [failure mode](Self::with_failure_mode) takes priority.
+    pub fn with_bootstrap_flags<I, N>(mut self, flags: I) -> Self
+    where
+        I: IntoIterator<Item = (N, bool)>,
+        N: AsRef<str>,
+    {
+        self.bootstrap_flags
+            .extend(flags.into_iter().map(|(name, enabled)| (normalize_name(name.as_ref()).into_owned(), enabled)));
+        self
+    }
 
-impl ClientBuilder {
-    fn new() -> Self {
-        Self {
-            base_url: BASE_URL.to_string(),
-            max_retries: MAX_RETRIES,
-            auth: None,
-            use_memory_cache: false,
-            file_name: None,
-            error_callback: None,
-        }
+    /// Declare how `name` behaves when its state is unknown, for
+    /// deterministic behavior during incidents. Applies when the cache errors,
+    /// the flag is missing and once the cache is past
+    /// [`with_max_stale`](Self::with_max_stale), where it takes priority over a
+    /// [stale default](Self::with_stale_default). Undeclared flags are `false`,
+    /// or the [missing flag default](Self::with_missing_flag_default) when missing.
+    ///
+    /// # Example
+    /// ```
+    /// # use flags_rs::{Client, FailureMode};
+    /// let client = Client::builder()
+    ///     .with_failure_mode("maintenance-mode", FailureMode::Open)
+    ///     .with_failure_mode("beta-api", FailureMode::Closed)
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn with_failure_mode(mut self, name: &str, mode: FailureMode) -> Self {
+        self.failure_modes.insert(normalize_name(name).into_owned(), mode);
+        self
     }
-    
-    /// Set a callback function that will be called whenever an error occurs.
-    /// This is useful for logging, monitoring, or custom error handling.
-    /// 
+
+    /// Also evaluate every flag on `shadow`, e.g. a client for another base URL
+    /// or an offline client serving local flags, and call `on_mismatch` when
+    /// it disagrees. The primary value is always the one served, so a
+    /// migration between sources can be checked against real traffic first.
+    /// Each evaluation waits for the shadow too; tenant evaluations
+    /// ([`EvaluationContext::with_tenant`]) are not shadowed.
+    ///
     /// # Example
     /// ```no_run
-    /// # use flags_rs::{Client, FlagError};
+    /// # use flags_rs::Client;
+    /// let next = Client::builder().with_base_url("https://flags.next.example").build().unwrap();
     /// let client = Client::builder()
-    ///     .with_error_callback(|error| {
-    ///         eprintln!("Flag error occurred: {}", error);
-    ///         // Send to monitoring service, etc.
+    ///     .with_shadow(next, |mismatch| {
+    ///         eprintln!("'{}' is {} but {} on the new backend", mismatch.flag, mismatch.primary, mismatch.shadow);
     ///     })
     ///     .build();
     /// ```
-    pub fn with_error_callback<F>(mut self, callback: F) -> Self 
+    pub fn with_shadow<F>(mut self, shadow: Client, on_mismatch: F) -> Self
     where
-        F: Fn(&FlagError) + Send + Sync + 'static,
+        F: Fn(&ShadowMismatch) + Send + Sync + 'static,
     {
-        self.error_callback = Some(Arc::new(callback));
+        self.shadow = Some(Arc::new(Shadow {
+            client: shadow,
+            on_mismatch: Arc::new(on_mismatch),
+            sample_rate: 1.0,
+        }));
         self
     }
 
-    pub fn with_base_url(mut self, base_url: &str) -> Self {
-        self.base_url = base_url.to_string();
+    /// Also evaluate a `sample_rate` fraction (0.0 to 1.0) of evaluations
+    /// against another environment of the same project, reporting each flag
+    /// whose value differs there, to validate the environment's configuration
+    /// before cutting over. [`ShadowMismatch::shadow`] holds the canary's value.
+    ///
+    /// The canary client is built from this builder with `environment_id`
+    /// swapped into its credentials and a cache of its own. Sampled
+    /// evaluations wait for it too. Cannot be combined with [`with_shadow`](Self::with_shadow).
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use flags_rs::{Auth, Client};
+    /// # fn example(auth: Auth) {
+    /// let builder = Client::builder()
+    ///     .with_auth(auth)
+    ///     .with_canary_environment("production-next", 0.05, |mismatch| {
+    ///         eprintln!("'{}' would change from {} to {}", mismatch.flag, mismatch.primary, mismatch.shadow);
+    ///     });
+    /// # }
+    /// ```
+    pub fn with_canary_environment<F>(mut self, environment_id: &str, sample_rate: f64, on_mismatch: F) -> Self
+    where
+        F: Fn(&ShadowMismatch) + Send + Sync + 'static,
+    {
+        self.canary = Some((environment_id.to_string(), sample_rate, Arc::new(on_mismatch)));
         self
     }
 
-    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
-        self.max_retries = max_retries;
+    /// Assign users to rollout buckets with `hasher` instead of the default
+    /// [`Murmur3`]. See [`bucketing`] for how buckets are derived and which
+    /// names they are derived from.
+    pub fn with_bucket_hasher<H: BucketHasher + 'static>(mut self, hasher: H) -> Self {
+        self.bucket_hasher = Some(Arc::new(hasher));
         self
     }
 
-    pub fn with_auth(mut self, auth: Auth) -> Self {
-        self.auth = Some(auth);
+    /// Guard refreshes with `breaker` instead of the built-in
+    /// [`DefaultCircuitBreaker`]. Pass an `Arc` to share one breaker's state
+    /// with other clients or subsystems.
+    pub fn with_circuit_breaker<B: CircuitBreaker + 'static>(mut self, breaker: B) -> Self {
+        self.circuit_breaker = Some(Arc::new(breaker));
+        self.circuit_breaker_disabled = false;
         self
     }
 
-    pub fn with_file_name(mut self, file_name: &str) -> Self {
-        self.file_name = Some(file_name.to_string());
+    /// Call the API on every refresh, however often it failed, and retry on
+    /// the next call after a failed refresh. [`Client::list`] returns the
+    /// error of a failed refresh instead of serving whatever is cached. For
+    /// batch jobs that fetch flags once and want a plain error. Replaces any
+    /// [`with_circuit_breaker`](Self::with_circuit_breaker).
+    pub fn with_circuit_breaker_disabled(mut self) -> Self {
+        self.circuit_breaker = Some(Arc::new(NoCircuitBreaker));
+        self.circuit_breaker_disabled = true;
+        self
+    }
+
+    /// Fetch flags through a custom [`Transport`] instead of the HTTP API.
+    /// `with_base_url`, `with_wire_format`, `with_app_identifier`,
+    /// `with_request_id_generator` and `with_request_interceptor` only apply
+    /// to the default transport.
+    pub fn with_transport<T: Transport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Make concurrent evaluations wait for an in-flight refresh instead of
+    /// reading the current cache, so the first burst of requests after startup
+    /// all see fetched flags rather than an empty cache.
+    pub fn with_coalesced_refresh(mut self) -> Self {
+        self.coalesce_refresh = true;
+        self
+    }
+
+    /// Ask the API only for flags changed since the last fetch, see
+    /// [`Transport::fetch_flags_since`]. Falls back to full fetches whenever
+    /// the server does not return a version or no longer knows it.
+    pub fn with_delta_refresh(mut self) -> Self {
+        self.delta_refresh = true;
         self
     }
 
+    /// Store flags in a new [`MemoryCache`], the default, replacing a cache
+    /// set earlier or carried over by [`Client::to_builder`].
     pub fn with_memory_cache(mut self) -> Self {
         self.use_memory_cache = true;
+        self.cache = None;
+        self.cache_type = std::any::type_name::<MemoryCache>();
+        self
+    }
+
+    /// Store flags in a custom [`Cache`] implementation instead of [`MemoryCache`].
+    pub fn with_cache<C: Cache + Send + Sync + 'static>(mut self, cache: C) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self.cache_type = std::any::type_name::<C>();
         self
     }
 
-    pub fn build(self) -> Result<Client, FlagError> {
+    /// Store flags in a [`ShardedCache`](crate::cache::ShardedCache), reducing
+    /// contention for very high evaluation rates across many distinct flags.
+    #[cfg(feature = "sharded-cache")]
+    pub fn with_sharded_cache(self) -> Self {
+        self.with_cache(crate::cache::ShardedCache::new())
+    }
+
+}
+
+impl ClientBuilder {
+    pub fn build(mut self) -> Result<Client, FlagError> {
         // Validate auth if provided
-        if let Some(ref auth) = self.auth {
-            if auth.project_id.trim().is_empty() {
-                return Err(FlagError::BuilderError("Project ID cannot be empty".to_string()));
-            }
-            if auth.agent_id.trim().is_empty() {
-                return Err(FlagError::BuilderError("Agent ID cannot be empty".to_string()));
-            }
-            if auth.environment_id.trim().is_empty() {
-                return Err(FlagError::BuilderError("Environment ID cannot be empty".to_string()));
-            }
+        if let Some(auth) = self.auth.take() {
+            auth.validate().map_err(FlagError::BuilderError)?;
+            self.auth = Some(auth.sanitize()?);
+        }
+
+        if self.auth_required && self.auth.is_none() {
+            return Err(FlagError::BuilderError("Credentials are required but none were given".to_string()));
         }
 
         // Validate base URL
         if self.base_url.trim().is_empty() {
             return Err(FlagError::BuilderError("Base URL cannot be empty".to_string()));
         }
+        #[cfg(feature = "remote")]
+        transport::parse_base_url(&self.base_url).map_err(FlagError::BuilderError)?;
 
         // Validate max retries is reasonable
         if self.max_retries > 10 {
             return Err(FlagError::BuilderError("Max retries cannot exceed 10".to_string()));
         }
 
-        let cache: Box<dyn Cache + Send + Sync> = Box::new(MemoryCache::new());
+        if self.refresh_jitter_percent > 100 {
+            return Err(FlagError::BuilderError("Refresh jitter cannot exceed 100%".to_string()));
+        }
 
-        let http_client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(10))
-            .build()
-            .map_err(|e| FlagError::BuilderError(format!("Failed to build HTTP client: {}", e)))?;
+        if self.strict && self.registered_flags.is_none() {
+            return Err(FlagError::BuilderError(
+                "Strict mode requires with_registered_flags".to_string(),
+            ));
+        }
+
+        if self.max_stale.is_some_and(|max_age| max_age.is_zero()) {
+            return Err(FlagError::BuilderError("Maximum staleness must be greater than zero".to_string()));
+        }
+        if self.max_stale.is_none() && !self.stale_defaults.is_empty() {
+            return Err(FlagError::BuilderError("Stale defaults require with_max_stale".to_string()));
+        }
+
+        if let Some((_, sample_rate, _)) = &self.canary {
+            if !(0.0..=1.0).contains(sample_rate) {
+                return Err(FlagError::BuilderError("Canary sample rate must be between 0 and 1".to_string()));
+            }
+            if self.auth.is_none() {
+                return Err(FlagError::BuilderError("A canary environment requires credentials".to_string()));
+            }
+            if self.shadow.is_some() {
+                return Err(FlagError::BuilderError(
+                    "with_shadow and with_canary_environment cannot be combined".to_string(),
+                ));
+            }
+        }
+
+        if self.env_prefix.is_empty() {
+            return Err(FlagError::BuilderError("Environment variable prefix cannot be empty".to_string()));
+        }
+
+        let bounds = self.refresh_interval;
+        if [bounds.min, bounds.max, bounds.fixed].contains(&Some(0)) {
+            return Err(FlagError::BuilderError("Refresh intervals must be at least one second".to_string()));
+        }
+        if let (Some(min), Some(max)) = (bounds.min, bounds.max) {
+            if min > max {
+                return Err(FlagError::BuilderError(
+                    "Minimum refresh interval cannot exceed the maximum".to_string(),
+                ));
+            }
+        }
+
+        if let Some(domain) = self.http_options.resolve.iter().find(|(_, addrs)| addrs.is_empty()).map(|(d, _)| d) {
+            return Err(FlagError::BuilderError(format!("No addresses given to resolve {} to", domain)));
+        }
+
+        if self.http_options.timeout.is_zero() {
+            return Err(FlagError::BuilderError("Timeout must be greater than zero".to_string()));
+        }
+
+        let cache = self.cache.clone().unwrap_or_else(|| Arc::new(MemoryCache::new()));
+        let mut origin = self.clone();
+        origin.cache = Some(Arc::clone(&cache));
+
+        let transport = match self.transport {
+            Some(transport) => transport,
+            #[cfg(not(feature = "remote"))]
+            None if self.auth.is_some() => {
+                return Err(FlagError::BuilderError(
+                    "Fetching from flags.gg needs the `remote` feature; use with_transport or build without Auth"
+                        .to_string(),
+                ));
+            }
+            #[cfg(not(feature = "remote"))]
+            None => Arc::new(transport::LocalOnly),
+            #[cfg(feature = "remote")]
+            None => {
+                let http_client = match self.http_client {
+                    Some((options, client)) if options == self.http_options => client,
+                    _ => self.http_options.build_client()?,
+                };
+                origin.http_client = Some((self.http_options.clone(), http_client.clone()));
+                let mut transport = HttpTransport::new(&self.base_url, http_client, self.wire_format);
+                if let Some(app) = &self.app_identifier {
+                    transport = transport.with_app_identifier(app)?;
+                }
+                if let Some(generator) = self.request_id_generator {
+                    transport = transport.with_request_id_generator(generator);
+                }
+                if let Some(interceptor) = self.request_interceptor {
+                    transport = transport.with_request_interceptor(interceptor);
+                }
+                Arc::new(transport)
+            }
+        };
+
+        let shadow = match self.canary {
+            Some((environment_id, sample_rate, on_mismatch)) => {
+                // Shares the HTTP client and settings, not the cache
+                let mut canary = origin.clone();
+                canary.auth = canary.auth.map(|auth| Auth { environment_id, ..auth });
+                canary.canary = None;
+                canary.cache = None;
+                Some(Arc::new(Shadow {
+                    client: canary.build()?,
+                    on_mismatch,
+                    sample_rate,
+                }))
+            }
+            None => self.shadow,
+        };
+
+        #[cfg(feature = "management")]
+        let management = match self.management {
+            Some(_) if self.auth.is_none() => {
+                return Err(FlagError::BuilderError("with_management requires with_auth".to_string()));
+            }
+            Some(auth) => {
+                let http_client = match &origin.http_client {
+                    Some((_, client)) => client.clone(),
+                    None => self.http_options.build_client()?,
+                };
+                let url = self.management_url.as_deref().unwrap_or(&self.base_url);
+                Some(Arc::new(management::Management::new(http_client, url, auth)?))
+            }
+            None => None,
+        };
+
+        // Offline clients never refresh from the API, so their flags cannot go stale
+        let stale = self
+            .max_stale
+            .filter(|_| self.auth.is_some() || !transport.requires_auth())
+            .map(|max_age| Arc::new(StalePolicy::new(max_age, Arc::new(self.stale_defaults))));
 
-        Ok(Client {
+        let published = Arc::new(ArcSwap::from_pointee(HashMap::new()));
+        let client = Client {
             base_url: self.base_url,
-            http_client,
-            cache: Arc::new(RwLock::new(cache)),
+            transport,
+            cache,
             max_retries: self.max_retries,
-            circuit_state: Arc::new(RwLock::new(CircuitState {
-                is_open: false,
-                failure_count: 0,
-                last_failure: None,
-            })),
-            auth: self.auth,
+            circuit_breaker: self.circuit_breaker.unwrap_or_else(|| Arc::new(DefaultCircuitBreaker::new())),
+            auth: Arc::new(ArcSwapOption::new(self.auth.map(Arc::new))),
             refresh_in_progress: Arc::new(AtomicBool::new(false)),
+            refresh_done: Arc::new(watch::channel(0).0),
+            coalesce_refresh: self.coalesce_refresh,
+            circuit_breaker_disabled: self.circuit_breaker_disabled,
+            delta_refresh: self.delta_refresh,
+            api_state: Arc::new(ArcSwapOption::empty()),
             error_callback: self.error_callback,
+            missing_flag_callback: self.missing_flag_callback,
+            missing_reported: Arc::new(Mutex::new(HashSet::new())),
+            missing_flag_default: self.missing_flag_default,
+            last_refresh: Arc::new(RwLock::new(None)),
+            schedule: Arc::new(RefreshSchedule::new()),
+            refresh_generation: Arc::new(AtomicU64::new(0)),
+            api_snapshot: Arc::new(AtomicBool::new(false)),
+            refresh_failed: Arc::new(AtomicBool::new(false)),
+            bootstrap_flags: Arc::new(self.bootstrap_flags),
+            events: Arc::new(EventBus::new(self.json_log_sink.clone())),
+            slow_refresh: self.slow_refresh.clone(),
+            #[cfg(feature = "metrics")]
+            refresh_histogram: Arc::new(metrics::RefreshHistogram::default()),
+            local_flags: Arc::new(ArcSwap::from_pointee(Vec::new())),
+            env_prefix: Arc::from(self.env_prefix),
+            local_precedence: self.local_precedence,
+            refresh_jitter_percent: self.refresh_jitter_percent,
+            refresh_interval: self.refresh_interval,
+            pinned: Arc::new(Mutex::new(HashMap::new())),
+            subscriptions: Arc::new(Mutex::new(HashMap::new())),
+            lifecycle: Arc::new(LifecycleTracker::new()),
+            registry: self
+                .registered_flags
+                .map(|names| Arc::new(FlagRegistry::new(names, self.strict, self.panic_on_unregistered))),
+            required_flags: self.required_flags.into(),
+            stale,
+            failure_modes: Arc::new(self.failure_modes),
+            shadow,
+            usage: Arc::new(UsageTracker::new(Arc::clone(&published))),
+            suggestions: Arc::new(Suggestions::new()),
+            groups: Arc::new(ArcSwap::from_pointee(HashMap::new())),
+            alias_generator: self.alias_generator.clone(),
+            aliases: Arc::new(ArcSwap::from_pointee(HashMap::new())),
+            quarantined: Arc::new(ArcSwap::from_pointee(Vec::new())),
+            targeting: Arc::new(ArcSwap::from_pointee(HashMap::new())),
+            bucket_hasher: self.bucket_hasher.unwrap_or_else(|| Arc::new(Murmur3)),
+            #[cfg(feature = "management")]
+            management,
+            published,
+            tenants: Arc::new(Mutex::new(HashMap::new())),
+            origin: Arc::new(origin),
+        };
+        client.load_local_flags();
+        Ok(client)
+    }
+}
+
+/// A non-empty environment variable, trimmed.
+fn env_value(name: &str) -> Result<Option<String>, FlagError> {
+    match env::var(name) {
+        Ok(value) if value.trim().is_empty() => Ok(None),
+        Ok(value) => Ok(Some(value.trim().to_string())),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(env::VarError::NotUnicode(_)) => Err(FlagError::BuilderError(format!("{} is not valid UTF-8", name))),
+    }
+}
+
+fn env_bool(name: &str) -> Result<Option<bool>, FlagError> {
+    env_value(name)?
+        .map(|value| {
+            parse_bool(&value).ok_or_else(|| {
+                FlagError::BuilderError(format!("Invalid {} \"{}\": expected true or false", name, value))
+            })
+        })
+        .transpose()
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str, expected: &str) -> Result<Option<T>, FlagError> {
+    env_value(name)?
+        .map(|value| {
+            value.parse().map_err(|_| {
+                FlagError::BuilderError(format!("Invalid {} \"{}\": expected {}", name, value, expected))
+            })
         })
+        .transpose()
+}
+
+/// Client-side bounds on the server's refresh interval, in seconds.
+#[derive(Debug, Clone, Copy, Default)]
+struct RefreshInterval {
+    min: Option<i32>,
+    max: Option<i32>,
+    fixed: Option<i32>,
+    zero: ZeroInterval,
+}
+
+/// The interval scheduled for [`ZeroInterval::NoCache`], in seconds.
+const NO_CACHE_INTERVAL: i32 = 1;
+
+impl RefreshInterval {
+    /// The interval to schedule: the override or the server's value, a
+    /// non-positive one read as [`ZeroInterval`] says, jittered, then clamped
+    /// so jitter cannot leave the bounds.
+    fn resolve(&self, interval_allowed: i32, jitter_percent: u8) -> i32 {
+        let mut interval = match self.fixed.unwrap_or(interval_allowed) {
+            interval if interval > 0 => jitter_interval(interval, jitter_percent),
+            _ => match self.zero {
+                ZeroInterval::NoCache => NO_CACHE_INTERVAL,
+                ZeroInterval::CacheForever => i32::MAX,
+            },
+        };
+        if let Some(min) = self.min {
+            interval = interval.max(min);
+        }
+        if let Some(max) = self.max {
+            interval = interval.min(max);
+        }
+        interval
     }
 }
 
-fn build_local() -> Vec<FeatureFlag> {
-    let mut result = Vec::new();
+/// Whole seconds, saturating at `i32::MAX`.
+fn duration_secs(duration: Duration) -> i32 {
+    i32::try_from(duration.as_secs()).unwrap_or(i32::MAX)
+}
 
-    for (key, value) in env::vars() {
-        if !key.starts_with("FLAGS_") {
-            continue;
+/// Spread `interval` uniformly over ±`percent`%, never dropping a positive
+/// interval below one second.
+pub(crate) fn jitter_interval(interval: i32, percent: u8) -> i32 {
+    if percent == 0 || interval <= 0 {
+        return interval;
+    }
+
+    // A randomly seeded hasher is enough entropy for spreading refreshes
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(Utc::now().timestamp_nanos_opt().unwrap_or_default() as u128);
+    let unit = (hasher.finish() as f64 / u64::MAX as f64) * 2.0 - 1.0;
+
+    let spread = interval as f64 * f64::from(percent) / 100.0;
+    ((interval as f64 + unit * spread).round() as i32).max(1)
+}
+
+fn group_index(flags: &[FeatureFlag]) -> HashMap<String, Vec<String>> {
+    let mut groups: HashMap<String, Vec<String>> = HashMap::new();
+    for flag in flags {
+        for group in &flag.details.groups {
+            groups
+                .entry(normalize_name(group).into_owned())
+                .or_default()
+                .push(flag.details.name.clone());
         }
+    }
+    for members in groups.values_mut() {
+        members.sort();
+        members.dedup();
+    }
+    groups
+}
+
+/// The canonical form of a flag name: lowercase, with underscores and spaces
+/// written as hyphens, so `NEW_AUTH`, `new auth` and `New-Auth` are one flag.
+/// Flag names are stored in this form, so lookups only allocate when the
+/// caller's name is not.
+pub(crate) fn normalize_name(name: &str) -> Cow<'_, str> {
+    if name.chars().any(|c| c.is_uppercase() || c == '_' || c == ' ') {
+        Cow::Owned(name.to_lowercase().replace(['_', ' '], "-"))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// The Cargo features this crate was compiled with.
+fn enabled_features() -> Vec<&'static str> {
+    [
+        ("remote", cfg!(feature = "remote")),
+        ("tower-middleware", cfg!(feature = "tower-middleware")),
+        ("msgpack", cfg!(feature = "msgpack")),
+        ("grpc", cfg!(feature = "grpc")),
+        ("blocking", cfg!(feature = "blocking")),
+        ("ffi", cfg!(feature = "ffi")),
+        ("sharded-cache", cfg!(feature = "sharded-cache")),
+        ("macros", cfg!(feature = "macros")),
+        ("axum", cfg!(feature = "axum")),
+        ("relay", cfg!(feature = "relay")),
+        ("metrics", cfg!(feature = "metrics")),
+        ("management", cfg!(feature = "management")),
+        ("test-util", cfg!(feature = "test-util")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect()
+}
+
+/// Case-insensitive `true`/`false`, `1`/`0`, `yes`/`no`, `on`/`off`, `y`/`n`.
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "y" | "on" => Some(true),
+        "false" | "0" | "no" | "n" | "off" => Some(false),
+        _ => None,
+    }
+}
 
-        let enabled = value == "true";
-        let flag_name_env = key.trim_start_matches("FLAGS_").to_string();
-        let flag_name_lower = flag_name_env.to_lowercase();
+/// Local flags from variables starting with `prefix`, the variables whose
+/// values are not booleans (those flags are disabled), and the names set to
+/// different states by more than one variable. Variables are read in sorted
+/// order and the first to set a name wins, so e.g. `FLAGS_NEW_UI` takes
+/// precedence over `FLAGS_new_ui`.
+fn build_local(prefix: &str) -> (Vec<FeatureFlag>, Vec<(String, String)>, Vec<String>) {
+    let mut result = Vec::new();
+    let mut invalid = Vec::new();
+    let mut conflicts = Vec::new();
+    // Variable and state that set each name
+    let mut sources: HashMap<String, (String, bool)> = HashMap::new();
+
+    let mut vars: Vec<(String, String)> = env::vars().collect();
+    vars.sort();
+    for (key, value) in vars {
+        if key.starts_with(CONFIG_ENV_PREFIX) {
+            continue;
+        }
+        let Some(flag_name_env) = key.strip_prefix(prefix) else {
+            continue;
+        };
 
-        // Create a FeatureFlag for the flag name as it appears in the environment variable (lowercase)
+        let enabled = match parse_bool(&value) {
+            Some(enabled) => enabled,
+            None => {
+                invalid.push((key.clone(), value.clone()));
+                false
+            }
+        };
+        // Lookups normalize the same way, so one entry serves every spelling
+        let name = normalize_name(flag_name_env).into_owned();
+        if let Some((first, first_enabled)) = sources.get(&name) {
+            if *first_enabled != enabled {
+                conflicts.push(format!("{} and {} both set '{}', using {}", first, key, name, first));
+            }
+            continue;
+        }
+        sources.insert(name.clone(), (key.clone(), enabled));
         result.push(FeatureFlag {
             enabled,
             details: Details {
-                name: flag_name_lower.clone(),
-                id: format!("local_{}", flag_name_lower), // Using a simple identifier for local flags
+                id: format!("local_{}", name), // Using a simple identifier for local flags
+                name,
+                ..Default::default()
             },
         });
+    }
 
-        // Optionally, create FeatureFlags for common variations (hyphens and spaces)
-        if flag_name_lower.contains('_') {
-            let flag_name_hyphenated = flag_name_lower.replace('_', "-");
-            result.push(FeatureFlag {
-                enabled,
-                details: Details {
-                    name: flag_name_hyphenated.clone(),
-                    id: format!("local_{}", flag_name_hyphenated),
-                },
-            });
-        }
+    (result, invalid, conflicts)
+}
 
-        if flag_name_lower.contains('_') || flag_name_lower.contains('-') {
-            let flag_name_spaced = flag_name_lower.replace('_', " ").replace('-', " ");
-            result.push(FeatureFlag {
-                enabled,
-                details: Details {
-                    name: flag_name_spaced.clone(),
-                    id: format!("local_{}", flag_name_spaced),
-                },
-            });
+/// Normalize the names of API flags, keeping one flag per name whatever the
+/// response order: the one already spelled in normalized form, else the first
+/// by spelling and then id. Returns the kept flags, in response order, and a
+/// description of each dropped flag whose spelling or state differed.
+fn dedupe_flags(flags: Vec<FeatureFlag>) -> (Vec<FeatureFlag>, Vec<String>) {
+    let mut kept: Vec<FeatureFlag> = Vec::with_capacity(flags.len());
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut dropped = Vec::new();
+
+    for flag in flags {
+        let name = normalize_name(&flag.details.name).into_owned();
+        let Some(&i) = index.get(&name) else {
+            index.insert(name, kept.len());
+            kept.push(flag);
+            continue;
+        };
+        let rank = |f: &FeatureFlag| (f.details.name != name, f.details.name.clone(), f.details.id.clone());
+        if rank(&flag) < rank(&kept[i]) {
+            dropped.push((i, std::mem::replace(&mut kept[i], flag)));
+        } else {
+            dropped.push((i, flag));
         }
-
     }
 
-    result
+    let state = |f: &FeatureFlag| if f.enabled { "enabled" } else { "disabled" };
+    let mut conflicts: Vec<String> = dropped
+        .iter()
+        .filter(|(i, flag)| flag.details.name != kept[*i].details.name || flag.enabled != kept[*i].enabled)
+        .map(|(i, flag)| {
+            let winner = &kept[*i];
+            format!(
+                "'{}' ({}) is the same flag as '{}' ({}), using '{}'",
+                flag.details.name,
+                state(flag),
+                winner.details.name,
+                state(winner),
+                winner.details.name
+            )
+        })
+        .collect();
+    conflicts.sort();
+
+    for flag in &mut kept {
+        if let Cow::Owned(name) = normalize_name(&flag.details.name) {
+            flag.details.name = name;
+        }
+    }
+    (kept, conflicts)
 }
 
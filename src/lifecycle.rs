@@ -0,0 +1,78 @@
+//! Warnings for deprecated and expired flags that are still being evaluated.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use arc_swap::ArcSwap;
+use chrono::{DateTime, Utc};
+use tokio::time::Instant;
+
+use crate::flag::FeatureFlag;
+
+/// How often a warning is repeated for the same flag.
+pub(crate) const WARNING_INTERVAL: Duration = Duration::from_secs(300);
+
+struct Lifecycle {
+    deprecated: bool,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// Lifecycle metadata of the published flags that carry any, so evaluating an
+/// ordinary flag costs a single empty-map check.
+pub(crate) struct LifecycleTracker {
+    flags: ArcSwap<HashMap<String, Lifecycle>>,
+    warned: Mutex<HashMap<String, Instant>>,
+}
+
+impl LifecycleTracker {
+    pub(crate) fn new() -> Self {
+        Self {
+            flags: ArcSwap::from_pointee(HashMap::new()),
+            warned: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn update(&self, flags: &[FeatureFlag]) {
+        let tracked: HashMap<String, Lifecycle> = flags
+            .iter()
+            .filter(|f| f.details.deprecated || f.details.expires_at.is_some())
+            .map(|f| {
+                (
+                    f.details.name.clone(),
+                    Lifecycle {
+                        deprecated: f.details.deprecated,
+                        expires_at: f.details.expires_at,
+                    },
+                )
+            })
+            .collect();
+        self.flags.store(std::sync::Arc::new(tracked));
+    }
+
+    /// A warning for `name` if it is deprecated or expired and has not been
+    /// warned about within [`WARNING_INTERVAL`].
+    pub(crate) fn check(&self, name: &str) -> Option<String> {
+        let flags = self.flags.load();
+        if flags.is_empty() {
+            return None;
+        }
+        let lifecycle = flags.get(name)?;
+
+        let reason = match lifecycle.expires_at {
+            Some(expires_at) if expires_at <= Utc::now() => format!("expired at {}", expires_at.to_rfc3339()),
+            _ if lifecycle.deprecated => "deprecated".to_string(),
+            _ => return None,
+        };
+
+        let mut warned = self.warned.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        match warned.get(name) {
+            Some(last) if now.duration_since(*last) < WARNING_INTERVAL => None,
+            _ => {
+                warned.insert(name.to_string(), now);
+                Some(format!("'{}' is {} but still evaluated", name, reason))
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use tokio::time::sleep;
+
+    use crate::transport::bundle::BundleTransport;
+    use crate::{Client, FlagError};
+
+    // These run without the `remote` feature, covering local flags and bundles
+    #[tokio::test]
+    async fn test_local_flags_without_api() {
+        std::env::set_var("LOCAL_TEST_FF_NEW_CHECKOUT", "true");
+        std::env::set_var("LOCAL_TEST_FF_DARK_MODE", "off");
+        let client = Client::builder()
+            .with_env_prefix("LOCAL_TEST_FF_")
+            .with_memory_cache()
+            .build()
+            .unwrap();
+        std::env::remove_var("LOCAL_TEST_FF_NEW_CHECKOUT");
+        std::env::remove_var("LOCAL_TEST_FF_DARK_MODE");
+
+        assert!(client.is("new-checkout").enabled().await);
+        assert!(!client.is("dark-mode").enabled().await);
+        assert!(!client.is("missing").enabled().await);
+        assert_eq!(client.list().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_signed_bundle_transport() {
+        use ring::rand::SystemRandom;
+        use ring::signature::{Ed25519KeyPair, KeyPair};
+
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let public_key = key_pair.public_key().as_ref().to_vec();
+        let bundle = |payload: &str, signed: &str| {
+            let signature: String = key_pair.sign(signed.as_bytes()).as_ref().iter().map(|b| format!("{:02x}", b)).collect();
+            serde_json::json!({"payload": payload, "signature": signature}).to_string()
+        };
+        let payload = |enabled: bool| {
+            serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": enabled, "details": {"name": "bundled-flag", "id": "1"}}]
+            })
+            .to_string()
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bundle.json");
+        std::fs::write(&path, bundle(&payload(true), &payload(true))).unwrap();
+
+        // No Auth and no base URL: the bundle is the only source
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let errors_clone = Arc::clone(&errors);
+        let client = Client::builder()
+            .with_transport(BundleTransport::from_file(&path, &public_key).unwrap())
+            .with_refresh_interval(Duration::from_secs(1))
+            .with_error_callback(move |e| errors_clone.lock().unwrap().push(e.code()))
+            .with_memory_cache()
+            .build()
+            .unwrap();
+        assert!(client.is("bundled-flag").enabled().await);
+
+        // A tampered bundle fails the refresh instead of being served
+        std::fs::write(&path, bundle(&payload(true), &payload(false))).unwrap();
+        sleep(Duration::from_millis(1100)).await;
+        assert!(!client.is("bundled-flag").enabled().await);
+        assert!(errors.lock().unwrap().contains(&"invalid_bundle"));
+
+        let err = BundleTransport::from_file(&path, &public_key).err().unwrap();
+        assert!(matches!(err, FlagError::InvalidBundle(_)), "{:?}", err);
+        assert!(!err.is_retryable());
+        let err = BundleTransport::from_file(&path, &public_key[..16]).err().unwrap();
+        assert!(matches!(err, FlagError::BuilderError(_)), "{:?}", err);
+        let err = BundleTransport::from_file(dir.path().join("missing.json"), &public_key).err().unwrap();
+        assert_eq!(err.code(), "invalid_bundle");
+    }
+}
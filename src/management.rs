@@ -0,0 +1,342 @@
+//! Flag management through the flags.gg management API, enabled with the
+//! `management` feature.
+//!
+//! Management calls use their own [`ManagementAuth`] API key, sent as a bearer
+//! token alongside the project and environment of the client's [`Auth`]:
+//!
+//! | Operation | Request |
+//! |---|---|
+//! | [`Client::set_flag_enabled`] | `PATCH /management/flags/{id}` with `{"enabled": bool}` |
+//! | [`Client::kill`] | `PATCH /management/flags/{id}` with `{"enabled": false, "reason": reason}` |
+//! | [`Client::create_flag`] | `POST /management/flags` with `{"name": name}`, answered with the new flag |
+//! | [`Client::environment_flags`] | `GET /management/flags`, answered with `{"flags": [...]}` |
+//!
+//! [`Client::diff_environments`] compares the flags of two environments of
+//! the project and [`Client::apply_environment_diff`] promotes one onto the
+//! other; both send the environment in `X-Environment-ID` in place of the
+//! client's own.
+//!
+//! # Example
+//! ```no_run
+//! # use flags_rs::{Auth, Client};
+//! # use flags_rs::management::ManagementAuth;
+//! # async fn example(auth: Auth) -> Result<(), flags_rs::FlagError> {
+//! let client = Client::builder()
+//!     .with_auth(auth)
+//!     .with_management(ManagementAuth::new(std::env::var("FLAGS_ADMIN_KEY").unwrap_or_default()))
+//!     .build()?;
+//! let flag = client.create_flag("new-checkout").await?;
+//! client.set_flag_enabled(&flag.details.id, true).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+
+use reqwest::{Method, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::flag::FeatureFlag;
+use crate::{normalize_name, Auth, Client, FlagError};
+
+/// Credentials for the management API, separate from the read-only [`Auth`]
+/// used to fetch flags. Its `Debug` output hides the key.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ManagementAuth {
+    pub api_key: String,
+}
+
+impl ManagementAuth {
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self { api_key: api_key.into() }
+    }
+}
+
+impl std::fmt::Debug for ManagementAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ManagementAuth").field("api_key", &"<redacted>").finish()
+    }
+}
+
+/// How the flags of two environments differ, from
+/// [`Client::diff_environments`]. Flags are matched by normalized name and
+/// every list is sorted by name.
+#[derive(Debug, Clone, Default)]
+pub struct EnvironmentDiff {
+    pub source: String,
+    pub target: String,
+    /// Flags of `source` that `target` does not have.
+    pub only_in_source: Vec<FeatureFlag>,
+    /// Flags of `target` that `source` does not have.
+    pub only_in_target: Vec<FeatureFlag>,
+    /// Flags in both environments with a different state.
+    pub changed: Vec<FlagChange>,
+}
+
+impl EnvironmentDiff {
+    pub fn is_empty(&self) -> bool {
+        self.only_in_source.is_empty() && self.only_in_target.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// A flag enabled in one environment of an [`EnvironmentDiff`] and disabled
+/// in the other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlagChange {
+    pub name: String,
+    /// The id of the flag in the target environment.
+    pub target_id: String,
+    pub source_enabled: bool,
+    pub target_enabled: bool,
+}
+
+#[derive(Deserialize)]
+struct FlagList {
+    #[serde(default)]
+    flags: Vec<FeatureFlag>,
+}
+
+pub(crate) struct Management {
+    http_client: reqwest::Client,
+    base_url: Url,
+    auth: ManagementAuth,
+}
+
+impl Management {
+    pub(crate) fn new(http_client: reqwest::Client, base_url: &str, auth: ManagementAuth) -> Result<Self, FlagError> {
+        let base_url = Url::parse(base_url)
+            .map_err(|e| FlagError::BuilderError(format!("Invalid management URL '{}': {}", base_url, e)))?;
+        if auth.api_key.trim().is_empty() {
+            return Err(FlagError::BuilderError("Management API key cannot be empty".to_string()));
+        }
+        Ok(Self {
+            http_client,
+            base_url,
+            auth,
+        })
+    }
+
+    /// `/management/flags`, followed by `segment` if given.
+    fn url(&self, segment: Option<&str>) -> Url {
+        let mut url = self.base_url.clone();
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.pop_if_empty().extend(["management", "flags"]);
+            if let Some(segment) = segment {
+                segments.push(segment);
+            }
+        }
+        url
+    }
+
+    /// Send a management request for `environment_id` of the project in `auth`.
+    async fn send<T: DeserializeOwned>(
+        &self,
+        auth: &Auth,
+        environment_id: &str,
+        method: Method,
+        url: Url,
+        body: Option<serde_json::Value>,
+    ) -> Result<Option<T>, FlagError> {
+        let mut request = self
+            .http_client
+            .request(method, url)
+            .bearer_auth(&self.auth.api_key)
+            .header("X-Project-ID", &auth.project_id)
+            .header("X-Environment-ID", environment_id);
+        if let Some(company_id) = &auth.company_id {
+            request = request.header("X-Company-ID", company_id);
+        }
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+            return Err(FlagError::AuthError(format!(
+                "Management API rejected the credentials: {}",
+                status
+            )));
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(FlagError::ApiError(format!("Management API returned {}: {}", status, body.trim())));
+        }
+        if status == StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+        let bytes = response.bytes().await?;
+        if bytes.is_empty() {
+            return Ok(None);
+        }
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| FlagError::ApiError(format!("Invalid management API response: {}", e)))
+    }
+}
+
+impl Client {
+    fn management(&self) -> Result<(&Management, std::sync::Arc<Auth>), FlagError> {
+        let management = self.management.as_deref().ok_or_else(|| {
+            FlagError::AuthError("Management credentials not configured, see ClientBuilder::with_management".to_string())
+        })?;
+        let auth = self
+            .auth
+            .load_full()
+            .ok_or_else(|| FlagError::AuthError("Management calls need the client's Auth".to_string()))?;
+        Ok((management, auth))
+    }
+
+    /// Enable or disable the flag with `id` in the client's environment. The
+    /// next evaluation refreshes to pick up the change.
+    pub async fn set_flag_enabled(&self, id: &str, enabled: bool) -> Result<(), FlagError> {
+        self.patch_flag(id, serde_json::json!({"enabled": enabled})).await
+    }
+
+    /// Disable the flag called `name` and record `reason` against the change
+    /// in a single API call, for incident response. The flag is looked up by
+    /// name in the management API's flags for the client's environment, so a
+    /// local override of it doesn't hide its id; a name only defined locally
+    /// fails with [`FlagError::UnknownFlag`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// # async fn example(client: &flags_rs::Client) -> Result<(), flags_rs::FlagError> {
+    /// client.kill("new-checkout", "INC-1234: checkout errors spiking").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn kill(&self, name: &str, reason: &str) -> Result<(), FlagError> {
+        let (_, auth) = self.management()?;
+        let name = normalize_name(name);
+        let flag = self
+            .environment_flags(&auth.environment_id)
+            .await?
+            .into_iter()
+            .find(|flag| normalize_name(&flag.details.name) == name)
+            .ok_or_else(|| FlagError::UnknownFlag(format!("No flag named '{}' to kill", name)))?;
+        self.patch_flag(&flag.details.id, serde_json::json!({"enabled": false, "reason": reason}))
+            .await
+    }
+
+    /// Create a flag called `name`, returning it as the API created it,
+    /// including its id. The next evaluation refreshes to pick it up.
+    pub async fn create_flag(&self, name: &str) -> Result<FeatureFlag, FlagError> {
+        let flag = self.create_flag_in(None, name).await?;
+        self.schedule.invalidate();
+        Ok(flag)
+    }
+
+    /// Every flag of `environment_id` in the client's project, as the
+    /// management API reports them.
+    pub async fn environment_flags(&self, environment_id: &str) -> Result<Vec<FeatureFlag>, FlagError> {
+        let (management, auth) = self.management()?;
+        let list = management
+            .send::<FlagList>(&auth, environment_id, Method::GET, management.url(None), None)
+            .await?;
+        Ok(list.map(|list| list.flags).unwrap_or_default())
+    }
+
+    /// Fetch the flags of environments `source` and `target` and compare them,
+    /// e.g. staging against production before a promotion.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # async fn example(client: &flags_rs::Client) -> Result<(), flags_rs::FlagError> {
+    /// let diff = client.diff_environments("staging", "production").await?;
+    /// for change in &diff.changed {
+    ///     println!("{}: {} -> {}", change.name, change.target_enabled, change.source_enabled);
+    /// }
+    /// client.apply_environment_diff(&diff).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn diff_environments(&self, source: &str, target: &str) -> Result<EnvironmentDiff, FlagError> {
+        let by_name = |flags: Vec<FeatureFlag>| -> BTreeMap<String, FeatureFlag> {
+            flags
+                .into_iter()
+                .map(|flag| (normalize_name(&flag.details.name).into_owned(), flag))
+                .collect()
+        };
+        let source_flags = by_name(self.environment_flags(source).await?);
+        let mut target_flags = by_name(self.environment_flags(target).await?);
+
+        let mut diff = EnvironmentDiff {
+            source: source.to_string(),
+            target: target.to_string(),
+            ..EnvironmentDiff::default()
+        };
+        for (name, flag) in source_flags {
+            match target_flags.remove(&name) {
+                Some(target_flag) if target_flag.enabled != flag.enabled => diff.changed.push(FlagChange {
+                    name,
+                    target_id: target_flag.details.id,
+                    source_enabled: flag.enabled,
+                    target_enabled: target_flag.enabled,
+                }),
+                Some(_) => {}
+                None => diff.only_in_source.push(flag),
+            }
+        }
+        diff.only_in_target = target_flags.into_values().collect();
+        Ok(diff)
+    }
+
+    /// Make the target of `diff` match its source: changed flags take the
+    /// source's state and flags only in the source are created in the target.
+    /// Flags only in the target are left alone. Stops at the first failed
+    /// call; applying the diff again resumes where it stopped.
+    pub async fn apply_environment_diff(&self, diff: &EnvironmentDiff) -> Result<(), FlagError> {
+        let target = Some(diff.target.as_str());
+        for change in &diff.changed {
+            self.patch_flag_in(target, &change.target_id, serde_json::json!({"enabled": change.source_enabled}))
+                .await?;
+        }
+        for flag in &diff.only_in_source {
+            let created = self.create_flag_in(target, &flag.details.name).await?;
+            if created.enabled != flag.enabled {
+                self.patch_flag_in(target, &created.details.id, serde_json::json!({"enabled": flag.enabled}))
+                    .await?;
+            }
+        }
+        self.schedule.invalidate();
+        Ok(())
+    }
+
+    async fn patch_flag(&self, id: &str, body: serde_json::Value) -> Result<(), FlagError> {
+        self.patch_flag_in(None, id, body).await?;
+        self.schedule.invalidate();
+        Ok(())
+    }
+
+    /// PATCH flag `id` in `environment_id`, the client's environment if `None`.
+    async fn patch_flag_in(
+        &self,
+        environment_id: Option<&str>,
+        id: &str,
+        body: serde_json::Value,
+    ) -> Result<(), FlagError> {
+        let (management, auth) = self.management()?;
+        let environment_id = environment_id.unwrap_or(&auth.environment_id);
+        management
+            .send::<serde_json::Value>(&auth, environment_id, Method::PATCH, management.url(Some(id)), Some(body))
+            .await?;
+        Ok(())
+    }
+
+    async fn create_flag_in(&self, environment_id: Option<&str>, name: &str) -> Result<FeatureFlag, FlagError> {
+        let (management, auth) = self.management()?;
+        let environment_id = environment_id.unwrap_or(&auth.environment_id);
+        management
+            .send::<FeatureFlag>(
+                &auth,
+                environment_id,
+                Method::POST,
+                management.url(None),
+                Some(serde_json::json!({"name": name})),
+            )
+            .await?
+            .ok_or_else(|| FlagError::ApiError("Management API did not return the created flag".to_string()))
+    }
+}
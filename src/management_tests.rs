@@ -0,0 +1,267 @@
+#[cfg(all(test, feature = "management"))]
+mod tests {
+    use crate::management::ManagementAuth;
+    use crate::{Auth, Client, FlagError};
+    use wiremock::matchers::{body_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn auth() -> Auth {
+        Auth {
+            project_id: "test-project".to_string(),
+            agent_id: "test-agent".to_string(),
+            environment_id: "test-env".to_string(),
+            company_id: None,
+            agent_secret: None,
+        }
+    }
+
+    fn client(mock_server: &MockServer) -> Client {
+        Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(auth())
+            .with_management(ManagementAuth::new("admin-key"))
+            .with_memory_cache()
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_create_and_toggle_flag() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/management/flags"))
+            .and(header("Authorization", "Bearer admin-key"))
+            .and(header("X-Project-ID", "test-project"))
+            .and(header("X-Environment-ID", "test-env"))
+            .and(body_json(serde_json::json!({"name": "new-checkout"})))
+            .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({
+                "enabled": false,
+                "details": {"name": "new-checkout", "id": "42"}
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/management/flags/42"))
+            .and(header("Authorization", "Bearer admin-key"))
+            .and(body_json(serde_json::json!({"enabled": true})))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "new-checkout", "id": "42"}}]
+            })))
+            .mount(&mock_server)
+            .await;
+        let client = client(&mock_server);
+
+        let flag = client.create_flag("new-checkout").await.unwrap();
+        assert_eq!(flag.details.id, "42");
+        assert!(!flag.enabled);
+
+        client.set_flag_enabled(&flag.details.id, true).await.unwrap();
+        assert!(client.is("new-checkout").enabled().await);
+    }
+
+    #[tokio::test]
+    async fn test_management_errors() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .and(path("/management/flags/42"))
+            .respond_with(ResponseTemplate::new(403))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/management/flags/missing"))
+            .respond_with(ResponseTemplate::new(404).set_body_string("no such flag"))
+            .mount(&mock_server)
+            .await;
+        let client = client(&mock_server);
+
+        let err = client.set_flag_enabled("42", true).await.unwrap_err();
+        assert!(matches!(err, FlagError::AuthError(_)), "{:?}", err);
+        let err = client.set_flag_enabled("missing", true).await.unwrap_err();
+        assert!(matches!(err, FlagError::ApiError(ref message) if message.contains("no such flag")), "{:?}", err);
+
+        let unconfigured = Client::builder().with_base_url(&mock_server.uri()).with_auth(auth()).build().unwrap();
+        let err = unconfigured.create_flag("new-checkout").await.unwrap_err();
+        assert!(matches!(err, FlagError::AuthError(_)), "{:?}", err);
+
+        let result = Client::builder().with_management(ManagementAuth::new("admin-key")).build();
+        assert!(matches!(result, Err(FlagError::BuilderError(_))));
+        let result = Client::builder().with_auth(auth()).with_management(ManagementAuth::new(" ")).build();
+        assert!(matches!(result, Err(FlagError::BuilderError(_))));
+        assert!(!format!("{:?}", ManagementAuth::new("admin-key")).contains("admin-key"));
+    }
+
+    #[tokio::test]
+    async fn test_kill_disables_flag_with_reason() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "new-checkout", "id": "42"}}]
+            })))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": false, "details": {"name": "new-checkout", "id": "42"}}]
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/management/flags"))
+            .and(header("X-Environment-ID", "test-env"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"flags": [
+                {"enabled": true, "details": {"name": "new-checkout", "id": "42"}}
+            ]})))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/management/flags/42"))
+            .and(header("Authorization", "Bearer admin-key"))
+            .and(body_json(serde_json::json!({"enabled": false, "reason": "INC-1234"})))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let client = client(&mock_server);
+
+        assert!(client.is("new-checkout").enabled().await);
+        client.kill("New-Checkout", "INC-1234").await.unwrap();
+        assert!(!client.is("new-checkout").enabled().await);
+
+        let err = client.kill("no-such-flag", "INC-1234").await.unwrap_err();
+        assert!(matches!(err, FlagError::UnknownFlag(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn test_kill_ignores_local_overrides() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "new-checkout", "id": "42"}}]
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/management/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"flags": [
+                {"enabled": true, "details": {"name": "new-checkout", "id": "42"}}
+            ]})))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/management/flags/42"))
+            .and(body_json(serde_json::json!({"enabled": false, "reason": "INC-1234"})))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        std::env::set_var("KILL_TEST_FF_NEW_CHECKOUT", "false");
+        std::env::set_var("KILL_TEST_FF_LOCAL_ONLY", "true");
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(auth())
+            .with_management(ManagementAuth::new("admin-key"))
+            .with_env_prefix("KILL_TEST_FF_")
+            .with_memory_cache()
+            .build()
+            .unwrap();
+        std::env::remove_var("KILL_TEST_FF_NEW_CHECKOUT");
+        std::env::remove_var("KILL_TEST_FF_LOCAL_ONLY");
+
+        // The local override is served, but the kill goes to the API's flag
+        assert!(!client.is("new-checkout").enabled().await);
+        client.kill("new-checkout", "INC-1234").await.unwrap();
+
+        assert!(client.is("local-only").enabled().await);
+        let err = client.kill("local-only", "INC-1234").await.unwrap_err();
+        assert!(matches!(err, FlagError::UnknownFlag(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn test_diff_and_apply_environments() {
+        let mock_server = MockServer::start().await;
+        let flag = |name: &str, id: &str, enabled: bool| {
+            serde_json::json!({"enabled": enabled, "details": {"name": name, "id": id}})
+        };
+        Mock::given(method("GET"))
+            .and(path("/management/flags"))
+            .and(header("X-Environment-ID", "staging"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"flags": [
+                flag("new-checkout", "s1", true),
+                flag("dark-mode", "s2", false),
+                flag("fast-path", "s3", true)
+            ]})))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/management/flags"))
+            .and(header("X-Environment-ID", "production"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"flags": [
+                flag("New-Checkout", "p1", false),
+                flag("dark-mode", "p2", false),
+                flag("legacy-api", "p4", true)
+            ]})))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/management/flags/p1"))
+            .and(header("X-Environment-ID", "production"))
+            .and(body_json(serde_json::json!({"enabled": true})))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/management/flags"))
+            .and(header("X-Environment-ID", "production"))
+            .and(body_json(serde_json::json!({"name": "fast-path"})))
+            .respond_with(ResponseTemplate::new(201).set_body_json(flag("fast-path", "p3", false)))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/management/flags/p3"))
+            .and(header("X-Environment-ID", "production"))
+            .and(body_json(serde_json::json!({"enabled": true})))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let client = client(&mock_server);
+
+        let diff = client.diff_environments("staging", "production").await.unwrap();
+        assert_eq!(diff.source, "staging");
+        assert_eq!(diff.target, "production");
+        assert_eq!(
+            diff.changed,
+            vec![crate::management::FlagChange {
+                name: "new-checkout".to_string(),
+                target_id: "p1".to_string(),
+                source_enabled: true,
+                target_enabled: false,
+            }]
+        );
+        let names = |flags: &[crate::flag::FeatureFlag]| -> Vec<String> {
+            flags.iter().map(|flag| flag.details.name.clone()).collect()
+        };
+        assert_eq!(names(&diff.only_in_source), vec!["fast-path"]);
+        assert_eq!(names(&diff.only_in_target), vec!["legacy-api"]);
+        assert!(!diff.is_empty());
+
+        client.apply_environment_diff(&diff).await.unwrap();
+    }
+}
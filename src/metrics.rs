@@ -0,0 +1,68 @@
+//! Refresh latency metrics, enabled with the `metrics` feature.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds, in seconds, of the [`RefreshHistogram`] buckets.
+pub const REFRESH_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Durations of a client's refreshes that called the API, failed ones
+/// included, see [`Client::refresh_histogram`](crate::Client::refresh_histogram).
+#[derive(Debug, Default)]
+pub struct RefreshHistogram {
+    // One past the last bound for slower refreshes
+    counts: [AtomicU64; REFRESH_BUCKETS.len() + 1],
+    sum_micros: AtomicU64,
+}
+
+impl RefreshHistogram {
+    pub(crate) fn record(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let bucket = REFRESH_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(REFRESH_BUCKETS.len());
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(u64::try_from(duration.as_micros()).unwrap_or(u64::MAX), Ordering::Relaxed);
+    }
+
+    /// Refreshes recorded.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().map(|c| c.load(Ordering::Relaxed)).sum()
+    }
+
+    /// Total duration of the recorded refreshes.
+    pub fn sum(&self) -> Duration {
+        Duration::from_micros(self.sum_micros.load(Ordering::Relaxed))
+    }
+
+    /// Cumulative counts per bucket as `(upper bound in seconds, refreshes
+    /// at most that long)`, ending with `f64::INFINITY` and [`count`](Self::count).
+    pub fn buckets(&self) -> Vec<(f64, u64)> {
+        let mut total = 0;
+        REFRESH_BUCKETS
+            .iter()
+            .copied()
+            .chain([f64::INFINITY])
+            .zip(&self.counts)
+            .map(|(bound, count)| {
+                total += count.load(Ordering::Relaxed);
+                (bound, total)
+            })
+            .collect()
+    }
+
+    /// The histogram in the Prometheus text exposition format, as metric `name`.
+    pub fn to_prometheus(&self, name: &str) -> String {
+        let mut out = format!("# TYPE {} histogram\n", name);
+        for (bound, count) in self.buckets() {
+            let le = if bound.is_infinite() { "+Inf".to_string() } else { bound.to_string() };
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, le, count);
+        }
+        let _ = writeln!(out, "{}_sum {}", name, self.sum().as_secs_f64());
+        let _ = writeln!(out, "{}_count {}", name, self.count());
+        out
+    }
+}
@@ -0,0 +1,67 @@
+#[cfg(all(test, feature = "metrics", feature = "remote"))]
+mod tests {
+    use std::time::Duration;
+
+    use crate::metrics::RefreshHistogram;
+    use crate::{Auth, Client};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_refresh_histogram_buckets() {
+        let histogram = RefreshHistogram::default();
+        histogram.record(Duration::from_millis(3));
+        histogram.record(Duration::from_millis(40));
+        histogram.record(Duration::from_secs(30));
+
+        assert_eq!(histogram.count(), 3);
+        assert_eq!(histogram.sum(), Duration::from_millis(30_043));
+        let buckets = histogram.buckets();
+        assert_eq!(buckets[0], (0.005, 1));
+        assert_eq!(buckets[3], (0.05, 2));
+        assert_eq!(buckets[10], (10.0, 2));
+        assert_eq!(buckets[11], (f64::INFINITY, 3));
+
+        let text = histogram.to_prometheus("flags_refresh_duration_seconds");
+        assert!(text.starts_with("# TYPE flags_refresh_duration_seconds histogram\n"));
+        assert!(text.contains("flags_refresh_duration_seconds_bucket{le=\"0.05\"} 2\n"));
+        assert!(text.contains("flags_refresh_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.ends_with("flags_refresh_duration_seconds_count 3\n"));
+    }
+
+    #[tokio::test]
+    async fn test_refreshes_are_recorded() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": []
+            })))
+            .mount(&mock_server)
+            .await;
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_max_retries(1)
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-environment".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_memory_cache()
+            .build()
+            .unwrap();
+
+        assert!(client.refetch().await.is_err());
+        client.refetch().await.unwrap();
+        assert_eq!(client.clone().refresh_histogram().count(), 2);
+    }
+}
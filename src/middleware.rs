@@ -1,17 +1,60 @@
-use crate::{Client, FlagError};
+use crate::context::{EvaluationContext, EvaluationMemo};
+use crate::{normalize_name, Client, FlagError};
 use futures::future::BoxFuture;
-use http::{Request, Response};
+use http::{HeaderMap, HeaderValue, Request, Response, StatusCode};
 use pin_project::pin_project;
+use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
 use std::task::{Context, Poll};
+use log::warn;
 use tower::{Layer, Service};
 
+/// Response header carrying the evaluation context, see
+/// [`FlagsLayer::with_context_echo`].
+pub const CONTEXT_ECHO_HEADER: &str = "X-Flags-Context";
+
+/// Most flag names evaluated from one request header by default.
+pub const DEFAULT_MAX_HEADER_FLAGS: usize = 64;
+
+/// Longest flag name, in bytes, evaluated from a request header by default.
+pub const DEFAULT_MAX_FLAG_NAME_LEN: usize = 128;
+
+/// Builds the evaluation context of a request from its headers.
+pub type ContextExtractor = Arc<dyn Fn(&HeaderMap) -> EvaluationContext + Send + Sync>;
+
+/// What happens to a flag header beyond the layer's limits, see
+/// [`FlagsLayer::with_header_overflow`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HeaderOverflow {
+    /// Evaluate the names within the limits: the first
+    /// [`with_max_header_flags`](FlagsLayer::with_max_header_flags) names,
+    /// skipping any that are too long.
+    #[default]
+    Truncate,
+    /// Evaluate none of the header's names.
+    Reject,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct HeaderLimits {
+    max_flags: usize,
+    max_name_len: usize,
+    overflow: HeaderOverflow,
+}
+
 #[derive(Clone)]
 pub struct FlagsLayer {
     client: Arc<Client>,
     header_name: String,
+    context: Option<ContextExtractor>,
+    echo_context: bool,
+    limits: HeaderLimits,
+    // Requests whose header broke a limit, shared with every middleware
+    rejected: Arc<AtomicU64>,
+    shed_flag: Option<Arc<str>>,
 }
 
 impl FlagsLayer {
@@ -19,13 +62,81 @@ impl FlagsLayer {
         Self {
             client: Arc::new(client),
             header_name: "X-Feature-Flags".to_string(),
+            context: None,
+            echo_context: false,
+            limits: HeaderLimits {
+                max_flags: DEFAULT_MAX_HEADER_FLAGS,
+                max_name_len: DEFAULT_MAX_FLAG_NAME_LEN,
+                overflow: HeaderOverflow::default(),
+            },
+            rejected: Arc::new(AtomicU64::new(0)),
+            shed_flag: None,
         }
     }
 
+    /// Answer every request with `503 Service Unavailable`, without calling
+    /// the inner service, while `flag` is enabled: a remote kill switch for
+    /// shedding load. The flag is read from the last refresh so the check never
+    /// waits. A request that finds the flags due for a refresh starts one as a
+    /// Tokio task, and later requests see its result.
+    pub fn with_load_shedding(mut self, flag: &str) -> Self {
+        self.shed_flag = Some(normalize_name(flag).into());
+        self
+    }
+
+    /// Evaluate at most `max` flag names per request header,
+    /// [`DEFAULT_MAX_HEADER_FLAGS`] unless set, so a client cannot make one
+    /// request look up thousands of flags.
+    pub fn with_max_header_flags(mut self, max: usize) -> Self {
+        self.limits.max_flags = max;
+        self
+    }
+
+    /// Ignore header flag names longer than `max` bytes,
+    /// [`DEFAULT_MAX_FLAG_NAME_LEN`] unless set.
+    pub fn with_max_flag_name_len(mut self, max: usize) -> Self {
+        self.limits.max_name_len = max;
+        self
+    }
+
+    /// How a header beyond the limits is handled, truncated unless set.
+    pub fn with_header_overflow(mut self, overflow: HeaderOverflow) -> Self {
+        self.limits.overflow = overflow;
+        self
+    }
+
+    /// Requests so far whose flag header broke a limit, whether truncated or
+    /// rejected, counted across every service this layer wraps.
+    pub fn rejected_headers(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
     pub fn with_header_name(mut self, name: impl Into<String>) -> Self {
         self.header_name = name.into();
         self
     }
+
+    /// Evaluate the flags named in the request header for the context
+    /// `extractor` builds from the request headers. Handlers find it in
+    /// [`FlagsState::context`].
+    pub fn with_context<F>(mut self, extractor: F) -> Self
+    where
+        F: Fn(&HeaderMap) -> EvaluationContext + Send + Sync + 'static,
+    {
+        self.context = Some(Arc::new(extractor));
+        self
+    }
+
+    /// Describe the evaluation context of each request in a
+    /// [`CONTEXT_ECHO_HEADER`] response header, e.g.
+    /// `environment=staging, user=5e884898da28, tenant=acme`, so frontend
+    /// engineers can check how their requests are targeted. The user ID is
+    /// hashed, but the header still reveals targeting details: enable it
+    /// only in debug environments, e.g. with `cfg!(debug_assertions)`.
+    pub fn with_context_echo(mut self, enabled: bool) -> Self {
+        self.echo_context = enabled;
+        self
+    }
 }
 
 impl<S> Layer<S> for FlagsLayer {
@@ -36,6 +147,11 @@ impl<S> Layer<S> for FlagsLayer {
             inner,
             client: self.client.clone(),
             header_name: self.header_name.clone(),
+            context: self.context.clone(),
+            echo_context: self.echo_context,
+            limits: self.limits,
+            rejected: Arc::clone(&self.rejected),
+            shed_flag: self.shed_flag.clone(),
         }
     }
 }
@@ -45,15 +161,23 @@ pub struct FlagsMiddleware<S> {
     inner: S,
     client: Arc<Client>,
     header_name: String,
+    context: Option<ContextExtractor>,
+    echo_context: bool,
+    limits: HeaderLimits,
+    rejected: Arc<AtomicU64>,
+    shed_flag: Option<Arc<str>>,
 }
 
 #[pin_project]
 pub struct FlagsFuture<F, B> {
+    // `None` when the request is shed
     #[pin]
-    inner: F,
+    inner: Option<F>,
     client: Arc<Client>,
     header_name: String,
     flags_future: Option<BoxFuture<'static, Result<Vec<String>, FlagError>>>,
+    enabled_flags: Vec<String>,
+    echo: Option<HeaderValue>,
     _phantom: std::marker::PhantomData<B>,
 }
 
@@ -62,7 +186,7 @@ where
     S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
     S::Future: Send + 'static,
     ReqBody: Send + 'static,
-    ResBody: http_body::Body + Send + 'static,
+    ResBody: http_body::Body + Default + Send + 'static,
     ResBody::Error: std::error::Error + Send + Sync + 'static,
 {
     type Response = Response<ResBody>;
@@ -74,21 +198,87 @@ where
     }
 
     fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        if self.shed_flag.is_some() && self.client.schedule.is_stale() {
+            // Keeps the shed flag current without holding up this request
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                let client = self.client.clone();
+                runtime.spawn(async move { client.refresh_if_needed().await });
+            }
+        }
+        let shed = self
+            .shed_flag
+            .as_deref()
+            .is_some_and(|flag| self.client.try_is_enabled(flag) == Some(true));
+        if shed {
+            return FlagsFuture {
+                inner: None,
+                client: self.client.clone(),
+                header_name: self.header_name.clone(),
+                flags_future: None,
+                enabled_flags: Vec::new(),
+                echo: None,
+                _phantom: std::marker::PhantomData,
+            };
+        }
+
         let flags_from_header = req
             .headers()
             .get(&self.header_name)
             .and_then(|v| v.to_str().ok())
-            .map(|s| s.split(',').map(|f| f.trim().to_string()).collect::<Vec<_>>());
+            .map(|s| {
+                let (flags, limited) = parse_flag_header(s, &self.limits);
+                if limited {
+                    self.rejected.fetch_add(1, Ordering::Relaxed);
+                    warn!(
+                        "{} header exceeds {} flags of {} bytes, {}",
+                        self.header_name,
+                        self.limits.max_flags,
+                        self.limits.max_name_len,
+                        match self.limits.overflow {
+                            HeaderOverflow::Truncate => "truncated",
+                            HeaderOverflow::Reject => "ignored",
+                        }
+                    );
+                }
+                flags
+            });
+
+        let context = self.context.as_ref().map(|extract| Arc::new(extract(req.headers())));
+        let echo = self
+            .echo_context
+            .then(|| context_echo(&self.client, context.as_deref()))
+            .and_then(|echo| HeaderValue::from_str(&echo).ok());
 
         let client = self.client.clone();
+        let memo = Arc::new(EvaluationMemo::new());
+        let header_memo = Arc::clone(&memo);
+        let header_context = context.clone();
+        let header_flags = Arc::new(OnceLock::new());
+        let header_results = Arc::clone(&header_flags);
         let flags_future = if let Some(flags) = flags_from_header {
             let fut = async move {
+                // Each name is evaluated once, however often the header repeats it
+                let mut results: HashMap<String, bool> = HashMap::with_capacity(flags.len());
                 let mut enabled_flags = Vec::new();
                 for flag in flags {
-                    if client.is(&flag).enabled().await {
+                    let name = normalize_name(&flag).into_owned();
+                    let enabled = match results.get(&name) {
+                        Some(&enabled) => enabled,
+                        None => {
+                            let mut check = client.is(&name).with_memo(&header_memo);
+                            if let Some(context) = &header_context {
+                                check = check.with_context(context);
+                            }
+                            let enabled = check.enabled().await;
+                            results.insert(name, enabled);
+                            enabled
+                        }
+                    };
+                    if enabled {
                         enabled_flags.push(flag);
                     }
                 }
+                let _ = header_results.set(results);
                 Ok(enabled_flags)
             };
             Some(Box::pin(fut) as BoxFuture<'static, Result<Vec<String>, FlagError>>)
@@ -98,15 +288,20 @@ where
 
         req.extensions_mut().insert(FlagsState {
             client: self.client.clone(),
+            memo,
+            context,
+            header_flags,
         });
 
         let inner = self.inner.call(req);
 
         FlagsFuture {
-            inner,
+            inner: Some(inner),
             client: self.client.clone(),
             header_name: self.header_name.clone(),
             flags_future,
+            enabled_flags: Vec::new(),
+            echo,
             _phantom: std::marker::PhantomData,
         }
     }
@@ -115,58 +310,137 @@ where
 impl<F, ResBody, E> Future for FlagsFuture<F, ResBody>
 where
     F: Future<Output = Result<Response<ResBody>, E>>,
-    ResBody: http_body::Body,
+    ResBody: http_body::Body + Default,
 {
     type Output = Result<Response<ResBody>, E>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
 
+        let Some(inner) = this.inner.as_pin_mut() else {
+            let mut response = Response::new(ResBody::default());
+            *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            return Poll::Ready(Ok(response));
+        };
+
         // If we have flags to check, we need to wait for them first
         if let Some(flags_future) = this.flags_future.as_mut() {
             match flags_future.as_mut().poll(cx) {
-                Poll::Ready(Ok(enabled_flags)) => {
-                    *this.flags_future = None;
-                    
-                    // Now poll the inner service
-                    match this.inner.poll(cx) {
-                        Poll::Ready(Ok(mut response)) => {
-                            if !enabled_flags.is_empty() {
-                                // Only add header if we can create a valid HeaderValue
-                                if let Ok(header_value) = enabled_flags.join(",").parse() {
-                                    response.headers_mut().insert(
-                                        "X-Enabled-Flags",
-                                        header_value,
-                                    );
-                                }
-                                // If parsing fails, we still return the response without the header
-                            }
-                            Poll::Ready(Ok(response))
-                        }
-                        Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
-                        Poll::Pending => Poll::Pending,
-                    }
-                }
-                Poll::Ready(Err(_)) => {
-                    *this.flags_future = None;
+                Poll::Ready(result) => {
                     // Continue without flags on error
-                    this.inner.poll(cx)
+                    *this.enabled_flags = result.unwrap_or_default();
+                    *this.flags_future = None;
                 }
-                Poll::Pending => Poll::Pending,
+                Poll::Pending => return Poll::Pending,
             }
-        } else {
-            this.inner.poll(cx)
         }
+
+        // Now poll the inner service
+        let mut response = match inner.poll(cx) {
+            Poll::Ready(Ok(response)) => response,
+            other => return other,
+        };
+        if !this.enabled_flags.is_empty() {
+            // Only add header if we can create a valid HeaderValue
+            if let Ok(header_value) = this.enabled_flags.join(",").parse() {
+                response.headers_mut().insert("X-Enabled-Flags", header_value);
+            }
+            // If parsing fails, we still return the response without the header
+        }
+        if let Some(echo) = this.echo.take() {
+            response.headers_mut().insert(CONTEXT_ECHO_HEADER, echo);
+        }
+        Poll::Ready(Ok(response))
+    }
+}
+
+/// The non-empty names in a flag header within `limits`, and whether any
+/// limit was broken. Parsing stops at the first name past the count limit.
+fn parse_flag_header(value: &str, limits: &HeaderLimits) -> (Vec<String>, bool) {
+    let mut flags = Vec::new();
+    let mut limited = false;
+    for name in value.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        if flags.len() == limits.max_flags {
+            limited = true;
+            break;
+        }
+        if name.len() > limits.max_name_len {
+            limited = true;
+            continue;
+        }
+        flags.push(name.to_string());
+    }
+    if limited && limits.overflow == HeaderOverflow::Reject {
+        flags.clear();
+    }
+    (flags, limited)
+}
+
+/// `environment=..., user=..., tenant=..., app-version=...` for the parts
+/// known, the user ID as the first 12 hex digits of its SHA-256.
+fn context_echo(client: &Client, context: Option<&EvaluationContext>) -> String {
+    let mut parts = Vec::new();
+    if let Some(auth) = client.auth.load_full() {
+        parts.push(format!("environment={}", auth.environment_id));
+    }
+    if let Some(context) = context {
+        if let Some(user_id) = context.user_id() {
+            let digest = ring::digest::digest(&ring::digest::SHA256, user_id.as_bytes());
+            let hash: String = digest.as_ref()[..6].iter().map(|b| format!("{:02x}", b)).collect();
+            parts.push(format!("user={}", hash));
+        }
+        if let Some(tenant) = context.tenant() {
+            parts.push(format!("tenant={}", tenant));
+        }
+        if let Some(version) = context.app_version() {
+            parts.push(format!("app-version={}", version));
+        }
+    }
+    if parts.is_empty() {
+        return "none".to_string();
     }
+    parts.join(", ")
 }
 
 #[derive(Clone)]
 pub struct FlagsState {
     pub client: Arc<Client>,
+    /// Shared by every evaluation for this request, including the header check.
+    pub memo: Arc<EvaluationMemo>,
+    /// The request's context, when the layer was built with
+    /// [`FlagsLayer::with_context`].
+    pub context: Option<Arc<EvaluationContext>>,
+    // Results for the flags named in the request header, set before the
+    // inner service is polled
+    header_flags: Arc<OnceLock<HashMap<String, bool>>>,
+}
+
+impl FlagsState {
+    /// Whether `name` is enabled for this request. Flags named in the request
+    /// header are answered from the middleware's evaluation; others are
+    /// evaluated with the request's memo and context.
+    pub async fn is_enabled(&self, name: &str) -> bool {
+        let name = normalize_name(name);
+        if let Some(&enabled) = self.header_flags.get().and_then(|flags| flags.get(name.as_ref())) {
+            return enabled;
+        }
+        let mut check = self.client.is(&name).with_memo(&self.memo);
+        if let Some(context) = &self.context {
+            check = check.with_context(context);
+        }
+        check.enabled().await
+    }
 }
 
 pub trait RequestExt {
     fn flags_client(&self) -> Option<&Client>;
+
+    /// The request's [`EvaluationMemo`], for use with [`Flag::with_memo`](crate::Flag::with_memo).
+    fn flags_memo(&self) -> Option<&EvaluationMemo>;
+
+    /// The request's [`FlagsState`], whose [`is_enabled`](FlagsState::is_enabled)
+    /// reuses the results for the flags named in the request header.
+    fn flags_state(&self) -> Option<&FlagsState>;
 }
 
 impl<T> RequestExt for Request<T> {
@@ -175,4 +449,14 @@ impl<T> RequestExt for Request<T> {
             .get::<FlagsState>()
             .map(|state| state.client.as_ref())
     }
+
+    fn flags_memo(&self) -> Option<&EvaluationMemo> {
+        self.extensions()
+            .get::<FlagsState>()
+            .map(|state| state.memo.as_ref())
+    }
+
+    fn flags_state(&self) -> Option<&FlagsState> {
+        self.extensions().get::<FlagsState>()
+    }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-#[cfg(all(test, feature = "tower-middleware"))]
+#[cfg(all(test, feature = "tower-middleware", feature = "remote"))]
 mod tests {
     use crate::{Client, middleware::{FlagsLayer, RequestExt}};
     use http::{Request, Response, StatusCode};
@@ -20,6 +20,8 @@ mod tests {
                 project_id: "test-project".to_string(),
                 agent_id: "test-agent".to_string(),
                 environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
             })
             .build()
             .expect("Failed to build test client")
@@ -44,6 +46,7 @@ mod tests {
             .layer(FlagsLayer::new(client))
             .service_fn(|req: Request<Empty<Bytes>>| async move {
                 assert!(req.flags_client().is_some());
+                assert!(req.flags_memo().is_some());
                 Ok::<_, Infallible>(Response::new(Full::new(Bytes::new())))
             });
 
@@ -167,6 +170,8 @@ mod tests {
                 project_id: "test-project".to_string(),
                 agent_id: "test-agent".to_string(),
                 environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
             })
             .build()
             .expect("Failed to build test client");
@@ -221,4 +226,247 @@ mod tests {
         let response = service.oneshot(request).await.unwrap();
         assert!(response.headers().get("X-Enabled-Flags").is_none());
     }
+
+    #[tokio::test]
+    async fn test_middleware_echoes_evaluation_context() {
+        use crate::context::EvaluationContext;
+        use crate::middleware::CONTEXT_ECHO_HEADER;
+
+        let mock_server = setup_mock_server().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": true, "details": {"name": "eu-only", "id": "1", "rules": [
+                        {"attribute": "region", "operator": "in", "values": ["eu-west-1"]}
+                    ]}}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+        let client = create_test_client(&mock_server).await;
+
+        let layer = FlagsLayer::new(client).with_context(|headers| {
+            let mut context = EvaluationContext::new();
+            if let Some(user) = headers.get("X-User-ID").and_then(|v| v.to_str().ok()) {
+                context = context.with_user_id(user);
+            }
+            if let Some(region) = headers.get("X-Region").and_then(|v| v.to_str().ok()) {
+                context = context.with_region(region);
+            }
+            context
+        });
+        let handler = |req: Request<Empty<Bytes>>| async move {
+            let state = req.extensions().get::<crate::middleware::FlagsState>().unwrap();
+            assert_eq!(state.context.as_ref().unwrap().user_id(), Some("user-42"));
+            Ok::<_, Infallible>(Response::new(Full::new(Bytes::new())))
+        };
+        let request = |region: &str| {
+            Request::builder()
+                .uri("/")
+                .header("X-Feature-Flags", "eu-only")
+                .header("X-User-ID", "user-42")
+                .header("X-Region", region)
+                .body(Empty::new())
+                .unwrap()
+        };
+
+        let service = ServiceBuilder::new()
+            .layer(layer.clone().with_context_echo(true))
+            .service_fn(handler);
+        let response = service.oneshot(request("eu-west-1")).await.unwrap();
+        assert_eq!(response.headers().get("X-Enabled-Flags").unwrap(), "eu-only");
+        assert_eq!(
+            response.headers().get(CONTEXT_ECHO_HEADER).unwrap(),
+            "environment=test-env, user=6d894aa3ee80"
+        );
+
+        let service = ServiceBuilder::new().layer(layer).service_fn(handler);
+        let response = service.oneshot(request("us-east-1")).await.unwrap();
+        assert!(response.headers().get("X-Enabled-Flags").is_none());
+        assert!(response.headers().get(CONTEXT_ECHO_HEADER).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_header_flags_are_evaluated_once_per_request() {
+        use crate::cache::{Cache, MemoryCache};
+        use crate::flag::FeatureFlag;
+        use std::error::Error;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        // Counts the lookups the middleware and handler make
+        struct CountingCache(MemoryCache, Arc<AtomicUsize>);
+
+        #[async_trait::async_trait]
+        impl Cache for CountingCache {
+            async fn get(&self, name: &str) -> Result<(bool, bool), Box<dyn Error + Send + Sync>> {
+                self.1.fetch_add(1, Ordering::SeqCst);
+                self.0.get(name).await
+            }
+            async fn get_all(&self) -> Result<Vec<Arc<FeatureFlag>>, Box<dyn Error + Send + Sync>> {
+                self.0.get_all().await
+            }
+            async fn refresh(&self, flags: &[FeatureFlag], interval: i32) -> Result<(), Box<dyn Error + Send + Sync>> {
+                self.0.refresh(flags, interval).await
+            }
+            async fn should_refresh_cache(&self) -> bool {
+                self.0.should_refresh_cache().await
+            }
+            async fn init(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+                self.0.init().await
+            }
+        }
+
+        let mock_server = setup_mock_server().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": true, "details": {"name": "feature-1", "id": "1"}},
+                    {"enabled": false, "details": {"name": "feature-2", "id": "2"}},
+                    {"enabled": true, "details": {"name": "feature-3", "id": "3"}}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(crate::Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_cache(CountingCache(MemoryCache::new(), Arc::clone(&lookups)))
+            .build()
+            .unwrap();
+        client.refetch().await.unwrap();
+
+        let service = ServiceBuilder::new()
+            .layer(FlagsLayer::new(client))
+            .service_fn(|req: Request<Empty<Bytes>>| async move {
+                let state = req.flags_state().unwrap();
+                assert!(state.is_enabled("feature-1").await);
+                assert!(!state.is_enabled("Feature-2").await);
+                assert!(state.is_enabled("feature-3").await);
+                Ok::<_, Infallible>(Response::new(Full::new(Bytes::new())))
+            });
+        let request = Request::builder()
+            .uri("/")
+            .header("X-Feature-Flags", "feature-1, feature-2, Feature-1")
+            .body(Empty::new())
+            .unwrap();
+
+        let response = service.oneshot(request).await.unwrap();
+        assert_eq!(response.headers().get("X-Enabled-Flags").unwrap(), "feature-1,Feature-1");
+        // feature-1 and feature-2 once for the header, feature-3 for the handler
+        assert_eq!(lookups.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_header_limits() {
+        use crate::middleware::HeaderOverflow;
+
+        let mock_server = setup_mock_server().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": true, "details": {"name": "feature-1", "id": "1"}},
+                    {"enabled": true, "details": {"name": "feature-2", "id": "2"}},
+                    {"enabled": true, "details": {"name": "feature-3", "id": "3"}},
+                    {"enabled": true, "details": {"name": "a-very-long-flag", "id": "4"}}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+        let client = create_test_client(&mock_server).await;
+        let layer = FlagsLayer::new(client).with_max_header_flags(2).with_max_flag_name_len(10);
+        let enabled_flags = |layer: FlagsLayer, header: &str| {
+            let service = ServiceBuilder::new()
+                .layer(layer)
+                .service_fn(|_req: Request<Empty<Bytes>>| async move {
+                    Ok::<_, Infallible>(Response::new(Full::new(Bytes::new())))
+                });
+            let request = Request::builder()
+                .uri("/")
+                .header("X-Feature-Flags", header)
+                .body(Empty::new())
+                .unwrap();
+            async move {
+                let response = service.oneshot(request).await.unwrap();
+                response.headers().get("X-Enabled-Flags").map(|v| v.to_str().unwrap().to_string())
+            }
+        };
+
+        assert_eq!(enabled_flags(layer.clone(), "feature-1, ,feature-2").await.as_deref(), Some("feature-1,feature-2"));
+        assert_eq!(layer.rejected_headers(), 0);
+
+        // Long names are skipped and names past the cap are not parsed
+        let header = "feature-1,,a-very-long-flag,feature-3,feature-2";
+        assert_eq!(enabled_flags(layer.clone(), header).await.as_deref(), Some("feature-1,feature-3"));
+        assert_eq!(layer.rejected_headers(), 1);
+
+        let rejecting = layer.clone().with_header_overflow(HeaderOverflow::Reject);
+        assert_eq!(enabled_flags(rejecting, header).await, None);
+        assert_eq!(layer.rejected_headers(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_load_shedding_flag() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let mock_server = setup_mock_server().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "shed-load", "id": "1"}}]
+            })))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": false, "details": {"name": "shed-load", "id": "1"}}]
+            })))
+            .mount(&mock_server)
+            .await;
+        let client = create_test_client(&mock_server).await;
+        let calls = Arc::new(AtomicUsize::new(0));
+        let layer = FlagsLayer::new(client.clone()).with_load_shedding("Shed-Load");
+        let status = || {
+            let calls = Arc::clone(&calls);
+            let service = ServiceBuilder::new()
+                .layer(layer.clone())
+                .service_fn(move |_req: Request<Empty<Bytes>>| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    async move { Ok::<_, Infallible>(Response::new(Full::new(Bytes::new()))) }
+                });
+            let request = Request::builder().uri("/").body(Empty::new()).unwrap();
+            async move { service.oneshot(request).await.unwrap().status() }
+        };
+
+        // The first request finds nothing fetched yet, and is served while
+        // its refresh runs in the background
+        assert_eq!(status().await, StatusCode::OK);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        client.wait_until_ready().await.unwrap();
+
+        assert_eq!(status().await, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        client.refetch().await.unwrap();
+        assert_eq!(status().await, StatusCode::OK);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,121 @@
+//! Clients for many tenants, each with its own credentials.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::{Auth, Client, ClientBuilder, FlagError};
+
+/// Tenants kept by default before the least recently used is evicted.
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// Lazily built [`Client`]s keyed by tenant [`Auth`], for control planes
+/// serving many customer projects or environments.
+///
+/// Every tenant client is derived from a template client with
+/// [`Client::to_builder`], so all share its HTTP connection pool, transport,
+/// callbacks and settings, but each keeps its own in-memory cache. Once
+/// [`with_capacity`](Self::with_capacity) tenants are held, adding another
+/// evicts the least recently used one.
+///
+/// # Example
+/// ```no_run
+/// # use flags_rs::{Auth, Client};
+/// # use flags_rs::pool::TenantClientPool;
+/// # async fn example(template: &Client, tenant: Auth) -> Result<(), flags_rs::FlagError> {
+/// let pool = TenantClientPool::new(template);
+/// let client = pool.get(&tenant)?;
+/// if client.is("new-dashboard").enabled().await {
+///     // Enabled for this tenant
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct TenantClientPool {
+    template: ClientBuilder,
+    capacity: usize,
+    idle_timeout: Option<Duration>,
+    clients: Mutex<HashMap<Auth, Tenant>>,
+}
+
+struct Tenant {
+    client: Client,
+    last_used: Instant,
+}
+
+impl TenantClientPool {
+    pub fn new(template: &Client) -> Self {
+        Self {
+            template: template.to_builder(),
+            capacity: DEFAULT_CAPACITY,
+            idle_timeout: None,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Most tenants kept at once, at least one. Defaults to [`DEFAULT_CAPACITY`].
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Also evict tenants unused for `timeout`, checked whenever a tenant is
+    /// added and by [`evict_idle`](Self::evict_idle).
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// The client for `auth`, built on first use. Fails if `auth` would be
+    /// rejected by [`ClientBuilder::build`].
+    pub fn get(&self, auth: &Auth) -> Result<Client, FlagError> {
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(tenant) = clients.get_mut(auth) {
+            tenant.last_used = now;
+            return Ok(tenant.client.clone());
+        }
+
+        let client = self
+            .template
+            .clone()
+            .with_auth(auth.clone())
+            .with_memory_cache()
+            .build()?;
+        self.evict(&mut clients, now);
+        if clients.len() >= self.capacity {
+            if let Some(oldest) = clients.iter().min_by_key(|(_, t)| t.last_used).map(|(auth, _)| auth.clone()) {
+                clients.remove(&oldest);
+            }
+        }
+        clients.insert(auth.clone(), Tenant { client: client.clone(), last_used: now });
+        Ok(client)
+    }
+
+    /// Drop the client for `auth`, returning whether there was one.
+    pub fn remove(&self, auth: &Auth) -> bool {
+        self.clients.lock().unwrap_or_else(|e| e.into_inner()).remove(auth).is_some()
+    }
+
+    /// Drop every tenant unused for longer than the idle timeout.
+    pub fn evict_idle(&self) {
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        self.evict(&mut clients, Instant::now());
+    }
+
+    pub fn len(&self) -> usize {
+        self.clients.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn evict(&self, clients: &mut HashMap<Auth, Tenant>, now: Instant) {
+        if let Some(timeout) = self.idle_timeout {
+            clients.retain(|_, tenant| now.duration_since(tenant.last_used) <= timeout);
+        }
+    }
+}
@@ -0,0 +1,237 @@
+//! Carrying flag decisions and evaluation context across gRPC calls,
+//! enabled with the `grpc` feature.
+//!
+//! A caller attaches [`FlagsInterceptor`] to its tonic client to send the
+//! flags it evaluated, and the context it evaluated them for, as request
+//! metadata. The called service installs [`extract`] as a server interceptor
+//! and reads them back as [`PropagatedFlags`], so every hop of a request
+//! makes the decision the edge made, even if a refresh lands in between.
+//!
+//! # Example
+//! ```no_run
+//! # use flags_rs::Client;
+//! # use flags_rs::context::EvaluationContext;
+//! # use flags_rs::propagation::{FlagsInterceptor, PropagatedFlags};
+//! # async fn caller(client: &Client, channel: tonic::transport::Channel) {
+//! let context = EvaluationContext::new().with_user_id("user-42");
+//! let interceptor = FlagsInterceptor::new()
+//!     .with_context(context)
+//!     .evaluate(client, &["new-checkout", "fast-path"])
+//!     .await;
+//! // Or `MyServiceClient::with_interceptor(channel, interceptor)` with generated clients
+//! let service = tonic::service::interceptor::InterceptedService::new(channel, interceptor);
+//! let grpc = tonic::client::Grpc::new(service);
+//! # }
+//! # fn handler(request: &tonic::Request<()>) {
+//! // In the called service, behind `extract`
+//! let flags = request.extensions().get::<PropagatedFlags>().unwrap();
+//! if flags.get("new-checkout") == Some(true) {
+//!     // ...
+//! }
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+
+use log::warn;
+use tonic::metadata::{AsciiMetadataKey, AsciiMetadataValue, MetadataMap};
+use tonic::service::Interceptor;
+use tonic::{Request, Status};
+
+use crate::context::EvaluationContext;
+use crate::{normalize_name, Client};
+
+/// Metadata key of the propagated decisions, `name=1,other=0`.
+pub const FLAGS_METADATA_KEY: &str = "x-flags-decisions";
+/// Metadata key of the propagated [`EvaluationContext::user_id`].
+pub const USER_ID_METADATA_KEY: &str = "x-flags-user-id";
+/// Metadata key of the propagated [`EvaluationContext::tenant`].
+pub const TENANT_METADATA_KEY: &str = "x-flags-tenant";
+/// Metadata key of the propagated [`EvaluationContext::app_version`].
+pub const APP_VERSION_METADATA_KEY: &str = "x-flags-app-version";
+/// Prefix of the metadata keys of propagated context attributes. Metadata keys
+/// are lowercase, so each value is `name=value` with the attribute's name as
+/// given, and `planTier` arrives as `planTier`.
+pub const ATTRIBUTE_METADATA_PREFIX: &str = "x-flags-attr-";
+
+/// A tonic client interceptor adding flag decisions and an evaluation
+/// context to every outgoing request, see the [module docs](self).
+#[derive(Debug, Clone, Default)]
+pub struct FlagsInterceptor {
+    flags: BTreeMap<String, bool>,
+    context: Option<EvaluationContext>,
+}
+
+impl FlagsInterceptor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Propagate `context`, and use it for [`evaluate`](Self::evaluate).
+    pub fn with_context(mut self, context: EvaluationContext) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Propagate a decision made elsewhere.
+    pub fn with_flag(mut self, name: &str, enabled: bool) -> Self {
+        self.flags.insert(normalize_name(name).into_owned(), enabled);
+        self
+    }
+
+    /// Evaluate `names` on `client`, for the context if one was set, and
+    /// propagate the results.
+    pub async fn evaluate(mut self, client: &Client, names: &[&str]) -> Self {
+        for name in names {
+            let mut flag = client.is(name);
+            if let Some(context) = &self.context {
+                flag = flag.with_context(context);
+            }
+            let enabled = flag.enabled().await;
+            self.flags.insert(normalize_name(name).into_owned(), enabled);
+        }
+        self
+    }
+
+    /// Write the decisions and context into `metadata`. Names and values
+    /// that can't be carried as ASCII metadata are skipped with a warning.
+    pub fn inject(&self, metadata: &mut MetadataMap) {
+        let decisions: Vec<String> = self
+            .flags
+            .iter()
+            .filter(|(name, _)| {
+                let valid = !name.contains([',', '=']);
+                if !valid {
+                    warn!("Flag '{}' can't be propagated in gRPC metadata", name);
+                }
+                valid
+            })
+            .map(|(name, enabled)| format!("{}={}", name, u8::from(*enabled)))
+            .collect();
+        if !decisions.is_empty() {
+            insert(metadata, FLAGS_METADATA_KEY, &decisions.join(","));
+        }
+
+        let Some(context) = &self.context else {
+            return;
+        };
+        let fields = [
+            (USER_ID_METADATA_KEY, context.user_id()),
+            (TENANT_METADATA_KEY, context.tenant()),
+            (APP_VERSION_METADATA_KEY, context.app_version()),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                insert(metadata, key, value);
+            }
+        }
+        for (name, value) in context.attributes() {
+            if name.contains('=') {
+                warn!("Attribute '{}' can't be propagated in gRPC metadata", name);
+                continue;
+            }
+            let key = format!("{}{}", ATTRIBUTE_METADATA_PREFIX, name.to_ascii_lowercase());
+            // Appended: names differing only in case share a key
+            match (key.parse::<AsciiMetadataKey>(), format!("{}={}", name, value).parse::<AsciiMetadataValue>()) {
+                (Ok(key), Ok(value)) => {
+                    metadata.append(key, value);
+                }
+                _ => warn!("'{}' can't be propagated in gRPC metadata", key),
+            }
+        }
+    }
+}
+
+impl Interceptor for FlagsInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        self.inject(request.metadata_mut());
+        Ok(request)
+    }
+}
+
+fn insert(metadata: &mut MetadataMap, key: &str, value: &str) {
+    match (key.parse::<AsciiMetadataKey>(), value.parse::<AsciiMetadataValue>()) {
+        (Ok(key), Ok(value)) => {
+            metadata.insert(key, value);
+        }
+        _ => warn!("'{}' can't be propagated in gRPC metadata", key),
+    }
+}
+
+/// Flag decisions and context received from the caller, see the
+/// [module docs](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PropagatedFlags {
+    flags: BTreeMap<String, bool>,
+    context: Option<EvaluationContext>,
+}
+
+impl PropagatedFlags {
+    /// Read what a [`FlagsInterceptor`] wrote into `metadata`.
+    pub fn from_metadata(metadata: &MetadataMap) -> Self {
+        let value = |key: &str| metadata.get(key).and_then(|v| v.to_str().ok());
+
+        let flags = value(FLAGS_METADATA_KEY)
+            .into_iter()
+            .flat_map(|decisions| decisions.split(','))
+            .filter_map(|decision| {
+                let (name, enabled) = decision.trim().split_once('=')?;
+                let enabled = match enabled {
+                    "1" => true,
+                    "0" => false,
+                    _ => return None,
+                };
+                Some((normalize_name(name).into_owned(), enabled))
+            })
+            .collect();
+
+        let mut context = EvaluationContext::new();
+        if let Some(user_id) = value(USER_ID_METADATA_KEY) {
+            context = context.with_user_id(user_id);
+        }
+        if let Some(tenant) = value(TENANT_METADATA_KEY) {
+            context = context.with_tenant(tenant);
+        }
+        if let Some(version) = value(APP_VERSION_METADATA_KEY) {
+            context = context.with_app_version(version);
+        }
+        for entry in metadata.iter() {
+            if let tonic::metadata::KeyAndValueRef::Ascii(key, value) = entry {
+                if !key.as_str().starts_with(ATTRIBUTE_METADATA_PREFIX) {
+                    continue;
+                }
+                if let Some((name, value)) = value.to_str().ok().and_then(|v| v.split_once('=')) {
+                    context = context.with_attribute(name, value);
+                }
+            }
+        }
+
+        Self {
+            flags,
+            context: (context != EvaluationContext::default()).then_some(context),
+        }
+    }
+
+    /// The caller's decision for `name`, if it sent one.
+    pub fn get(&self, name: &str) -> Option<bool> {
+        self.flags.get(normalize_name(name).as_ref()).copied()
+    }
+
+    /// Every propagated decision.
+    pub fn flags(&self) -> &BTreeMap<String, bool> {
+        &self.flags
+    }
+
+    /// The caller's evaluation context, for flags it did not decide.
+    pub fn context(&self) -> Option<&EvaluationContext> {
+        self.context.as_ref()
+    }
+}
+
+/// A tonic server interceptor storing the caller's [`PropagatedFlags`] in the
+/// request extensions.
+pub fn extract(mut request: Request<()>) -> Result<Request<()>, Status> {
+    let flags = PropagatedFlags::from_metadata(request.metadata());
+    request.extensions_mut().insert(flags);
+    Ok(request)
+}
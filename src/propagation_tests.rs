@@ -0,0 +1,88 @@
+#[cfg(all(test, feature = "grpc", feature = "remote"))]
+mod tests {
+    use tonic::service::Interceptor;
+    use tonic::Request;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::context::EvaluationContext;
+    use crate::propagation::{extract, FlagsInterceptor, PropagatedFlags, FLAGS_METADATA_KEY};
+    use crate::{Auth, Client};
+
+    #[test]
+    fn test_flags_and_context_round_trip() {
+        let context = EvaluationContext::new()
+            .with_user_id("user-42")
+            .with_tenant("acme")
+            .with_region("eu-west-1")
+            .with_attribute("planTier", "pro")
+            .with_attribute("plantier", "free")
+            .with_attribute("query", "a=b");
+        let mut interceptor = FlagsInterceptor::new()
+            .with_context(context.clone())
+            .with_flag("New-Checkout", true)
+            .with_flag("dark-mode", false)
+            .with_flag("bad,name", true);
+
+        let request = interceptor.call(Request::new(())).unwrap();
+        assert_eq!(
+            request.metadata().get(FLAGS_METADATA_KEY).unwrap(),
+            "dark-mode=0,new-checkout=1"
+        );
+
+        let request = extract(request).unwrap();
+        let flags = request.extensions().get::<PropagatedFlags>().unwrap();
+        assert_eq!(flags.get("new-checkout"), Some(true));
+        assert_eq!(flags.get("Dark-Mode"), Some(false));
+        assert_eq!(flags.get("bad,name"), None);
+        assert_eq!(flags.flags().len(), 2);
+        assert_eq!(flags.context(), Some(&context));
+    }
+
+    #[test]
+    fn test_missing_metadata_propagates_nothing() {
+        let flags = PropagatedFlags::from_metadata(&tonic::metadata::MetadataMap::new());
+        assert_eq!(flags, PropagatedFlags::default());
+        assert!(flags.context().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_interceptor_evaluates_for_its_context() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": true, "details": {"name": "eu-only", "id": "1", "rules": [
+                        {"attribute": "region", "operator": "in", "values": ["eu-west-1"]}
+                    ]}},
+                    {"enabled": true, "details": {"name": "everywhere", "id": "2"}}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_memory_cache()
+            .build()
+            .unwrap();
+
+        let mut interceptor = FlagsInterceptor::new()
+            .with_context(EvaluationContext::new().with_region("us-east-1"))
+            .evaluate(&client, &["eu-only", "everywhere", "unknown"])
+            .await;
+        let request = interceptor.call(Request::new(())).unwrap();
+        assert_eq!(
+            request.metadata().get(FLAGS_METADATA_KEY).unwrap(),
+            "eu-only=0,everywhere=1,unknown=0"
+        );
+    }
+}
@@ -0,0 +1,47 @@
+//! The allow-list of flag names the code declares it uses.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use crate::flag::{FeatureFlag, FlagSource};
+
+pub(crate) struct FlagRegistry {
+    names: HashSet<String>,
+    strict: bool,
+    panic: bool,
+    // API flags already reported as unregistered
+    reported: Mutex<HashSet<String>>,
+}
+
+impl FlagRegistry {
+    /// `names` must already be normalized.
+    pub(crate) fn new(names: HashSet<String>, strict: bool, panic: bool) -> Self {
+        Self {
+            names,
+            strict,
+            panic,
+            reported: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Whether evaluating `name` must be refused.
+    pub(crate) fn rejects(&self, name: &str) -> bool {
+        self.strict && !self.names.contains(name)
+    }
+
+    /// Whether a refused name panics rather than being reported.
+    pub(crate) fn panics(&self) -> bool {
+        self.panic
+    }
+
+    /// API flags no code registered, each returned only the first time it is seen.
+    pub(crate) fn newly_unregistered(&self, flags: &[FeatureFlag]) -> Vec<String> {
+        let mut reported = self.reported.lock().unwrap_or_else(|e| e.into_inner());
+        flags
+            .iter()
+            .filter(|f| f.source() == FlagSource::Api && !self.names.contains(&f.details.name))
+            .filter(|f| reported.insert(f.details.name.clone()))
+            .map(|f| f.details.name.clone())
+            .collect()
+    }
+}
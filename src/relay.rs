@@ -0,0 +1,231 @@
+//! A relay that fetches flags with one [`Client`] and re-serves them to local
+//! processes, enabled with the `relay` feature.
+//!
+//! Services on a node point their base URL at the relay instead of flags.gg,
+//! so N replicas of M services cost one upstream connection per node rather
+//! than one each. The relay answers `GET /flags` with the API's JSON schema,
+//! from its own cache and on its own refresh schedule.
+//!
+//! Requests must carry the usual `X-Project-ID`, `X-Agent-ID` and
+//! `X-Environment-ID` headers. Those of the relay's client are served from
+//! its cache, and must be signed if its [`Auth`] has an agent secret. Other
+//! credentials are passed through: the relay fetches for them with a client
+//! of their own from a [`TenantClientPool`], sharing its connection pool.
+//! Up to [`MAX_PASSTHROUGH_CLIENTS`] credential sets are kept, dropping the
+//! least recently used and any unused for [`PASSTHROUGH_IDLE_TIMEOUT`].
+//! Credentials the API rejects on their first fetch are answered with
+//! `401 Unauthorized` and not kept.
+//!
+//! # Example
+//! ```no_run
+//! # use flags_rs::Client;
+//! # use flags_rs::relay::Relay;
+//! # async fn example(client: Client) -> std::io::Result<()> {
+//! let listener = tokio::net::TcpListener::bind("127.0.0.1:8080").await?;
+//! Relay::new(client).serve(listener).await
+//! # }
+//! ```
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use chrono::Utc;
+use log::warn;
+
+use crate::flag::{FeatureFlag, FlagSource};
+use crate::pool::TenantClientPool;
+use crate::transport::signing;
+use crate::{Auth, Client, ClientStatus, FlagError};
+
+/// Most distinct downstream credential sets the relay fetches for besides
+/// its own; further ones evict the least recently used.
+pub const MAX_PASSTHROUGH_CLIENTS: usize = 64;
+
+/// How long a passed-through credential set is kept without requests.
+pub const PASSTHROUGH_IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Serves a [`Client`]'s flags over HTTP, see the [module docs](self).
+pub struct Relay {
+    client: Client,
+    interval_allowed: i32,
+}
+
+struct RelayState {
+    client: Client,
+    interval_allowed: i32,
+    passthrough: TenantClientPool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Credentials {
+    project_id: String,
+    agent_id: String,
+    environment_id: String,
+    company_id: Option<String>,
+}
+
+impl Credentials {
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let value = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+        Some(Credentials {
+            project_id: value("X-Project-ID")?,
+            agent_id: value("X-Agent-ID")?,
+            environment_id: value("X-Environment-ID")?,
+            company_id: value("X-Company-ID"),
+        })
+    }
+
+    fn to_auth(&self) -> Auth {
+        Auth {
+            project_id: self.project_id.clone(),
+            agent_id: self.agent_id.clone(),
+            environment_id: self.environment_id.clone(),
+            company_id: self.company_id.clone(),
+            agent_secret: None,
+        }
+    }
+
+    fn matches(&self, auth: &Auth) -> bool {
+        self.project_id == auth.project_id
+            && self.agent_id == auth.agent_id
+            && self.environment_id == auth.environment_id
+            && self.company_id == auth.company_id
+    }
+}
+
+impl Relay {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            interval_allowed: 60,
+        }
+    }
+
+    /// How often downstream clients are told to refresh, 60 seconds by
+    /// default. The relay refreshes from upstream on its client's own schedule.
+    pub fn with_interval_allowed(mut self, interval: Duration) -> Self {
+        self.interval_allowed = i32::try_from(interval.as_secs()).unwrap_or(i32::MAX).max(1);
+        self
+    }
+
+    /// The relay's routes, to mount in an existing axum application.
+    pub fn router(&self) -> Router {
+        let state = RelayState {
+            client: self.client.clone(),
+            interval_allowed: self.interval_allowed,
+            passthrough: TenantClientPool::new(&self.client)
+                .with_capacity(MAX_PASSTHROUGH_CLIENTS)
+                .with_idle_timeout(PASSTHROUGH_IDLE_TIMEOUT),
+        };
+        Router::new()
+            .route("/flags", get(serve_flags))
+            .with_state(Arc::new(state))
+    }
+
+    /// Serve on `listener` until the server fails.
+    pub async fn serve(self, listener: tokio::net::TcpListener) -> std::io::Result<()> {
+        axum::serve(listener, self.router()).await
+    }
+
+    /// Serve on a Unix domain socket, for clients built with
+    /// [`ClientBuilder::with_unix_socket`](crate::ClientBuilder::with_unix_socket).
+    #[cfg(unix)]
+    pub async fn serve_unix(self, listener: tokio::net::UnixListener) -> std::io::Result<()> {
+        axum::serve(listener, self.router()).await
+    }
+}
+
+impl RelayState {
+    /// The client to answer a request with and, for passed-through
+    /// credentials, their [`Auth`]; or the status to refuse it with.
+    fn client_for(&self, headers: &HeaderMap) -> Result<(Client, Option<Auth>), StatusCode> {
+        let Some(auth) = self.client.auth.load_full() else {
+            // A relay without credentials serves its local flags to anyone
+            return Ok((self.client.clone(), None));
+        };
+        let credentials = Credentials::from_headers(headers).ok_or(StatusCode::UNAUTHORIZED)?;
+
+        if credentials.matches(&auth) {
+            if let Some(secret) = &auth.agent_secret {
+                if !signed(headers, secret, &credentials) {
+                    return Err(StatusCode::UNAUTHORIZED);
+                }
+            }
+            return Ok((self.client.clone(), None));
+        }
+
+        let auth = credentials.to_auth();
+        let client = self.passthrough.get(&auth).map_err(|_| StatusCode::BAD_REQUEST)?;
+        Ok((client, Some(auth)))
+    }
+}
+
+/// Whether the API refused `error`'s request because of its credentials.
+fn rejected(error: &FlagError) -> bool {
+    matches!(error.status(), Some(401 | 403)) || error.code() == "auth"
+}
+
+/// Whether the request carries a valid signature of `credentials` with `secret`.
+fn signed(headers: &HeaderMap, secret: &str, credentials: &Credentials) -> bool {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let (Some(timestamp), Some(signature)) = (
+        header(signing::TIMESTAMP_HEADER).and_then(|t| t.parse().ok()),
+        header(signing::SIGNATURE_HEADER),
+    ) else {
+        return false;
+    };
+    signing::verify(
+        secret,
+        timestamp,
+        &credentials.project_id,
+        &credentials.agent_id,
+        signature,
+        Utc::now().timestamp(),
+        signing::DEFAULT_SKEW_TOLERANCE,
+    )
+}
+
+async fn serve_flags(State(state): State<Arc<RelayState>>, headers: HeaderMap) -> Response {
+    let (client, passthrough) = match state.client_for(&headers) {
+        Ok(found) => found,
+        Err(status) => return status.into_response(),
+    };
+    if let Some(auth) = passthrough.filter(|_| client.status() == ClientStatus::Initializing) {
+        if let Err(e) = client.try_refresh_if_needed().await {
+            if rejected(&e) {
+                warn!("Relay dropped credentials for project '{}': {}", auth.project_id, e);
+                state.passthrough.remove(&auth);
+                return StatusCode::UNAUTHORIZED.into_response();
+            }
+        }
+    }
+    let flags = match client.list().await {
+        Ok(flags) => flags,
+        Err(e) => return (StatusCode::SERVICE_UNAVAILABLE, e.to_string()).into_response(),
+    };
+
+    // The relay's own local overrides stay local
+    let flags: Vec<&FeatureFlag> = flags
+        .iter()
+        .filter(|f| f.source() == FlagSource::Api)
+        .map(|f| f.as_ref())
+        .collect();
+    let body = serde_json::json!({
+        "intervalAllowed": state.interval_allowed,
+        "flags": flags,
+    });
+    ([(CONTENT_TYPE, "application/json")], body.to_string()).into_response()
+}
@@ -0,0 +1,116 @@
+#[cfg(all(test, feature = "relay"))]
+mod tests {
+    use crate::relay::Relay;
+    use crate::{Auth, Client};
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn auth(environment_id: &str) -> Auth {
+        Auth {
+            project_id: "test-project".to_string(),
+            agent_id: "test-agent".to_string(),
+            environment_id: environment_id.to_string(),
+            company_id: None,
+            agent_secret: None,
+        }
+    }
+
+    async fn mount_flags(server: &MockServer, environment_id: &str, enabled: bool) {
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(header("X-Environment-ID", environment_id))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": enabled, "details": {"name": "new-checkout", "id": "1"}}]
+            })))
+            .expect(1)
+            .mount(server)
+            .await;
+    }
+
+    /// Serve a relay for `upstream` on a local port, returning its base URL.
+    async fn start_relay(upstream: &MockServer) -> String {
+        let client = Client::builder()
+            .with_base_url(&upstream.uri())
+            .with_auth(auth("test-env"))
+            .build()
+            .unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(Relay::new(client).serve(listener));
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_relay_serves_one_upstream_fetch() {
+        let upstream = MockServer::start().await;
+        mount_flags(&upstream, "test-env", true).await;
+        let relay = start_relay(&upstream).await;
+
+        for _ in 0..3 {
+            let client = Client::builder()
+                .with_base_url(&relay)
+                .with_auth(auth("test-env"))
+                .build()
+                .unwrap();
+            assert!(client.is("new-checkout").enabled().await);
+            assert!(!client.is("unknown").enabled().await);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_relay_requires_credentials() {
+        let upstream = MockServer::start().await;
+        let relay = start_relay(&upstream).await;
+
+        let response = reqwest::get(format!("{}/flags", relay)).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_relay_passes_through_other_credentials() {
+        let upstream = MockServer::start().await;
+        mount_flags(&upstream, "staging", false).await;
+        mount_flags(&upstream, "test-env", true).await;
+        let relay = start_relay(&upstream).await;
+
+        let staging = Client::builder()
+            .with_base_url(&relay)
+            .with_auth(auth("staging"))
+            .build()
+            .unwrap();
+        let production = Client::builder()
+            .with_base_url(&relay)
+            .with_auth(auth("test-env"))
+            .build()
+            .unwrap();
+        assert!(!staging.is("new-checkout").enabled().await);
+        assert!(production.is("new-checkout").enabled().await);
+    }
+
+    #[tokio::test]
+    async fn test_relay_does_not_keep_rejected_credentials() {
+        let upstream = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(header("X-Environment-ID", "revoked"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&upstream)
+            .await;
+        let relay = start_relay(&upstream).await;
+
+        // A kept client would answer the second request from its empty cache
+        let http = reqwest::Client::new();
+        for _ in 0..2 {
+            let response = http
+                .get(format!("{}/flags", relay))
+                .header("X-Project-ID", "test-project")
+                .header("X-Agent-ID", "test-agent")
+                .header("X-Environment-ID", "revoked")
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+        }
+    }
+}
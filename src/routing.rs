@@ -0,0 +1,74 @@
+//! Flag-gated axum routes, enabled with the `axum` feature.
+//!
+//! A route registered with [`RouterExt::route_flagged`] answers 404 while its
+//! flag is disabled and goes live as soon as the flag is enabled, so
+//! endpoints can be dark-launched without redeploying.
+//!
+//! # Example
+//! ```no_run
+//! # use axum::routing::get;
+//! # use axum::Router;
+//! # use flags_rs::routing::RouterExt;
+//! let app: Router = Router::new()
+//!     .route("/", get(|| async { "home" }))
+//!     .route_flagged("/checkout/v2", "new-checkout", get(|| async { "new checkout" }));
+//! ```
+
+use std::sync::Arc;
+
+use axum::extract::Request;
+use axum::http::StatusCode;
+use axum::middleware::{self, Next};
+use axum::response::IntoResponse;
+use axum::routing::MethodRouter;
+use axum::Router;
+
+use crate::Client;
+
+/// Adds flag-gated routes to an axum [`Router`], see the [module docs](self).
+pub trait RouterExt<S> {
+    /// Route `path` to `method_router` while `flag` is enabled on the global
+    /// client ([`flags_rs::init`](crate::init)), answering 404 otherwise,
+    /// also before the global client is initialized.
+    fn route_flagged(self, path: &str, flag: &str, method_router: MethodRouter<S>) -> Self;
+
+    /// [`route_flagged`](Self::route_flagged), checking `flag` on `client`.
+    fn route_flagged_with(self, client: &Client, path: &str, flag: &str, method_router: MethodRouter<S>) -> Self;
+}
+
+impl<S> RouterExt<S> for Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    fn route_flagged(self, path: &str, flag: &str, method_router: MethodRouter<S>) -> Self {
+        self.route(path, gate(None, flag, method_router))
+    }
+
+    fn route_flagged_with(self, client: &Client, path: &str, flag: &str, method_router: MethodRouter<S>) -> Self {
+        self.route(path, gate(Some(client.clone()), flag, method_router))
+    }
+}
+
+/// Wrap every method of `method_router`, including its 405 fallback, so a
+/// disabled flag hides the route entirely.
+fn gate<S>(client: Option<Client>, flag: &str, method_router: MethodRouter<S>) -> MethodRouter<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let flag: Arc<str> = Arc::from(flag);
+    method_router.layer(middleware::from_fn(move |request: Request, next: Next| {
+        let client = client.clone();
+        let flag = Arc::clone(&flag);
+        async move {
+            let enabled = match &client {
+                Some(client) => client.is(&flag).enabled().await,
+                None => crate::enabled(&flag).await,
+            };
+            if enabled {
+                next.run(request).await
+            } else {
+                StatusCode::NOT_FOUND.into_response()
+            }
+        }
+    }))
+}
@@ -0,0 +1,70 @@
+#[cfg(all(test, feature = "axum", feature = "remote"))]
+mod tests {
+    use crate::routing::RouterExt;
+    use crate::{Auth, Client};
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::routing::get;
+    use axum::Router;
+    use tower::ServiceExt;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn flags(enabled: bool) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "intervalAllowed": 60,
+            "flags": [{"enabled": enabled, "details": {"name": "new-checkout", "id": "1"}}]
+        }))
+    }
+
+    async fn status(app: &Router, method: &str, uri: &str) -> StatusCode {
+        let request = Request::builder().method(method).uri(uri).body(Body::empty()).unwrap();
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_route_flagged_follows_the_flag() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(flags(false))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(flags(true))
+            .mount(&mock_server)
+            .await;
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_memory_cache()
+            .build()
+            .unwrap();
+
+        let app = Router::new()
+            .route("/", get(|| async { "home" }))
+            .route_flagged_with(&client, "/checkout", "new-checkout", get(|| async { "checkout" }));
+
+        assert_eq!(status(&app, "GET", "/").await, StatusCode::OK);
+        assert_eq!(status(&app, "GET", "/checkout").await, StatusCode::NOT_FOUND);
+        assert_eq!(status(&app, "POST", "/checkout").await, StatusCode::NOT_FOUND);
+
+        client.clear_cache().await.unwrap();
+        assert_eq!(status(&app, "GET", "/checkout").await, StatusCode::OK);
+        assert_eq!(status(&app, "POST", "/checkout").await, StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn test_route_flagged_is_hidden_without_a_global_client() {
+        let app: Router = Router::new().route_flagged("/beta", "beta-api", get(|| async { "beta" }));
+        assert_eq!(status(&app, "GET", "/beta").await, StatusCode::NOT_FOUND);
+    }
+}
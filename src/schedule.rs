@@ -0,0 +1,149 @@
+//! Recurring activation periods of flags: time-of-day windows and cron
+//! expressions, evaluated in UTC.
+
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Timelike, Utc, Weekday};
+use log::warn;
+
+use crate::flag::TimeRule;
+
+/// The parsed [`TimeRule`]s of one flag; it is active while any of them is.
+#[derive(Debug)]
+pub(crate) struct Schedule {
+    rules: Vec<Period>,
+}
+
+#[derive(Debug)]
+enum Period {
+    // `None` for an invalid expression, which never matches
+    Cron(Option<Cron>),
+    Window {
+        days: Vec<Weekday>,
+        from: NaiveTime,
+        until: NaiveTime,
+    },
+}
+
+impl Schedule {
+    pub(crate) fn new(flag: &str, rules: &[TimeRule]) -> Self {
+        let rules = rules
+            .iter()
+            .map(|rule| match rule {
+                TimeRule::Cron { cron } => {
+                    let parsed = Cron::parse(cron);
+                    if parsed.is_none() {
+                        warn!("Flag '{}' has an invalid cron expression '{}'", flag, cron);
+                    }
+                    Period::Cron(parsed)
+                }
+                TimeRule::Window { days, from, until } => Period::Window {
+                    days: days.clone(),
+                    from: *from,
+                    until: *until,
+                },
+            })
+            .collect();
+        Self { rules }
+    }
+
+    pub(crate) fn is_active_at(&self, now: DateTime<Utc>) -> bool {
+        self.rules.iter().any(|rule| rule.is_active_at(now))
+    }
+}
+
+impl Period {
+    fn is_active_at(&self, now: DateTime<Utc>) -> bool {
+        match self {
+            Period::Cron(cron) => cron.as_ref().is_some_and(|cron| cron.matches(now)),
+            Period::Window { days, from, until } => {
+                let on = |day: Weekday| days.is_empty() || days.contains(&day);
+                let time = now.time();
+                if from <= until {
+                    on(now.weekday()) && *from <= time && time < *until
+                } else {
+                    // Overnight, e.g. 22:00-06:00: the early hours belong to the previous day
+                    (on(now.weekday()) && time >= *from) || (on((now - Duration::days(1)).weekday()) && time < *until)
+                }
+            }
+        }
+    }
+}
+
+/// A five-field cron expression, `minute hour day-of-month month day-of-week`,
+/// matching the minutes it would fire in. Fields take `*`, numbers, ranges
+/// `a-b`, steps `*/n` or `a-b/n` and comma-separated lists; Sunday is 0 or 7.
+/// As in Vixie cron, when both day fields are restricted either may match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Cron {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    // Written as `*`, so only the other day field restricts days
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl Cron {
+    pub(crate) fn parse(expression: &str) -> Option<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days_of_month, months, days_of_week] = fields.as_slice() else {
+            return None;
+        };
+        let mut days_of_week_bits = parse_field(days_of_week, 0, 7)?;
+        // Sunday may be written as 7
+        if days_of_week_bits & (1 << 7) != 0 {
+            days_of_week_bits |= 1;
+        }
+        Some(Cron {
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days_of_month: parse_field(days_of_month, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            days_of_week: days_of_week_bits,
+            any_day_of_month: days_of_month.starts_with('*'),
+            any_day_of_week: days_of_week.starts_with('*'),
+        })
+    }
+
+    pub(crate) fn matches(&self, now: DateTime<Utc>) -> bool {
+        let bit = |set: u64, value: u32| set & (1 << value) != 0;
+        let day_of_month = bit(self.days_of_month, now.day());
+        let day_of_week = bit(self.days_of_week, now.weekday().num_days_from_sunday());
+        let day = match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        };
+        day && bit(self.minutes, now.minute()) && bit(self.hours, now.hour()) && bit(self.months, now.month())
+    }
+}
+
+/// The values a field allows, as a bit set.
+fn parse_field(field: &str, min: u32, max: u32) -> Option<u64> {
+    let mut set = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0)?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (start.parse().ok()?, end.parse().ok()?),
+                // `5/15` counts from 5 to the end of the range
+                None if part.contains('/') => (range.parse().ok()?, max),
+                None => {
+                    let value = range.parse().ok()?;
+                    (value, value)
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            return None;
+        }
+        for value in (start..=end).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Some(set)
+}
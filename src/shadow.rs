@@ -0,0 +1,90 @@
+//! Shadow evaluation against a second flag source, for migrations and canary
+//! environments.
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::BuildHasher;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::context::EvaluationContext;
+use crate::Client;
+
+/// A flag the primary and shadow sources disagree on, see
+/// [`ClientBuilder::with_shadow`](crate::ClientBuilder::with_shadow) and
+/// [`ClientBuilder::with_canary_environment`](crate::ClientBuilder::with_canary_environment).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowMismatch {
+    pub flag: String,
+    /// The value served.
+    pub primary: bool,
+    pub shadow: bool,
+}
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+pub type MismatchCallback = Arc<dyn Fn(&ShadowMismatch) + Send + Sync>;
+
+pub(crate) struct Shadow {
+    pub(crate) client: Client,
+    pub(crate) on_mismatch: MismatchCallback,
+    // Fraction of evaluations compared
+    pub(crate) sample_rate: f64,
+}
+
+impl Shadow {
+    /// Evaluate `name` on the shadow in the same `context` and report it if it
+    /// differs from `primary`.
+    // Boxed: the shadow's evaluation recurses into the same async fns
+    pub(crate) fn compare<'a>(
+        &'a self,
+        name: &'a str,
+        context: Option<&'a EvaluationContext>,
+        primary: bool,
+    ) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            if !self.sampled() {
+                return;
+            }
+            let flag = self.client.is(name);
+            let shadow = match context {
+                Some(context) => flag.with_context(context).enabled().await,
+                None => flag.enabled().await,
+            };
+            self.report(name, primary, shadow);
+        })
+    }
+
+    /// Evaluate `names` on the shadow and report each that differs from `primary`.
+    pub(crate) fn compare_all<'a>(&'a self, names: &'a [&'a str], primary: &'a HashMap<String, bool>) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            if !self.sampled() {
+                return;
+            }
+            let shadowed = self.client.get_multiple(names).await;
+            for (name, &enabled) in primary {
+                self.report(name, enabled, shadowed.get(name).copied().unwrap_or(false));
+            }
+        })
+    }
+
+    fn sampled(&self) -> bool {
+        if self.sample_rate >= 1.0 {
+            return true;
+        }
+        // Each RandomState is keyed afresh, so hashing nothing is a random draw
+        let draw = RandomState::new().hash_one(()) as f64 / u64::MAX as f64;
+        draw < self.sample_rate
+    }
+
+    fn report(&self, name: &str, primary: bool, shadow: bool) {
+        if primary != shadow {
+            (self.on_mismatch)(&ShadowMismatch {
+                flag: name.to_string(),
+                primary,
+                shadow,
+            });
+        }
+    }
+}
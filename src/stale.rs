@@ -0,0 +1,51 @@
+//! How long cached flags may be served after refreshes start failing.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// Serves the cache for up to `max_age` after the last successful refresh,
+/// then answers every evaluation from per-flag defaults until one succeeds.
+pub(crate) struct StalePolicy {
+    max_age: Duration,
+    defaults: Arc<HashMap<String, bool>>,
+    // Start of the current freshness window: the last success, or creation
+    fresh_since: Mutex<Instant>,
+    expired: AtomicBool,
+}
+
+impl StalePolicy {
+    /// `defaults` must be keyed by normalized flag name.
+    pub(crate) fn new(max_age: Duration, defaults: Arc<HashMap<String, bool>>) -> Self {
+        Self {
+            max_age,
+            defaults,
+            fresh_since: Mutex::new(Instant::now()),
+            expired: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn record_success(&self) {
+        *self.fresh_since.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+        self.expired.store(false, Ordering::Release);
+    }
+
+    /// How stale the cache is if it is past `max_age`.
+    pub(crate) fn expired_for(&self) -> Option<Duration> {
+        let age = self.fresh_since.lock().unwrap_or_else(|e| e.into_inner()).elapsed();
+        (age > self.max_age).then_some(age)
+    }
+
+    /// Mark the cache expired, returning whether it was fresh until now.
+    pub(crate) fn mark_expired(&self) -> bool {
+        !self.expired.swap(true, Ordering::AcqRel)
+    }
+
+    /// The value served for `name` once the cache has expired.
+    pub(crate) fn default_for(&self, name: &str) -> bool {
+        self.defaults.get(name).copied().unwrap_or(false)
+    }
+}
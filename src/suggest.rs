@@ -0,0 +1,92 @@
+//! "Did you mean" hints for evaluations of flags that do not exist.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+/// Longest edit distance still offered as a suggestion.
+const MAX_DISTANCE: usize = 2;
+
+/// How many unknown names are remembered, each for checks and suggestions.
+pub(crate) const MAX_NAMES: usize = 1024;
+
+#[derive(Default)]
+struct State {
+    // Unknown names already looked up, with the refresh generation they were looked up in
+    checked: HashMap<String, u64>,
+    // Unknown names a suggestion was already given for
+    suggested: HashSet<String>,
+}
+
+/// Remembers which unknown names were looked up, so a miss only scans the
+/// flags once per refresh and each suggestion is given once. Once
+/// [`MAX_NAMES`] names are remembered, names checked before the current
+/// refresh are forgotten, and further misses are not checked until then.
+#[derive(Default)]
+pub(crate) struct Suggestions {
+    state: Mutex<State>,
+}
+
+impl Suggestions {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a miss on `name` should look for a suggestion, marking it checked.
+    pub(crate) fn should_check(&self, name: &str, generation: u64) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.suggested.contains(name) || state.checked.get(name) == Some(&generation) {
+            return false;
+        }
+        if state.checked.len() >= MAX_NAMES && !state.checked.contains_key(name) {
+            state.checked.retain(|_, checked| *checked == generation);
+            if state.checked.len() >= MAX_NAMES {
+                return false;
+            }
+        }
+        state.checked.insert(name.to_string(), generation);
+        true
+    }
+
+    /// Stop suggesting for `name`. Past [`MAX_NAMES`] suggestions, it is
+    /// suggested again after the next refresh instead.
+    pub(crate) fn mark_suggested(&self, name: &str) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.suggested.len() < MAX_NAMES {
+            state.checked.remove(name);
+            state.suggested.insert(name.to_string());
+        }
+    }
+}
+
+/// The candidate nearest to `name` within [`MAX_DISTANCE`] edits, ties
+/// broken alphabetically. Both are normalized names, so spellings differing
+/// only in case or separators are already the same flag.
+pub(crate) fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    // Short names are within two edits of too many others to suggest anything useful
+    let max_distance = MAX_DISTANCE.min(name.chars().count() / 3);
+
+    candidates
+        .into_iter()
+        .filter(|c| *c != name)
+        .filter_map(|c| {
+            let d = distance(name, c);
+            (d <= max_distance).then_some((d, c))
+        })
+        .min()
+        .map(|(_, c)| c)
+}
+
+/// Levenshtein distance between `a` and `b`.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
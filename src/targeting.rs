@@ -0,0 +1,87 @@
+//! Per-evaluation targeting of flags: app version ranges, attribute rules,
+//! percentage rollouts and ramps, checked against the [`EvaluationContext`],
+//! and activation schedules, checked for every evaluation.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use log::warn;
+
+use crate::bucketing::{self, BucketHasher};
+use crate::context::EvaluationContext;
+use crate::flag::{FeatureFlag, Ramp, Rule};
+use crate::schedule::Schedule;
+use crate::version::{Version, VersionRange};
+
+/// The targeting a published flag declares.
+#[derive(Debug, Default)]
+pub(crate) struct Targeting {
+    // `Some(None)` for an invalid range, which no version satisfies
+    app_version: Option<Option<VersionRange>>,
+    rules: Vec<Rule>,
+    rollout: Option<f64>,
+    ramp: Option<Ramp>,
+    schedule: Option<Schedule>,
+}
+
+impl Targeting {
+    /// The index of the flags that declare any targeting.
+    pub(crate) fn index(flags: &[FeatureFlag]) -> HashMap<String, Targeting> {
+        flags
+            .iter()
+            .filter_map(|flag| {
+                let details = &flag.details;
+                let targeting = Targeting {
+                    app_version: details.app_version.as_deref().map(|range| {
+                        let parsed = VersionRange::parse(range);
+                        if parsed.is_none() {
+                            warn!("Flag '{}' has an invalid app version range '{}'", details.name, range);
+                        }
+                        parsed
+                    }),
+                    rules: details.rules.clone(),
+                    rollout: details.rollout,
+                    ramp: details.ramp.clone(),
+                    schedule: (!details.schedule.is_empty()).then(|| Schedule::new(&details.name, &details.schedule)),
+                };
+                let targeted = targeting.app_version.is_some()
+                    || !targeting.rules.is_empty()
+                    || targeting.rollout.is_some()
+                    || targeting.ramp.is_some()
+                    || targeting.schedule.is_some();
+                targeted.then(|| (details.name.clone(), targeting))
+            })
+            .collect()
+    }
+
+    /// Whether the flag's schedule, if it has one, is active at `now`.
+    pub(crate) fn is_active_at(&self, now: DateTime<Utc>) -> bool {
+        self.schedule.as_ref().is_none_or(|schedule| schedule.is_active_at(now))
+    }
+
+    /// Whether an evaluation of the enabled flag `name` in `context` stays enabled.
+    pub(crate) fn allows(&self, name: &str, context: &EvaluationContext, hasher: &dyn BucketHasher) -> bool {
+        if let (Some(range), Some(app_version)) = (&self.app_version, context.app_version()) {
+            let Some(version) = Version::parse(app_version) else {
+                warn!("Invalid app version '{}', disabling version-restricted flag '{}'", app_version, name);
+                return false;
+            };
+            if !range.as_ref().is_some_and(|range| range.matches(&version)) {
+                return false;
+            }
+        }
+        if !self.rules.iter().all(|rule| rule.matches(context.attribute(&rule.attribute))) {
+            return false;
+        }
+        let percentage = match &self.ramp {
+            Some(ramp) => Some(ramp.percentage_at(Utc::now())),
+            None => self.rollout,
+        };
+        match percentage {
+            Some(percentage) => context
+                .user_id()
+                .is_some_and(|user_id| bucketing::in_rollout(hasher, name, user_id, percentage)),
+            None => true,
+        }
+    }
+}
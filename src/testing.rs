@@ -0,0 +1,235 @@
+//! A local flags.gg API for integration tests, enabled with the `test-util`
+//! feature.
+//!
+//! [`MockFlagsServer`] answers `GET /flags` with the API's JSON schema from a
+//! flag set the test controls, and records every request so tests can assert
+//! on the credentials a client sent.
+//!
+//! # Example
+//! ```no_run
+//! # use flags_rs::Auth;
+//! # use flags_rs::testing::MockFlagsServer;
+//! # async fn example() {
+//! let server = MockFlagsServer::start().await;
+//! server.set_flag("new-checkout", true);
+//!
+//! let client = server.client_builder().build().unwrap();
+//! assert!(client.is("new-checkout").enabled().await);
+//! server.assert_auth(&MockFlagsServer::auth());
+//! # }
+//! ```
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use tokio::task::JoinHandle;
+
+use crate::flag::{Details, FeatureFlag};
+use crate::{Auth, Client, ClientBuilder};
+
+/// A local HTTP server serving a programmable flag set, see the
+/// [module docs](self). Stops when dropped.
+pub struct MockFlagsServer {
+    addr: SocketAddr,
+    state: Arc<MockState>,
+    task: JoinHandle<()>,
+}
+
+struct MockState {
+    flags: Mutex<Vec<FeatureFlag>>,
+    interval_allowed: Mutex<i32>,
+    failure: Mutex<Option<StatusCode>>,
+    requests: Mutex<Vec<ReceivedRequest>>,
+}
+
+/// A request the server received.
+#[derive(Debug, Clone)]
+pub struct ReceivedRequest {
+    pub path: String,
+    pub headers: HeaderMap,
+}
+
+impl ReceivedRequest {
+    /// The value of header `name`, if present and valid UTF-8.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|v| v.to_str().ok())
+    }
+}
+
+impl MockFlagsServer {
+    /// Start a server on a random local port, serving no flags.
+    pub async fn start() -> Self {
+        let state = Arc::new(MockState {
+            flags: Mutex::new(Vec::new()),
+            interval_allowed: Mutex::new(60),
+            failure: Mutex::new(None),
+            requests: Mutex::new(Vec::new()),
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind mock flags server");
+        let addr = listener.local_addr().expect("mock flags server address");
+        let router = Router::new()
+            .route("/flags", get(serve_flags))
+            .fallback(record_unknown)
+            .with_state(Arc::clone(&state));
+        let task = tokio::spawn(async move {
+            let _ = axum::serve(listener, router).await;
+        });
+        Self { addr, state, task }
+    }
+
+    /// The base URL to pass to [`ClientBuilder::with_base_url`].
+    pub fn uri(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// The credentials [`client_builder`](Self::client_builder) uses.
+    pub fn auth() -> Auth {
+        Auth {
+            project_id: "test-project".to_string(),
+            agent_id: "test-agent".to_string(),
+            environment_id: "test-env".to_string(),
+            company_id: None,
+            agent_secret: None,
+        }
+    }
+
+    /// A builder pointed at this server with [`auth`](Self::auth) and an
+    /// in-memory cache, so tests don't share state on disk.
+    pub fn client_builder(&self) -> ClientBuilder {
+        Client::builder()
+            .with_base_url(&self.uri())
+            .with_auth(Self::auth())
+            .with_memory_cache()
+    }
+
+    /// Serve flag `name` as `enabled`, adding it if it isn't served yet.
+    pub fn set_flag(&self, name: &str, enabled: bool) {
+        let mut flags = lock(&self.state.flags);
+        match flags.iter_mut().find(|f| f.details.name == name) {
+            Some(flag) => flag.enabled = enabled,
+            None => {
+                let id = (flags.len() + 1).to_string();
+                flags.push(FeatureFlag {
+                    enabled,
+                    details: Details {
+                        name: name.to_string(),
+                        id,
+                        ..Default::default()
+                    },
+                });
+            }
+        }
+    }
+
+    /// Stop serving flag `name`, returning whether it was served.
+    pub fn remove_flag(&self, name: &str) -> bool {
+        let mut flags = lock(&self.state.flags);
+        let before = flags.len();
+        flags.retain(|f| f.details.name != name);
+        flags.len() != before
+    }
+
+    /// Replace the served flags, for flags with details beyond a name.
+    pub fn set_flags(&self, flags: impl IntoIterator<Item = FeatureFlag>) {
+        *lock(&self.state.flags) = flags.into_iter().collect();
+    }
+
+    /// The `intervalAllowed` served, 60 seconds by default.
+    pub fn set_interval_allowed(&self, interval: Duration) {
+        *lock(&self.state.interval_allowed) = i32::try_from(interval.as_secs()).unwrap_or(i32::MAX);
+    }
+
+    /// Answer `/flags` with `status` and no body until [`recover`](Self::recover).
+    pub fn fail_with(&self, status: u16) {
+        *lock(&self.state.failure) = Some(StatusCode::from_u16(status).expect("valid status code"));
+    }
+
+    /// Serve flags again after [`fail_with`](Self::fail_with).
+    pub fn recover(&self) {
+        *lock(&self.state.failure) = None;
+    }
+
+    /// Every request received so far, oldest first.
+    pub fn received_requests(&self) -> Vec<ReceivedRequest> {
+        lock(&self.state.requests).clone()
+    }
+
+    /// How many requests were received so far.
+    pub fn request_count(&self) -> usize {
+        lock(&self.state.requests).len()
+    }
+
+    /// Forget the requests received so far.
+    pub fn reset_requests(&self) {
+        lock(&self.state.requests).clear();
+    }
+
+    /// Panic unless at least one request was received and every request
+    /// carried the credential headers of `auth`.
+    pub fn assert_auth(&self, auth: &Auth) {
+        let requests = self.received_requests();
+        assert!(!requests.is_empty(), "mock flags server received no requests");
+        for request in &requests {
+            let expected = [
+                ("X-Project-ID", Some(auth.project_id.as_str())),
+                ("X-Agent-ID", Some(auth.agent_id.as_str())),
+                ("X-Environment-ID", Some(auth.environment_id.as_str())),
+                ("X-Company-ID", auth.company_id.as_deref()),
+            ];
+            for (name, value) in expected {
+                assert_eq!(
+                    request.header(name),
+                    value,
+                    "request to {} has unexpected {} header",
+                    request.path,
+                    name
+                );
+            }
+        }
+    }
+}
+
+impl Drop for MockFlagsServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl MockState {
+    fn record(&self, path: &str, headers: HeaderMap) {
+        lock(&self.requests).push(ReceivedRequest {
+            path: path.to_string(),
+            headers,
+        });
+    }
+}
+
+async fn serve_flags(State(state): State<Arc<MockState>>, headers: HeaderMap) -> Response {
+    state.record("/flags", headers);
+    if let Some(status) = *lock(&state.failure) {
+        return status.into_response();
+    }
+    let body = serde_json::json!({
+        "intervalAllowed": *lock(&state.interval_allowed),
+        "flags": *lock(&state.flags),
+    });
+    ([(CONTENT_TYPE, "application/json")], body.to_string()).into_response()
+}
+
+async fn record_unknown(State(state): State<Arc<MockState>>, uri: axum::http::Uri, headers: HeaderMap) -> StatusCode {
+    state.record(uri.path(), headers);
+    StatusCode::NOT_FOUND
+}
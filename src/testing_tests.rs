@@ -0,0 +1,67 @@
+#[cfg(all(test, feature = "test-util"))]
+mod tests {
+    use crate::testing::MockFlagsServer;
+    use crate::Auth;
+
+    #[tokio::test]
+    async fn test_mock_server_serves_and_mutates_flags() {
+        let server = MockFlagsServer::start().await;
+        server.set_flag("new-checkout", true);
+        server.set_flag("dark-mode", false);
+
+        let client = server.client_builder().build().unwrap();
+        assert!(client.is("new-checkout").enabled().await);
+        assert!(!client.is("dark-mode").enabled().await);
+
+        server.set_flag("new-checkout", false);
+        server.set_flag("dark-mode", true);
+        assert!(server.remove_flag("dark-mode"));
+        assert!(!server.remove_flag("dark-mode"));
+        client.clear_cache().await.unwrap();
+        assert!(!client.is("new-checkout").enabled().await);
+        assert!(!client.is("dark-mode").enabled().await);
+        assert_eq!(server.request_count(), 2);
+        server.assert_auth(&MockFlagsServer::auth());
+    }
+
+    #[tokio::test]
+    async fn test_mock_server_failures_and_headers() {
+        let server = MockFlagsServer::start().await;
+        server.set_flag("new-checkout", true);
+        server.fail_with(503);
+
+        let auth = Auth {
+            company_id: Some("test-company".to_string()),
+            ..MockFlagsServer::auth()
+        };
+        let client = server
+            .client_builder()
+            .with_auth(auth.clone())
+            .with_max_retries(1)
+            .build()
+            .unwrap();
+        assert!(!client.is("new-checkout").enabled().await);
+
+        server.recover();
+        client.clear_cache().await.unwrap();
+        assert!(client.is("new-checkout").enabled().await);
+
+        let requests = server.received_requests();
+        assert!(requests.iter().all(|r| r.path == "/flags"));
+        assert_eq!(requests[0].header("X-Company-ID"), Some("test-company"));
+        server.assert_auth(&auth);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "unexpected X-Environment-ID header")]
+    async fn test_mock_server_assert_auth_rejects_other_credentials() {
+        let server = MockFlagsServer::start().await;
+        let client = server.client_builder().build().unwrap();
+        client.is("new-checkout").enabled().await;
+
+        server.assert_auth(&Auth {
+            environment_id: "production".to_string(),
+            ..MockFlagsServer::auth()
+        });
+    }
+}
@@ -1,11 +1,12 @@
-#[cfg(test)]
+#[cfg(all(test, feature = "remote"))]
+#[allow(clippy::module_inception)]
 mod tests {
     use std::env;
     use std::time::Duration;
 
     use tokio::time::sleep;
     use wiremock::{Mock, MockServer, ResponseTemplate};
-    use wiremock::matchers::{method, path};
+    use wiremock::matchers::{header, method, path};
 
     use crate::cache::{Cache, MemoryCache};
     use crate::{Auth, Client};
@@ -20,6 +21,8 @@ mod tests {
                 project_id: "test-project".to_string(),
                 agent_id: "test-agent".to_string(),
                 environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
             })
             .with_memory_cache()
             .build()
@@ -45,6 +48,8 @@ mod tests {
                 project_id: "test-project".to_string(),
                 agent_id: "test-agent".to_string(),
                 environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
             })
             .with_memory_cache()
             .build()
@@ -52,7 +57,7 @@ mod tests {
 
         assert_eq!(client.base_url, "https://test-api.example.com");
         assert_eq!(client.max_retries, 5);
-        assert!(client.auth.is_some());
+        assert!(client.auth.load().is_some());
     }
 
     #[tokio::test]
@@ -182,7 +187,7 @@ mod tests {
     #[tokio::test]
     async fn test_cache_refresh_simple() {
         // Create a memory cache
-        let mut cache = MemoryCache::new();
+        let cache = MemoryCache::new();
 
         // Initialize with a flag that's enabled
         let flags = vec![
@@ -191,6 +196,7 @@ mod tests {
                 details: crate::flag::Details {
                     name: "cache-test-flag".to_string(),
                     id: "123".to_string(),
+                    ..Default::default()
                 },
             },
         ];
@@ -216,6 +222,7 @@ mod tests {
                 details: crate::flag::Details {
                     name: "cache-test-flag".to_string(),
                     id: "123".to_string(),
+                    ..Default::default()
                 },
             },
         ];
@@ -250,8 +257,7 @@ mod tests {
         assert!(!enabled);
 
         // Verify circuit is open
-        let circuit_state = client.circuit_state.read().await;
-        assert!(!circuit_state.is_open);
+        assert!(!client.circuit_breaker.is_open());
     }
 
     #[tokio::test]
@@ -321,6 +327,8 @@ mod tests {
                 project_id: "test-project".to_string(),
                 agent_id: "test-agent".to_string(),
                 environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
             })
             .with_error_callback(move |_error| {
                 error_count_clone.fetch_add(1, Ordering::SeqCst);
@@ -337,7 +345,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_memory_cache() {
-        let mut cache = MemoryCache::new();
+        let cache = MemoryCache::new();
 
         // Initialize cache
         cache.init().await.unwrap();
@@ -354,6 +362,7 @@ mod tests {
                 details: crate::flag::Details {
                     name: "test-flag".to_string(),
                     id: "123".to_string(),
+                    ..Default::default()
                 },
             },
         ];
@@ -370,7 +379,4044 @@ mod tests {
         assert_eq!(all_flags.len(), 1);
         assert_eq!(all_flags[0].details.name, "test-flag");
 
+        // Listing shares the cached flags instead of copying them
+        let again = cache.get_all().await.unwrap();
+        assert!(std::sync::Arc::ptr_eq(&all_flags[0], &again[0]));
+
         // Test cache refresh timing
         assert!(!cache.should_refresh_cache().await);
     }
+
+    #[tokio::test]
+    async fn test_json_wire_format_is_default() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(header("accept", "application/json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {
+                        "enabled": true,
+                        "details": {
+                            "name": "json-flag",
+                            "id": "1"
+                        }
+                    }
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = create_test_client(&mock_server).await;
+        assert!(client.is("json-flag").enabled().await);
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[tokio::test]
+    async fn test_msgpack_wire_format() {
+        use crate::WireFormat;
+        use wiremock::matchers::header_regex;
+
+        let mock_server = MockServer::start().await;
+
+        let payload = rmp_serde::to_vec_named(&serde_json::json!({
+            "intervalAllowed": 60,
+            "flags": [
+                {
+                    "enabled": true,
+                    "details": {
+                        "name": "packed-flag",
+                        "id": "1"
+                    }
+                },
+                {
+                    "enabled": false,
+                    "details": {
+                        "name": "packed-off",
+                        "id": "2"
+                    }
+                }
+            ]
+        }))
+        .unwrap();
+
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(header_regex("accept", "^application/msgpack"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(payload, "application/msgpack"))
+            .mount(&mock_server)
+            .await;
+
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_wire_format(WireFormat::MessagePack)
+            .build()
+            .expect("Failed to build client");
+
+        assert!(client.is("packed-flag").enabled().await);
+        assert!(!client.is("packed-off").enabled().await);
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[tokio::test]
+    async fn test_msgpack_v2_skips_malformed_flags() {
+        use crate::WireFormat;
+
+        let mock_server = MockServer::start().await;
+        let payload = rmp_serde::to_vec_named(&serde_json::json!({
+            "meta": {"note": [1, 2, 3]},
+            "refreshInterval": 60,
+            "flags": [
+                {"key": "packed-v2", "id": "1", "enabled": true, "groups": ["new"]},
+                {"key": "broken", "id": "2", "enabled": "yes", "rules": [{"attribute": "plan"}]},
+                {"key": "after-broken", "id": "3", "enabled": true}
+            ]
+        }))
+        .unwrap();
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200)
+                .insert_header("X-API-Version", "2")
+                .set_body_raw(payload, "application/msgpack"))
+            .mount(&mock_server)
+            .await;
+
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_wire_format(WireFormat::MessagePack)
+            .build()
+            .expect("Failed to build client");
+
+        assert!(client.is("packed-v2").enabled().await);
+        assert!(client.is("after-broken").enabled().await);
+        assert_eq!(client.list().await.unwrap().len(), 2);
+        assert_eq!(client.quarantined_flags().len(), 1);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_export_formats() {
+        use crate::export::Format;
+
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {
+                        "enabled": false,
+                        "details": {
+                            "name": "zeta",
+                            "id": "2"
+                        }
+                    },
+                    {
+                        "enabled": true,
+                        "details": {
+                            "name": "alpha,beta",
+                            "id": "1"
+                        }
+                    }
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = create_test_client(&mock_server).await;
+        let _ = client.is("zeta").enabled().await;
+        let last_refresh = client.last_refresh().await.expect("cache was refreshed").to_rfc3339();
+
+        let json: serde_json::Value = serde_json::from_str(&client.export(Format::Json).await).unwrap();
+        assert_eq!(json, serde_json::json!([
+            {"name": "alpha,beta", "id": "1", "enabled": true, "source": "api", "last_refresh": last_refresh},
+            {"name": "zeta", "id": "2", "enabled": false, "source": "api", "last_refresh": last_refresh},
+        ]));
+
+        let csv = client.export(Format::Csv).await;
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "name,id,enabled,source,last_refresh");
+        assert_eq!(lines[1], format!("\"alpha,beta\",1,true,api,{}", last_refresh));
+        assert_eq!(lines[2], format!("zeta,2,false,api,{}", last_refresh));
+        assert_eq!(lines.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_flagd_http_backend() {
+        use crate::transport::flagd::{FlagdTransport, RESOLVE_ALL_PATH};
+
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path(RESOLVE_ALL_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "flags": {
+                    "new-checkout": {"reason": "STATIC", "variant": "on", "boolValue": true},
+                    "legacy-banner": {"reason": "STATIC", "variant": "off", "boolValue": false},
+                    "header-color": {"reason": "STATIC", "variant": "red", "stringValue": "red"}
+                }
+            })))
+            .mount(&mock_server)
+            .await;
+
+        // flagd needs no flags.gg credentials
+        let client = Client::builder()
+            .with_transport(FlagdTransport::http(&mock_server.uri()).unwrap())
+            .build()
+            .expect("Failed to build client");
+
+        assert!(client.is("new-checkout").enabled().await);
+        assert!(!client.is("legacy-banner").enabled().await);
+
+        let names: Vec<String> = client.list().await.unwrap().into_iter().map(|f| f.details.name.clone()).collect();
+        assert!(!names.contains(&"header-color".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_cache_snapshot_survives_refresh() {
+        let cache = MemoryCache::new();
+        let flag = |enabled| FeatureFlag {
+            enabled,
+            details: crate::flag::Details {
+                name: "snapshot-flag".to_string(),
+                id: "1".to_string(),
+                ..Default::default()
+            },
+        };
+
+        cache.refresh(&[flag(true)], 60).await.unwrap();
+        let before = cache.snapshot();
+
+        cache.refresh(&[flag(false)], 60).await.unwrap();
+
+        // A reader holding the old snapshot keeps a consistent view
+        assert!(before["snapshot-flag"].enabled);
+        assert!(!cache.snapshot()["snapshot-flag"].enabled);
+        assert_eq!(cache.get("snapshot-flag").await.unwrap(), (false, true));
+    }
+
+    #[tokio::test]
+    async fn test_coalesced_refresh_waits_for_in_flight_fetch() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200)
+                .set_delay(Duration::from_millis(200))
+                .set_body_json(serde_json::json!({
+                    "intervalAllowed": 60,
+                    "flags": [
+                        {
+                            "enabled": true,
+                            "details": {
+                                "name": "burst-flag",
+                                "id": "1"
+                            }
+                        }
+                    ]
+                })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_coalesced_refresh()
+            .build()
+            .expect("Failed to build client");
+
+        let handles: Vec<_> = (0..10)
+            .map(|_| {
+                let client = client.clone();
+                tokio::spawn(async move { client.is("burst-flag").enabled().await })
+            })
+            .collect();
+
+        for handle in handles {
+            assert!(handle.await.unwrap());
+        }
+    }
+
+    #[test]
+    fn test_normalize_name_borrows_lowercase_names() {
+        use std::borrow::Cow;
+        use crate::normalize_name;
+
+        assert!(matches!(normalize_name("already-lower"), Cow::Borrowed("already-lower")));
+        assert_eq!(normalize_name("Mixed-Case"), "mixed-case");
+        assert!(matches!(normalize_name("Mixed-Case"), Cow::Owned(_)));
+    }
+
+    #[tokio::test]
+    async fn test_mixed_case_evaluation() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {
+                        "enabled": true,
+                        "details": {
+                            "name": "New-Checkout",
+                            "id": "1"
+                        }
+                    }
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = create_test_client(&mock_server).await;
+        assert!(client.is("new-checkout").enabled().await);
+        assert!(client.is("NEW-CHECKOUT").enabled().await);
+        assert_eq!(client.get_multiple(&["New-Checkout"]).await.get("New-Checkout"), Some(&true));
+    }
+
+    #[cfg(feature = "sharded-cache")]
+    #[tokio::test]
+    async fn test_sharded_cache() {
+        use crate::cache::ShardedCache;
+
+        let cache = ShardedCache::new();
+        assert!(cache.should_refresh_cache().await);
+
+        let flag = |name: &str, enabled| FeatureFlag {
+            enabled,
+            details: crate::flag::Details {
+                name: name.to_string(),
+                id: name.to_string(),
+                ..Default::default()
+            },
+        };
+
+        cache.refresh(&[flag("kept", true), flag("removed", true)], 60).await.unwrap();
+        assert_eq!(cache.get("kept").await.unwrap(), (true, true));
+        assert!(!cache.should_refresh_cache().await);
+
+        cache.refresh(&[flag("kept", false), flag("added", true)], 60).await.unwrap();
+        assert_eq!(cache.get("kept").await.unwrap(), (false, true));
+        assert_eq!(cache.get("added").await.unwrap(), (true, true));
+        assert_eq!(cache.get("removed").await.unwrap(), (false, false));
+        assert_eq!(cache.get_all().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_local_flags_are_read_at_build_time() {
+        env::set_var("FLAGS_RELOADED_FLAG", "true");
+        let client = Client::builder()
+            .build()
+            .expect("Failed to build client");
+        env::remove_var("FLAGS_RELOADED_FLAG");
+
+        // The environment is not re-scanned on refresh
+        assert!(client.is("reloaded_flag").enabled().await);
+        client.refetch().await.unwrap();
+        assert!(client.is("reloaded_flag").enabled().await);
+
+        // ...until asked to
+        client.reload_local_flags().await;
+        assert!(!client.is("reloaded_flag").enabled().await);
+    }
+
+    #[test]
+    fn test_streaming_json_parser_handles_arbitrary_chunking() {
+        use crate::transport::json_stream::FlagsStreamParser;
+
+        let body = br#" {
+            "meta": {"note": "brackets ] and braces } in \"strings\"", "list": [1, {"a": []}]},
+            "flags": [
+                {"enabled": true, "details": {"name": "first", "id": "1"}},
+                {"details": {"id": "2", "name": "second"}, "enabled": false}
+            ],
+            "intervalAllowed": 30,
+            "trailing": null
+        } "#;
+
+        // Feed one byte at a time as the worst case for chunk boundaries
+        let mut parser = FlagsStreamParser::new();
+        for byte in body.iter() {
+            parser.feed(std::slice::from_ref(byte)).unwrap();
+        }
+        let resp = parser.finish().unwrap();
+        assert_eq!(resp.interval_allowed, 30);
+        assert_eq!(resp.flags.len(), 2);
+        assert_eq!(resp.flags[0].details.name, "first");
+        assert!(resp.flags[0].enabled);
+        assert_eq!(resp.flags[1].details.name, "second");
+        assert!(!resp.flags[1].enabled);
+
+        let mut parser = FlagsStreamParser::new();
+        parser.feed(br#"{"intervalAllowed": 5, "flags": []}"#).unwrap();
+        assert!(parser.finish().unwrap().flags.is_empty());
+    }
+
+    #[test]
+    fn test_streaming_json_parser_rejects_bad_payloads() {
+        use crate::transport::json_stream::FlagsStreamParser;
+
+        let parse = |body: &[u8]| {
+            let mut parser = FlagsStreamParser::new();
+            parser.feed(body).and_then(|_| parser.finish())
+        };
+
+        assert!(parse(br#"{"intervalAllowed": 60, "flags": [{"enabled": true"#).is_err());
+        assert!(parse(br#"{"intervalAllowed": 60}"#).is_err());
+        assert!(parse(br#"{"flags": []}"#).is_err());
+        assert!(parse(br#"[]"#).is_err());
+
+        // A malformed element is skipped rather than failing the payload
+        let resp = parse(br#"{"intervalAllowed": 60, "flags": [42, {"enabled": true, "details": {"name": "a", "id": "1"}}]}"#).unwrap();
+        assert_eq!(resp.flags.len(), 1);
+        assert_eq!(resp.skipped.len(), 1);
+        assert!(resp.skipped[0].starts_with("flag 0:"));
+    }
+
+    #[tokio::test]
+    async fn test_connection_pool_options() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {
+                        "enabled": true,
+                        "details": {
+                            "name": "pooled-flag",
+                            "id": "1"
+                        }
+                    }
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_pool_idle_timeout(Some(Duration::from_secs(300)))
+            .with_pool_max_idle_per_host(2)
+            .with_tcp_keepalive(Some(Duration::from_secs(30)))
+            .build()
+            .expect("Failed to build client");
+
+        assert!(client.is("pooled-flag").enabled().await);
+
+        // http2 prior knowledge only changes the client, building must still succeed
+        assert!(Client::builder().with_http2_prior_knowledge().build().is_ok());
+    }
+
+    #[test]
+    fn test_refresh_jitter_stays_within_bounds() {
+        use crate::jitter_interval;
+
+        assert_eq!(jitter_interval(60, 0), 60);
+        assert_eq!(jitter_interval(0, 50), 0);
+
+        let samples: Vec<i32> = (0..200).map(|_| jitter_interval(60, 10)).collect();
+        assert!(samples.iter().all(|&i| (54..=66).contains(&i)));
+        assert!(samples.iter().any(|&i| i != samples[0]), "jitter never varied");
+
+        assert!((0..100).all(|_| jitter_interval(1, 100) >= 1));
+        assert!(Client::builder().with_refresh_jitter(101).build().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_staleness_is_tracked_outside_the_cache() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        struct CountingCache {
+            inner: MemoryCache,
+            refreshes: Arc<AtomicUsize>,
+            staleness_checks: Arc<AtomicUsize>,
+        }
+
+        #[async_trait::async_trait]
+        impl Cache for CountingCache {
+            async fn get(&self, name: &str) -> Result<(bool, bool), Box<dyn std::error::Error + Send + Sync>> {
+                self.inner.get(name).await
+            }
+            async fn get_all(&self) -> Result<Vec<Arc<FeatureFlag>>, Box<dyn std::error::Error + Send + Sync>> {
+                self.inner.get_all().await
+            }
+            async fn refresh(&self, flags: &[FeatureFlag], interval_allowed: i32) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+                self.refreshes.fetch_add(1, Ordering::SeqCst);
+                self.inner.refresh(flags, interval_allowed).await
+            }
+            async fn should_refresh_cache(&self) -> bool {
+                self.staleness_checks.fetch_add(1, Ordering::SeqCst);
+                self.inner.should_refresh_cache().await
+            }
+            async fn init(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+                self.inner.init().await
+            }
+        }
+
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        let staleness_checks = Arc::new(AtomicUsize::new(0));
+        let client = Client::builder()
+            .with_cache(CountingCache {
+                inner: MemoryCache::new(),
+                refreshes: Arc::clone(&refreshes),
+                staleness_checks: Arc::clone(&staleness_checks),
+            })
+            .build()
+            .expect("Failed to build client");
+
+        for _ in 0..10 {
+            client.is("anything").enabled().await;
+        }
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+        assert_eq!(staleness_checks.load(Ordering::SeqCst), 0);
+
+        // Invalidation goes through the client's schedule too
+        client.reload_local_flags().await;
+        client.is("anything").enabled().await;
+        assert_eq!(refreshes.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pinned_flag_follows_refreshes() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "hot-flag", "id": "1"}}]
+            })))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": false, "details": {"name": "hot-flag", "id": "1"}}]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = create_test_client(&mock_server).await;
+        let pinned = client.pin("Hot-Flag").await;
+        let missing = client.pin("not-there").await;
+        assert_eq!(pinned.name(), "hot-flag");
+        assert!(pinned.is_enabled());
+        assert!(!missing.is_enabled());
+
+        client.refetch().await.unwrap();
+        assert!(!pinned.is_enabled());
+        assert!(!pinned.clone().is_enabled());
+
+        // Released handles are dropped from the registry on the next refresh
+        drop(missing);
+        client.refetch().await.unwrap();
+        assert_eq!(client.pinned.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refresh_schedule_uses_monotonic_clock() {
+        use crate::cache::RefreshSchedule;
+
+        let schedule = RefreshSchedule::new();
+        assert!(schedule.is_stale());
+
+        schedule.schedule(60);
+        assert!(!schedule.is_stale());
+
+        // Only monotonic time moves the schedule; wall-clock steps do not
+        tokio::time::advance(Duration::from_secs(59)).await;
+        assert!(!schedule.is_stale());
+        tokio::time::advance(Duration::from_secs(2)).await;
+        assert!(schedule.is_stale());
+
+        schedule.schedule(60);
+        schedule.invalidate();
+        assert!(schedule.is_stale());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_cooldown_uses_monotonic_clock() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use crate::circuit::CircuitBreaker;
+        use crate::transport::Transport;
+        use crate::{ApiResponse, FlagError};
+
+        struct CountingTransport(Arc<AtomicUsize>);
+
+        #[async_trait::async_trait]
+        impl Transport for CountingTransport {
+            async fn fetch_flags(&self, _auth: Option<&Auth>) -> Result<ApiResponse, FlagError> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(ApiResponse { interval_allowed: 60, flags: vec![], skipped: vec![], version: None })
+            }
+        }
+
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let breaker = Arc::new(crate::circuit::DefaultCircuitBreaker::new());
+        let client = Client::builder()
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_transport(CountingTransport(Arc::clone(&fetches)))
+            .with_circuit_breaker(Arc::clone(&breaker))
+            .build()
+            .expect("Failed to build client");
+
+        breaker.open();
+
+        client.refetch().await.unwrap();
+        assert_eq!(fetches.load(Ordering::SeqCst), 0);
+
+        tokio::time::advance(Duration::from_secs(11)).await;
+        client.refetch().await.unwrap();
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        assert!(!breaker.is_open());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failed_refresh_is_debounced() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use crate::transport::Transport;
+        use crate::{ApiResponse, FlagError};
+
+        struct CountingTransport(Arc<AtomicUsize>);
+
+        #[async_trait::async_trait]
+        impl Transport for CountingTransport {
+            async fn fetch_flags(&self, _auth: Option<&Auth>) -> Result<ApiResponse, FlagError> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(ApiResponse { interval_allowed: 60, flags: vec![], skipped: vec![], version: None })
+            }
+        }
+
+        // A cache that can never be refreshed leaves the client permanently stale
+        struct BrokenCache;
+
+        #[async_trait::async_trait]
+        impl Cache for BrokenCache {
+            async fn get(&self, _name: &str) -> Result<(bool, bool), Box<dyn std::error::Error + Send + Sync>> {
+                Ok((false, false))
+            }
+            async fn get_all(&self) -> Result<Vec<Arc<FeatureFlag>>, Box<dyn std::error::Error + Send + Sync>> {
+                Ok(vec![])
+            }
+            async fn refresh(&self, _flags: &[FeatureFlag], _interval_allowed: i32) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+                Err("read-only".into())
+            }
+            async fn should_refresh_cache(&self) -> bool {
+                true
+            }
+            async fn init(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+                Ok(())
+            }
+        }
+
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let client = Client::builder()
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_transport(CountingTransport(Arc::clone(&fetches)))
+            .with_cache(BrokenCache)
+            .build()
+            .expect("Failed to build client");
+
+        for _ in 0..5 {
+            client.is("anything").enabled().await;
+        }
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        tokio::time::advance(Duration::from_millis(300)).await;
+        client.is("anything").enabled().await;
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_flag_metadata_is_optional_and_surfaced() {
+        use crate::export::Format;
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": true, "details": {
+                        "name": "Checkout-V2", "id": "1",
+                        "description": "New checkout flow", "tags": ["payments", "web"],
+                        "owner": "payments-team", "createdAt": "2024-05-01T12:00:00Z"
+                    }},
+                    {"enabled": false, "details": {"name": "bare", "id": "2"}}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = create_test_client(&mock_server).await;
+        let mut flags = client.list().await.unwrap();
+        flags.sort_by(|a, b| a.details.name.cmp(&b.details.name));
+
+        assert_eq!(flags[0].details.name, "bare");
+        assert!(flags[0].details.description.is_none());
+        assert!(flags[0].details.tags.is_empty());
+
+        let details = &flags[1].details;
+        assert_eq!(details.name, "checkout-v2");
+        assert_eq!(details.description.as_deref(), Some("New checkout flow"));
+        assert_eq!(details.tags, vec!["payments", "web"]);
+        assert_eq!(details.owner.as_deref(), Some("payments-team"));
+        assert_eq!(details.created_at.unwrap().to_rfc3339(), "2024-05-01T12:00:00+00:00");
+
+        let json: serde_json::Value = serde_json::from_str(&client.export(Format::Json).await).unwrap();
+        assert_eq!(json[0].get("description"), None);
+        assert_eq!(json[1]["description"], "New checkout flow");
+        assert_eq!(json[1]["tags"], serde_json::json!(["payments", "web"]));
+        assert_eq!(json[1]["owner"], "payments-team");
+    }
+
+    #[tokio::test]
+    async fn test_malformed_flags_are_skipped_and_reported() {
+        use std::sync::{Arc, Mutex};
+
+        let body = serde_json::json!({
+            "intervalAllowed": 60,
+            "unknownTopLevel": {"ignored": true},
+            "flags": [
+                {"enabled": true, "details": {"name": "good", "id": "1", "futureField": 7}},
+                {"enabled": "yes", "details": {"name": "bad-type", "id": "2"}},
+                {"details": {"name": "no-enabled", "id": "3"}},
+                42,
+                {"enabled": false, "details": {"name": "also-good", "id": "4"}}
+            ]
+        });
+
+        // Buffered decoding (also used for MessagePack) is lenient too
+        let resp: crate::ApiResponse = serde_json::from_value(body.clone()).unwrap();
+        assert_eq!(resp.flags.len(), 2);
+        assert_eq!(resp.skipped.len(), 3);
+        assert!(resp.skipped[0].starts_with("flag 1:"));
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(&mock_server)
+            .await;
+
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let errors_clone = Arc::clone(&errors);
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_error_callback(move |e| errors_clone.lock().unwrap().push(e.to_string()))
+            .build()
+            .expect("Failed to build client");
+
+        client.refetch().await.unwrap();
+        assert!(client.is("good").enabled().await);
+        assert!(!client.is("also-good").enabled().await);
+        assert_eq!(client.list().await.unwrap().len(), 2);
+
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("Skipped 3 malformed flag(s)"), "{}", errors[0]);
+        assert!(errors[0].contains("flag 3:"), "{}", errors[0]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_deprecated_and_expired_flags_warn_throttled() {
+        use std::sync::{Arc, Mutex};
+        use crate::export::Format;
+        use crate::transport::Transport;
+        use crate::{ApiResponse, FlagError};
+
+        struct LifecycleTransport;
+
+        #[async_trait::async_trait]
+        impl Transport for LifecycleTransport {
+            async fn fetch_flags(&self, _auth: Option<&Auth>) -> Result<ApiResponse, FlagError> {
+                Ok(serde_json::from_value(serde_json::json!({
+                    "intervalAllowed": 3600,
+                    "flags": [
+                        {"enabled": true, "details": {"name": "old-banner", "id": "1", "deprecated": true}},
+                        {"enabled": true, "details": {"name": "launch-promo", "id": "2", "expiresAt": "2020-01-01T00:00:00Z"}},
+                        {"enabled": true, "details": {"name": "future-promo", "id": "3", "expiresAt": "2999-01-01T00:00:00Z"}},
+                        {"enabled": true, "details": {"name": "current", "id": "4"}}
+                    ]
+                })).unwrap())
+            }
+        }
+
+        let warnings = Arc::new(Mutex::new(Vec::new()));
+        let warnings_clone = Arc::clone(&warnings);
+        let client = Client::builder()
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_transport(LifecycleTransport)
+            .with_error_callback(move |e| {
+                if let FlagError::DeprecatedFlag(message) = e {
+                    warnings_clone.lock().unwrap().push(message.clone());
+                }
+            })
+            .build()
+            .expect("Failed to build client");
+
+        for _ in 0..3 {
+            for name in ["old-banner", "launch-promo", "future-promo", "current"] {
+                assert!(client.is(name).enabled().await);
+            }
+        }
+        {
+            let warnings = warnings.lock().unwrap();
+            assert_eq!(warnings.len(), 2);
+            assert!(warnings[0].contains("'old-banner' is deprecated"));
+            assert!(warnings[1].contains("'launch-promo' is expired at 2020-01-01"));
+        }
+
+        tokio::time::advance(crate::lifecycle::WARNING_INTERVAL).await;
+        client.get_multiple(&["old-banner", "current"]).await;
+        assert_eq!(warnings.lock().unwrap().len(), 3);
+
+        let flags = client.list().await.unwrap();
+        let expired: Vec<&str> = flags.iter().filter(|f| f.is_expired()).map(|f| f.details.name.as_str()).collect();
+        assert_eq!(expired, vec!["launch-promo"]);
+
+        let json: serde_json::Value = serde_json::from_str(&client.export(Format::Json).await).unwrap();
+        assert_eq!(json[0]["name"], "current");
+        assert_eq!(json[0].get("deprecated"), None);
+        assert_eq!(json[1]["expires_at"], "2999-01-01T00:00:00+00:00");
+        assert_eq!(json[1].get("expired"), None);
+        assert_eq!(json[2]["name"], "launch-promo");
+        assert_eq!(json[2]["expired"], true);
+        assert_eq!(json[3]["deprecated"], true);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_flag_groups() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": true, "details": {"name": "new-cart", "id": "1", "groups": ["Checkout-Redesign"]}},
+                    {"enabled": true, "details": {"name": "new-payment", "id": "2", "groups": ["checkout-redesign", "payments"]}},
+                    {"enabled": false, "details": {"name": "new-receipt", "id": "3", "groups": ["checkout-redesign"]}},
+                    {"enabled": true, "details": {"name": "ungrouped", "id": "4"}}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        env::set_var("FLAGS_NEW_RECEIPT", "true");
+        let client = create_test_client(&mock_server).await;
+        env::remove_var("FLAGS_NEW_RECEIPT");
+
+        let group = client.group("checkout-redesign");
+        assert_eq!(group.flags().await, vec!["new-cart", "new-payment", "new-receipt"]);
+        // The local override keeps new-receipt in its group and turns it on
+        assert!(group.all_enabled().await);
+        assert!(group.any_enabled().await);
+
+        let groups = client.groups().await;
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups["payments"], vec!["new-payment"]);
+
+        let unknown = client.group("nope");
+        assert!(unknown.flags().await.is_empty());
+        assert!(!unknown.all_enabled().await);
+        assert!(!unknown.any_enabled().await);
+    }
+
+    #[tokio::test]
+    async fn test_company_id_header_and_tenant_override() {
+        use crate::context::EvaluationContext;
+
+        let mock_server = MockServer::start().await;
+        let flags = |enabled: bool| serde_json::json!({
+            "intervalAllowed": 60,
+            "flags": [{"enabled": enabled, "details": {"name": "tenant-feature", "id": "1"}}]
+        });
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(header("X-Company-ID", "acme"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(flags(true)))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(header("X-Company-ID", "globex"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(flags(false)))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: Some("globex".to_string()),
+                agent_secret: None,
+            })
+            .build()
+            .expect("Failed to build client");
+
+        assert!(!client.is("tenant-feature").enabled().await);
+
+        let acme = EvaluationContext::new().with_tenant("acme");
+        assert!(client.is("tenant-feature").with_context(&acme).enabled().await);
+        // Tenant flags are cached per tenant
+        assert!(client.is("tenant-feature").with_context(&acme).enabled().await);
+
+        // Overriding with the client's own company uses the client directly
+        let globex = EvaluationContext::new().with_tenant("globex");
+        assert!(!client.is("tenant-feature").with_context(&globex).enabled().await);
+
+        let empty_company = Client::builder()
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: Some(" ".to_string()),
+                agent_secret: None,
+            })
+            .build();
+        assert!(empty_company.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_delta_refresh() {
+        use wiremock::matchers::{query_param, query_param_is_missing};
+
+        let flag = |name: &str, enabled: bool| serde_json::json!({"enabled": enabled, "details": {"name": name, "id": name}});
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(query_param("since", "v1"))
+            .respond_with(ResponseTemplate::new(226).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "version": "v2",
+                "upserted": [flag("charlie", true)],
+                "removed": ["Bravo"]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(query_param("since", "v2"))
+            .respond_with(ResponseTemplate::new(304))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(query_param_is_missing("since"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "version": "v1",
+                "flags": [flag("alpha", true), flag("bravo", true), flag("charlie", false)]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_delta_refresh()
+            .build()
+            .expect("Failed to build client");
+
+        client.refetch().await.unwrap();
+        assert!(client.is("bravo").enabled().await);
+        assert!(!client.is("charlie").enabled().await);
+
+        client.refetch().await.unwrap();
+        assert!(client.is("alpha").enabled().await);
+        assert!(!client.is("bravo").enabled().await);
+        assert!(client.is("charlie").enabled().await);
+        assert_eq!(client.list().await.unwrap().len(), 2);
+
+        // Not modified keeps the current set
+        client.refetch().await.unwrap();
+        assert!(client.is("charlie").enabled().await);
+        mock_server.verify().await;
+
+        // A version the server no longer knows falls back to a full fetch
+        mock_server.reset().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(query_param("since", "v2"))
+            .respond_with(ResponseTemplate::new(410))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(query_param_is_missing("since"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [flag("delta", true)]
+            })))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        client.refetch().await.unwrap();
+        assert!(client.is("delta").enabled().await);
+        assert!(!client.is("alpha").enabled().await);
+
+        // Without a version from the server, the next refresh is a full fetch again
+        client.refetch().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_paginated_flags_are_assembled() {
+        use wiremock::matchers::{query_param, query_param_is_missing};
+
+        let flag = |name: &str| serde_json::json!({"enabled": true, "details": {"name": name, "id": name}});
+        let mock_server = MockServer::start().await;
+        // Page 1 links to page 2 with a Link header, page 2 to page 3 with a body cursor
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(query_param_is_missing("page"))
+            .and(query_param_is_missing("cursor"))
+            .respond_with(ResponseTemplate::new(200)
+                .insert_header("Link", "</flags?page=2>; rel=\"next\", </flags?page=3>; rel=\"last\"")
+                .set_body_json(serde_json::json!({"intervalAllowed": 30, "flags": [flag("one")]})))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(query_param("page", "2"))
+            .and(header("X-Project-ID", "test-project"))
+            .respond_with(ResponseTemplate::new(200)
+                .set_body_json(serde_json::json!({"intervalAllowed": 30, "flags": [flag("two")], "next": "abc"})))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(query_param("cursor", "abc"))
+            .respond_with(ResponseTemplate::new(200)
+                .set_body_json(serde_json::json!({"intervalAllowed": 30, "flags": [flag("three")], "next": null})))
+            .mount(&mock_server)
+            .await;
+
+        let client = create_test_client(&mock_server).await;
+        for name in ["one", "two", "three"] {
+            assert!(client.is(name).enabled().await, "{} missing", name);
+        }
+        assert_eq!(client.list().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_pagination_is_capped() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200)
+                .set_body_json(serde_json::json!({"intervalAllowed": 30, "flags": [], "next": "again"})))
+            .mount(&mock_server)
+            .await;
+
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_max_retries(1)
+            .build()
+            .expect("Failed to build client");
+
+        let err = client.refetch().await.unwrap_err();
+        assert!(err.to_string().contains("exceeded 100 pages"), "{}", err);
+        assert!(err.request_id().is_some());
+        assert_eq!(mock_server.received_requests().await.unwrap().len(), crate::transport::MAX_PAGES);
+    }
+
+    #[tokio::test]
+    async fn test_next_page_on_another_origin_is_not_followed() {
+        let mock_server = MockServer::start().await;
+        let other_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200)
+                .insert_header("Link", format!("<{}/flags?page=2>; rel=\"next\"", other_server.uri()))
+                .set_body_json(serde_json::json!({"intervalAllowed": 30, "flags": []})))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200)
+                .set_body_json(serde_json::json!({"intervalAllowed": 30, "flags": []})))
+            .mount(&other_server)
+            .await;
+
+        let client = create_test_client(&mock_server).await;
+        let err = client.refetch().await.unwrap_err();
+        assert!(err.to_string().contains("another origin"), "{}", err);
+        assert!(err.request_id().is_some());
+        assert!(other_server.received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_api_version_negotiation() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(header("X-API-Version", "2"))
+            .respond_with(ResponseTemplate::new(200)
+                .insert_header("X-API-Version", "2")
+                .set_body_json(serde_json::json!({
+                    "refreshInterval": 60,
+                    "flags": [
+                        {"key": "V2-Flag", "id": "1", "enabled": true, "owner": "core", "groups": ["new"]},
+                        {"key": "broken", "enabled": true}
+                    ]
+                })))
+            .mount(&mock_server)
+            .await;
+
+        let client = create_test_client(&mock_server).await;
+        assert!(client.is("v2-flag").enabled().await);
+        let flags = client.list().await.unwrap();
+        assert_eq!(flags.len(), 1);
+        assert_eq!(flags[0].details.owner.as_deref(), Some("core"));
+        assert_eq!(client.group("new").flags().await, vec!["v2-flag"]);
+
+        let unsupported = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200)
+                .insert_header("X-API-Version", "9")
+                .set_body_json(serde_json::json!({})))
+            .mount(&unsupported)
+            .await;
+
+        let client = Client::builder()
+            .with_base_url(&unsupported.uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_max_retries(1)
+            .build()
+            .expect("Failed to build client");
+        let err = client.refetch().await.unwrap_err();
+        assert!(err.to_string().contains("Unsupported API version: 9"), "{}", err);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_builder_from_env() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(header("X-Project-ID", "env-project"))
+            .and(header("X-Company-ID", "env-company"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "from-env", "id": "1"}}]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        env::set_var("FLAGS_GG_BASE_URL", mock_server.uri());
+        env::set_var("FLAGS_GG_PROJECT_ID", "env-project");
+        env::set_var("FLAGS_GG_AGENT_ID", "env-agent");
+        env::set_var("FLAGS_GG_ENVIRONMENT_ID", "env-environment");
+        env::set_var("FLAGS_GG_COMPANY_ID", "env-company");
+        env::set_var("FLAGS_GG_TIMEOUT_SECS", "5");
+        env::set_var("FLAGS_GG_MAX_RETRIES", "1");
+        env::set_var("FLAGS_GG_CACHE", "memory");
+        env::set_var("FLAGS_GG_COALESCE_REFRESH", "true");
+
+        let client = crate::ClientBuilder::from_env().unwrap().build().unwrap();
+        assert!(client.is("from-env").enabled().await);
+        // Configuration variables never become local flags
+        assert!(!client.is("gg_coalesce_refresh").enabled().await);
+
+        env::set_var("FLAGS_GG_TIMEOUT_SECS", "soon");
+        match crate::ClientBuilder::from_env() {
+            Err(crate::FlagError::BuilderError(msg)) => assert!(msg.contains("FLAGS_GG_TIMEOUT_SECS") && msg.contains("soon")),
+            other => panic!("expected a builder error, got {:?}", other.map(|_| ())),
+        }
+        env::remove_var("FLAGS_GG_TIMEOUT_SECS");
+
+        env::set_var("FLAGS_GG_CACHE", "redis");
+        assert!(matches!(crate::ClientBuilder::from_env(), Err(crate::FlagError::BuilderError(msg)) if msg.contains("FLAGS_GG_CACHE")));
+        env::remove_var("FLAGS_GG_CACHE");
+
+        env::remove_var("FLAGS_GG_AGENT_ID");
+        match crate::ClientBuilder::from_env() {
+            Err(crate::FlagError::BuilderError(msg)) => {
+                assert!(msg.contains("FLAGS_GG_AGENT_ID"));
+                assert!(!msg.contains("FLAGS_GG_PROJECT_ID"));
+            }
+            other => panic!("expected a builder error, got {:?}", other.map(|_| ())),
+        }
+
+        for var in ["FLAGS_GG_BASE_URL", "FLAGS_GG_PROJECT_ID", "FLAGS_GG_ENVIRONMENT_ID", "FLAGS_GG_COMPANY_ID", "FLAGS_GG_MAX_RETRIES", "FLAGS_GG_COALESCE_REFRESH"] {
+            env::remove_var(var);
+        }
+        assert!(crate::ClientBuilder::from_env().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_base_url_with_path_prefix() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags-gg/api/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "prefixed", "id": "1"}}]
+            })))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        for base_url in [format!("{}/flags-gg/api", mock_server.uri()), format!("{}/flags-gg/api/", mock_server.uri())] {
+            let client = Client::builder()
+                .with_base_url(&base_url)
+                .with_auth(Auth {
+                    project_id: "test-project".to_string(),
+                    agent_id: "test-agent".to_string(),
+                    environment_id: "test-environment".to_string(),
+                    company_id: None,
+                    agent_secret: None,
+                })
+                .build()
+                .unwrap();
+            assert!(client.is("prefixed").enabled().await);
+        }
+    }
+
+    #[test]
+    fn test_invalid_base_url_is_rejected_at_build() {
+        for base_url in ["not a url", "ftp://flags.example.com", "https://flags.example.com/api?x=1", "/relative"] {
+            match Client::builder().with_base_url(base_url).build() {
+                Err(crate::FlagError::BuilderError(msg)) => assert!(msg.contains("Invalid base URL"), "{}", msg),
+                other => panic!("expected {} to be rejected, got {:?}", base_url, other.map(|_| ())),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_user_agent_names_sdk_and_app() {
+        let mock_server = MockServer::start().await;
+        let sdk = concat!("flags-rs/", env!("CARGO_PKG_VERSION"));
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(header("User-Agent", sdk))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "sdk-only", "id": "1"}}]
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(header("User-Agent", format!("{} checkout-service/3.2.1", sdk).as_str()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "with-app", "id": "2"}}]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let auth = Auth {
+            project_id: "test-project".to_string(),
+            agent_id: "test-agent".to_string(),
+            environment_id: "test-environment".to_string(),
+            company_id: None,
+            agent_secret: None,
+        };
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(auth.clone())
+            .build()
+            .unwrap();
+        assert!(client.is("sdk-only").enabled().await);
+
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(auth.clone())
+            .with_app_identifier("checkout-service/3.2.1")
+            .build()
+            .unwrap();
+        assert!(client.is("with-app").enabled().await);
+
+        let result = Client::builder().with_auth(auth).with_app_identifier("bad\napp").build();
+        assert!(matches!(result, Err(crate::FlagError::BuilderError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_request_id_is_sent_and_reported_on_errors() {
+        use std::sync::{Arc, Mutex};
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(header("X-Request-ID", "trace-1"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&mock_server)
+            .await;
+
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let reported_clone = reported.clone();
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_max_retries(1)
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-environment".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_request_id_generator(|| "trace-1".to_string())
+            .with_error_callback(move |e| {
+                reported_clone.lock().unwrap().push((e.request_id().map(str::to_string), e.to_string()));
+            })
+            .build()
+            .unwrap();
+
+        assert!(!client.is("anything").enabled().await);
+        let reported = reported.lock().unwrap();
+        let (request_id, message) = &reported[0];
+        assert_eq!(request_id.as_deref(), Some("trace-1"));
+        assert!(message.contains("503") && message.contains("trace-1"), "{}", message);
+    }
+
+    #[test]
+    fn test_default_request_ids_are_unique_uuids() {
+        let ids: Vec<String> = (0..100).map(|_| crate::transport::http::random_request_id()).collect();
+        for id in &ids {
+            assert_eq!(id.len(), 36);
+            assert_eq!(&id[14..15], "4");
+        }
+        let unique: std::collections::HashSet<_> = ids.iter().collect();
+        assert_eq!(unique.len(), ids.len());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refresh_interval_is_clamped() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use crate::transport::Transport;
+        use crate::{ApiResponse, FlagError};
+
+        struct HammeringTransport(Arc<AtomicUsize>);
+
+        #[async_trait::async_trait]
+        impl Transport for HammeringTransport {
+            async fn fetch_flags(&self, _auth: Option<&Auth>) -> Result<ApiResponse, FlagError> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(ApiResponse { interval_allowed: 1, flags: vec![], skipped: vec![], version: None })
+            }
+        }
+
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let client = Client::builder()
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_transport(HammeringTransport(Arc::clone(&fetches)))
+            .with_min_refresh_interval(Duration::from_secs(30))
+            .build()
+            .expect("Failed to build client");
+
+        client.is("anything").enabled().await;
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+        client.is("anything").enabled().await;
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        tokio::time::advance(Duration::from_secs(26)).await;
+        client.is("anything").enabled().await;
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_refresh_interval_bounds() {
+        let bounds = crate::RefreshInterval { min: Some(30), max: Some(300), fixed: None, ..Default::default() };
+        assert_eq!(bounds.resolve(1, 0), 30);
+        assert_eq!(bounds.resolve(60, 0), 60);
+        assert_eq!(bounds.resolve(3600, 0), 300);
+        for _ in 0..50 {
+            assert!((30..=300).contains(&bounds.resolve(300, 50)));
+        }
+
+        let fixed = crate::RefreshInterval { min: None, max: None, fixed: Some(120), ..Default::default() };
+        assert_eq!(fixed.resolve(1, 0), 120);
+
+        let zero = crate::RefreshInterval::default();
+        assert_eq!(zero.resolve(0, 50), 1);
+        assert_eq!(zero.resolve(-5, 0), 1);
+        let forever = crate::RefreshInterval { zero: crate::ZeroInterval::CacheForever, ..Default::default() };
+        assert_eq!(forever.resolve(0, 50), i32::MAX);
+        let bounded = crate::RefreshInterval { max: Some(300), ..forever };
+        assert_eq!(bounded.resolve(-1, 0), 300);
+
+        let result = Client::builder()
+            .with_min_refresh_interval(Duration::from_secs(600))
+            .with_max_refresh_interval(Duration::from_secs(60))
+            .build();
+        assert!(matches!(result, Err(crate::FlagError::BuilderError(_))));
+        let result = Client::builder().with_refresh_interval(Duration::ZERO).build();
+        assert!(matches!(result, Err(crate::FlagError::BuilderError(_))));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_custom_env_prefix() {
+        env::set_var("MYAPP_FF_PREFIXED_FLAG", "true");
+        env::set_var("FLAGS_PREFIXED_FLAG", "false");
+        env::set_var("FLAGS_DEFAULT_ONLY", "true");
+
+        let client = Client::builder().with_env_prefix("MYAPP_FF_").build().unwrap();
+        assert!(client.is("prefixed_flag").enabled().await);
+        assert!(client.is("prefixed-flag").enabled().await);
+        assert!(!client.is("default_only").enabled().await);
+
+        let details = client.list().await.unwrap();
+        assert!(!details.is_empty());
+        assert!(details.iter().all(|f| f.source() == crate::flag::FlagSource::Local));
+
+        assert!(Client::builder().with_env_prefix("").build().is_err());
+
+        env::remove_var("MYAPP_FF_PREFIXED_FLAG");
+        env::remove_var("FLAGS_PREFIXED_FLAG");
+        env::remove_var("FLAGS_DEFAULT_ONLY");
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_local_flag_boolean_parsing() {
+        use std::sync::{Arc, Mutex};
+
+        for (value, expected) in [("TRUE", true), ("1", true), ("yes", true), (" On ", true), ("False", false), ("0", false), ("off", false)] {
+            env::set_var("FLAGS_PARSED_FLAG", value);
+            let client = Client::builder().build().unwrap();
+            assert_eq!(client.is("parsed_flag").enabled().await, expected, "value {:?}", value);
+        }
+
+        env::set_var("FLAGS_PARSED_FLAG", "maybe");
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let reported_clone = reported.clone();
+        let client = Client::builder()
+            .with_error_callback(move |e| reported_clone.lock().unwrap().push(e.to_string()))
+            .build()
+            .unwrap();
+        assert!(!client.is("parsed_flag").enabled().await);
+        {
+            let reported = reported.lock().unwrap();
+            assert_eq!(reported.len(), 1);
+            assert!(reported[0].contains("FLAGS_PARSED_FLAG") && reported[0].contains("maybe"), "{}", reported[0]);
+        }
+
+        client.reload_local_flags().await;
+        assert_eq!(reported.lock().unwrap().len(), 2);
+
+        env::remove_var("FLAGS_PARSED_FLAG");
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_local_flag_precedence() {
+        use crate::LocalFlagPrecedence;
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": false, "details": {"name": "shared", "id": "1"}},
+                    {"enabled": true, "details": {"name": "api_only", "id": "2"}}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+        env::set_var("FLAGS_SHARED", "true");
+        env::set_var("FLAGS_LOCAL_ONLY", "true");
+
+        for (precedence, shared, local_only) in [
+            (LocalFlagPrecedence::LocalOverridesApi, true, true),
+            (LocalFlagPrecedence::ApiOverridesLocal, false, true),
+            (LocalFlagPrecedence::LocalOnlyIfApiMissing, false, false),
+        ] {
+            let client = Client::builder()
+                .with_base_url(&mock_server.uri())
+                .with_auth(Auth {
+                    project_id: "test-project".to_string(),
+                    agent_id: "test-agent".to_string(),
+                    environment_id: "test-environment".to_string(),
+                    company_id: None,
+                    agent_secret: None,
+                })
+                .with_local_precedence(precedence)
+                .build()
+                .unwrap();
+            assert_eq!(client.is("shared").enabled().await, shared, "{:?}", precedence);
+            assert_eq!(client.is("local_only").enabled().await, local_only, "{:?}", precedence);
+            assert!(client.is("api_only").enabled().await, "{:?}", precedence);
+        }
+
+        // Without the API, local flags are served under every policy
+        let client = Client::builder()
+            .with_local_precedence(LocalFlagPrecedence::LocalOnlyIfApiMissing)
+            .build()
+            .unwrap();
+        assert!(client.is("shared").enabled().await);
+
+        env::remove_var("FLAGS_SHARED");
+        env::remove_var("FLAGS_LOCAL_ONLY");
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_builder_presets() {
+        env::set_var("FLAGS_PRESET_FLAG", "true");
+        let development = crate::ClientBuilder::development();
+        assert!(development.json_log_sink.is_some() && development.missing_flag_callback.is_some());
+        let client = development.build().unwrap();
+        assert!(client.is("preset_flag").enabled().await);
+        env::remove_var("FLAGS_PRESET_FLAG");
+
+        match crate::ClientBuilder::production().build() {
+            Err(crate::FlagError::BuilderError(msg)) => assert!(msg.contains("Credentials are required")),
+            other => panic!("expected a builder error, got {:?}", other.map(|_| ())),
+        }
+        let client = crate::ClientBuilder::production()
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-environment".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .build();
+        assert!(client.is_ok());
+
+        // Presets stay overridable
+        assert!(crate::ClientBuilder::production().with_auth_required(false).build().is_ok());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_typed_builder_requires_auth_or_offline() {
+        env::set_var("FLAGS_TYPED_FLAG", "true");
+        let client = Client::typed_builder().offline().with_max_retries(1).build().unwrap();
+        assert!(client.is("typed_flag").enabled().await);
+        env::remove_var("FLAGS_TYPED_FLAG");
+
+        let client = Client::typed_builder()
+            .with_max_retries(1)
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-environment".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .build()
+            .unwrap();
+        assert!(client.debug_info().contains("test-project"));
+    }
+
+    #[test]
+    fn test_client_config_and_debug() {
+        use std::sync::Arc;
+
+        let client = Client::builder()
+            .with_base_url("https://flags.example.com")
+            .with_max_retries(5)
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-environment".to_string(),
+                company_id: None,
+                agent_secret: Some("top-secret".to_string()),
+            })
+            .with_memory_cache()
+            .build()
+            .unwrap();
+
+        let config = client.config();
+        assert_eq!(config.base_url, "https://flags.example.com");
+        assert_eq!(config.max_retries, 5);
+        assert_eq!(config.cache_type, "flags_rs::cache::MemoryCache");
+        assert!(config.authenticated);
+        assert_eq!(config.environment_id.as_deref(), Some("test-environment"));
+        assert!(config.features.contains(&"remote"));
+
+        let debug = format!("{:?}", client);
+        assert!(debug.contains("https://flags.example.com"), "{}", debug);
+        assert!(!debug.contains("top-secret"), "{}", debug);
+
+        struct NullCache;
+        #[async_trait::async_trait]
+        impl Cache for NullCache {
+            async fn get(&self, _: &str) -> Result<(bool, bool), Box<dyn std::error::Error + Send + Sync>> {
+                Ok((false, false))
+            }
+            async fn get_all(&self) -> Result<Vec<Arc<FeatureFlag>>, Box<dyn std::error::Error + Send + Sync>> {
+                Ok(Vec::new())
+            }
+            async fn refresh(&self, _: &[FeatureFlag], _: i32) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+                Ok(())
+            }
+            async fn should_refresh_cache(&self) -> bool {
+                true
+            }
+            async fn init(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+                Ok(())
+            }
+        }
+        let client = Client::builder().with_cache(NullCache).build().unwrap();
+        assert!(client.config().cache_type.ends_with("NullCache"));
+        assert!(!client.config().authenticated);
+        assert_eq!(client.to_builder().build().unwrap().config().cache_type, client.config().cache_type);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_global_client() {
+        // The only test that installs the process-wide client
+        assert!(crate::try_global().is_none());
+        env::set_var("FLAGS_GLOBAL_FLAG", "true");
+        assert!(!crate::enabled("global_flag").await);
+
+        crate::init(Client::builder().build().unwrap()).unwrap();
+        assert!(crate::enabled("global_flag").await);
+        assert!(crate::global().is("global-flag").enabled().await);
+        assert!(crate::init(Client::builder().build().unwrap()).is_err());
+
+        env::remove_var("FLAGS_GLOBAL_FLAG");
+    }
+
+    #[tokio::test]
+    async fn test_custom_circuit_breaker() {
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use crate::circuit::CircuitBreaker;
+
+        #[derive(Default)]
+        struct SharedBreaker {
+            open: AtomicBool,
+            failures: AtomicUsize,
+        }
+
+        impl CircuitBreaker for SharedBreaker {
+            fn allow_request(&self) -> bool {
+                !self.open.load(Ordering::SeqCst)
+            }
+            fn record_success(&self) {}
+            fn record_failure(&self) {
+                self.failures.fetch_add(1, Ordering::SeqCst);
+                self.open.store(true, Ordering::SeqCst);
+            }
+            fn is_open(&self) -> bool {
+                self.open.load(Ordering::SeqCst)
+            }
+        }
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let breaker = Arc::new(SharedBreaker::default());
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_max_retries(1)
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-environment".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_circuit_breaker(Arc::clone(&breaker))
+            .build()
+            .unwrap();
+
+        assert!(client.refetch().await.is_err());
+        assert_eq!(breaker.failures.load(Ordering::SeqCst), 1);
+        // The open breaker keeps further refreshes away from the API
+        client.refetch().await.unwrap();
+        assert!(client.circuit_breaker.is_open());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_evaluation_memo() {
+        use crate::context::EvaluationMemo;
+
+        env::set_var("FLAGS_MEMO_FLAG", "true");
+        let client = Client::builder().build().unwrap();
+        client.refetch().await.unwrap();
+        let memo = EvaluationMemo::new();
+        assert!(client.is("memo_flag").with_memo(&memo).enabled().await);
+
+        // Change the cache behind the client's back: the memo still answers
+        let disabled = FeatureFlag {
+            enabled: false,
+            details: crate::flag::Details {
+                name: "memo_flag".to_string(),
+                id: "1".to_string(),
+                ..Default::default()
+            },
+        };
+        client.cache.refresh(&[disabled], 60).await.unwrap();
+        assert!(!client.is("memo_flag").enabled().await);
+        assert!(client.is("memo_flag").with_memo(&memo).enabled().await);
+        let counts = &client.evaluation_counts()["memo-flag"];
+        assert_eq!((counts.evaluated, counts.enabled), (3, 2));
+
+        // A refresh invalidates memoized results
+        env::set_var("FLAGS_MEMO_FLAG", "false");
+        client.reload_local_flags().await;
+        client.refetch().await.unwrap();
+        assert!(!client.is("memo_flag").with_memo(&memo).enabled().await);
+
+        memo.clear();
+        env::remove_var("FLAGS_MEMO_FLAG");
+    }
+
+    #[cfg(feature = "macros")]
+    #[tokio::test]
+    #[serial]
+    async fn test_feature_gated_macro() {
+        use crate::feature_gated;
+
+        #[feature_gated("gated-pricing", fallback = "old_price", client = "client")]
+        async fn new_price(client: &Client, amount: u32) -> u32 {
+            amount * 2
+        }
+
+        async fn old_price(_client: &Client, amount: u32) -> u32 {
+            amount
+        }
+
+        struct Checkout {
+            flags: Client,
+        }
+
+        impl Checkout {
+            #[feature_gated("gated-pricing", fallback = "old_total", client = "self.flags")]
+            async fn new_total(&self, amount: u32) -> u32 {
+                amount + 1
+            }
+
+            async fn old_total(&self, amount: u32) -> u32 {
+                amount
+            }
+        }
+
+        let client = Client::builder().build().unwrap();
+        assert_eq!(new_price(&client, 10).await, 10);
+
+        env::set_var("FLAGS_GATED_PRICING", "true");
+        let client = Client::builder().build().unwrap();
+        assert_eq!(new_price(&client, 10).await, 20);
+        let checkout = Checkout { flags: client };
+        assert_eq!(checkout.new_total(10).await, 11);
+        env::remove_var("FLAGS_GATED_PRICING");
+    }
+
+    #[test]
+    fn test_manifest_codegen() {
+        let manifest = r#"{"flags": [
+            {"enabled": true, "details": {"name": "new-checkout", "id": "1", "description": "Checkout v2"}},
+            {"name": "3d-preview", "id": "2"}
+        ]}"#;
+        let code = crate::codegen::render(manifest, "flags.manifest.json").unwrap();
+        assert!(code.contains("/// Checkout v2\n    pub const NEW_CHECKOUT: ::flags_rs::flag::FlagKey = ::flags_rs::flag::FlagKey::new(\"new-checkout\");"));
+        assert!(code.contains("pub const _3D_PREVIEW"));
+        assert!(code.contains("pub const ALL: &[::flags_rs::flag::FlagKey] = &[_3D_PREVIEW, NEW_CHECKOUT, ];"));
+
+        let clash = r#"[{"name": "a-b"}, {"name": "a_b"}]"#;
+        assert!(crate::codegen::render(clash, "m").is_err());
+        assert!(crate::codegen::render(r#"[{"id": "1"}]"#, "m").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_check_manifest_reports_drift() {
+        use crate::flag::FlagKey;
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": true, "details": {"name": "new-checkout", "id": "1"}},
+                    {"enabled": true, "details": {"name": "untracked", "id": "2"}}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+        let client = create_test_client(&mock_server).await;
+
+        const NEW_CHECKOUT: FlagKey = FlagKey::new("new-checkout");
+        const REMOVED: FlagKey = FlagKey::new("removed");
+        assert!(client.is(&NEW_CHECKOUT).enabled().await);
+
+        let drift = client.check_manifest(&[NEW_CHECKOUT, REMOVED]).await;
+        assert_eq!(drift.missing_from_api, vec!["removed".to_string()]);
+        assert_eq!(drift.missing_from_manifest, vec!["untracked".to_string()]);
+        assert!(!drift.is_empty());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_strict_registered_flags() {
+        use crate::flag::FlagKey;
+        use crate::FlagError;
+        use std::sync::{Arc, Mutex};
+
+        env::set_var("FLAGS_KNOWN", "true");
+        env::set_var("FLAGS_UNKNOWN", "true");
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let reported_clone = reported.clone();
+        let client = Client::builder()
+            .with_registered_flags([FlagKey::new("Known")])
+            .strict(true)
+            .with_error_callback(move |e| {
+                if let FlagError::UnregisteredFlag(msg) = e {
+                    reported_clone.lock().unwrap().push(msg.clone());
+                }
+            })
+            .build()
+            .unwrap();
+
+        assert!(client.is("known").enabled().await);
+        assert!(!client.is("unknown").enabled().await);
+        let results = client.get_multiple(&["known", "unknown"]).await;
+        assert_eq!(results.get("known"), Some(&true));
+        assert_eq!(results.get("unknown"), Some(&false));
+        assert_eq!(reported.lock().unwrap().len(), 2);
+        assert!(reported.lock().unwrap()[0].contains("'unknown'"));
+
+        // Without strict mode the list only flags dead API flags
+        let client = Client::builder().with_registered_flags(["known"]).build().unwrap();
+        assert!(client.is("unknown").enabled().await);
+
+        assert!(matches!(
+            Client::builder().strict(true).build(),
+            Err(FlagError::BuilderError(_))
+        ));
+
+        env::remove_var("FLAGS_KNOWN");
+        env::remove_var("FLAGS_UNKNOWN");
+    }
+
+    #[test]
+    #[should_panic(expected = "'unknown' is not in the registered flags")]
+    fn test_panic_on_unregistered_is_opt_in() {
+        let client = Client::builder()
+            .with_registered_flags(["known"])
+            .strict(true)
+            .panic_on_unregistered(true)
+            .build()
+            .unwrap();
+        client.try_is_enabled("unknown");
+    }
+
+    #[tokio::test]
+    async fn test_unused_flags() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": true, "details": {"name": "checked", "id": "1"}},
+                    {"enabled": false, "details": {"name": "batched", "id": "2"}},
+                    {"enabled": true, "details": {"name": "stale-b", "id": "3"}},
+                    {"enabled": true, "details": {"name": "stale-a", "id": "4"}}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+        let client = create_test_client(&mock_server).await;
+
+        assert!(client.unused_flags().await.is_empty());
+        assert!(client.is("checked").enabled().await);
+        client.get_multiple(&["batched", "missing"]).await;
+        assert_eq!(client.unused_flags().await, vec!["stale-a".to_string(), "stale-b".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_did_you_mean_unknown_flag() {
+        use crate::suggest::{closest, Suggestions, MAX_NAMES};
+        use crate::FlagError;
+        use std::sync::{Arc, Mutex};
+
+        let names = ["new-checkout", "dark-mode", "beta"];
+        assert_eq!(closest("new-chekout", names), Some("new-checkout"));
+        assert_eq!(closest("dark-mdoe", names), Some("dark-mode"));
+        assert_eq!(closest("light-mode", names), None);
+        assert_eq!(closest("bet", names), Some("beta"));
+        assert_eq!(closest("be", names), None);
+
+        // Checked names are bounded, forgetting older refreshes first
+        let suggestions = Suggestions::new();
+        for i in 0..MAX_NAMES {
+            assert!(suggestions.should_check(&format!("typo-{}", i), 1));
+        }
+        assert!(!suggestions.should_check("one-more", 1));
+        assert!(suggestions.should_check("one-more", 2));
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "new-checkout", "id": "1"}}]
+            })))
+            .mount(&mock_server)
+            .await;
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let reported_clone = reported.clone();
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_error_callback(move |e| {
+                if let FlagError::UnknownFlag(_) = e {
+                    reported_clone.lock().unwrap().push(e.to_string());
+                }
+            })
+            .build()
+            .unwrap();
+
+        assert!(!client.is("new-chekout").enabled().await);
+        assert!(!client.is("new-chekout").enabled().await);
+        client.get_multiple(&["new-chekout", "unrelated"]).await;
+        let reported = reported.lock().unwrap();
+        assert_eq!(*reported, vec!["Unknown flag: 'new-chekout', did you mean 'new-checkout'?".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_try_is_enabled_reads_last_refresh() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": true, "details": {"name": "on-flag", "id": "1"}},
+                    {"enabled": false, "details": {"name": "off-flag", "id": "2"}}
+                ]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let client = create_test_client(&mock_server).await;
+
+        // Never refreshes on its own
+        assert_eq!(client.try_is_enabled("on-flag"), None);
+        client.is("on-flag").enabled().await;
+        assert_eq!(client.try_is_enabled("ON-FLAG"), Some(true));
+        assert_eq!(client.try_is_enabled("off-flag"), Some(false));
+        assert_eq!(client.try_is_enabled("missing"), None);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_is_consistent_across_refreshes() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "checkout", "id": "1"}}]
+            })))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": false, "details": {"name": "checkout", "id": "1"}}]
+            })))
+            .mount(&mock_server)
+            .await;
+        let client = create_test_client(&mock_server).await;
+
+        let snapshot = client.snapshot().await;
+        assert_eq!(snapshot.len(), 1);
+        assert!(snapshot.enabled("Checkout"));
+        assert_eq!(snapshot.get("missing"), None);
+
+        client.refetch().await.unwrap();
+        assert!(!client.is("checkout").enabled().await);
+        assert!(snapshot.enabled("checkout"));
+        assert!(!client.snapshot().await.enabled("checkout"));
+        assert!(client.unused_flags().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_follows_refreshes() {
+        let mock_server = MockServer::start().await;
+        for enabled in [false, false, true] {
+            Mock::given(method("GET"))
+                .and(path("/flags"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "intervalAllowed": 60,
+                    "flags": [{"enabled": enabled, "details": {"name": "big-red-switch", "id": "1"}}]
+                })))
+                .up_to_n_times(1)
+                .mount(&mock_server)
+                .await;
+        }
+        let client = create_test_client(&mock_server).await;
+
+        let mut stop = client.subscribe("Big-Red-Switch");
+        assert!(!*stop.borrow_and_update());
+
+        client.refetch().await.unwrap();
+        client.refetch().await.unwrap();
+        assert!(!stop.has_changed().unwrap());
+
+        let waiter = tokio::spawn(async move {
+            stop.changed().await.unwrap();
+            *stop.borrow()
+        });
+        client.refetch().await.unwrap();
+        assert!(tokio::time::timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap());
+        assert!(*client.subscribe("big-red-switch").borrow());
+    }
+
+    #[tokio::test]
+    async fn test_client_events() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use crate::circuit::CircuitBreaker;
+        use crate::events::ClientEvent;
+        use tokio_stream::StreamExt;
+
+        // Opens on a failure and closes on the next success
+        #[derive(Default)]
+        struct FlappingBreaker {
+            open: AtomicBool,
+        }
+
+        impl CircuitBreaker for FlappingBreaker {
+            fn allow_request(&self) -> bool {
+                true
+            }
+            fn record_success(&self) {
+                self.open.store(false, Ordering::SeqCst);
+            }
+            fn record_failure(&self) {
+                self.open.store(true, Ordering::SeqCst);
+            }
+            fn is_open(&self) -> bool {
+                self.open.load(Ordering::SeqCst)
+            }
+        }
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": true, "details": {"name": "a", "id": "1"}},
+                    {"enabled": false, "details": {"name": "b", "id": "2"}}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_max_retries(1)
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-environment".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_circuit_breaker(FlappingBreaker::default())
+            .build()
+            .unwrap();
+
+        let mut events = Box::pin(client.events());
+        assert!(client.refetch().await.is_err());
+        client.refetch().await.unwrap();
+        drop(client);
+
+        let mut seen = Vec::new();
+        while let Some(event) = events.next().await {
+            seen.push(event);
+        }
+        assert!(matches!(seen[0], ClientEvent::RefreshStarted));
+        assert!(matches!(seen[1], ClientEvent::CircuitOpened));
+        assert!(matches!(&seen[2], ClientEvent::RefreshFailed { error } if error.contains("500")), "{:?}", seen[2]);
+        assert!(matches!(seen[3], ClientEvent::RefreshStarted));
+        assert!(matches!(seen[4], ClientEvent::CircuitClosed));
+        assert!(matches!(seen[5], ClientEvent::RefreshSucceeded { count: 2, .. }));
+        assert_eq!(seen.len(), 6);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_breaker_opens_after_failure_threshold() {
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use crate::circuit::DefaultCircuitBreaker;
+        use crate::events::ClientEvent;
+        use crate::transport::Transport;
+        use crate::{ApiResponse, FlagError};
+        use tokio_stream::StreamExt;
+
+        struct FlakyTransport {
+            up: Arc<AtomicBool>,
+            fetches: Arc<AtomicUsize>,
+        }
+
+        #[async_trait::async_trait]
+        impl Transport for FlakyTransport {
+            async fn fetch_flags(&self, _auth: Option<&Auth>) -> Result<ApiResponse, FlagError> {
+                self.fetches.fetch_add(1, Ordering::SeqCst);
+                if !self.up.load(Ordering::SeqCst) {
+                    return Err(FlagError::ApiError("Unexpected status code: 503".to_string()));
+                }
+                Ok(ApiResponse { interval_allowed: 60, flags: vec![], skipped: vec![], version: None })
+            }
+        }
+
+        let up = Arc::new(AtomicBool::new(false));
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let client = Client::builder()
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_transport(FlakyTransport { up: Arc::clone(&up), fetches: Arc::clone(&fetches) })
+            .with_circuit_breaker(DefaultCircuitBreaker::with_failure_threshold(3))
+            .with_max_retries(1)
+            .build()
+            .unwrap();
+        let mut events = Box::pin(client.events());
+
+        for _ in 0..3 {
+            assert!(client.refetch().await.is_err());
+        }
+        // Open: the next refresh is skipped without calling the API
+        client.refetch().await.unwrap();
+        assert_eq!(fetches.load(Ordering::SeqCst), 3);
+
+        // Half-open: one refresh goes through, and its failure keeps the circuit open
+        tokio::time::advance(Duration::from_secs(11)).await;
+        assert!(client.refetch().await.is_err());
+        client.refetch().await.unwrap();
+        assert_eq!(fetches.load(Ordering::SeqCst), 4);
+
+        up.store(true, Ordering::SeqCst);
+        tokio::time::advance(Duration::from_secs(11)).await;
+        client.refetch().await.unwrap();
+        assert_eq!(fetches.load(Ordering::SeqCst), 5);
+        drop(client);
+
+        let mut opened = 0;
+        let mut closed = 0;
+        while let Some(event) = events.next().await {
+            match event {
+                ClientEvent::CircuitOpened => opened += 1,
+                ClientEvent::CircuitClosed => closed += 1,
+                _ => {}
+            }
+        }
+        assert_eq!((opened, closed), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_max_stale_serves_defaults() {
+        use crate::FlagError;
+        use std::sync::{Arc, Mutex};
+
+        let mock_server = MockServer::start().await;
+        let flags = ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "intervalAllowed": 60,
+            "flags": [
+                {"enabled": true, "details": {"name": "admin-panel", "id": "1"}},
+                {"enabled": false, "details": {"name": "maintenance", "id": "2"}}
+            ]
+        }));
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(flags.clone())
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(flags)
+            .mount(&mock_server)
+            .await;
+
+        let reported = Arc::new(Mutex::new(0));
+        let reported_clone = reported.clone();
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_max_retries(1)
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_max_stale(Duration::from_millis(200))
+            .with_stale_default("Maintenance", true)
+            .with_stale_default("admin-panel", true)
+            .with_failure_mode("admin-panel", crate::FailureMode::Closed)
+            .with_error_callback(move |e| {
+                if let FlagError::StaleFlags(_) = e {
+                    *reported_clone.lock().unwrap() += 1;
+                }
+            })
+            .build()
+            .unwrap();
+
+        assert!(client.is("admin-panel").enabled().await);
+        assert!(client.refetch().await.is_err());
+        sleep(Duration::from_millis(300)).await;
+
+        assert!(!client.is("admin-panel").enabled().await);
+        assert!(client.is("maintenance").enabled().await);
+        assert_eq!(client.try_is_enabled("admin-panel"), Some(false));
+        assert_eq!(*reported.lock().unwrap(), 1);
+
+        client.refetch().await.unwrap();
+        assert!(client.is("admin-panel").enabled().await);
+        assert!(!client.is("maintenance").enabled().await);
+
+        assert!(matches!(
+            Client::builder().with_stale_default("maintenance", true).build(),
+            Err(FlagError::BuilderError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_failure_modes() {
+        use std::sync::Arc;
+        use crate::cache::Cache;
+        use crate::FailureMode;
+
+        struct FailingCache;
+
+        #[async_trait::async_trait]
+        impl Cache for FailingCache {
+            async fn get(&self, _name: &str) -> Result<(bool, bool), Box<dyn std::error::Error + Send + Sync>> {
+                Err("unavailable".into())
+            }
+            async fn get_all(&self) -> Result<Vec<Arc<FeatureFlag>>, Box<dyn std::error::Error + Send + Sync>> {
+                Err("unavailable".into())
+            }
+            async fn refresh(&self, _flags: &[FeatureFlag], _interval_allowed: i32) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+                Ok(())
+            }
+            async fn should_refresh_cache(&self) -> bool {
+                false
+            }
+            async fn init(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+                Ok(())
+            }
+        }
+
+        let client = Client::builder()
+            .with_failure_mode("Maintenance-Mode", FailureMode::Open)
+            .with_failure_mode("beta-api", FailureMode::Closed)
+            .build()
+            .unwrap();
+        assert!(client.is("maintenance-mode").enabled().await);
+        assert!(!client.is("beta-api").enabled().await);
+        assert!(!client.is("undeclared").enabled().await);
+        let results = client.get_multiple(&["maintenance-mode", "undeclared"]).await;
+        assert_eq!(results.get("maintenance-mode"), Some(&true));
+        assert_eq!(results.get("undeclared"), Some(&false));
+
+        let client = Client::builder()
+            .with_cache(FailingCache)
+            .with_failure_mode("maintenance-mode", FailureMode::Open)
+            .build()
+            .unwrap();
+        assert!(client.is("maintenance-mode").enabled().await);
+        let results = client.get_multiple(&["maintenance-mode", "beta-api"]).await;
+        assert_eq!(results.get("maintenance-mode"), Some(&true));
+        assert_eq!(results.get("beta-api"), Some(&false));
+    }
+
+    #[tokio::test]
+    async fn test_missing_flag_callback_and_default() {
+        use std::sync::{Arc, Mutex};
+        use crate::FailureMode;
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": false, "details": {"name": "known", "id": "1"}}]
+            })))
+            .mount(&mock_server)
+            .await;
+        let missing = Arc::new(Mutex::new(Vec::new()));
+        let missing_clone = missing.clone();
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .on_missing_flag(move |name| missing_clone.lock().unwrap().push(name.to_string()))
+            .with_missing_flag_default(true)
+            .with_failure_mode("gated", FailureMode::Closed)
+            .build()
+            .unwrap();
+
+        assert!(!client.is("known").enabled().await);
+        assert!(client.is("Typo").enabled().await);
+        assert!(client.is("typo").enabled().await);
+        assert!(!client.is("gated").enabled().await);
+        let results = client.get_multiple(&["typo", "other"]).await;
+        assert_eq!(results.get("other"), Some(&true));
+        assert_eq!(*missing.lock().unwrap(), vec!["typo", "gated", "other"]);
+    }
+
+    #[tokio::test]
+    async fn test_signed_requests_correct_for_clock_skew() {
+        use crate::transport::signing::{self, DEFAULT_SKEW_TOLERANCE, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+        use wiremock::{Match, Request};
+
+        let signature = signing::sign("s3cret", 1_700_000_000, "test-project", "test-agent");
+        assert_eq!(signature.len(), 64);
+        assert!(signing::verify("s3cret", 1_700_000_000, "test-project", "test-agent", &signature, 1_700_000_100, DEFAULT_SKEW_TOLERANCE));
+        assert!(!signing::verify("wrong", 1_700_000_000, "test-project", "test-agent", &signature, 1_700_000_000, DEFAULT_SKEW_TOLERANCE));
+        assert!(!signing::verify("s3cret", 1_700_000_000, "other-project", "test-agent", &signature, 1_700_000_000, DEFAULT_SKEW_TOLERANCE));
+        assert!(!signing::verify("s3cret", 1_700_000_000, "test-project", "test-agent", &signature, 1_700_001_000, DEFAULT_SKEW_TOLERANCE));
+
+        // A server whose clock runs an hour ahead of ours
+        const SKEW: i64 = 3600;
+        struct ValidSignature;
+
+        impl Match for ValidSignature {
+            fn matches(&self, request: &Request) -> bool {
+                let header = |name: &str| request.headers.get(name).and_then(|v| v.to_str().ok());
+                let (Some(timestamp), Some(signature)) = (header(TIMESTAMP_HEADER), header(SIGNATURE_HEADER)) else {
+                    return false;
+                };
+                let now = chrono::Utc::now().timestamp() + SKEW;
+                timestamp.parse().is_ok_and(|timestamp| {
+                    signing::verify("s3cret", timestamp, "test-project", "test-agent", signature, now, DEFAULT_SKEW_TOLERANCE)
+                })
+            }
+        }
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(ValidSignature)
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "signed", "id": "1"}}]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let server_date = (chrono::Utc::now() + chrono::Duration::seconds(SKEW))
+            .format("%a, %d %b %Y %H:%M:%S GMT")
+            .to_string();
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(401).insert_header("Date", server_date.as_str()))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_max_retries(1)
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: Some("s3cret".to_string()),
+            })
+            .build()
+            .unwrap();
+        assert!(client.is("signed").enabled().await);
+        assert!(!format!("{:?}", client.auth).contains("s3cret"));
+    }
+
+    #[tokio::test]
+    async fn test_shadow_reports_mismatches() {
+        use crate::shadow::ShadowMismatch;
+        use std::sync::{Arc, Mutex};
+
+        let mut servers = Vec::new();
+        for (beta, onboarding) in [(false, ">=2.3.0"), (true, "*")] {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/flags"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "intervalAllowed": 60,
+                    "flags": [
+                        {"enabled": true, "details": {"name": "stable", "id": "1"}},
+                        {"enabled": beta, "details": {"name": "beta", "id": "2"}},
+                        {"enabled": true, "details": {"name": "never", "id": "3", "schedule": [{"from": "00:00", "until": "00:00"}]}},
+                        {"enabled": true, "details": {"name": "new-onboarding", "id": "4", "appVersion": onboarding}}
+                    ]
+                })))
+                .mount(&server)
+                .await;
+            servers.push(server);
+        }
+
+        let mismatches = Arc::new(Mutex::new(Vec::new()));
+        let mismatches_clone = mismatches.clone();
+        let client = Client::builder()
+            .with_base_url(&servers[0].uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_shadow(create_test_client(&servers[1]).await, move |mismatch| {
+                mismatches_clone.lock().unwrap().push(mismatch.clone())
+            })
+            .build()
+            .unwrap();
+
+        assert!(client.is("stable").enabled().await);
+        assert!(!client.is("beta").enabled().await);
+        let results = client.get_multiple(&["beta", "stable"]).await;
+        assert_eq!(results.get("beta"), Some(&false));
+
+        // Both sides apply the schedule, and targeting in the same context
+        assert!(!client.is("never").enabled().await);
+        let old = crate::context::EvaluationContext::new().with_app_version("2.2.0");
+        assert!(!client.is("new-onboarding").with_context(&old).enabled().await);
+
+        let expected = ShadowMismatch {
+            flag: "beta".to_string(),
+            primary: false,
+            shadow: true,
+        };
+        let onboarding = ShadowMismatch {
+            flag: "new-onboarding".to_string(),
+            primary: false,
+            shadow: true,
+        };
+        assert_eq!(*mismatches.lock().unwrap(), vec![expected.clone(), expected, onboarding]);
+    }
+
+    #[test]
+    fn test_version_ranges() {
+        use crate::version::{Version, VersionRange};
+
+        let matches = |range: &str, version: &str| {
+            VersionRange::parse(range).unwrap().matches(&Version::parse(version).unwrap())
+        };
+        assert!(matches(">=2.3.0", "2.3.0"));
+        assert!(matches(">=2.3.0", "v2.10.1"));
+        assert!(!matches(">=2.3.0", "2.2.9"));
+        assert!(!matches(">=2.3.0", "2.3.0-beta.1"));
+        assert!(matches(">=2.3.0-beta.2", "2.3.0-beta.10"));
+        assert!(matches(">=2.3, <3", "2.9.9"));
+        assert!(!matches(">=2.3, <3", "3.0.0"));
+        assert!(matches(">= 2.3.0, < 3", "2.9.9"));
+        assert!(!matches(">= 2.3.0, < 3", "3.0.0") && !matches(">= 2.3.0, < 3", "2.2.0"));
+        assert!(matches(">=2.3 <3", "2.5.0"));
+        assert!(VersionRange::parse(">=").is_none());
+        assert!(VersionRange::parse(">=, 2.3").is_none());
+        assert!(matches("^2.3", "2.99.0") && !matches("^2.3", "3.0.0"));
+        assert!(matches("^0.2.3", "0.2.9") && !matches("^0.2.3", "0.3.0"));
+        assert!(matches("~1.2.3", "1.2.9") && !matches("~1.2.3", "1.3.0"));
+        assert!(matches("1.4.0", "1.4.0+build.7") && !matches("=1.4.0", "1.4.1"));
+        assert!(matches("*", "0.0.1"));
+        assert!(VersionRange::parse(">=two").is_none());
+        assert!(Version::parse("1.2.3.4").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_app_version_targeting() {
+        use crate::context::EvaluationContext;
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": true, "details": {"name": "new-onboarding", "id": "1", "appVersion": ">=2.3.0"}},
+                    {"enabled": true, "details": {"name": "broken-range", "id": "2", "appVersion": ">=banana"}},
+                    {"enabled": true, "details": {"name": "everyone", "id": "3"}}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+        let client = create_test_client(&mock_server).await;
+
+        let old = EvaluationContext::new().with_app_version("2.2.0");
+        let new = EvaluationContext::new().with_app_version("2.4.1");
+        assert!(!client.is("new-onboarding").with_context(&old).enabled().await);
+        assert!(client.is("new-onboarding").with_context(&new).enabled().await);
+        assert!(client.is("new-onboarding").enabled().await);
+        assert!(!client.is("broken-range").with_context(&new).enabled().await);
+        assert!(client.is("everyone").with_context(&old).enabled().await);
+        let garbage = EvaluationContext::new().with_app_version("latest");
+        assert!(!client.is("new-onboarding").with_context(&garbage).enabled().await);
+    }
+
+    #[tokio::test]
+    async fn test_region_targeting_rules() {
+        use crate::context::EvaluationContext;
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": true, "details": {"name": "eu-rollout", "id": "1", "rules": [
+                        {"attribute": "region", "operator": "in", "values": ["eu-west-1", "eu-west-2"]},
+                        {"attribute": "country", "operator": "not_in", "values": ["CH"]}
+                    ]}}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+        let client = create_test_client(&mock_server).await;
+
+        let eu = EvaluationContext::new().with_region("EU-West-1");
+        let swiss = EvaluationContext::new().with_region("eu-west-2").with_country("ch");
+        let us = EvaluationContext::new().with_region("us-east-1").with_datacenter("iad");
+        assert!(client.is("eu-rollout").with_context(&eu).enabled().await);
+        assert!(!client.is("eu-rollout").with_context(&swiss).enabled().await);
+        assert!(!client.is("eu-rollout").with_context(&us).enabled().await);
+        assert!(!client.is("eu-rollout").with_context(&EvaluationContext::new()).enabled().await);
+        assert!(client.is("eu-rollout").enabled().await);
+        assert_eq!(us.attribute("datacenter"), Some("iad"));
+    }
+
+    #[test]
+    fn test_murmur3_bucketing() {
+        use crate::bucketing::{bucket, in_rollout, BucketHasher, Murmur3, BUCKETS};
+
+        assert_eq!(Murmur3.hash(b""), 0);
+        assert_eq!(Murmur3.hash(b"hello"), 0x248b_fa47);
+        assert_eq!(Murmur3.hash(b"The quick brown fox jumps over the lazy dog"), 0x2e4f_f723);
+
+        let inside = (0..10_000)
+            .filter(|i| in_rollout(&Murmur3, "new-checkout", &format!("user-{}", i), 25.0))
+            .count();
+        assert!((2_300..2_700).contains(&inside), "{} of 10000 users in a 25% rollout", inside);
+        assert_eq!(bucket(&Murmur3, "new-checkout", "user-7"), bucket(&Murmur3, "new-checkout", "user-7"));
+        assert!(bucket(&Murmur3, "new-checkout", "user-7") < BUCKETS);
+        assert!(!in_rollout(&Murmur3, "new-checkout", "user-7", 0.0));
+        assert!(in_rollout(&Murmur3, "new-checkout", "user-7", 100.0));
+    }
+
+    #[tokio::test]
+    async fn test_percentage_rollout_with_custom_hasher() {
+        use crate::bucketing::BucketHasher;
+        use crate::context::EvaluationContext;
+
+        // Buckets users by the number in their ID, whatever the flag
+        struct Trailing;
+        impl BucketHasher for Trailing {
+            fn hash(&self, key: &[u8]) -> u32 {
+                let key = std::str::from_utf8(key).unwrap();
+                key.rsplit('-').next().unwrap().parse().unwrap()
+            }
+        }
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": true, "details": {"name": "new-checkout", "id": "1", "rollout": 25.0}}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_memory_cache()
+            .with_bucket_hasher(Trailing)
+            .build()
+            .unwrap();
+
+        let inside = EvaluationContext::new().with_user_id("user-2499");
+        let outside = EvaluationContext::new().with_user_id("user-2500");
+        assert!(client.is("new-checkout").with_context(&inside).enabled().await);
+        assert!(!client.is("new-checkout").with_context(&outside).enabled().await);
+        assert!(!client.is("new-checkout").with_context(&EvaluationContext::new()).enabled().await);
+        assert!(client.is("new-checkout").enabled().await);
+    }
+
+    #[tokio::test]
+    async fn test_to_builder_derives_client() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let mock_server = MockServer::start().await;
+        for (environment, enabled) in [("test-env", true), ("staging", false)] {
+            Mock::given(method("GET"))
+                .and(path("/flags"))
+                .and(header("X-Environment-ID", environment))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "intervalAllowed": 60,
+                    "flags": [{"enabled": enabled, "details": {"name": "new-checkout", "id": "1"}}]
+                })))
+                .mount(&mock_server)
+                .await;
+        }
+        let auth = Auth {
+            project_id: "test-project".to_string(),
+            agent_id: "test-agent".to_string(),
+            environment_id: "test-env".to_string(),
+            company_id: None,
+            agent_secret: None,
+        };
+        let missing = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&missing);
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(auth.clone())
+            .on_missing_flag(move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .build()
+            .unwrap();
+        assert!(client.is("new-checkout").enabled().await);
+
+        let staging = client
+            .to_builder()
+            .with_auth(Auth { environment_id: "staging".to_string(), ..auth })
+            .with_memory_cache()
+            .build()
+            .unwrap();
+        assert!(!staging.is("new-checkout").enabled().await);
+        assert!(client.is("new-checkout").enabled().await);
+        assert!(!staging.is("unknown").enabled().await);
+        assert_eq!(missing.load(Ordering::SeqCst), 1);
+
+        let same = client.to_builder().with_max_retries(1).build().unwrap();
+        assert!(same.is("new-checkout").enabled().await);
+    }
+
+    #[tokio::test]
+    async fn test_set_auth_rotates_credentials() {
+        use crate::FlagError;
+
+        let mock_server = MockServer::start().await;
+        for (environment, enabled) in [("test-env", true), ("staging", false)] {
+            Mock::given(method("GET"))
+                .and(path("/flags"))
+                .and(header("X-Environment-ID", environment))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "intervalAllowed": 60,
+                    "flags": [
+                        {"enabled": enabled, "details": {"name": "new-checkout", "id": "1"}},
+                        {"enabled": true, "details": {"name": "everywhere", "id": "2"}}
+                    ]
+                })))
+                .mount(&mock_server)
+                .await;
+        }
+        let client = create_test_client(&mock_server).await;
+        let auth = |environment_id: &str| Auth {
+            project_id: "test-project".to_string(),
+            agent_id: "test-agent".to_string(),
+            environment_id: environment_id.to_string(),
+            company_id: None,
+            agent_secret: None,
+        };
+        assert!(client.is("new-checkout").enabled().await);
+
+        let clone = client.clone();
+        client.set_auth(auth("staging")).unwrap();
+        assert!(!clone.is("new-checkout").enabled().await);
+
+        assert!(matches!(client.set_auth(auth(" ")), Err(FlagError::AuthError(_))));
+        assert!(!client.is("new-checkout").enabled().await);
+
+        client.set_auth(auth("revoked")).unwrap();
+        assert_eq!(client.try_is_enabled("everywhere"), Some(true));
+        client.clear_cache().await.unwrap();
+        assert_eq!(client.try_is_enabled("everywhere"), None);
+    }
+
+    #[test]
+    fn test_error_codes_and_serialization() {
+        use crate::FlagError;
+
+        let error = FlagError::ApiError("Unexpected status code: 503".to_string()).for_request("req-1");
+        assert_eq!(error.code(), "api");
+        assert!(error.is_retryable());
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "code": "api",
+                "message": "API error: Unexpected status code: 503 (request ID: req-1)",
+                "retryable": true,
+                "request_id": "req-1"
+            })
+        );
+
+        let error = FlagError::BuilderError("Base URL cannot be empty".to_string());
+        assert_eq!(error.code(), "builder");
+        assert!(!error.is_retryable());
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({"code": "builder", "message": "Builder error: Base URL cannot be empty", "retryable": false})
+        );
+        assert_eq!(FlagError::StaleFlags(String::new()).code(), "stale_flags");
+        assert!(FlagError::CacheError(String::new()).is_retryable());
+
+        let status = |status: u16| FlagError::ApiStatus { status, message: String::new() };
+        assert!(status(503).is_retryable());
+        assert!(status(429).is_retryable());
+        assert!(!status(404).is_retryable());
+        assert_eq!(status(404).for_request("req-2").status(), Some(404));
+        assert_eq!(status(404).code(), "api");
+    }
+
+    #[tokio::test]
+    async fn test_rejected_requests_are_not_retryable() {
+        for (code, retryable) in [(403, false), (400, false), (500, true), (503, true)] {
+            let mock_server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/flags"))
+                .respond_with(ResponseTemplate::new(code))
+                .mount(&mock_server)
+                .await;
+            let client = Client::builder()
+                .with_base_url(&mock_server.uri())
+                .with_auth(Auth {
+                    project_id: "test-project".to_string(),
+                    agent_id: "test-agent".to_string(),
+                    environment_id: "test-env".to_string(),
+                    company_id: None,
+                    agent_secret: None,
+                })
+                .with_max_retries(1)
+                .build()
+                .unwrap();
+
+            let err = client.refetch().await.unwrap_err();
+            assert_eq!(err.status(), Some(code));
+            assert_eq!(err.is_retryable(), retryable, "{}", err);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejected_requests_are_not_retried() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(403))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_max_retries(3)
+            .build()
+            .unwrap();
+
+        let err = client.refetch().await.unwrap_err();
+        assert_eq!(err.status(), Some(403));
+        assert_eq!(mock_server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_override() {
+        use crate::FlagError;
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "new-checkout", "id": "1"}}]
+            })))
+            .mount(&mock_server)
+            .await;
+        let auth = Auth {
+            project_id: "test-project".to_string(),
+            agent_id: "test-agent".to_string(),
+            environment_id: "test-env".to_string(),
+            company_id: None,
+            agent_secret: None,
+        };
+        let client = Client::builder()
+            .with_base_url(&format!("http://flags.invalid:{}", mock_server.address().port()))
+            .with_resolve("flags.invalid", [*mock_server.address()])
+            .with_auth(auth)
+            .build()
+            .unwrap();
+        assert!(client.is("new-checkout").enabled().await);
+
+        let result = Client::builder().with_resolve("flags.invalid", []).build();
+        assert!(matches!(result, Err(FlagError::BuilderError(_))));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket_transport() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::UnixListener;
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("relay.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            let body = r#"{"intervalAllowed": 60, "flags": [{"enabled": true, "details": {"name": "new-checkout", "id": "1"}}]}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let client = Client::builder()
+            .with_base_url("http://localhost")
+            .with_unix_socket(&socket)
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .build()
+            .unwrap();
+        assert!(client.is("new-checkout").enabled().await);
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("GET /flags"));
+        assert!(request.to_lowercase().contains("x-project-id: test-project"));
+    }
+
+    #[tokio::test]
+    async fn test_canary_environment() {
+        use std::sync::{Arc, Mutex};
+        use crate::FlagError;
+
+        let mock_server = MockServer::start().await;
+        for (environment, enabled) in [("test-env", true), ("next", false)] {
+            Mock::given(method("GET"))
+                .and(path("/flags"))
+                .and(header("X-Environment-ID", environment))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "intervalAllowed": 60,
+                    "flags": [
+                        {"enabled": enabled, "details": {"name": "new-checkout", "id": "1"}},
+                        {"enabled": true, "details": {"name": "everywhere", "id": "2"}}
+                    ]
+                })))
+                .mount(&mock_server)
+                .await;
+        }
+        let auth = Auth {
+            project_id: "test-project".to_string(),
+            agent_id: "test-agent".to_string(),
+            environment_id: "test-env".to_string(),
+            company_id: None,
+            agent_secret: None,
+        };
+        let builder = |sample_rate: f64, mismatches: Arc<Mutex<Vec<String>>>| {
+            Client::builder()
+                .with_base_url(&mock_server.uri())
+                .with_auth(auth.clone())
+                .with_canary_environment("next", sample_rate, move |mismatch| {
+                    assert!(mismatch.primary && !mismatch.shadow);
+                    mismatches.lock().unwrap().push(mismatch.flag.clone());
+                })
+        };
+
+        let mismatches = Arc::new(Mutex::new(Vec::new()));
+        let client = builder(1.0, Arc::clone(&mismatches)).build().unwrap();
+        assert!(client.is("new-checkout").enabled().await);
+        assert!(client.is("everywhere").enabled().await);
+        assert_eq!(*mismatches.lock().unwrap(), vec!["new-checkout".to_string()]);
+
+        let unsampled = Arc::new(Mutex::new(Vec::new()));
+        let client = builder(0.0, Arc::clone(&unsampled)).build().unwrap();
+        assert!(client.is("new-checkout").enabled().await);
+        assert!(unsampled.lock().unwrap().is_empty());
+
+        let invalid = builder(1.5, Arc::new(Mutex::new(Vec::new()))).build();
+        assert!(matches!(invalid, Err(FlagError::BuilderError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_ramp_schedule() {
+        use chrono::{Duration as ChronoDuration, Utc};
+        use crate::bucketing::BucketHasher;
+        use crate::context::EvaluationContext;
+        use crate::flag::Ramp;
+
+        let starts_at = Utc::now() - ChronoDuration::minutes(150);
+        let ramp = Ramp { starts_at, start: 5.0, step: 10.0, interval_secs: 3600, max: 50.0 };
+        assert_eq!(ramp.percentage_at(starts_at - ChronoDuration::seconds(1)), 0.0);
+        assert_eq!(ramp.percentage_at(starts_at), 5.0);
+        assert_eq!(ramp.percentage_at(starts_at + ChronoDuration::minutes(150)), 25.0);
+        assert_eq!(ramp.percentage_at(starts_at + ChronoDuration::days(1)), 50.0);
+
+        // Buckets users by the number in their ID
+        struct Trailing;
+        impl BucketHasher for Trailing {
+            fn hash(&self, key: &[u8]) -> u32 {
+                let key = std::str::from_utf8(key).unwrap();
+                key.rsplit('-').next().unwrap().parse().unwrap()
+            }
+        }
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "new-checkout", "id": "1", "rollout": 100.0, "ramp": {
+                    "startsAt": starts_at.to_rfc3339(), "start": 5.0, "step": 10.0, "intervalSecs": 3600, "max": 50.0
+                }}}]
+            })))
+            .mount(&mock_server)
+            .await;
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_bucket_hasher(Trailing)
+            .build()
+            .unwrap();
+
+        let inside = EvaluationContext::new().with_user_id("user-2499");
+        let outside = EvaluationContext::new().with_user_id("user-2500");
+        assert!(client.is("new-checkout").with_context(&inside).enabled().await);
+        assert!(!client.is("new-checkout").with_context(&outside).enabled().await);
+    }
+
+    #[test]
+    fn test_time_rules() {
+        use chrono::{TimeZone, Utc};
+        use crate::flag::TimeRule;
+        use crate::schedule::{Cron, Schedule};
+
+        // Wednesday
+        let at = |hour, minute| Utc.with_ymd_and_hms(2026, 10, 14, hour, minute, 0).unwrap();
+        let office_hours = Cron::parse("* 9-16 * * 1-5").unwrap();
+        assert!(office_hours.matches(at(9, 0)));
+        assert!(office_hours.matches(at(16, 59)));
+        assert!(!office_hours.matches(at(17, 0)));
+        assert!(!office_hours.matches(Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap()));
+        assert!(Cron::parse("*/15 * * * 7").unwrap().matches(Utc.with_ymd_and_hms(2026, 10, 18, 3, 45, 0).unwrap()));
+        assert!(Cron::parse("0 12 1 * 3").unwrap().matches(at(12, 0)));
+        for invalid in ["* * * *", "60 * * * *", "* * * * 8", "5-1 * * * *", "*/0 * * * *"] {
+            assert!(Cron::parse(invalid).is_none(), "{}", invalid);
+        }
+
+        let rules: Vec<TimeRule> = serde_json::from_value(serde_json::json!([
+            {"days": ["mon", "tue", "wed", "thu", "fri"], "from": "09:00", "until": "17:00"},
+            {"days": ["Sat"], "from": "22:00", "until": "02:00"}
+        ]))
+        .unwrap();
+        let schedule = Schedule::new("support-chat", &rules);
+        assert!(schedule.is_active_at(at(10, 30)));
+        assert!(!schedule.is_active_at(at(17, 0)));
+        assert!(schedule.is_active_at(Utc.with_ymd_and_hms(2026, 10, 18, 1, 0, 0).unwrap()));
+        assert!(!schedule.is_active_at(Utc.with_ymd_and_hms(2026, 10, 19, 1, 0, 0).unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_flags() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": true, "details": {"name": "always", "id": "1", "schedule": [{"cron": "* * * * *"}]}},
+                    {"enabled": true, "details": {"name": "never", "id": "2", "schedule": [{"from": "00:00", "until": "00:00"}]}},
+                    {"enabled": true, "details": {"name": "broken", "id": "3", "schedule": [{"cron": "whenever"}]}}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+        let client = create_test_client(&mock_server).await;
+
+        assert!(client.is("always").enabled().await);
+        assert!(!client.is("never").enabled().await);
+        assert!(!client.is("broken").enabled().await);
+        let flags = client.get_multiple(&["always", "never"]).await;
+        assert!(flags["always"]);
+        assert!(!flags["never"]);
+        assert_eq!(client.try_is_enabled("never"), Some(false));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_tenant_client_pool() {
+        use crate::pool::TenantClientPool;
+        use crate::FlagError;
+
+        let mock_server = MockServer::start().await;
+        for (company, enabled) in [("acme", true), ("globex", false)] {
+            Mock::given(method("GET"))
+                .and(path("/flags"))
+                .and(header("X-Company-ID", company))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "intervalAllowed": 60,
+                    "flags": [{"enabled": enabled, "details": {"name": "new-dashboard", "id": "1"}}]
+                })))
+                .mount(&mock_server)
+                .await;
+        }
+        let tenant = |company: &str| Auth {
+            project_id: "test-project".to_string(),
+            agent_id: "test-agent".to_string(),
+            environment_id: "test-env".to_string(),
+            company_id: Some(company.to_string()),
+            agent_secret: None,
+        };
+        let template = create_test_client(&mock_server).await;
+        let pool = TenantClientPool::new(&template)
+            .with_capacity(2)
+            .with_idle_timeout(Duration::from_secs(600));
+
+        assert!(pool.get(&tenant("acme")).unwrap().is("new-dashboard").enabled().await);
+        assert!(!pool.get(&tenant("globex")).unwrap().is("new-dashboard").enabled().await);
+        assert_eq!(pool.len(), 2);
+
+        // globex is the least recently used
+        tokio::time::advance(Duration::from_secs(1)).await;
+        pool.get(&tenant("acme")).unwrap();
+        pool.get(&tenant("initech")).unwrap();
+        assert_eq!(pool.len(), 2);
+        assert!(pool.remove(&tenant("initech")));
+        assert!(!pool.remove(&tenant("globex")));
+
+        tokio::time::advance(Duration::from_secs(601)).await;
+        pool.evict_idle();
+        assert!(pool.is_empty());
+
+        let invalid = pool.get(&Auth { project_id: String::new(), ..tenant("acme") });
+        assert!(matches!(invalid, Err(FlagError::BuilderError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_request_interceptor() {
+        use crate::FlagError;
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(header("Authorization", "Bearer gateway-token"))
+            .and(header("X-Project-ID", "test-project"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "new-checkout", "id": "1"}}]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let builder = |token: Option<&'static str>| {
+            Client::builder()
+                .with_base_url(&mock_server.uri())
+                .with_auth(Auth {
+                    project_id: "test-project".to_string(),
+                    agent_id: "test-agent".to_string(),
+                    environment_id: "test-env".to_string(),
+                    company_id: None,
+                    agent_secret: None,
+                })
+                .with_max_retries(1)
+                .with_request_interceptor(move |request| {
+                    let token = token.ok_or_else(|| FlagError::AuthError("No gateway token".to_string()))?;
+                    request
+                        .headers_mut()
+                        .insert("Authorization", format!("Bearer {}", token).parse().unwrap());
+                    Ok(())
+                })
+                .build()
+                .unwrap()
+        };
+
+        assert!(builder(Some("gateway-token")).is("new-checkout").enabled().await);
+
+        let errors = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = std::sync::Arc::clone(&errors);
+        let client = builder(None)
+            .to_builder()
+            .with_error_callback(move |e| recorded.lock().unwrap().push(e.code()))
+            .build()
+            .unwrap();
+        assert!(!client.is("new-checkout").enabled().await);
+        let errors = errors.lock().unwrap();
+        assert!(!errors.is_empty() && errors.iter().all(|code| *code == "auth"), "{:?}", errors);
+    }
+
+    #[tokio::test]
+    async fn test_json_log_sink() {
+        use std::sync::{Arc, Mutex};
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "a", "id": "1"}}]
+            })))
+            .mount(&mock_server)
+            .await;
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&records);
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_max_retries(1)
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-environment".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_json_log_sink(move |record| sink.lock().unwrap().push(record.clone()))
+            .build()
+            .unwrap();
+
+        assert!(client.refetch().await.is_err());
+        client.refetch().await.unwrap();
+
+        let records = records.lock().unwrap();
+        let events: Vec<&str> = records.iter().map(|r| r["event"].as_str().unwrap()).collect();
+        assert_eq!(events, ["refresh_started", "error", "refresh_failed", "refresh_started", "refresh_succeeded"]);
+        assert_eq!(records[1]["code"], "api");
+        assert_eq!(records[1]["level"], "error");
+        assert!(records[2]["error"].as_str().unwrap().contains("500"));
+        assert_eq!(records[4]["count"], 1);
+        assert_eq!(records[4]["level"], "info");
+        assert!(records[4]["duration_ms"].is_u64());
+        assert!(records.iter().all(|r| r["timestamp"].is_string()));
+    }
+
+    #[tokio::test]
+    async fn test_slow_refresh_threshold() {
+        use std::sync::{Arc, Mutex};
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"intervalAllowed": 60, "flags": []}))
+                    .set_delay(Duration::from_millis(100)),
+            )
+            .mount(&mock_server)
+            .await;
+        let slow = Arc::new(Mutex::new(Vec::new()));
+        let builder = |threshold: Duration| {
+            let slow = Arc::clone(&slow);
+            Client::builder()
+                .with_base_url(&mock_server.uri())
+                .with_auth(Auth {
+                    project_id: "test-project".to_string(),
+                    agent_id: "test-agent".to_string(),
+                    environment_id: "test-environment".to_string(),
+                    company_id: None,
+                    agent_secret: None,
+                })
+                .with_memory_cache()
+                .with_slow_refresh_threshold(threshold, move |d| slow.lock().unwrap().push(d))
+                .build()
+                .unwrap()
+        };
+
+        builder(Duration::from_secs(10)).refetch().await.unwrap();
+        assert!(slow.lock().unwrap().is_empty());
+
+        builder(Duration::from_millis(20)).refetch().await.unwrap();
+        let slow = slow.lock().unwrap();
+        assert_eq!(slow.len(), 1);
+        assert!(slow[0] >= Duration::from_millis(100), "{:?}", slow[0]);
+    }
+
+    #[tokio::test]
+    async fn test_evaluation_counts() {
+        use crate::EvaluationCounts;
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": true, "details": {"name": "new-checkout", "id": "1"}},
+                    {"enabled": false, "details": {"name": "dark-mode", "id": "2"}}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-environment".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_memory_cache()
+            .build()
+            .unwrap();
+
+        assert!(client.is("new-checkout").enabled().await);
+        assert!(client.is("new-checkout").enabled().await);
+        client.get_multiple(&["dark-mode", "removed-flag"]).await;
+        assert_eq!(client.try_is_enabled("removed-flag"), None);
+        assert!(!client.snapshot().await.enabled("dark-mode"));
+
+        let counts = client.evaluation_counts();
+        assert_eq!(counts.len(), 3);
+        assert_eq!(
+            counts["new-checkout"],
+            EvaluationCounts { evaluated: 2, enabled: 2, disabled: 0, missing: 0 }
+        );
+        assert_eq!(
+            counts["dark-mode"],
+            EvaluationCounts { evaluated: 2, enabled: 0, disabled: 2, missing: 0 }
+        );
+        assert_eq!(
+            counts["removed-flag"],
+            EvaluationCounts { evaluated: 2, enabled: 0, disabled: 2, missing: 2 }
+        );
+
+        client.reset_evaluation_counts();
+        assert!(client.evaluation_counts().is_empty());
+        assert_eq!(client.unused_flags().await, Vec::<String>::new());
+        client.is("dark-mode").enabled().await;
+        assert_eq!(client.evaluation_counts()["dark-mode"].evaluated, 1);
+
+        // Unknown names are capped and forgotten on reset
+        let max = crate::usage::MAX_UNKNOWN_NAMES;
+        for i in 0..max + 100 {
+            client.try_is_enabled(&format!("typo-{}", i));
+        }
+        assert_eq!(client.evaluation_counts().len(), max + 1);
+        client.reset_evaluation_counts();
+        client.try_is_enabled("typo-new");
+        let counts = client.evaluation_counts();
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts["typo-new"].missing, 1);
+        client.is("new-checkout").enabled().await;
+        assert_eq!(client.evaluation_counts()["new-checkout"].evaluated, 1);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_duplicate_flags_pick_a_deterministic_winner() {
+        use std::sync::{Arc, Mutex};
+
+        let orders = [
+            serde_json::json!([
+                {"enabled": true, "details": {"name": "New-UI", "id": "1"}},
+                {"enabled": false, "details": {"name": "new-ui", "id": "2"}},
+                {"enabled": true, "details": {"name": "Beta", "id": "3"}},
+                {"enabled": true, "details": {"name": "beta", "id": "3"}}
+            ]),
+            serde_json::json!([
+                {"enabled": true, "details": {"name": "beta", "id": "3"}},
+                {"enabled": false, "details": {"name": "new-ui", "id": "2"}},
+                {"enabled": true, "details": {"name": "Beta", "id": "3"}},
+                {"enabled": true, "details": {"name": "New-UI", "id": "1"}}
+            ]),
+        ];
+        for flags in orders {
+            let mock_server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/flags"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "intervalAllowed": 60,
+                    "flags": flags
+                })))
+                .mount(&mock_server)
+                .await;
+            let errors = Arc::new(Mutex::new(Vec::new()));
+            let recorded = Arc::clone(&errors);
+            let client = create_test_client(&mock_server)
+                .await
+                .to_builder()
+                .with_error_callback(move |e| recorded.lock().unwrap().push((e.code(), e.to_string())))
+                .build()
+                .unwrap();
+
+            assert!(!client.is("new-ui").enabled().await);
+            assert!(client.is("beta").enabled().await);
+            assert_eq!(client.list().await.unwrap().len(), 2);
+            let errors = errors.lock().unwrap();
+            assert_eq!(errors.len(), 1, "{:?}", errors);
+            assert_eq!(errors[0].0, "duplicate_flag");
+            assert_eq!(
+                errors[0].1,
+                "Duplicate flag: 'Beta' (enabled) is the same flag as 'beta' (enabled), using 'beta'; \
+                 'New-UI' (enabled) is the same flag as 'new-ui' (disabled), using 'new-ui'"
+            );
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_conflicting_local_flags() {
+        use std::sync::{Arc, Mutex};
+
+        env::set_var("FLAGS_DUPLICATE_LOCAL", "true");
+        env::set_var("FLAGS_duplicate_local", "false");
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&errors);
+        let client = Client::builder()
+            .with_memory_cache()
+            .with_error_callback(move |e| recorded.lock().unwrap().push(e.to_string()))
+            .build()
+            .unwrap();
+        env::remove_var("FLAGS_DUPLICATE_LOCAL");
+        env::remove_var("FLAGS_duplicate_local");
+
+        assert!(client.is("duplicate_local").enabled().await);
+        assert!(client.is("duplicate-local").enabled().await);
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with(
+            "Duplicate flag: FLAGS_DUPLICATE_LOCAL and FLAGS_duplicate_local both set 'duplicate-local', using FLAGS_DUPLICATE_LOCAL"
+        ), "{}", errors[0]);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_flags_are_quarantined() {
+        use crate::flag::QuarantinedFlag;
+        use std::sync::{Arc, Mutex};
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": true, "details": {"name": "good", "id": "1"}},
+                    {"enabled": true, "details": {"name": " ", "id": "2"}},
+                    {"enabled": true, "details": {"name": "wide-rollout", "id": "3", "rollout": 150.0}},
+                    {"enabled": true, "details": {"name": "bad-ramp", "id": "4", "ramp": {
+                        "startsAt": "2026-01-01T00:00:00Z", "start": 5.0, "step": -1.0, "intervalSecs": 60, "max": 50.0
+                    }}},
+                    {"enabled": "yes", "details": {"name": "bad-type", "id": "5"}}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&errors);
+        let client = create_test_client(&mock_server)
+            .await
+            .to_builder()
+            .with_error_callback(move |e| recorded.lock().unwrap().push(e.to_string()))
+            .build()
+            .unwrap();
+
+        assert!(client.is("good").enabled().await);
+        assert!(!client.is("wide-rollout").enabled().await);
+        assert_eq!(client.list().await.unwrap().len(), 1);
+
+        let quarantined = client.quarantined_flags();
+        assert_eq!(quarantined.len(), 4);
+        assert_eq!(quarantined[0].name, None);
+        assert!(quarantined[0].reason.starts_with("flag 4:"));
+        assert_eq!(quarantined[1], QuarantinedFlag { name: None, reason: "empty name".to_string() });
+        assert_eq!(
+            quarantined[2],
+            QuarantinedFlag {
+                name: Some("wide-rollout".to_string()),
+                reason: "rollout 150 is not between 0 and 100".to_string()
+            }
+        );
+        assert_eq!(quarantined[3].name.as_deref(), Some("bad-ramp"));
+
+        let errors = errors.lock().unwrap();
+        assert!(errors.iter().any(|e| e.contains("Quarantined 3 invalid flag(s): '': empty name; 'wide-rollout'")), "{:?}", errors);
+    }
+
+    #[tokio::test]
+    async fn test_all_enabled_detailed() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": true, "details": {"name": "new-checkout", "id": "1"}},
+                    {"enabled": false, "details": {"name": "new-payments", "id": "2"}}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+        let client = create_test_client(&mock_server).await;
+
+        assert_eq!(client.all_enabled_detailed(&[]).await, Ok(()));
+        assert_eq!(client.all_enabled_detailed(&["new-checkout"]).await, Ok(()));
+        assert_eq!(
+            client.all_enabled_detailed(&["unknown", "new-checkout", "New-Payments"]).await,
+            Err(vec!["unknown".to_string(), "New-Payments".to_string()])
+        );
+        assert!(!client.all_enabled(&["new-checkout", "new-payments"]).await);
+    }
+
+    #[tokio::test]
+    async fn test_credentials_are_sanitized_at_build_time() {
+        use crate::FlagError;
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .and(header("X-Project-ID", "test-project"))
+            .and(header("X-Agent-ID", "test-agent"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "new-checkout", "id": "1"}}]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let auth = |project_id: &str| Auth {
+            project_id: project_id.to_string(),
+            agent_id: " test-agent\n".to_string(),
+            environment_id: "test-env".to_string(),
+            company_id: None,
+            agent_secret: None,
+        };
+
+        // Surrounding whitespace, e.g. from a secrets file, is trimmed
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(auth("test-project\r\n"))
+            .with_memory_cache()
+            .build()
+            .unwrap();
+        assert!(client.is("new-checkout").enabled().await);
+
+        for (project_id, reason) in [
+            ("proj\u{7}secret", "contains a control character at position 4"),
+            ("prøject-secret", "contains a non-ASCII character at position 2"),
+        ] {
+            let err = Client::builder().with_auth(auth(project_id)).build().err().unwrap();
+            match &err {
+                FlagError::InvalidCredentialFormat { field, reason: actual } => {
+                    assert_eq!(*field, "project_id");
+                    assert_eq!(actual, reason);
+                }
+                other => panic!("unexpected error: {:?}", other),
+            }
+            assert_eq!(err.code(), "invalid_credential_format");
+            assert!(!err.to_string().contains("secret"), "{}", err);
+
+            let err = client.set_auth(auth(project_id)).unwrap_err();
+            assert!(matches!(err, FlagError::InvalidCredentialFormat { field: "project_id", .. }));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_disabled_propagates_errors() {
+        use crate::FlagError;
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "batch-flag", "id": "1"}}]
+            })))
+            .mount(&mock_server)
+            .await;
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_max_retries(1)
+            .with_circuit_breaker_disabled()
+            .with_memory_cache()
+            .build()
+            .unwrap();
+
+        // Every call fails with the API's error until a refresh succeeds
+        for _ in 0..2 {
+            let err = client.list().await.unwrap_err();
+            assert!(matches!(err, FlagError::Request { .. } | FlagError::ApiError(_)), "{:?}", err);
+        }
+        let flags = client.list().await.unwrap();
+        assert_eq!(flags.len(), 1);
+        assert!(client.is("batch-flag").enabled().await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_zero_interval_allowed() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use crate::transport::Transport;
+        use crate::{ApiResponse, FlagError, ZeroInterval};
+
+        struct ZeroIntervalTransport(Arc<AtomicUsize>);
+
+        #[async_trait::async_trait]
+        impl Transport for ZeroIntervalTransport {
+            async fn fetch_flags(&self, _auth: Option<&Auth>) -> Result<ApiResponse, FlagError> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(ApiResponse { interval_allowed: 0, flags: vec![], skipped: vec![], version: None })
+            }
+        }
+
+        let client = |zero: Option<ZeroInterval>, fetches: &Arc<AtomicUsize>| {
+            let mut builder = Client::builder()
+                .with_auth(Auth {
+                    project_id: "test-project".to_string(),
+                    agent_id: "test-agent".to_string(),
+                    environment_id: "test-env".to_string(),
+                    company_id: None,
+                    agent_secret: None,
+                })
+                .with_transport(ZeroIntervalTransport(Arc::clone(fetches)));
+            if let Some(zero) = zero {
+                builder = builder.with_zero_interval(zero);
+            }
+            builder.build().unwrap()
+        };
+
+        // No caching, but not a fetch on every call either
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let no_cache = client(None, &fetches);
+        for _ in 0..10 {
+            no_cache.is("anything").enabled().await;
+        }
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        tokio::time::advance(Duration::from_millis(1100)).await;
+        no_cache.is("anything").enabled().await;
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let forever = client(Some(ZeroInterval::CacheForever), &fetches);
+        forever.is("anything").enabled().await;
+        tokio::time::advance(Duration::from_secs(86_400)).await;
+        forever.is("anything").enabled().await;
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        forever.clear_cache().await.unwrap();
+        forever.is("anything").enabled().await;
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_handoff() {
+        use crate::flag::FlagSnapshot;
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": true, "details": {"name": "new-checkout", "id": "1"}},
+                    {"enabled": false, "details": {"name": "dark-mode", "id": "2"}}
+                ]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let parent = create_test_client(&mock_server).await;
+
+        let handoff = serde_json::to_string(&parent.snapshot().await).unwrap();
+        assert_eq!(handoff, r#"{"flags":{"dark-mode":false,"new-checkout":true}}"#);
+
+        let snapshot: FlagSnapshot = serde_json::from_str(&handoff).unwrap();
+        assert!(snapshot.enabled("new-checkout"));
+        let child = Client::from_snapshot(snapshot);
+        assert!(child.is("new-checkout").enabled().await);
+        assert!(!child.is("dark-mode").enabled().await);
+        assert!(!child.is("missing").enabled().await);
+        assert_eq!(child.list().await.unwrap().len(), 2);
+
+        // Hand-written snapshots are normalized like evaluations
+        let snapshot: FlagSnapshot = serde_json::from_str(r#"{"flags":{"Promo-Banner":true}}"#).unwrap();
+        assert!(Client::from_snapshot(snapshot).is("promo-banner").enabled().await);
+    }
+
+    #[tokio::test]
+    async fn test_require_flags() {
+        use crate::FlagError;
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": false, "details": {"name": "Payments-V2", "id": "1"}}]
+            })))
+            .mount(&mock_server)
+            .await;
+        let builder = || {
+            Client::builder()
+                .with_base_url(&mock_server.uri())
+                .with_auth(Auth {
+                    project_id: "test-project".to_string(),
+                    agent_id: "test-agent".to_string(),
+                    environment_id: "test-env".to_string(),
+                    company_id: None,
+                    agent_secret: None,
+                })
+                .with_memory_cache()
+        };
+
+        // A disabled flag is still defined; names are normalized
+        let client = builder().require_flags(["payments-v2"]).build().unwrap();
+        client.wait_until_ready().await.unwrap();
+
+        let client = builder()
+            .require_flags(["payments-v2", "new-auth", "New-Auth", "audit-log"])
+            .build()
+            .unwrap();
+        let err = client.wait_until_ready().await.unwrap_err();
+        assert_eq!(err.code(), "missing_required_flags");
+        assert!(!err.is_retryable());
+        assert!(matches!(&err, FlagError::MissingRequiredFlags(names) if names == &["new-auth", "audit-log"]));
+        assert_eq!(err.to_string(), "Missing required flags: new-auth, audit-log");
+    }
+
+    #[tokio::test]
+    async fn test_owned_flag_outlives_borrow() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "owned-flag", "id": "1"}}]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = create_test_client(&mock_server).await;
+        let flag = client.flag("Owned-Flag");
+        let missing = client.flag("not-there");
+        drop(client);
+        assert_eq!(flag.name(), "owned-flag");
+
+        let handle = tokio::spawn(async move { (flag.enabled().await, missing.enabled().await) });
+        assert_eq!(handle.await.unwrap(), (true, false));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_name_normalization_and_aliases() {
+        use crate::alias;
+
+        assert_eq!(alias::camel_case("new-auth-flow"), vec!["newAuthFlow".to_string()]);
+        assert!(alias::camel_case("checkout").is_empty());
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [
+                    {"enabled": true, "details": {"name": "New Auth", "id": "1"}},
+                    {"enabled": false, "details": {"name": "newauth", "id": "2"}}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        env::set_var("FLAGS_DARK_MODE", "true");
+        let client = Client::builder()
+            .with_base_url(&mock_server.uri())
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_alias_generator(alias::camel_case)
+            .with_memory_cache()
+            .build()
+            .unwrap();
+        env::remove_var("FLAGS_DARK_MODE");
+
+        // One stored entry per flag, found by every separator convention
+        let names: Vec<String> = client.list().await.unwrap().iter().map(|f| f.details.name.clone()).collect();
+        assert_eq!(names.iter().filter(|n| n.contains("dark")).count(), 1);
+        for name in ["new-auth", "new_auth", "NEW AUTH", "dark-mode", "DARK_MODE", "dark mode"] {
+            assert!(client.is(name).enabled().await, "{}", name);
+        }
+
+        // Aliases apply to local and API flags alike, but never shadow a real flag
+        assert!(client.is("darkMode").enabled().await);
+        assert!(client.is("DarkMode").enabled().await);
+        assert!(client.get_multiple(&["darkMode"]).await["darkMode"]);
+        assert!(!client.is("newAuth").enabled().await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cold_start_degraded_serves_bootstrap_flags() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        use crate::flag::Details;
+        use crate::transport::Transport;
+        use crate::{ApiResponse, ClientStatus, FailureMode, FlagError};
+
+        struct FlakyTransport(Arc<AtomicBool>);
+
+        #[async_trait::async_trait]
+        impl Transport for FlakyTransport {
+            async fn fetch_flags(&self, _auth: Option<&Auth>) -> Result<ApiResponse, FlagError> {
+                if !self.0.load(Ordering::SeqCst) {
+                    return Err(FlagError::ApiError("Unexpected status code: 503".to_string()));
+                }
+                Ok(ApiResponse {
+                    interval_allowed: 60,
+                    flags: vec![FeatureFlag {
+                        enabled: false,
+                        details: Details { name: "payments".to_string(), id: "1".to_string(), ..Default::default() },
+                    }],
+                    skipped: vec![],
+                    version: None,
+                })
+            }
+        }
+
+        let up = Arc::new(AtomicBool::new(false));
+        let client = Client::builder()
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_transport(FlakyTransport(Arc::clone(&up)))
+            .with_max_retries(1)
+            .with_bootstrap_flags([("Payments", true), ("checkout", true), ("search", true)])
+            .with_failure_mode("search", FailureMode::Closed)
+            .build()
+            .unwrap();
+        assert_eq!(client.status(), ClientStatus::Initializing);
+
+        // Outage before anything was fetched
+        assert!(client.refetch().await.is_err());
+        assert_eq!(client.status(), ClientStatus::ColdStartDegraded);
+        assert!(client.is("payments").enabled().await);
+        assert!(client.get_multiple(&["checkout"]).await["checkout"]);
+        assert!(!client.is("search").enabled().await);
+        assert!(!client.is("unlisted").enabled().await);
+
+        // Once the API answers, its flags win and bootstrap values are unused
+        up.store(true, Ordering::SeqCst);
+        client.refetch().await.unwrap();
+        assert_eq!(client.status(), ClientStatus::Healthy);
+        assert!(!client.is("payments").enabled().await);
+        assert!(!client.is("checkout").enabled().await);
+
+        // A later outage republishes local flags only, but is not a cold start
+        up.store(false, Ordering::SeqCst);
+        assert!(client.refetch().await.is_err());
+        assert_eq!(client.status(), ClientStatus::Degraded);
+        assert!(!client.is("checkout").enabled().await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_outage_after_api_flags_with_local_only_if_api_missing() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        use crate::flag::Details;
+        use crate::transport::Transport;
+        use crate::{ApiResponse, ClientStatus, FlagError, LocalFlagPrecedence};
+
+        struct FlakyTransport(Arc<AtomicBool>);
+
+        #[async_trait::async_trait]
+        impl Transport for FlakyTransport {
+            async fn fetch_flags(&self, _auth: Option<&Auth>) -> Result<ApiResponse, FlagError> {
+                if !self.0.load(Ordering::SeqCst) {
+                    return Err(FlagError::ApiError("Unexpected status code: 503".to_string()));
+                }
+                Ok(ApiResponse {
+                    interval_allowed: 60,
+                    flags: vec![FeatureFlag {
+                        enabled: false,
+                        details: Details { name: "payments".to_string(), id: "1".to_string(), ..Default::default() },
+                    }],
+                    skipped: vec![],
+                    version: None,
+                })
+            }
+        }
+
+        let up = Arc::new(AtomicBool::new(true));
+        let client = Client::builder()
+            .with_auth(Auth {
+                project_id: "test-project".to_string(),
+                agent_id: "test-agent".to_string(),
+                environment_id: "test-env".to_string(),
+                company_id: None,
+                agent_secret: None,
+            })
+            .with_transport(FlakyTransport(Arc::clone(&up)))
+            .with_max_retries(1)
+            .with_local_precedence(LocalFlagPrecedence::LocalOnlyIfApiMissing)
+            .with_bootstrap_flags([("checkout", true)])
+            .build()
+            .unwrap();
+
+        client.refetch().await.unwrap();
+        assert_eq!(client.status(), ClientStatus::Healthy);
+
+        up.store(false, Ordering::SeqCst);
+        assert!(client.refetch().await.is_err());
+        assert_eq!(client.status(), ClientStatus::Degraded);
+        assert!(!client.is("checkout").enabled().await);
+    }
+
+    #[tokio::test]
+    async fn test_ambient_context_scope() {
+        use crate::context::{self, EvaluationContext};
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "intervalAllowed": 60,
+                "flags": [{"enabled": true, "details": {"name": "eu-only", "id": "1", "rules": [
+                    {"attribute": "region", "operator": "in", "values": ["eu-west-1"]}
+                ]}}]
+            })))
+            .mount(&mock_server)
+            .await;
+        let client = create_test_client(&mock_server).await;
+
+        async fn nested(client: &Client) -> bool {
+            client.is("eu-only").enabled().await
+        }
+
+        // Rules only apply to evaluations with a context
+        assert!(nested(&client).await);
+        assert!(context::current().is_none());
+        let eu = EvaluationContext::new().with_region("eu-west-1");
+        let us = EvaluationContext::new().with_region("us-east-1");
+        context::scope(us.clone(), async {
+            assert_eq!(context::current().as_deref(), Some(&us));
+            assert!(!nested(&client).await);
+            assert!(!client.flag("eu-only").enabled().await);
+            // An explicit context wins over the ambient one, and scopes nest
+            assert!(client.is("eu-only").with_context(&eu).enabled().await);
+            assert!(context::scope(eu.clone(), nested(&client)).await);
+            assert!(!nested(&client).await);
+
+            // Spawned tasks do not inherit the scope
+            let spawned = client.clone();
+            assert!(tokio::spawn(async move { nested(&spawned).await }).await.unwrap());
+        })
+        .await;
+        assert!(nested(&client).await);
+    }
+
+    #[test]
+    fn test_evaluation_context_builder() {
+        use crate::context::{EvaluationContext, MAX_ATTRIBUTES, MAX_VALUE_LEN};
+
+        let built = EvaluationContext::builder()
+            .user_id("user-42")
+            .tenant("company-42")
+            .app_version("2.4.1")
+            .region("eu-west-1")
+            .attribute("plan", "pro")
+            .build()
+            .unwrap();
+        let chained = EvaluationContext::new()
+            .with_user_id("user-42")
+            .with_tenant("company-42")
+            .with_app_version("2.4.1")
+            .with_region("eu-west-1")
+            .with_attribute("plan", "pro");
+        assert_eq!(built, chained);
+        assert_eq!(built.memo_key(), chained.memo_key());
+        assert_ne!(built.memo_key(), chained.clone().with_attribute("plan", "free").memo_key());
+        assert_eq!(built.attribute("plan"), Some("pro"));
+        assert!(!format!("{:?}", built).contains("key"));
+
+        let err = EvaluationContext::builder().attribute("user_id", "user-42").build().unwrap_err();
+        assert_eq!(err.code(), "builder");
+        assert!(err.to_string().contains("'user_id' is reserved"), "{}", err);
+        assert!(EvaluationContext::builder().attribute("", "x").build().is_err());
+        assert!(EvaluationContext::builder().user_id("u".repeat(MAX_VALUE_LEN)).build().is_ok());
+        assert!(EvaluationContext::builder().user_id("u".repeat(MAX_VALUE_LEN + 1)).build().is_err());
+        assert!(EvaluationContext::builder().attribute("plan", "p".repeat(MAX_VALUE_LEN + 1)).build().is_err());
+        let many = (0..=MAX_ATTRIBUTES).fold(EvaluationContext::builder(), |b, i| b.attribute(format!("a{}", i), "x"));
+        assert!(many.build().is_err());
+    }
 }
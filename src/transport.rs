@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::flag::{Details, FeatureFlag, FlagSnapshot};
+use crate::{ApiResponse, Auth, FlagError, FlagsDelta};
+
+pub mod bundle;
+#[cfg(feature = "remote")]
+pub mod flagd;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "remote")]
+pub(crate) mod http;
+#[cfg(feature = "remote")]
+pub(crate) mod json_stream;
+pub mod signing;
+#[cfg(feature = "remote")]
+mod v2;
+
+#[cfg(feature = "remote")]
+pub use http::{HttpTransport, RequestInterceptor};
+#[cfg(feature = "remote")]
+pub(crate) use http::parse_base_url;
+
+/// Newest flags payload schema the client understands, requested with the
+/// `X-API-Version` header. Servers reply with the version they used in the same
+/// header; a reply without it is version 1. Delta responses always use version 1.
+pub const API_VERSION: u32 = 2;
+
+/// Default `User-Agent`, naming the SDK and its version.
+pub const USER_AGENT: &str = concat!("flags-rs/", env!("CARGO_PKG_VERSION"));
+
+/// Produces the `X-Request-ID` for one API call.
+pub type RequestIdGenerator = Arc<dyn Fn() -> String + Send + Sync>;
+
+/// Most pages followed for one fetch before giving up, guarding against a
+/// server that keeps returning `next` links.
+pub const MAX_PAGES: usize = 100;
+
+/// A backend the client fetches flags from.
+///
+/// The client owns caching, retries, local overrides and the circuit breaker;
+/// a transport only performs a single fetch for the given credentials.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn fetch_flags(&self, auth: Option<&Auth>) -> Result<ApiResponse, FlagError>;
+
+    /// Fetch what changed since `version`, a value previously returned in
+    /// [`ApiResponse::version`]. Backends without incremental refresh return
+    /// the full flag set.
+    async fn fetch_flags_since(&self, auth: Option<&Auth>, version: &str) -> Result<FlagsUpdate, FlagError> {
+        let _ = version;
+        self.fetch_flags(auth).await.map(FlagsUpdate::Full)
+    }
+
+    /// Whether the backend needs flags.gg credentials. When it does and the
+    /// client has no [`Auth`], only local flags are served.
+    fn requires_auth(&self) -> bool {
+        true
+    }
+}
+
+/// Result of [`Transport::fetch_flags_since`].
+#[derive(Debug, Clone)]
+pub enum FlagsUpdate {
+    /// The complete flag set, e.g. when the server no longer knows the version.
+    Full(ApiResponse),
+    /// Only the flags that changed.
+    Delta(FlagsDelta),
+    /// Nothing changed since the version.
+    NotModified,
+}
+
+#[cfg_attr(not(any(feature = "remote", feature = "grpc")), allow(dead_code))]
+pub(crate) fn require_auth(auth: Option<&Auth>) -> Result<&Auth, FlagError> {
+    auth.ok_or_else(|| FlagError::AuthError("Authentication is required".to_string()))
+}
+
+/// Connection settings for the reqwest client behind `HttpTransport`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct HttpOptions {
+    pub(crate) timeout: Duration,
+    pub(crate) pool_idle_timeout: Option<Option<Duration>>,
+    pub(crate) pool_max_idle_per_host: Option<usize>,
+    pub(crate) tcp_keepalive: Option<Option<Duration>>,
+    pub(crate) http2_prior_knowledge: bool,
+    // Domain to the addresses used instead of resolving it
+    pub(crate) resolve: BTreeMap<String, Vec<SocketAddr>>,
+    #[cfg(unix)]
+    pub(crate) unix_socket: Option<PathBuf>,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            pool_idle_timeout: None,
+            pool_max_idle_per_host: None,
+            tcp_keepalive: None,
+            http2_prior_knowledge: false,
+            resolve: BTreeMap::new(),
+            #[cfg(unix)]
+            unix_socket: None,
+        }
+    }
+}
+
+/// Serves the flags of a [`FlagSnapshot`](crate::flag::FlagSnapshot) for
+/// [`Client::from_snapshot`](crate::Client::from_snapshot).
+pub(crate) struct SnapshotTransport {
+    flags: Vec<FeatureFlag>,
+}
+
+impl SnapshotTransport {
+    pub(crate) fn new(snapshot: &FlagSnapshot) -> Self {
+        let mut flags: Vec<FeatureFlag> = snapshot
+            .flags()
+            .iter()
+            .map(|(name, enabled)| FeatureFlag {
+                enabled: *enabled,
+                details: Details {
+                    name: name.clone(),
+                    id: name.clone(),
+                    ..Default::default()
+                },
+            })
+            .collect();
+        flags.sort_by(|a, b| a.details.name.cmp(&b.details.name));
+        Self { flags }
+    }
+}
+
+#[async_trait]
+impl Transport for SnapshotTransport {
+    async fn fetch_flags(&self, _auth: Option<&Auth>) -> Result<ApiResponse, FlagError> {
+        Ok(ApiResponse {
+            interval_allowed: 0,
+            flags: self.flags.clone(),
+            skipped: Vec::new(),
+            version: None,
+        })
+    }
+
+    fn requires_auth(&self) -> bool {
+        false
+    }
+}
+
+/// The transport of clients built without the `remote` feature or a
+/// [`Transport`] of their own. Such clients have no credentials, so it is
+/// never asked to fetch.
+#[cfg(not(feature = "remote"))]
+pub(crate) struct LocalOnly;
+
+#[cfg(not(feature = "remote"))]
+#[async_trait]
+impl Transport for LocalOnly {
+    async fn fetch_flags(&self, _auth: Option<&Auth>) -> Result<ApiResponse, FlagError> {
+        Err(FlagError::ApiError("Built without the `remote` feature".to_string()))
+    }
+}
@@ -0,0 +1,102 @@
+//! Signed flag bundles as the only flag source, for air-gapped deployments.
+//!
+//! A bundle exported from flags.gg is a JSON file
+//! `{"payload": "...", "signature": "..."}`. `payload` is the flags response
+//! (`{"intervalAllowed": 60, "flags": [...]}`) as a string, and `signature` is
+//! the hex Ed25519 signature of its UTF-8 bytes. The bundle is verified with a
+//! public key embedded in the application, so a modified bundle is rejected
+//! rather than served.
+//!
+//! [`BundleTransport`] never makes network calls and needs no [`Auth`]. It
+//! re-reads the file on every refresh, so a new bundle can be dropped in
+//! place. A bundle that fails verification is never served: the refresh
+//! fails as it would for an unreachable API.
+//!
+//! # Example
+//! ```no_run
+//! # use flags_rs::Client;
+//! # use flags_rs::transport::bundle::BundleTransport;
+//! # fn example() -> Result<(), flags_rs::FlagError> {
+//! # const PUBLIC_KEY: &[u8; 32] = &[0; 32];
+//! // Embedded at compile time, e.g. `include_bytes!("flags.pub")`
+//! let client = Client::builder()
+//!     .with_transport(BundleTransport::from_file("/etc/flags/bundle.json", PUBLIC_KEY)?)
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use ring::signature::{UnparsedPublicKey, ED25519};
+use serde::Deserialize;
+
+use crate::transport::signing::decode_hex;
+use crate::transport::Transport;
+use crate::{ApiResponse, Auth, FlagError};
+
+/// Length of an Ed25519 public key.
+pub const PUBLIC_KEY_LEN: usize = 32;
+
+#[derive(Deserialize)]
+struct Bundle {
+    payload: String,
+    signature: String,
+}
+
+pub struct BundleTransport {
+    path: PathBuf,
+    public_key: [u8; PUBLIC_KEY_LEN],
+}
+
+impl BundleTransport {
+    /// Serve the bundle at `path`, verified with the raw 32-byte Ed25519
+    /// `public_key`. The bundle is checked now, so a missing or tampered
+    /// bundle fails the build instead of the first refresh.
+    pub fn from_file(path: impl AsRef<Path>, public_key: &[u8]) -> Result<Self, FlagError> {
+        let public_key: [u8; PUBLIC_KEY_LEN] = public_key.try_into().map_err(|_| {
+            FlagError::BuilderError(format!(
+                "Bundle public key must be {} bytes, got {}",
+                PUBLIC_KEY_LEN,
+                public_key.len()
+            ))
+        })?;
+        let transport = Self {
+            path: path.as_ref().to_path_buf(),
+            public_key,
+        };
+        let bytes = std::fs::read(&transport.path).map_err(|e| transport.read_error(e))?;
+        verify(&bytes, &transport.public_key)?;
+        Ok(transport)
+    }
+
+    fn read_error(&self, e: std::io::Error) -> FlagError {
+        FlagError::InvalidBundle(format!("Failed to read {}: {}", self.path.display(), e))
+    }
+}
+
+/// Check the signature of `bundle` against `public_key` and decode its flags.
+pub fn verify(bundle: &[u8], public_key: &[u8]) -> Result<ApiResponse, FlagError> {
+    let bundle: Bundle =
+        serde_json::from_slice(bundle).map_err(|e| FlagError::InvalidBundle(format!("Malformed bundle: {}", e)))?;
+    let signature = decode_hex(bundle.signature.trim())
+        .ok_or_else(|| FlagError::InvalidBundle("Signature is not valid hex".to_string()))?;
+    UnparsedPublicKey::new(&ED25519, public_key)
+        .verify(bundle.payload.as_bytes(), &signature)
+        .map_err(|_| FlagError::InvalidBundle("Signature does not match the public key".to_string()))?;
+    serde_json::from_str(&bundle.payload)
+        .map_err(|e| FlagError::InvalidBundle(format!("Malformed bundle payload: {}", e)))
+}
+
+#[async_trait]
+impl Transport for BundleTransport {
+    async fn fetch_flags(&self, _auth: Option<&Auth>) -> Result<ApiResponse, FlagError> {
+        let bytes = tokio::fs::read(&self.path).await.map_err(|e| self.read_error(e))?;
+        verify(&bytes, &self.public_key)
+    }
+
+    fn requires_auth(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,195 @@
+//! OpenFeature [flagd](https://flagd.dev) as an alternative backend.
+//!
+//! Flags are resolved in bulk through the `ResolveAll` RPC of flagd's
+//! `flagd.evaluation.v1` service, over HTTP (Connect protocol, JSON) or, with
+//! the `grpc` feature, over gRPC. flagd has no notion of flags.gg credentials,
+//! so the client needs no [`Auth`]. Only boolean flags are kept.
+//!
+//! # Example
+//! ```no_run
+//! # use flags_rs::Client;
+//! # use flags_rs::transport::flagd::FlagdTransport;
+//! # fn example() -> Result<(), flags_rs::FlagError> {
+//! let client = Client::builder()
+//!     .with_transport(FlagdTransport::http("http://localhost:8013")?)
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::flag::{Details, FeatureFlag};
+use crate::transport::Transport;
+use crate::{ApiResponse, Auth, FlagError};
+
+pub const RESOLVE_ALL_PATH: &str = "/flagd.evaluation.v1.Service/ResolveAll";
+
+const DEFAULT_REFRESH_INTERVAL: i32 = 60;
+
+enum Backend {
+    Http {
+        base_url: String,
+        http_client: reqwest::Client,
+    },
+    #[cfg(feature = "grpc")]
+    Grpc(tonic::transport::Channel),
+}
+
+pub struct FlagdTransport {
+    backend: Backend,
+    interval_allowed: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResolveAllResponse {
+    #[serde(default)]
+    flags: HashMap<String, ResolvedFlag>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ResolvedFlag {
+    bool_value: Option<bool>,
+}
+
+impl FlagdTransport {
+    /// Resolve flags over flagd's HTTP endpoint (port 8013 by default).
+    pub fn http(base_url: &str) -> Result<Self, FlagError> {
+        let http_client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .map_err(|e| FlagError::BuilderError(format!("Failed to build HTTP client: {}", e)))?;
+
+        Ok(Self {
+            backend: Backend::Http {
+                base_url: base_url.trim_end_matches('/').to_string(),
+                http_client,
+            },
+            interval_allowed: DEFAULT_REFRESH_INTERVAL,
+        })
+    }
+
+    /// Resolve flags over flagd's gRPC endpoint. Connects lazily.
+    #[cfg(feature = "grpc")]
+    pub fn grpc(endpoint: &str) -> Result<Self, FlagError> {
+        let channel = tonic::transport::Endpoint::from_shared(endpoint.to_string())
+            .map_err(|e| FlagError::BuilderError(format!("Invalid gRPC endpoint: {}", e)))?
+            .connect_timeout(Duration::from_secs(10))
+            .connect_lazy();
+
+        Ok(Self {
+            backend: Backend::Grpc(channel),
+            interval_allowed: DEFAULT_REFRESH_INTERVAL,
+        })
+    }
+
+    /// flagd does not dictate a refresh interval; defaults to 60 seconds.
+    pub fn with_refresh_interval(mut self, seconds: i32) -> Self {
+        self.interval_allowed = seconds;
+        self
+    }
+
+    fn to_response(&self, flags: impl IntoIterator<Item = (String, Option<bool>)>) -> ApiResponse {
+        let mut flags: Vec<FeatureFlag> = flags
+            .into_iter()
+            .filter_map(|(key, value)| {
+                value.map(|enabled| FeatureFlag {
+                    enabled,
+                    details: Details {
+                        name: key.clone(),
+                        id: key,
+                        ..Default::default()
+                    },
+                })
+            })
+            .collect();
+        flags.sort_by(|a, b| a.details.name.cmp(&b.details.name));
+
+        ApiResponse {
+            interval_allowed: self.interval_allowed,
+            flags,
+            skipped: Vec::new(),
+            version: None,
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for FlagdTransport {
+    async fn fetch_flags(&self, _auth: Option<&Auth>) -> Result<ApiResponse, FlagError> {
+        match &self.backend {
+            Backend::Http { base_url, http_client } => {
+                let response = http_client
+                    .post(format!("{}{}", base_url, RESOLVE_ALL_PATH))
+                    .json(&serde_json::json!({ "context": {} }))
+                    .send()
+                    .await?;
+
+                if !response.status().is_success() {
+                    return Err(FlagError::ApiStatus {
+                        status: response.status().as_u16(),
+                        message: format!("Unexpected status code from flagd: {}", response.status()),
+                    });
+                }
+
+                let resolved = response.json::<ResolveAllResponse>().await?;
+                Ok(self.to_response(resolved.flags.into_iter().map(|(k, f)| (k, f.bool_value))))
+            }
+            #[cfg(feature = "grpc")]
+            Backend::Grpc(channel) => {
+                use tonic::codegen::http::uri::PathAndQuery;
+                use tonic_prost::ProstCodec;
+
+                let mut grpc = tonic::client::Grpc::new(channel.clone());
+                grpc.ready()
+                    .await
+                    .map_err(|e| FlagError::ApiError(format!("flagd not ready: {}", e)))?;
+
+                let mut request = tonic::Request::new(proto::ResolveAllRequest {});
+                request.set_timeout(Duration::from_secs(10));
+                let codec: ProstCodec<proto::ResolveAllRequest, proto::ResolveAllResponse> = ProstCodec::default();
+                let response = grpc
+                    .unary(request, PathAndQuery::from_static(RESOLVE_ALL_PATH), codec)
+                    .await
+                    .map_err(|status| FlagError::ApiError(format!("flagd gRPC error: {}", status)))?;
+
+                Ok(self.to_response(response.into_inner().flags.into_iter().map(|(k, f)| (k, f.bool_value))))
+            }
+        }
+    }
+
+    fn requires_auth(&self) -> bool {
+        false
+    }
+}
+
+/// The subset of `flagd.evaluation.v1` messages the transport needs. Fields
+/// that are not declared (non-boolean values, metadata) are skipped on decode.
+#[cfg(feature = "grpc")]
+pub mod proto {
+    use std::collections::HashMap;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ResolveAllRequest {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct AnyFlag {
+        #[prost(string, tag = "1")]
+        pub reason: String,
+        #[prost(string, tag = "2")]
+        pub variant: String,
+        #[prost(bool, optional, tag = "3")]
+        pub bool_value: Option<bool>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ResolveAllResponse {
+        #[prost(map = "string, message", tag = "1")]
+        pub flags: HashMap<String, AnyFlag>,
+    }
+}
@@ -0,0 +1,207 @@
+//! gRPC transport for flags.gg, enabled with the `grpc` feature.
+//!
+//! Messages are declared by hand with `prost` instead of being generated, so
+//! building the crate never needs `protoc`. `proto/flags.proto` is the schema
+//! they mirror.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use log::{error, warn};
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::metadata::MetadataValue;
+use tonic::transport::{Channel, Endpoint};
+use tonic_prost::ProstCodec;
+
+use crate::flag::{Details, FeatureFlag};
+use crate::transport::{require_auth, signing, Transport};
+use crate::{ApiResponse, Auth, Client, FlagError};
+
+pub const GET_FLAGS_PATH: &str = "/flags.v1.FlagsService/GetFlags";
+pub const WATCH_FLAGS_PATH: &str = "/flags.v1.FlagsService/WatchFlags";
+
+const MAX_WATCH_BACKOFF: Duration = Duration::from_secs(60);
+
+pub mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FlagsRequest {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Flag {
+        #[prost(bool, tag = "1")]
+        pub enabled: bool,
+        #[prost(string, tag = "2")]
+        pub name: String,
+        #[prost(string, tag = "3")]
+        pub id: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FlagsResponse {
+        #[prost(int32, tag = "1")]
+        pub interval_allowed: i32,
+        #[prost(message, repeated, tag = "2")]
+        pub flags: Vec<Flag>,
+    }
+}
+
+impl From<proto::FlagsResponse> for ApiResponse {
+    fn from(resp: proto::FlagsResponse) -> Self {
+        ApiResponse {
+            interval_allowed: resp.interval_allowed,
+            flags: resp
+                .flags
+                .into_iter()
+                .map(|f| FeatureFlag {
+                    enabled: f.enabled,
+                    details: Details { name: f.name, id: f.id, ..Default::default() },
+                })
+                .collect(),
+            skipped: Vec::new(),
+            version: None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GrpcTransport {
+    channel: Channel,
+}
+
+impl GrpcTransport {
+    /// Create a transport for `endpoint` (e.g. `https://grpc.flags.gg`).
+    /// The connection is established lazily on the first call.
+    pub fn new(endpoint: &str) -> Result<Self, FlagError> {
+        let channel = Endpoint::from_shared(endpoint.to_string())
+            .map_err(|e| FlagError::BuilderError(format!("Invalid gRPC endpoint: {}", e)))?
+            .connect_timeout(Duration::from_secs(10))
+            .connect_lazy();
+
+        Ok(Self { channel })
+    }
+
+    /// Use an already configured channel, e.g. one with custom TLS settings.
+    pub fn from_channel(channel: Channel) -> Self {
+        Self { channel }
+    }
+
+    fn request(auth: &Auth) -> Result<tonic::Request<proto::FlagsRequest>, FlagError> {
+        let mut request = tonic::Request::new(proto::FlagsRequest {});
+        let metadata = request.metadata_mut();
+        metadata.insert("x-project-id", MetadataValue::try_from(auth.project_id.as_str())
+            .map_err(|_| FlagError::invalid_header("project_id"))?);
+        metadata.insert("x-agent-id", MetadataValue::try_from(auth.agent_id.as_str())
+            .map_err(|_| FlagError::invalid_header("agent_id"))?);
+        metadata.insert("x-environment-id", MetadataValue::try_from(auth.environment_id.as_str())
+            .map_err(|_| FlagError::invalid_header("environment_id"))?);
+        if let Some(company_id) = &auth.company_id {
+            metadata.insert("x-company-id", MetadataValue::try_from(company_id.as_str())
+                .map_err(|_| FlagError::invalid_header("company_id"))?);
+        }
+        if let Some(secret) = &auth.agent_secret {
+            let timestamp = chrono::Utc::now().timestamp();
+            let signature = signing::sign(secret, timestamp, &auth.project_id, &auth.agent_id);
+            metadata.insert("x-flags-timestamp", MetadataValue::from(timestamp));
+            metadata.insert("x-flags-signature", MetadataValue::try_from(signature.as_str())
+                .map_err(|_| FlagError::AuthError("Invalid request signature".to_string()))?);
+        }
+        Ok(request)
+    }
+
+    async fn ready_client(&self) -> Result<tonic::client::Grpc<Channel>, FlagError> {
+        let mut grpc = tonic::client::Grpc::new(self.channel.clone());
+        grpc.ready()
+            .await
+            .map_err(|e| FlagError::ApiError(format!("gRPC service not ready: {}", e)))?;
+        Ok(grpc)
+    }
+
+    /// Open the server-streaming `WatchFlags` RPC. Each item is a complete flag set.
+    pub async fn watch_flags(
+        &self,
+        auth: &Auth,
+    ) -> Result<impl Stream<Item = Result<ApiResponse, FlagError>>, FlagError> {
+        let mut grpc = self.ready_client().await?;
+        let codec: ProstCodec<proto::FlagsRequest, proto::FlagsResponse> = ProstCodec::default();
+        let response = grpc
+            .server_streaming(Self::request(auth)?, PathAndQuery::from_static(WATCH_FLAGS_PATH), codec)
+            .await
+            .map_err(status_error)?;
+
+        Ok(response
+            .into_inner()
+            .map(|item| item.map(ApiResponse::from).map_err(status_error)))
+    }
+
+    /// Spawn a task that applies streamed updates to `client`'s cache as they
+    /// arrive, reconnecting with exponential backoff whenever the stream ends.
+    pub fn spawn_watch(&self, client: &Client) -> tokio::task::JoinHandle<()> {
+        let transport = self.clone();
+        let client = client.clone();
+
+        tokio::spawn(async move {
+            let mut backoff = Duration::from_secs(1);
+            loop {
+                let auth = match client.auth.load_full() {
+                    Some(auth) => auth,
+                    None => {
+                        warn!("gRPC watch requires authentication, stopping.");
+                        return;
+                    }
+                };
+
+                match transport.watch_flags(&auth).await {
+                    Ok(stream) => {
+                        futures::pin_mut!(stream);
+                        while let Some(update) = stream.next().await {
+                            match update {
+                                Ok(resp) => {
+                                    backoff = Duration::from_secs(1);
+                                    if let Err(e) = client.apply_api_response(resp).await {
+                                        error!("Failed to apply streamed flags: {}", e);
+                                        client.handle_error(&e);
+                                    }
+                                }
+                                Err(e) => {
+                                    error!("gRPC flag stream failed: {}", e);
+                                    client.handle_error(&e);
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        error!("Failed to open gRPC flag stream: {}", e);
+                        client.handle_error(&e);
+                    }
+                }
+
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_WATCH_BACKOFF);
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl Transport for GrpcTransport {
+    async fn fetch_flags(&self, auth: Option<&Auth>) -> Result<ApiResponse, FlagError> {
+        let auth = require_auth(auth)?;
+        let mut grpc = self.ready_client().await?;
+        let mut request = Self::request(auth)?;
+        request.set_timeout(Duration::from_secs(10));
+
+        let codec: ProstCodec<proto::FlagsRequest, proto::FlagsResponse> = ProstCodec::default();
+        let response = grpc
+            .unary(request, PathAndQuery::from_static(GET_FLAGS_PATH), codec)
+            .await
+            .map_err(status_error)?;
+
+        Ok(response.into_inner().into())
+    }
+}
+
+fn status_error(status: tonic::Status) -> FlagError {
+    FlagError::ApiError(format!("gRPC error: {}", status))
+}
@@ -0,0 +1,420 @@
+//! The default transport, the flags.gg HTTP API over reqwest, enabled with
+//! the `remote` feature.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+
+use super::json_stream::FlagsStreamParser;
+use super::signing::{self, ClockOffset};
+use super::{require_auth, HttpOptions, v2, FlagsUpdate, RequestIdGenerator, Transport, API_VERSION, MAX_PAGES, USER_AGENT};
+use crate::{ApiResponse, Auth, FlagError, WireFormat};
+
+/// Adjusts each API request just before it is sent, see
+/// [`ClientBuilder::with_request_interceptor`](crate::ClientBuilder::with_request_interceptor).
+pub type RequestInterceptor = Arc<dyn Fn(&mut reqwest::Request) -> Result<(), FlagError> + Send + Sync>;
+
+/// Parse an API base URL. It must be an absolute `http` or `https` URL without
+/// a query or fragment. A path prefix such as `https://gw.corp/flags-gg/api` is
+/// kept, see [`endpoint_url`].
+pub(crate) fn parse_base_url(base_url: &str) -> Result<reqwest::Url, String> {
+    let url = reqwest::Url::parse(base_url.trim()).map_err(|e| format!("Invalid base URL \"{}\": {}", base_url, e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("Invalid base URL \"{}\": scheme must be http or https", base_url));
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        return Err(format!("Invalid base URL \"{}\": missing host", base_url));
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(format!("Invalid base URL \"{}\": query and fragment are not allowed", base_url));
+    }
+    Ok(url)
+}
+
+/// `endpoint` below the base URL's path, whether or not the base ends in `/`:
+/// `https://gw.corp/api` and `https://gw.corp/api/` both give `https://gw.corp/api/flags`.
+pub(crate) fn endpoint_url(base_url: &str, endpoint: &str) -> Result<reqwest::Url, String> {
+    let mut url = parse_base_url(base_url)?;
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    url.join(endpoint)
+        .map_err(|e| format!("Invalid {} URL: {}", endpoint, e))
+}
+
+impl HttpOptions {
+    /// Unset options keep reqwest's defaults.
+    pub(crate) fn build_client(&self) -> Result<reqwest::Client, FlagError> {
+        let mut builder = reqwest::Client::builder().timeout(self.timeout);
+        if let Some(idle_timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(idle_timeout);
+        }
+        if let Some(max_idle) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle);
+        }
+        if let Some(keepalive) = self.tcp_keepalive {
+            builder = builder.tcp_keepalive(keepalive);
+        }
+        if self.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+        for (domain, addrs) in &self.resolve {
+            builder = builder.resolve_to_addrs(domain, addrs);
+        }
+        #[cfg(unix)]
+        if let Some(path) = &self.unix_socket {
+            builder = builder.unix_socket(path.as_path());
+        }
+
+        builder
+            .build()
+            .map_err(|e| FlagError::BuilderError(format!("Failed to build HTTP client: {}", e)))
+    }
+}
+
+/// The default transport, fetching `/flags` from the flags.gg HTTP API.
+pub struct HttpTransport {
+    base_url: String,
+    http_client: reqwest::Client,
+    wire_format: WireFormat,
+    user_agent: HeaderValue,
+    request_id: RequestIdGenerator,
+    interceptor: Option<RequestInterceptor>,
+    clock: ClockOffset,
+}
+
+impl HttpTransport {
+    pub fn new(base_url: &str, http_client: reqwest::Client, wire_format: WireFormat) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+            http_client,
+            wire_format,
+            user_agent: HeaderValue::from_static(USER_AGENT),
+            request_id: Arc::new(random_request_id),
+            interceptor: None,
+            clock: ClockOffset::default(),
+        }
+    }
+
+    /// Produce each request's `X-Request-ID` with `generator` instead of a
+    /// random UUID.
+    pub fn with_request_id_generator(mut self, generator: RequestIdGenerator) -> Self {
+        self.request_id = generator;
+        self
+    }
+
+    /// Pass each request to `interceptor` once the client's own headers are
+    /// set, right before it is sent.
+    pub fn with_request_interceptor(mut self, interceptor: RequestInterceptor) -> Self {
+        self.interceptor = Some(interceptor);
+        self
+    }
+
+    /// Append the calling service to the [`USER_AGENT`], e.g.
+    /// `checkout-service/3.2.1` gives `flags-rs/<version> checkout-service/3.2.1`.
+    pub fn with_app_identifier(mut self, app: &str) -> Result<Self, FlagError> {
+        self.user_agent = app_user_agent(app)?;
+        Ok(self)
+    }
+}
+
+pub(crate) fn app_user_agent(app: &str) -> Result<HeaderValue, FlagError> {
+    let app = app.trim();
+    if app.is_empty() {
+        return Err(FlagError::BuilderError("App identifier cannot be empty".to_string()));
+    }
+    HeaderValue::from_str(&format!("{} {}", USER_AGENT, app))
+        .map_err(|_| FlagError::BuilderError(format!("Invalid app identifier: {}", app)))
+}
+
+/// A random version 4 UUID.
+pub(crate) fn random_request_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let seed = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let half = |salt: u64| {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(seed);
+        hasher.write_u64(salt);
+        hasher.finish()
+    };
+    let bits = (u128::from(half(0)) << 64 | u128::from(half(1)))
+        & !(0xf000 << 64 | 0xc000 << 48)
+        | (0x4000 << 64 | 0x8000 << 48);
+    let hex = format!("{:032x}", bits);
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+impl HttpTransport {
+    fn flags_url(&self, since: Option<&str>) -> Result<reqwest::Url, FlagError> {
+        let mut url = endpoint_url(&self.base_url, "flags").map_err(FlagError::ApiError)?;
+        if let Some(version) = since {
+            url.query_pairs_mut().append_pair("since", version);
+        }
+        Ok(url)
+    }
+
+    /// Send one request, returning the response and the `X-Request-ID` it
+    /// carried. Errors once the ID is assigned are tagged with it. A signed
+    /// request rejected with 401 is sent once more if the response's `Date`
+    /// shows our clock was too far off.
+    async fn get(&self, auth: Option<&Auth>, url: reqwest::Url) -> Result<(reqwest::Response, String), FlagError> {
+        let auth = require_auth(auth)?;
+        let (response, request_id, resynced) = self.send(auth, url.clone()).await?;
+        if resynced && response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            let (response, request_id, _) = self.send(auth, url).await?;
+            return Ok((response, request_id));
+        }
+        Ok((response, request_id))
+    }
+
+    /// Send `url` once, also returning whether the clock offset was corrected.
+    async fn send(&self, auth: &Auth, url: reqwest::Url) -> Result<(reqwest::Response, String, bool), FlagError> {
+        let mut headers = HeaderMap::new();
+        headers.insert("User-Agent", self.user_agent.clone());
+        headers.insert("Accept", HeaderValue::from_static(self.wire_format.accept()));
+        headers.insert("Content-Type", HeaderValue::from_static("application/json"));
+        headers.insert("X-API-Version", HeaderValue::from(API_VERSION));
+        headers.insert("X-Project-ID", HeaderValue::from_str(&auth.project_id)
+            .map_err(|_| FlagError::invalid_header("project_id"))?);
+        headers.insert("X-Agent-ID", HeaderValue::from_str(&auth.agent_id)
+            .map_err(|_| FlagError::invalid_header("agent_id"))?);
+        headers.insert("X-Environment-ID", HeaderValue::from_str(&auth.environment_id)
+            .map_err(|_| FlagError::invalid_header("environment_id"))?);
+        if let Some(company_id) = &auth.company_id {
+            headers.insert("X-Company-ID", HeaderValue::from_str(company_id)
+                .map_err(|_| FlagError::invalid_header("company_id"))?);
+        }
+        if let Some(secret) = &auth.agent_secret {
+            let timestamp = self.clock.now();
+            let signature = signing::sign(secret, timestamp, &auth.project_id, &auth.agent_id);
+            headers.insert(signing::TIMESTAMP_HEADER, HeaderValue::from(timestamp));
+            headers.insert(signing::SIGNATURE_HEADER, HeaderValue::from_str(&signature)
+                .map_err(|_| FlagError::AuthError("Invalid request signature".to_string()))?);
+        }
+
+        let request_id = (self.request_id)();
+        headers.insert("X-Request-ID", HeaderValue::from_str(&request_id)
+            .map_err(|_| FlagError::ApiError(format!("Invalid request ID: {}", request_id)))?);
+
+        let mut request = self
+            .http_client
+            .get(url)
+            .headers(headers)
+            .build()
+            .map_err(|e| FlagError::from(e).for_request(&request_id))?;
+        if let Some(interceptor) = &self.interceptor {
+            interceptor(&mut request).map_err(|e| e.for_request(&request_id))?;
+        }
+
+        match self.http_client.execute(request).await {
+            Ok(response) => {
+                let resynced = auth.agent_secret.is_some()
+                    && response
+                        .headers()
+                        .get("Date")
+                        .and_then(|v| v.to_str().ok())
+                        .is_some_and(|date| self.clock.observe(date));
+                Ok((response, request_id, resynced))
+            }
+            Err(e) => Err(FlagError::from(e).for_request(&request_id)),
+        }
+    }
+
+    /// Decode `response` and follow any further pages, announced by a
+    /// `Link: <...>; rel="next"` header or a `next` cursor in the body, into
+    /// one flag set. A cursor is sent as `cursor=<next>` on the first page's
+    /// URL. Interval and version come from the first page. Next pages are only
+    /// fetched from the base URL's scheme, host and port, as each request
+    /// carries the credentials.
+    async fn fetch_pages(&self, auth: Option<&Auth>, response: reqwest::Response, request_id: &str) -> Result<ApiResponse, FlagError> {
+        let origin = self.flags_url(None)?.origin();
+        let first_url = response.url().clone();
+        let mut pages = 1;
+        let mut request_id = request_id.to_string();
+        let (mut full, mut next) = Self::decode_page(response, &first_url)
+            .await
+            .map_err(|e| e.for_request(&request_id))?;
+
+        while let Some(url) = next {
+            if pages >= MAX_PAGES {
+                return Err(FlagError::ApiError(format!("Flags response exceeded {} pages", MAX_PAGES))
+                    .for_request(&request_id));
+            }
+            if url.origin() != origin {
+                return Err(FlagError::ApiError(format!(
+                    "Refusing to follow a next page on another origin: {}",
+                    url.origin().ascii_serialization()
+                ))
+                .for_request(&request_id));
+            }
+            let (response, page_request_id) = self.get(auth, url).await?;
+            request_id = page_request_id;
+            pages += 1;
+
+            let (page, page_next) = Self::decode_page(response, &first_url)
+                .await
+                .map_err(|e| e.for_request(&request_id))?;
+            full.flags.extend(page.flags);
+            full.skipped.extend(page.skipped);
+            next = page_next;
+        }
+
+        Ok(full)
+    }
+
+    async fn decode_page(mut response: reqwest::Response, first_url: &reqwest::Url) -> Result<(ApiResponse, Option<reqwest::Url>), FlagError> {
+        if !response.status().is_success() {
+            return Err(FlagError::ApiStatus {
+                status: response.status().as_u16(),
+                message: format!("Unexpected status code: {}", response.status()),
+            });
+        }
+
+        let link_next = next_link(&response);
+        let schema = response
+            .headers()
+            .get("X-API-Version")
+            .map(|v| v.to_str().ok().and_then(|v| v.trim().parse::<u32>().ok()))
+            .unwrap_or(Some(1));
+
+        let (page, cursor) = match schema {
+            Some(1) if is_msgpack(&response) => {
+                let page: MsgpackPage = decode_msgpack(&response.bytes().await?)?;
+                (page.response, page.next)
+            }
+            Some(1) => {
+                let mut parser = FlagsStreamParser::new();
+                while let Some(chunk) = response.chunk().await? {
+                    parser.feed(&chunk)?;
+                }
+                parser.finish_page()?
+            }
+            Some(2) if is_msgpack(&response) => decode_msgpack_v2(&response.bytes().await?)?,
+            Some(2) => {
+                let mut parser = v2::json_parser();
+                while let Some(chunk) = response.chunk().await? {
+                    parser.feed(&chunk)?;
+                }
+                parser.finish_page()?
+            }
+            _ => {
+                let declared = response.headers().get("X-API-Version").and_then(|v| v.to_str().ok()).unwrap_or("");
+                return Err(FlagError::ApiError(format!("Unsupported API version: {}", declared)));
+            }
+        };
+
+        let next = match (link_next, cursor) {
+            (Some(url), _) => Some(url),
+            (None, Some(cursor)) => {
+                let mut url = first_url.clone();
+                let pairs: Vec<(String, String)> = url
+                    .query_pairs()
+                    .filter(|(k, _)| k != "cursor")
+                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                    .collect();
+                url.query_pairs_mut().clear().extend_pairs(pairs).append_pair("cursor", &cursor);
+                Some(url)
+            }
+            (None, None) => None,
+        };
+        Ok((page, next))
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[cfg_attr(not(feature = "msgpack"), allow(dead_code))]
+struct MsgpackPage {
+    #[serde(flatten)]
+    response: ApiResponse,
+    #[serde(default)]
+    next: Option<String>,
+}
+
+/// The `rel="next"` target of a `Link` header, resolved against the response URL.
+fn next_link(response: &reqwest::Response) -> Option<reqwest::Url> {
+    response
+        .headers()
+        .get_all(reqwest::header::LINK)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .find_map(|link| {
+            let mut parts = link.split(';');
+            let target = parts.next()?.trim().strip_prefix('<')?.strip_suffix('>')?;
+            parts
+                .any(|p| matches!(p.trim().replace(' ', "").as_str(), "rel=\"next\"" | "rel=next"))
+                .then(|| response.url().join(target).ok())
+                .flatten()
+        })
+}
+
+#[async_trait]
+impl Transport for HttpTransport {
+    async fn fetch_flags(&self, auth: Option<&Auth>) -> Result<ApiResponse, FlagError> {
+        let (response, request_id) = self.get(auth, self.flags_url(None)?).await?;
+        self.fetch_pages(auth, response, &request_id).await
+    }
+
+    /// Requests `/flags?since=<version>`. The server answers `226 IM Used` with
+    /// a delta, `304 Not Modified`, `409`/`410` when it no longer knows the
+    /// version (a full fetch follows), or `200` with the full set.
+    async fn fetch_flags_since(&self, auth: Option<&Auth>, version: &str) -> Result<FlagsUpdate, FlagError> {
+        let (response, request_id) = self.get(auth, self.flags_url(Some(version))?).await?;
+
+        match response.status().as_u16() {
+            226 => decode_delta(response).await.map_err(|e| e.for_request(&request_id)),
+            304 => Ok(FlagsUpdate::NotModified),
+            409 | 410 => self.fetch_flags(auth).await.map(FlagsUpdate::Full),
+            _ => self.fetch_pages(auth, response, &request_id).await.map(FlagsUpdate::Full),
+        }
+    }
+}
+
+async fn decode_delta(response: reqwest::Response) -> Result<FlagsUpdate, FlagError> {
+    let msgpack = is_msgpack(&response);
+    let body = response.bytes().await?;
+    let delta = if msgpack {
+        decode_msgpack(&body)?
+    } else {
+        serde_json::from_slice(&body)
+            .map_err(|e| FlagError::ApiError(format!("Invalid delta payload: {}", e)))?
+    };
+    Ok(FlagsUpdate::Delta(delta))
+}
+
+fn is_msgpack(response: &reqwest::Response) -> bool {
+    response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.starts_with("application/msgpack") || ct.starts_with("application/x-msgpack"))
+        .unwrap_or(false)
+}
+
+#[cfg(feature = "msgpack")]
+fn decode_msgpack<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, FlagError> {
+    rmp_serde::from_slice(body)
+        .map_err(|e| FlagError::ApiError(format!("Invalid MessagePack payload: {}", e)))
+}
+
+#[cfg(not(feature = "msgpack"))]
+fn decode_msgpack<T>(_body: &[u8]) -> Result<T, FlagError> {
+    Err(FlagError::ApiError(
+        "Received a MessagePack payload but the `msgpack` feature is disabled".to_string(),
+    ))
+}
+
+#[cfg(feature = "msgpack")]
+fn decode_msgpack_v2(body: &[u8]) -> Result<(ApiResponse, Option<String>), FlagError> {
+    v2::decode_msgpack(body)
+}
+
+#[cfg(not(feature = "msgpack"))]
+fn decode_msgpack_v2(body: &[u8]) -> Result<(ApiResponse, Option<String>), FlagError> {
+    decode_msgpack(body)
+}
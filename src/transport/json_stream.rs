@@ -0,0 +1,305 @@
+//! Incremental parser for the JSON flags payload.
+//!
+//! The body is fed chunk by chunk as it arrives. The top-level object is
+//! scanned byte by byte, and each element of the `flags` array is deserialized
+//! on its own as soon as it is complete, so only one element is ever buffered
+//! instead of the whole response. The payload versions differ only in the
+//! interval's key and the shape of an element, see
+//! [`FlagsStreamParser::with_schema`].
+
+use crate::flag::FeatureFlag;
+use crate::{ApiResponse, FlagError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    ObjectStart,
+    KeyOrEnd,
+    Key,
+    Colon,
+    Value,
+    FlagsElementOrEnd,
+    FlagsElement,
+    FlagsSeparatorOrEnd,
+    MemberValue,
+    SeparatorOrEnd,
+    Done,
+}
+
+/// Tracks where a single JSON value ends while its bytes are being collected.
+#[derive(Default)]
+struct ValueScanner {
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    started: bool,
+    scalar: bool,
+}
+
+enum Scan {
+    /// The byte belongs to the value and the value continues.
+    Continue,
+    /// The byte completes the value.
+    Complete,
+    /// The value ended before this byte, which must be processed again.
+    EndedBefore,
+}
+
+impl ValueScanner {
+    fn scan(&mut self, byte: u8) -> Scan {
+        if !self.started {
+            self.started = true;
+            match byte {
+                b'{' | b'[' => self.depth = 1,
+                b'"' => self.in_string = true,
+                _ => self.scalar = true,
+            }
+            return Scan::Continue;
+        }
+
+        if self.in_string {
+            if self.escaped {
+                self.escaped = false;
+            } else if byte == b'\\' {
+                self.escaped = true;
+            } else if byte == b'"' {
+                self.in_string = false;
+                if self.depth == 0 {
+                    return Scan::Complete;
+                }
+            }
+            return Scan::Continue;
+        }
+
+        if self.scalar {
+            return match byte {
+                b',' | b'}' | b']' | b' ' | b'\t' | b'\r' | b'\n' => Scan::EndedBefore,
+                _ => Scan::Continue,
+            };
+        }
+
+        match byte {
+            b'"' => self.in_string = true,
+            b'{' | b'[' => self.depth += 1,
+            b'}' | b']' => {
+                self.depth -= 1;
+                if self.depth == 0 {
+                    return Scan::Complete;
+                }
+            }
+            _ => {}
+        }
+        Scan::Continue
+    }
+}
+
+/// Decodes one element of `flags`.
+pub(crate) type DecodeFlag = fn(&[u8]) -> serde_json::Result<FeatureFlag>;
+
+pub(crate) struct FlagsStreamParser {
+    interval_key: &'static str,
+    decode: DecodeFlag,
+    state: State,
+    buf: Vec<u8>,
+    scanner: ValueScanner,
+    key: String,
+    interval_allowed: Option<i32>,
+    version: Option<String>,
+    next: Option<String>,
+    flags: Vec<FeatureFlag>,
+    flag_index: usize,
+    skipped: Vec<String>,
+    saw_flags: bool,
+}
+
+impl FlagsStreamParser {
+    /// A parser for the version 1 payload.
+    pub(crate) fn new() -> Self {
+        Self::with_schema("intervalAllowed", |element| serde_json::from_slice(element))
+    }
+
+    /// A parser reading the refresh interval from `interval_key` and each
+    /// element of `flags` with `decode`.
+    pub(crate) fn with_schema(interval_key: &'static str, decode: DecodeFlag) -> Self {
+        Self {
+            interval_key,
+            decode,
+            state: State::ObjectStart,
+            buf: Vec::new(),
+            scanner: ValueScanner::default(),
+            key: String::new(),
+            interval_allowed: None,
+            version: None,
+            next: None,
+            flags: Vec::new(),
+            flag_index: 0,
+            skipped: Vec::new(),
+            saw_flags: false,
+        }
+    }
+
+    pub(crate) fn feed(&mut self, chunk: &[u8]) -> Result<(), FlagError> {
+        for &byte in chunk {
+            self.push(byte)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn finish(self) -> Result<ApiResponse, FlagError> {
+        self.finish_page().map(|(response, _)| response)
+    }
+
+    /// The flag set and the `next` page cursor, if the body carries one.
+    pub(crate) fn finish_page(mut self) -> Result<(ApiResponse, Option<String>), FlagError> {
+        // A trailing scalar value is only terminated by the closing brace, so
+        // anything still pending here means the body was cut short.
+        if self.state != State::Done {
+            return Err(invalid("unexpected end of payload"));
+        }
+        if !self.saw_flags {
+            return Err(invalid("missing field `flags`"));
+        }
+        let interval_allowed = self
+            .interval_allowed
+            .ok_or_else(|| invalid(&format!("missing field `{}`", self.interval_key)))?;
+
+        let response = ApiResponse {
+            interval_allowed,
+            flags: std::mem::take(&mut self.flags),
+            skipped: std::mem::take(&mut self.skipped),
+            version: self.version.take(),
+        };
+        Ok((response, self.next.take()))
+    }
+
+    fn push(&mut self, byte: u8) -> Result<(), FlagError> {
+        let whitespace = matches!(byte, b' ' | b'\t' | b'\r' | b'\n');
+
+        match self.state {
+            State::ObjectStart => match byte {
+                _ if whitespace => {}
+                b'{' => self.state = State::KeyOrEnd,
+                _ => return Err(invalid("expected an object")),
+            },
+            State::KeyOrEnd => match byte {
+                _ if whitespace => {}
+                b'"' => self.start_value(byte, State::Key),
+                b'}' => self.state = State::Done,
+                _ => return Err(invalid("expected a key")),
+            },
+            State::Key => {
+                if let Scan::Complete = self.collect(byte) {
+                    self.key = serde_json::from_slice(&self.buf).map_err(|e| invalid(&e.to_string()))?;
+                    self.state = State::Colon;
+                }
+            }
+            State::Colon => match byte {
+                _ if whitespace => {}
+                b':' => self.state = State::Value,
+                _ => return Err(invalid("expected ':'")),
+            },
+            State::Value => match byte {
+                _ if whitespace => {}
+                b'[' if self.key == "flags" => {
+                    self.saw_flags = true;
+                    self.flags.clear();
+                    self.skipped.clear();
+                    self.flag_index = 0;
+                    self.state = State::FlagsElementOrEnd;
+                }
+                _ => self.start_value(byte, State::MemberValue),
+            },
+            State::FlagsElementOrEnd => match byte {
+                _ if whitespace => {}
+                b']' => self.state = State::SeparatorOrEnd,
+                _ => self.start_value(byte, State::FlagsElement),
+            },
+            State::FlagsElement => match self.collect(byte) {
+                Scan::Continue => {}
+                Scan::Complete => {
+                    self.finish_flag()?;
+                    self.state = State::FlagsSeparatorOrEnd;
+                }
+                Scan::EndedBefore => {
+                    self.finish_flag()?;
+                    self.state = State::FlagsSeparatorOrEnd;
+                    self.push(byte)?;
+                }
+            },
+            State::FlagsSeparatorOrEnd => match byte {
+                _ if whitespace => {}
+                b',' => self.state = State::FlagsElementOrEnd,
+                b']' => self.state = State::SeparatorOrEnd,
+                _ => return Err(invalid("expected ',' or ']' in flags")),
+            },
+            State::MemberValue => match self.collect(byte) {
+                Scan::Continue => {}
+                Scan::Complete => {
+                    self.finish_member()?;
+                    self.state = State::SeparatorOrEnd;
+                }
+                Scan::EndedBefore => {
+                    self.finish_member()?;
+                    self.state = State::SeparatorOrEnd;
+                    self.push(byte)?;
+                }
+            },
+            State::SeparatorOrEnd => match byte {
+                _ if whitespace => {}
+                b',' => self.state = State::KeyOrEnd,
+                b'}' => self.state = State::Done,
+                _ => return Err(invalid("expected ',' or '}'")),
+            },
+            State::Done => {
+                if !whitespace {
+                    return Err(invalid("trailing characters"));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn start_value(&mut self, byte: u8, state: State) {
+        self.buf.clear();
+        self.scanner = ValueScanner::default();
+        self.state = state;
+        let _ = self.collect(byte);
+    }
+
+    fn collect(&mut self, byte: u8) -> Scan {
+        let scan = self.scanner.scan(byte);
+        if !matches!(scan, Scan::EndedBefore) {
+            self.buf.push(byte);
+        }
+        scan
+    }
+
+    /// A malformed element is recorded and skipped rather than failing the
+    /// payload, matching [`ApiResponse`]'s lenient decoding.
+    fn finish_flag(&mut self) -> Result<(), FlagError> {
+        match (self.decode)(&self.buf) {
+            Ok(flag) => self.flags.push(flag),
+            Err(e) => self.skipped.push(format!("flag {}: {}", self.flag_index, e)),
+        }
+        self.flag_index += 1;
+        Ok(())
+    }
+
+    fn finish_member(&mut self) -> Result<(), FlagError> {
+        // Unknown members are skipped, matching the derived deserializer
+        if self.key == self.interval_key {
+            let interval = serde_json::from_slice(&self.buf).map_err(|e| invalid(&e.to_string()))?;
+            self.interval_allowed = Some(interval);
+        } else if self.key == "version" {
+            self.version = serde_json::from_slice(&self.buf).map_err(|e| invalid(&e.to_string()))?;
+        } else if self.key == "next" {
+            self.next = serde_json::from_slice(&self.buf).map_err(|e| invalid(&e.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+fn invalid(reason: &str) -> FlagError {
+    FlagError::ApiError(format!("Invalid JSON payload: {}", reason))
+}
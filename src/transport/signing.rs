@@ -0,0 +1,91 @@
+//! Request signing with an agent secret.
+//!
+//! A signed request carries [`TIMESTAMP_HEADER`], the Unix time in seconds,
+//! and [`SIGNATURE_HEADER`], the hex HMAC-SHA256 of
+//! `"{timestamp}:{project_id}:{agent_id}"` keyed with the agent secret. The
+//! secret itself is never sent. Servers (or a relay in front of them) check
+//! signatures with [`verify`].
+
+#[cfg(feature = "remote")]
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::Duration;
+
+#[cfg(feature = "remote")]
+use chrono::{DateTime, Utc};
+use ring::hmac;
+
+pub const TIMESTAMP_HEADER: &str = "X-Flags-Timestamp";
+pub const SIGNATURE_HEADER: &str = "X-Flags-Signature";
+
+/// How far a signed timestamp may be from the verifier's clock.
+pub const DEFAULT_SKEW_TOLERANCE: Duration = Duration::from_secs(300);
+
+/// The signature of a request sent at `timestamp`.
+pub fn sign(secret: &str, timestamp: i64, project_id: &str, agent_id: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, message(timestamp, project_id, agent_id).as_bytes());
+    tag.as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Whether `signature` was made with `secret` for these IDs, at a `timestamp`
+/// within `tolerance` of `now`. The comparison takes constant time.
+pub fn verify(
+    secret: &str,
+    timestamp: i64,
+    project_id: &str,
+    agent_id: &str,
+    signature: &str,
+    now: i64,
+    tolerance: Duration,
+) -> bool {
+    if now.abs_diff(timestamp) > tolerance.as_secs() {
+        return false;
+    }
+    let Some(tag) = decode_hex(signature) else {
+        return false;
+    };
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    hmac::verify(&key, message(timestamp, project_id, agent_id).as_bytes(), &tag).is_ok()
+}
+
+fn message(timestamp: i64, project_id: &str, agent_id: &str) -> String {
+    format!("{}:{}:{}", timestamp, project_id, agent_id)
+}
+
+pub(crate) fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(feature = "remote")]
+/// The difference between the server's clock and ours, learned from response
+/// `Date` headers, so signatures stay valid on hosts whose clock drifts.
+#[derive(Debug, Default)]
+pub(crate) struct ClockOffset {
+    seconds: AtomicI64,
+}
+
+#[cfg(feature = "remote")]
+impl ClockOffset {
+    /// The current time on the server's clock, as far as we know it.
+    pub(crate) fn now(&self) -> i64 {
+        Utc::now().timestamp() + self.seconds.load(Ordering::Relaxed)
+    }
+
+    /// Learn the offset from a response's `Date` header. Returns whether it
+    /// moved by more than [`DEFAULT_SKEW_TOLERANCE`], meaning signatures made
+    /// before may have been rejected.
+    pub(crate) fn observe(&self, date: &str) -> bool {
+        let Ok(server) = DateTime::parse_from_rfc2822(date) else {
+            return false;
+        };
+        let offset = server.timestamp() - Utc::now().timestamp();
+        let previous = self.seconds.swap(offset, Ordering::Relaxed);
+        offset.abs_diff(previous) > DEFAULT_SKEW_TOLERANCE.as_secs()
+    }
+}
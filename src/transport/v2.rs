@@ -0,0 +1,142 @@
+//! Version 2 of the flags payload.
+//!
+//! Flags are flat objects keyed by `key` with their metadata alongside, and the
+//! refresh interval is `refreshInterval`:
+//!
+//! ```json
+//! {"refreshInterval": 60, "flags": [{"key": "checkout", "id": "1", "enabled": true}]}
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use super::json_stream::FlagsStreamParser;
+use crate::flag::{Details, FeatureFlag, Ramp, Rule, TimeRule};
+#[cfg(feature = "msgpack")]
+use crate::{ApiResponse, FlagError};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Flag {
+    key: String,
+    id: String,
+    enabled: bool,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    owner: Option<String>,
+    #[serde(default)]
+    created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    groups: Vec<String>,
+    #[serde(default)]
+    deprecated: bool,
+    #[serde(default)]
+    expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    app_version: Option<String>,
+    #[serde(default)]
+    rules: Vec<Rule>,
+    #[serde(default)]
+    rollout: Option<f64>,
+    #[serde(default)]
+    ramp: Option<Ramp>,
+    #[serde(default)]
+    schedule: Vec<TimeRule>,
+}
+
+impl From<Flag> for FeatureFlag {
+    fn from(flag: Flag) -> Self {
+        FeatureFlag {
+            enabled: flag.enabled,
+            details: Details {
+                name: flag.key,
+                id: flag.id,
+                description: flag.description,
+                tags: flag.tags,
+                owner: flag.owner,
+                created_at: flag.created_at,
+                groups: flag.groups,
+                deprecated: flag.deprecated,
+                expires_at: flag.expires_at,
+                app_version: flag.app_version,
+                rules: flag.rules,
+                rollout: flag.rollout,
+                ramp: flag.ramp,
+                schedule: flag.schedule,
+            },
+        }
+    }
+}
+
+/// A streaming parser for the JSON payload. Malformed entries are skipped as
+/// with version 1.
+pub(crate) fn json_parser() -> FlagsStreamParser {
+    FlagsStreamParser::with_schema("refreshInterval", |element| {
+        serde_json::from_slice::<Flag>(element).map(FeatureFlag::from)
+    })
+}
+
+/// Decode a MessagePack payload and its `next` page cursor. Each element of
+/// `flags` is delimited before it is decoded, so a malformed one is skipped
+/// without losing its place in the rest of the body.
+#[cfg(feature = "msgpack")]
+pub(crate) fn decode_msgpack(body: &[u8]) -> Result<(ApiResponse, Option<String>), FlagError> {
+    use serde::de::IgnoredAny;
+
+    let mut rest = body;
+    let mut refresh_interval = None;
+    let mut flags = None;
+    let mut skipped = Vec::new();
+    let mut version = None;
+    let mut next = None;
+
+    let members = rmp::decode::read_map_len(&mut rest).map_err(|e| invalid_msgpack(&e))?;
+    for _ in 0..members {
+        let key: String = read_msgpack(&mut rest)?;
+        match key.as_str() {
+            "refreshInterval" => refresh_interval = Some(read_msgpack::<i32>(&mut rest)?),
+            "version" => version = read_msgpack(&mut rest)?,
+            "next" => next = read_msgpack(&mut rest)?,
+            "flags" => {
+                let len = rmp::decode::read_array_len(&mut rest).map_err(|e| invalid_msgpack(&e))?;
+                let mut decoded = Vec::new();
+                skipped.clear();
+                for index in 0..len {
+                    let start = rest;
+                    read_msgpack::<IgnoredAny>(&mut rest)?;
+                    let element = &start[..start.len() - rest.len()];
+                    match rmp_serde::from_slice::<Flag>(element) {
+                        Ok(flag) => decoded.push(flag.into()),
+                        Err(e) => skipped.push(format!("flag {}: {}", index, e)),
+                    }
+                }
+                flags = Some(decoded);
+            }
+            _ => {
+                read_msgpack::<IgnoredAny>(&mut rest)?;
+            }
+        }
+    }
+
+    let response = ApiResponse {
+        interval_allowed: refresh_interval.ok_or_else(|| invalid_msgpack(&"missing field `refreshInterval`"))?,
+        flags: flags.ok_or_else(|| invalid_msgpack(&"missing field `flags`"))?,
+        skipped,
+        version,
+    };
+    Ok((response, next))
+}
+
+/// Decode the value at the front of `rest` and advance past it.
+#[cfg(feature = "msgpack")]
+fn read_msgpack<T: serde::de::DeserializeOwned>(rest: &mut &[u8]) -> Result<T, FlagError> {
+    T::deserialize(&mut rmp_serde::Deserializer::new(rest)).map_err(|e| invalid_msgpack(&e))
+}
+
+#[cfg(feature = "msgpack")]
+fn invalid_msgpack(reason: &dyn std::fmt::Display) -> FlagError {
+    FlagError::ApiError(format!("Invalid v2 MessagePack payload: {}", reason))
+}
@@ -0,0 +1,120 @@
+//! Which flags have been evaluated since the client started, and how often.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+use arc_swap::ArcSwap;
+
+/// How many names without a flag are counted until the next
+/// [`reset`](UsageTracker::reset); evaluations of further unknown names are
+/// not counted, so arbitrary names can't grow the tracker without bound.
+pub(crate) const MAX_UNKNOWN_NAMES: usize = 1024;
+
+/// How often one flag was evaluated, see
+/// [`Client::evaluation_counts`](crate::Client::evaluation_counts).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvaluationCounts {
+    /// Every evaluation, `enabled + disabled`.
+    pub evaluated: u64,
+    pub enabled: u64,
+    pub disabled: u64,
+    /// Evaluations of a flag the client does not have, also counted by the
+    /// value they were given.
+    pub missing: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    enabled: AtomicU64,
+    disabled: AtomicU64,
+    missing: AtomicU64,
+}
+
+pub(crate) struct UsageTracker {
+    evaluated: RwLock<HashMap<String, Counters>>,
+    // The client's published flags, telling known names from unknown ones
+    published: Arc<ArcSwap<HashMap<String, bool>>>,
+    // Entries added for names that had no flag
+    unknown: AtomicUsize,
+}
+
+impl UsageTracker {
+    pub(crate) fn new(published: Arc<ArcSwap<HashMap<String, bool>>>) -> Self {
+        Self {
+            evaluated: RwLock::new(HashMap::new()),
+            published,
+            unknown: AtomicUsize::new(0),
+        }
+    }
+
+    /// Mark `name` as evaluated to `enabled`; after the first call for a flag
+    /// this only takes a read lock.
+    pub(crate) fn record(&self, name: &str, enabled: bool) {
+        self.with_counters(name, |counters| {
+            let counter = if enabled { &counters.enabled } else { &counters.disabled };
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+
+    /// Count an evaluation of `name` that found no such flag.
+    pub(crate) fn record_missing(&self, name: &str) {
+        self.with_counters(name, |counters| {
+            counters.missing.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+
+    pub(crate) fn is_used(&self, name: &str) -> bool {
+        self.evaluated.read().unwrap_or_else(|e| e.into_inner()).contains_key(name)
+    }
+
+    /// Counts of every flag evaluated since the last [`reset`](Self::reset).
+    pub(crate) fn counts(&self) -> HashMap<String, EvaluationCounts> {
+        self.evaluated
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(name, counters)| {
+                let enabled = counters.enabled.load(Ordering::Relaxed);
+                let disabled = counters.disabled.load(Ordering::Relaxed);
+                let counts = EvaluationCounts {
+                    evaluated: enabled + disabled,
+                    enabled,
+                    disabled,
+                    missing: counters.missing.load(Ordering::Relaxed),
+                };
+                (name.clone(), counts)
+            })
+            .filter(|(_, counts)| counts.evaluated > 0)
+            .collect()
+    }
+
+    /// Zero every count. Flags stay marked as used; names without a flag
+    /// are forgotten.
+    pub(crate) fn reset(&self) {
+        let published = self.published.load();
+        let mut evaluated = self.evaluated.write().unwrap_or_else(|e| e.into_inner());
+        evaluated.retain(|name, _| published.contains_key(name));
+        for counters in evaluated.values() {
+            counters.enabled.store(0, Ordering::Relaxed);
+            counters.disabled.store(0, Ordering::Relaxed);
+            counters.missing.store(0, Ordering::Relaxed);
+        }
+        self.unknown.store(0, Ordering::Relaxed);
+    }
+
+    fn with_counters(&self, name: &str, f: impl FnOnce(&Counters)) {
+        if let Some(counters) = self.evaluated.read().unwrap_or_else(|e| e.into_inner()).get(name) {
+            return f(counters);
+        }
+        let known = self.published.load().contains_key(name);
+        let mut evaluated = self.evaluated.write().unwrap_or_else(|e| e.into_inner());
+        if !known && !evaluated.contains_key(name) {
+            if self.unknown.load(Ordering::Relaxed) >= MAX_UNKNOWN_NAMES {
+                return;
+            }
+            self.unknown.fetch_add(1, Ordering::Relaxed);
+        }
+        f(evaluated.entry(name.to_string()).or_default());
+    }
+}
@@ -0,0 +1,197 @@
+//! Semantic versions and version ranges for `app_version` targeting.
+
+use std::cmp::Ordering;
+
+/// A `major.minor.patch[-pre][+build]` version. Missing minor or patch parts
+/// read as zero; build metadata is ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Version {
+    major: u64,
+    minor: u64,
+    patch: u64,
+    pre: Vec<String>,
+}
+
+impl Version {
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        Self::parse_partial(s).map(|(version, _)| version)
+    }
+
+    /// The version and how many of its numeric parts were given.
+    fn parse_partial(s: &str) -> Option<(Self, usize)> {
+        let s = s.trim().trim_start_matches('v');
+        let s = s.split('+').next()?;
+        let (core, pre) = match s.split_once('-') {
+            Some((core, pre)) if !pre.is_empty() => (core, pre.split('.').map(str::to_string).collect()),
+            Some(_) => return None,
+            None => (s, Vec::new()),
+        };
+        let parts = core
+            .split('.')
+            .map(|part| part.parse::<u64>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        if parts.is_empty() || parts.len() > 3 {
+            return None;
+        }
+        let part = |i: usize| parts.get(i).copied().unwrap_or(0);
+        Some((
+            Version {
+                major: part(0),
+                minor: part(1),
+                patch: part(2),
+                pre,
+            },
+            parts.len(),
+        ))
+    }
+
+    fn bump(&self, given: usize, caret: bool) -> Version {
+        // ^1.2.3 < 2.0.0, ^0.2.3 < 0.3.0, ~1.2.3 < 1.3.0, ~1 < 2.0.0
+        let part = if caret {
+            if self.major > 0 || given == 1 {
+                0
+            } else if self.minor > 0 || given == 2 {
+                1
+            } else {
+                2
+            }
+        } else if given == 1 {
+            0
+        } else {
+            1
+        };
+        let mut upper = Version {
+            major: self.major,
+            minor: self.minor,
+            patch: self.patch,
+            pre: Vec::new(),
+        };
+        match part {
+            0 => (upper.major, upper.minor, upper.patch) = (self.major + 1, 0, 0),
+            1 => (upper.minor, upper.patch) = (self.minor + 1, 0),
+            _ => upper.patch = self.patch + 1,
+        }
+        upper
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+                // A pre-release sorts before its release
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => compare_pre(&self.pre, &other.pre),
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn compare_pre(a: &[String], b: &[String]) -> Ordering {
+    for (x, y) in a.iter().zip(b) {
+        let ordering = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => x.cmp(y),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// Comparators that must all hold, e.g. `>=2.3.0, <3` or `^2.3`. `*` matches
+/// every version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct VersionRange {
+    comparators: Vec<(Op, Version)>,
+}
+
+impl VersionRange {
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        let mut comparators = Vec::new();
+        for term in terms(s)? {
+            let term = term.as_str();
+            if term == "*" {
+                continue;
+            }
+            let (op, version) = if let Some(v) = term.strip_prefix(">=") {
+                (">=", v)
+            } else if let Some(v) = term.strip_prefix("<=") {
+                ("<=", v)
+            } else if let Some(v) = term.strip_prefix('>') {
+                (">", v)
+            } else if let Some(v) = term.strip_prefix('<') {
+                ("<", v)
+            } else if let Some(v) = term.strip_prefix('=') {
+                ("=", v)
+            } else if let Some(v) = term.strip_prefix('^') {
+                ("^", v)
+            } else if let Some(v) = term.strip_prefix('~') {
+                ("~", v)
+            } else {
+                ("=", term)
+            };
+            let (version, given) = Version::parse_partial(version)?;
+            match op {
+                ">=" => comparators.push((Op::Ge, version)),
+                "<=" => comparators.push((Op::Le, version)),
+                ">" => comparators.push((Op::Gt, version)),
+                "<" => comparators.push((Op::Lt, version)),
+                "=" => comparators.push((Op::Eq, version)),
+                _ => {
+                    let upper = version.bump(given, op == "^");
+                    comparators.push((Op::Ge, version));
+                    comparators.push((Op::Lt, upper));
+                }
+            }
+        }
+        Some(VersionRange { comparators })
+    }
+
+    pub(crate) fn matches(&self, version: &Version) -> bool {
+        self.comparators.iter().all(|(op, bound)| match op {
+            Op::Eq => version == bound,
+            Op::Gt => version > bound,
+            Op::Ge => version >= bound,
+            Op::Lt => version < bound,
+            Op::Le => version <= bound,
+        })
+    }
+}
+
+/// The comparators of `s`, separated by commas or whitespace. An operator may
+/// be written apart from its version, as in `>= 2.3.0`.
+fn terms(s: &str) -> Option<Vec<String>> {
+    let mut terms = Vec::new();
+    for group in s.split(',') {
+        let mut tokens = group.split_whitespace();
+        while let Some(token) = tokens.next() {
+            if token.chars().all(|c| matches!(c, '<' | '>' | '=' | '^' | '~')) {
+                terms.push(format!("{}{}", token, tokens.next()?));
+            } else {
+                terms.push(token.to_string());
+            }
+        }
+    }
+    Some(terms)
+}